@@ -0,0 +1,205 @@
+#![no_std]
+
+//! Merchant registry for Stellarbill.
+//!
+//! Merchants register the address their payouts go to and a hash of their display
+//! metadata, and start out `Active`. The registry admin assigns fee tiers and can
+//! suspend abusive merchants. A subscription vault pointed at the registry refuses new
+//! subscriptions for merchants that are not active and sends their payouts to the
+//! registered payout address, so merchant governance lives outside the vault.
+//! See `docs/merchant_registry.md`.
+
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, Address, BytesN, Env, Symbol,
+};
+
+#[contracterror]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum Error {
+    /// Caller is not the registry admin.
+    Forbidden = 403,
+    /// The merchant is not registered.
+    NotFound = 404,
+    /// `init` was already called.
+    AlreadyInitialized = 1009,
+    /// `init` has not been called yet.
+    NotInitialized = 1013,
+    /// The merchant is already registered; use `update_merchant`.
+    AlreadyRegistered = 1100,
+}
+
+/// Whether the merchant may take new subscriptions and receive payouts.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MerchantStatus {
+    Active = 0,
+    Suspended = 1,
+}
+
+/// Registry entry of a merchant.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantRecord {
+    /// Address vault payouts are sent to.
+    pub payout_address: Address,
+    /// Hash of the merchant's off-chain display metadata (name, logo, terms).
+    pub metadata_hash: BytesN<32>,
+    /// Fee tier assigned by the admin; 0 until set.
+    pub fee_tier: u32,
+    pub status: MerchantStatus,
+    pub registered_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    Admin,
+    Merchant(Address),
+}
+
+#[contract]
+pub struct MerchantRegistry;
+
+fn require_admin(env: &Env, admin: &Address) -> Result<(), Error> {
+    admin.require_auth();
+    let stored: Address = env
+        .storage()
+        .instance()
+        .get(&DataKey::Admin)
+        .ok_or(Error::NotInitialized)?;
+    if *admin != stored {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+fn load(env: &Env, merchant: &Address) -> Result<MerchantRecord, Error> {
+    env.storage()
+        .instance()
+        .get(&DataKey::Merchant(merchant.clone()))
+        .ok_or(Error::NotFound)
+}
+
+fn store(env: &Env, merchant: &Address, record: &MerchantRecord) {
+    env.storage()
+        .instance()
+        .set(&DataKey::Merchant(merchant.clone()), record);
+}
+
+#[contractimpl]
+impl MerchantRegistry {
+    /// Sets the registry admin. Can only be called once.
+    pub fn init(env: Env, admin: Address) -> Result<(), Error> {
+        if env.storage().instance().has(&DataKey::Admin) {
+            return Err(Error::AlreadyInitialized);
+        }
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        Ok(())
+    }
+
+    pub fn get_admin(env: Env) -> Result<Address, Error> {
+        env.storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .ok_or(Error::NotInitialized)
+    }
+
+    /// Merchant registers its payout address and metadata hash. New merchants are
+    /// `Active` with fee tier 0.
+    pub fn register(
+        env: Env,
+        merchant: Address,
+        payout_address: Address,
+        metadata_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        merchant.require_auth();
+        if load(&env, &merchant).is_ok() {
+            return Err(Error::AlreadyRegistered);
+        }
+        let record = MerchantRecord {
+            payout_address,
+            metadata_hash,
+            fee_tier: 0,
+            status: MerchantStatus::Active,
+            registered_at: env.ledger().timestamp(),
+        };
+        store(&env, &merchant, &record);
+        env.events()
+            .publish((Symbol::new(&env, "registered"), merchant), record);
+        Ok(())
+    }
+
+    /// Merchant changes its payout address and metadata hash. Status and fee tier are
+    /// kept.
+    pub fn update_merchant(
+        env: Env,
+        merchant: Address,
+        payout_address: Address,
+        metadata_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        merchant.require_auth();
+        let mut record = load(&env, &merchant)?;
+        record.payout_address = payout_address;
+        record.metadata_hash = metadata_hash;
+        store(&env, &merchant, &record);
+        env.events()
+            .publish((Symbol::new(&env, "merchant_updated"), merchant), record);
+        Ok(())
+    }
+
+    /// **ADMIN ONLY**: Assign the merchant's fee tier.
+    pub fn set_fee_tier(
+        env: Env,
+        admin: Address,
+        merchant: Address,
+        fee_tier: u32,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        let mut record = load(&env, &merchant)?;
+        record.fee_tier = fee_tier;
+        store(&env, &merchant, &record);
+        env.events()
+            .publish((Symbol::new(&env, "fee_tier_set"), merchant), fee_tier);
+        Ok(())
+    }
+
+    /// **ADMIN ONLY**: Suspend the merchant or reinstate it.
+    pub fn set_status(
+        env: Env,
+        admin: Address,
+        merchant: Address,
+        status: MerchantStatus,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        let mut record = load(&env, &merchant)?;
+        record.status = status.clone();
+        store(&env, &merchant, &record);
+        env.events()
+            .publish((Symbol::new(&env, "status_set"), merchant), status);
+        Ok(())
+    }
+
+    /// Registry entry of the merchant, or `None` if it is not registered.
+    pub fn get_merchant(env: Env, merchant: Address) -> Option<MerchantRecord> {
+        load(&env, &merchant).ok()
+    }
+
+    /// Whether the merchant is registered and active. Consulted by the vault before a
+    /// subscription is created.
+    pub fn is_active(env: Env, merchant: Address) -> bool {
+        matches!(load(&env, &merchant), Ok(record) if record.status == MerchantStatus::Active)
+    }
+
+    /// Where the merchant's payouts go, or `None` if it is not registered or is
+    /// suspended. Consulted by the vault before paying out.
+    pub fn payout_address(env: Env, merchant: Address) -> Option<Address> {
+        match load(&env, &merchant) {
+            Ok(record) if record.status == MerchantStatus::Active => Some(record.payout_address),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
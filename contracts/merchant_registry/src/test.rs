@@ -0,0 +1,83 @@
+use crate::{Error, MerchantRegistry, MerchantRegistryClient, MerchantStatus};
+use soroban_sdk::testutils::Address as _;
+use soroban_sdk::{Address, BytesN, Env};
+
+fn setup(env: &Env) -> (MerchantRegistryClient<'_>, Address) {
+    env.mock_all_auths();
+    let client = MerchantRegistryClient::new(env, &env.register(MerchantRegistry, ()));
+    let admin = Address::generate(env);
+    client.init(&admin);
+    (client, admin)
+}
+
+#[test]
+fn test_init_only_once() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(
+        client.try_init(&Address::generate(&env)),
+        Err(Ok(Error::AlreadyInitialized))
+    );
+}
+
+#[test]
+fn test_register_and_update_merchant() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let merchant = Address::generate(&env);
+    let payout = Address::generate(&env);
+    let metadata = BytesN::from_array(&env, &[1; 32]);
+
+    assert!(!client.is_active(&merchant));
+    client.register(&merchant, &payout, &metadata);
+    let record = client.get_merchant(&merchant).unwrap();
+    assert_eq!(record.payout_address, payout);
+    assert_eq!(record.fee_tier, 0);
+    assert_eq!(record.status, MerchantStatus::Active);
+    assert!(client.is_active(&merchant));
+    assert_eq!(client.payout_address(&merchant), Some(payout.clone()));
+    assert_eq!(
+        client.try_register(&merchant, &payout, &metadata),
+        Err(Ok(Error::AlreadyRegistered))
+    );
+
+    let new_payout = Address::generate(&env);
+    let new_metadata = BytesN::from_array(&env, &[2; 32]);
+    client.update_merchant(&merchant, &new_payout, &new_metadata);
+    let record = client.get_merchant(&merchant).unwrap();
+    assert_eq!(record.payout_address, new_payout);
+    assert_eq!(record.metadata_hash, new_metadata);
+    assert_eq!(
+        client.try_update_merchant(&Address::generate(&env), &payout, &metadata),
+        Err(Ok(Error::NotFound))
+    );
+}
+
+#[test]
+fn test_admin_sets_fee_tier_and_suspends_merchant() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let merchant = Address::generate(&env);
+    let payout = Address::generate(&env);
+    client.register(&merchant, &payout, &BytesN::from_array(&env, &[1; 32]));
+
+    assert_eq!(
+        client.try_set_status(
+            &Address::generate(&env),
+            &merchant,
+            &MerchantStatus::Suspended
+        ),
+        Err(Ok(Error::Forbidden))
+    );
+    client.set_fee_tier(&admin, &merchant, &2);
+    client.set_status(&admin, &merchant, &MerchantStatus::Suspended);
+    let record = client.get_merchant(&merchant).unwrap();
+    assert_eq!(record.fee_tier, 2);
+    assert_eq!(record.status, MerchantStatus::Suspended);
+    assert!(!client.is_active(&merchant));
+    assert_eq!(client.payout_address(&merchant), None);
+
+    client.set_status(&admin, &merchant, &MerchantStatus::Active);
+    assert_eq!(client.payout_address(&merchant), Some(payout));
+}
@@ -1,34 +1,59 @@
-//! Admin and config: init, min_topup, batch_charge.
+//! Admin and config: init, min_topup, charge tolerance, billing engine and agents,
+//! get_config, batch_charge, batch_charge_usage.
+//!
+//! A batch charges its subscriptions in ascending ID order, whatever the order of the
+//! input, so a subscriber with several subscriptions in one batch is always debited
+//! lowest ID first. The admin can cap what one batch may debit from a single subscriber
+//! with [`do_set_batch_debit_cap`].
+//!
+//! Charging is kept apart from administration: the billing engine set at `init` (and
+//! rotated with [`do_set_billing_engine`]) and the billing agents may charge, while the
+//! admin keeps configuration and recovery. A leaked billing key can then neither rotate
+//! the admin nor move funds through recovery.
 //!
 //! **PRs that only change admin or batch behavior should edit this file only.**
 
 #![allow(dead_code)]
 
-use crate::charge_core::charge_one;
-use crate::types::{BatchChargeResult, Error, RecoveryEvent, RecoveryReason};
-use soroban_sdk::{Address, Env, Symbol, Vec};
+use crate::charge_core::charge_one_within;
+use crate::types::{
+    BatchChargeResult, ConfigKey, DataKey, Error, RecoveryEvent, RecoveryReason,
+    SubscriptionLimits, VaultConfig,
+};
+use soroban_sdk::{Address, BytesN, Env, Map, Symbol, Vec};
 
+/// One-time setup. Fails with `AlreadyInitialized` once an admin or token is stored,
+/// including under the legacy keys of storage not yet migrated (see
+/// `storage_migration.rs`), so a deployed vault cannot be taken over by a second call.
 pub fn do_init(
     env: &Env,
     token: Address,
     token_decimals: u32,
     admin: Address,
+    billing_engine: Address,
     min_topup: i128,
     grace_period: u64,
 ) -> Result<(), Error> {
     let instance = env.storage().instance();
-    if instance.has(&Symbol::new(env, "token")) || instance.has(&Symbol::new(env, "admin")) {
+    if instance.has(&DataKey::Token)
+        || instance.has(&DataKey::Admin)
+        || instance.has(&Symbol::new(env, "admin"))
+        || instance.has(&Symbol::new(env, "token"))
+    {
         return Err(Error::AlreadyInitialized);
     }
     if min_topup < 0 {
         return Err(Error::InvalidAmount);
     }
 
-    instance.set(&Symbol::new(env, "token"), &token);
-    instance.set(&Symbol::new(env, "token_decimals"), &token_decimals);
-    instance.set(&Symbol::new(env, "admin"), &admin);
-    instance.set(&Symbol::new(env, "min_topup"), &min_topup);
-    instance.set(&Symbol::new(env, "grace_period"), &grace_period);
+    instance.set(&DataKey::Token, &token);
+    instance.set(&DataKey::Config(ConfigKey::TokenDecimals), &token_decimals);
+    instance.set(&DataKey::Admin, &admin);
+    instance.set(&DataKey::Config(ConfigKey::BillingEngine), &billing_engine);
+    instance.set(&DataKey::Config(ConfigKey::MinTopup), &min_topup);
+    instance.set(&DataKey::Config(ConfigKey::GracePeriod), &grace_period);
+    crate::storage_migration::set_current_version(env);
+    crate::token_guard::cache_fingerprint(env, &token);
 
     env.events().publish(
         (Symbol::new(env, "initialized"),),
@@ -40,7 +65,7 @@ pub fn do_init(
 pub fn require_admin(env: &Env) -> Result<Address, Error> {
     env.storage()
         .instance()
-        .get(&Symbol::new(env, "admin"))
+        .get(&DataKey::Admin)
         .ok_or(Error::NotInitialized)
 }
 
@@ -52,7 +77,7 @@ pub fn do_set_min_topup(env: &Env, admin: Address, min_topup: i128) -> Result<()
     }
     env.storage()
         .instance()
-        .set(&Symbol::new(env, "min_topup"), &min_topup);
+        .set(&DataKey::Config(ConfigKey::MinTopup), &min_topup);
     env.events()
         .publish((Symbol::new(env, "min_topup_updated"),), min_topup);
     Ok(())
@@ -61,7 +86,7 @@ pub fn do_set_min_topup(env: &Env, admin: Address, min_topup: i128) -> Result<()
 pub fn get_min_topup(env: &Env) -> Result<i128, Error> {
     env.storage()
         .instance()
-        .get(&Symbol::new(env, "min_topup"))
+        .get(&DataKey::Config(ConfigKey::MinTopup))
         .ok_or(Error::NotInitialized)
 }
 
@@ -73,7 +98,7 @@ pub fn do_set_grace_period(env: &Env, admin: Address, grace_period: u64) -> Resu
     }
     env.storage()
         .instance()
-        .set(&Symbol::new(env, "grace_period"), &grace_period);
+        .set(&DataKey::Config(ConfigKey::GracePeriod), &grace_period);
     Ok(())
 }
 
@@ -81,14 +106,300 @@ pub fn get_grace_period(env: &Env) -> Result<u64, Error> {
     Ok(env
         .storage()
         .instance()
-        .get(&Symbol::new(env, "grace_period"))
+        .get(&DataKey::Config(ConfigKey::GracePeriod))
         .unwrap_or(0))
 }
 
+/// Upper bound for `charge_tolerance_seconds`; tolerance is for clock skew, not early billing.
+pub const MAX_CHARGE_TOLERANCE_SECONDS: u64 = 300;
+
+/// Admin sets how many seconds before the interval boundary a charge is still accepted.
+pub fn do_set_charge_tolerance(env: &Env, admin: Address, seconds: u64) -> Result<(), Error> {
+    admin.require_auth();
+    let stored = require_admin(env)?;
+    if admin != stored {
+        return Err(Error::Forbidden);
+    }
+    if seconds > MAX_CHARGE_TOLERANCE_SECONDS {
+        return Err(Error::InvalidInput);
+    }
+    env.storage()
+        .instance()
+        .set(&DataKey::Config(ConfigKey::ChargeTolerance), &seconds);
+    Ok(())
+}
+
+pub fn get_charge_tolerance(env: &Env) -> u64 {
+    env.storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::ChargeTolerance))
+        .unwrap_or(0)
+}
+
+/// Upper bound for `min_debit_spacing_seconds`; the floor guards against racing
+/// charge paths and must stay well below any billing interval.
+pub const MAX_MIN_DEBIT_SPACING_SECONDS: u64 = 3_600;
+
+/// Admin sets the minimum time between two successful debits on one subscription,
+/// across interval, usage and one-off charges. 0 disables the floor.
+pub fn do_set_min_debit_spacing(env: &Env, admin: Address, seconds: u64) -> Result<(), Error> {
+    admin.require_auth();
+    let stored = require_admin(env)?;
+    if admin != stored {
+        return Err(Error::Forbidden);
+    }
+    if seconds > MAX_MIN_DEBIT_SPACING_SECONDS {
+        return Err(Error::InvalidInput);
+    }
+    env.storage()
+        .instance()
+        .set(&DataKey::Config(ConfigKey::MinDebitSpacing), &seconds);
+    Ok(())
+}
+
+pub fn get_min_debit_spacing(env: &Env) -> u64 {
+    env.storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::MinDebitSpacing))
+        .unwrap_or(0)
+}
+
+/// Admin caps how many live subscriptions one subscriber or merchant may hold.
+/// A limit of 0 means unlimited.
+pub fn do_set_subscription_limits(
+    env: &Env,
+    admin: Address,
+    max_per_subscriber: u32,
+    max_per_merchant: u32,
+) -> Result<(), Error> {
+    admin.require_auth();
+    let stored = require_admin(env)?;
+    if admin != stored {
+        return Err(Error::Forbidden);
+    }
+    let limits = SubscriptionLimits {
+        max_per_subscriber,
+        max_per_merchant,
+    };
+    env.storage()
+        .instance()
+        .set(&DataKey::Config(ConfigKey::SubscriptionLimits), &limits);
+    env.events()
+        .publish((Symbol::new(env, "subscription_limits_updated"),), limits);
+    Ok(())
+}
+
+pub fn get_subscription_limits(env: &Env) -> SubscriptionLimits {
+    env.storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::SubscriptionLimits))
+        .unwrap_or(SubscriptionLimits {
+            max_per_subscriber: 0,
+            max_per_merchant: 0,
+        })
+}
+
+/// Admin turns soft-launch allowlist mode on or off. While on, only subscribers added
+/// with [`do_add_allowed_subscriber`] may open subscriptions.
+pub fn do_set_allowlist_mode(env: &Env, admin: Address, enabled: bool) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    env.storage()
+        .instance()
+        .set(&DataKey::Config(ConfigKey::AllowlistMode), &enabled);
+    env.events()
+        .publish((Symbol::new(env, "allowlist_mode_set"),), enabled);
+    Ok(())
+}
+
+pub fn get_allowlist_mode(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::AllowlistMode))
+        .unwrap_or(false)
+}
+
+/// Admin admits (`allowed == true`) or removes a subscriber from the allowlist.
+/// Removal does not affect subscriptions the subscriber already holds.
+pub fn do_set_allowed_subscriber(
+    env: &Env,
+    admin: Address,
+    subscriber: Address,
+    allowed: bool,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    let key = DataKey::AllowedSubscriber(subscriber.clone());
+    if allowed {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events()
+        .publish((Symbol::new(env, "allowlist_updated"), subscriber), allowed);
+    Ok(())
+}
+
+pub fn is_subscriber_allowed(env: &Env, subscriber: &Address) -> bool {
+    env.storage()
+        .instance()
+        .has(&DataKey::AllowedSubscriber(subscriber.clone()))
+}
+
+/// Fails with `SubscriberNotAllowlisted` while allowlist mode is on and `subscriber`
+/// has not been admitted.
+pub fn ensure_subscriber_allowed(env: &Env, subscriber: &Address) -> Result<(), Error> {
+    if get_allowlist_mode(env) && !is_subscriber_allowed(env, subscriber) {
+        return Err(Error::SubscriberNotAllowlisted);
+    }
+    Ok(())
+}
+
+/// Address allowed to call the charge entrypoints. Storage initialized before the
+/// billing engine existed falls back to the admin until one is set.
+pub fn get_billing_engine(env: &Env) -> Result<Address, Error> {
+    match env
+        .storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::BillingEngine))
+    {
+        Some(engine) => Ok(engine),
+        None => require_admin(env),
+    }
+}
+
+/// Requires the billing engine's authorization.
+pub fn require_billing_engine(env: &Env) -> Result<(), Error> {
+    get_billing_engine(env)?.require_auth();
+    Ok(())
+}
+
+/// Admin rotates the billing engine, e.g. after its key leaked.
+pub fn do_set_billing_engine(env: &Env, admin: Address, engine: Address) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    let previous = get_billing_engine(env)?;
+    env.storage()
+        .instance()
+        .set(&DataKey::Config(ConfigKey::BillingEngine), &engine);
+    env.events().publish(
+        (Symbol::new(env, "billing_engine_rotated"),),
+        (previous, engine),
+    );
+    Ok(())
+}
+
+/// Maximum number of billing agents (bounds the authorization check).
+pub const MAX_BILLING_AGENTS: u32 = 20;
+
+/// Backend keys allowed to call the operator charge entrypoints besides the billing
+/// engine.
+pub fn get_billing_agents(env: &Env) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::BillingAgents))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Replaces the billing agent list. Duplicates are dropped; more than
+/// [`MAX_BILLING_AGENTS`] distinct agents fail with `InvalidInput`.
+pub fn store_billing_agents(env: &Env, agents: &Vec<Address>) -> Result<(), Error> {
+    let mut unique: Vec<Address> = Vec::new(env);
+    for agent in agents.iter() {
+        if !unique.contains(&agent) {
+            unique.push_back(agent);
+        }
+    }
+    if unique.len() > MAX_BILLING_AGENTS {
+        return Err(Error::InvalidInput);
+    }
+    if unique.is_empty() {
+        env.storage()
+            .instance()
+            .remove(&DataKey::Config(ConfigKey::BillingAgents));
+    } else {
+        env.storage()
+            .instance()
+            .set(&DataKey::Config(ConfigKey::BillingAgents), &unique);
+    }
+    env.events()
+        .publish((Symbol::new(env, "billing_agents_set"),), unique);
+    Ok(())
+}
+
+/// Admin allows `agent` to call the charge entrypoints. Adding a listed agent is a no-op.
+pub fn do_add_billing_agent(env: &Env, admin: Address, agent: Address) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    let mut agents = get_billing_agents(env);
+    if agents.contains(&agent) {
+        return Ok(());
+    }
+    agents.push_back(agent);
+    store_billing_agents(env, &agents)
+}
+
+/// Admin revokes `agent`. Fails with `NotFound` if it is not listed.
+pub fn do_remove_billing_agent(env: &Env, admin: Address, agent: Address) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    let mut agents = get_billing_agents(env);
+    let index = agents.first_index_of(&agent).ok_or(Error::NotFound)?;
+    agents.remove(index);
+    store_billing_agents(env, &agents)
+}
+
+/// Requires `operator`'s authorization and that it is the billing engine or a billing
+/// agent.
+pub fn require_billing_operator(env: &Env, operator: &Address) -> Result<(), Error> {
+    operator.require_auth();
+    if *operator == get_billing_engine(env)? || get_billing_agents(env).contains(operator) {
+        Ok(())
+    } else {
+        Err(Error::Forbidden)
+    }
+}
+
+/// Snapshot of the global contract configuration. Fails with `NotInitialized` before
+/// `init`.
+pub fn get_config(env: &Env) -> Result<VaultConfig, Error> {
+    Ok(VaultConfig {
+        admin: require_admin(env)?,
+        billing_engine: get_billing_engine(env)?,
+        token: get_token(env)?,
+        token_decimals: get_token_decimals(env)?,
+        min_topup: get_min_topup(env)?,
+        grace_period: get_grace_period(env)?,
+        charge_tolerance_seconds: get_charge_tolerance(env),
+        min_debit_spacing_seconds: get_min_debit_spacing(env),
+        emergency_stop: crate::get_emergency_stop(env),
+        fee_recipient: crate::fees::get_fee_recipient(env)?,
+        subscription_limits: get_subscription_limits(env),
+        allowlist_mode: get_allowlist_mode(env),
+    })
+}
+
+pub fn get_token_decimals(env: &Env) -> Result<u32, Error> {
+    env.storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::TokenDecimals))
+        .ok_or(Error::NotFound)
+}
+
 pub fn get_token(env: &Env) -> Result<Address, Error> {
     env.storage()
         .instance()
-        .get(&Symbol::new(env, "token"))
+        .get(&DataKey::Token)
         .ok_or(Error::NotFound)
 }
 
@@ -96,21 +407,88 @@ pub fn do_batch_charge(
     env: &Env,
     subscription_ids: &Vec<u32>,
 ) -> Result<Vec<BatchChargeResult>, Error> {
-    let auth_admin = require_admin(env)?;
-    auth_admin.require_auth();
+    require_billing_engine(env)?;
+    charge_batch(env, subscription_ids, None)
+}
 
-    let now = env.ledger().timestamp();
+/// [`do_batch_charge`] authorized by `operator`, the billing engine or a billing agent.
+pub fn do_batch_charge_as(
+    env: &Env,
+    operator: Address,
+    subscription_ids: &Vec<u32>,
+) -> Result<Vec<BatchChargeResult>, Error> {
+    require_billing_operator(env, &operator)?;
+    charge_batch(env, subscription_ids, None)
+}
+
+/// [`do_batch_charge_as`] with one idempotency key per subscription, so a billing worker
+/// can safely resend a batch whose outcome it did not see. Fails with `InvalidInput` if
+/// the lists differ in length.
+pub fn do_batch_charge_with_keys(
+    env: &Env,
+    operator: Address,
+    subscription_ids: &Vec<u32>,
+    idempotency_keys: &Vec<BytesN<32>>,
+) -> Result<Vec<BatchChargeResult>, Error> {
+    require_billing_operator(env, &operator)?;
+    if subscription_ids.len() != idempotency_keys.len() {
+        return Err(Error::InvalidInput);
+    }
+    charge_batch(env, subscription_ids, Some(idempotency_keys))
+}
+
+/// Admin caps what one batch charge may debit from a single subscriber, summed over
+/// their subscriptions in the batch. 0 removes the cap.
+pub fn do_set_batch_debit_cap(env: &Env, admin: Address, cap: i128) -> Result<(), Error> {
+    admin.require_auth();
+    let stored = require_admin(env)?;
+    if admin != stored {
+        return Err(Error::Forbidden);
+    }
+    if cap < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let key = DataKey::Config(ConfigKey::BatchDebitCap);
+    if cap == 0 {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &cap);
+    }
+    Ok(())
+}
+
+/// Per-subscriber debit cap of one batch charge, or `None` if unset.
+pub fn get_batch_debit_cap(env: &Env) -> Option<i128> {
+    env.storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::BatchDebitCap))
+}
+
+/// Maximum entries per `batch_charge_usage` call.
+pub const MAX_USAGE_BATCH: u32 = 200;
+
+/// Billing engine debits metered usage for many subscriptions at once, one
+/// `(subscription_id, usage_amount)` entry at a time. Each entry is charged like
+/// `charge_usage` and fails on its own without affecting the others; results are in
+/// input order. More than [`MAX_USAGE_BATCH`] entries fail with `InvalidInput`.
+pub fn do_batch_charge_usage(
+    env: &Env,
+    entries: &Vec<(u32, i128)>,
+) -> Result<Vec<BatchChargeResult>, Error> {
+    require_billing_engine(env)?;
+    if entries.len() > MAX_USAGE_BATCH {
+        return Err(Error::InvalidInput);
+    }
     let mut results = Vec::new(env);
-    for id in subscription_ids.iter() {
-        let r = charge_one(env, id, now, None);
-        let res = match &r {
+    for (subscription_id, usage_amount) in entries.iter() {
+        let res = match crate::charge_core::charge_usage_one(env, subscription_id, usage_amount) {
             Ok(()) => BatchChargeResult {
                 success: true,
                 error_code: 0,
             },
             Err(e) => BatchChargeResult {
                 success: false,
-                error_code: e.clone().to_code(),
+                error_code: e.to_code(),
             },
         };
         results.push_back(res);
@@ -118,10 +496,85 @@ pub fn do_batch_charge(
     Ok(results)
 }
 
+/// Charges `subscription_ids` in ascending ID order and returns the results in input
+/// order. With a batch debit cap set, a charge that would take its subscriber's total
+/// for the batch past the cap fails with `BatchDebitCapExceeded` and debits nothing.
+fn charge_batch(
+    env: &Env,
+    subscription_ids: &Vec<u32>,
+    idempotency_keys: Option<&Vec<BytesN<32>>>,
+) -> Result<Vec<BatchChargeResult>, Error> {
+    crate::circuit_breaker::ensure_closed(env)?;
+    let now = env.ledger().timestamp();
+    let debit_cap = get_batch_debit_cap(env);
+    let mut debited: Map<Address, i128> = Map::new(env);
+    let mut results = Vec::new(env);
+    for _ in subscription_ids.iter() {
+        results.push_back(BatchChargeResult {
+            success: false,
+            error_code: 0,
+        });
+    }
+    let mut attempts = 0u32;
+    let mut failures = 0u32;
+    for index in processing_order(env, subscription_ids).iter() {
+        let id = subscription_ids.get_unchecked(index);
+        let key = idempotency_keys.and_then(|keys| keys.get(index));
+        let subscriber = debit_cap
+            .and_then(|_| crate::queries::load_subscription(env, id))
+            .map(|sub| sub.subscriber);
+        let already = subscriber
+            .as_ref()
+            .and_then(|s| debited.get(s.clone()))
+            .unwrap_or(0);
+        let max_debit = debit_cap.map(|cap| cap.saturating_sub(already));
+        let r = charge_one_within(env, id, now, key, max_debit);
+        let res = match &r {
+            Ok(amount) => {
+                attempts += 1;
+                if let Some(subscriber) = subscriber {
+                    debited.set(subscriber, already.saturating_add(*amount));
+                }
+                BatchChargeResult {
+                    success: true,
+                    error_code: 0,
+                }
+            }
+            Err(e) => {
+                if crate::circuit_breaker::counts_as_attempt(e) {
+                    attempts += 1;
+                    failures += 1;
+                }
+                BatchChargeResult {
+                    success: false,
+                    error_code: e.clone().to_code(),
+                }
+            }
+        };
+        results.set(index, res);
+    }
+    crate::circuit_breaker::record_batch(env, attempts, failures);
+    Ok(results)
+}
+
+/// Indices of `subscription_ids` sorted by ID; repeated IDs keep their input order.
+/// Insertion sort, so an already ascending batch costs one comparison per entry.
+fn processing_order(env: &Env, subscription_ids: &Vec<u32>) -> Vec<u32> {
+    let mut order: Vec<u32> = Vec::new(env);
+    for (index, id) in subscription_ids.iter().enumerate() {
+        let mut pos = order.len();
+        while pos > 0 && subscription_ids.get_unchecked(order.get_unchecked(pos - 1)) > id {
+            pos -= 1;
+        }
+        order.insert(pos, index as u32);
+    }
+    order
+}
+
 pub fn do_get_admin(env: &Env) -> Result<Address, Error> {
     env.storage()
         .instance()
-        .get(&Symbol::new(env, "admin"))
+        .get(&DataKey::Admin)
         .ok_or(Error::NotInitialized)
 }
 
@@ -131,16 +584,14 @@ pub fn do_rotate_admin(env: &Env, current_admin: Address, new_admin: Address) ->
     let stored_admin: Address = env
         .storage()
         .instance()
-        .get(&Symbol::new(env, "admin"))
+        .get(&DataKey::Admin)
         .ok_or(Error::NotInitialized)?;
 
     if current_admin != stored_admin {
         return Err(Error::Forbidden);
     }
 
-    env.storage()
-        .instance()
-        .set(&Symbol::new(env, "admin"), &new_admin);
+    env.storage().instance().set(&DataKey::Admin, &new_admin);
 
     env.events().publish(
         (Symbol::new(env, "admin_rotation"), current_admin.clone()),
@@ -150,6 +601,26 @@ pub fn do_rotate_admin(env: &Env, current_admin: Address, new_admin: Address) ->
     Ok(())
 }
 
+/// Rotates the admin, replaces the billing agent list and the fee recipient in one
+/// call, so a key rotation never leaves old agents authorized or fees flowing to an
+/// address controlled by the outgoing operator.
+///
+/// Authorized by the current admin. Emits the same `admin_rotation`,
+/// `billing_agents_set` and `fee_recipient_updated` events as the individual
+/// entrypoints.
+pub fn do_handoff_admin(
+    env: &Env,
+    current_admin: Address,
+    new_admin: Address,
+    new_agents: Vec<Address>,
+    new_fee_recipient: Address,
+) -> Result<(), Error> {
+    do_rotate_admin(env, current_admin, new_admin)?;
+    store_billing_agents(env, &new_agents)?;
+    crate::fees::store_fee_recipient(env, new_fee_recipient);
+    Ok(())
+}
+
 pub fn do_recover_stranded_funds(
     env: &Env,
     admin: Address,
@@ -162,7 +633,7 @@ pub fn do_recover_stranded_funds(
     let stored_admin: Address = env
         .storage()
         .instance()
-        .get(&Symbol::new(env, "admin"))
+        .get(&DataKey::Admin)
         .ok_or(Error::NotInitialized)?;
 
     if admin != stored_admin {
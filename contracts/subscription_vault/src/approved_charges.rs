@@ -0,0 +1,153 @@
+//! Future one-off charges approved in advance by the subscriber.
+//!
+//! Upsells and scheduled add-ons need a charge outside the billing schedule, but a
+//! merchant-initiated one-off (`charge_one_off`) only has a monthly allowance as
+//! subscriber consent. An approval instead names one amount and a window: the subscriber signs
+//! [`do_approve_future_charge`], and the merchant, the billing engine or a billing agent
+//! executes it once with [`do_execute_approved_charge`] between `execute_after` and
+//! `expires_at`. The subscriber can revoke it until then.
+//!
+//! Executed charges are settled as one-off charges and emit `OneOffChargedEvent`.
+//!
+//! **PRs that only change approved future charges should edit this file only.**
+
+use crate::types::{ApprovedCharge, ConfigKey, DataKey, Error, RecordKey, SubscriptionStatus};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn approval_key(approval_id: u32) -> DataKey {
+    DataKey::Record(RecordKey::ApprovedCharge(approval_id))
+}
+
+fn next_approval_id(env: &Env) -> u32 {
+    let key = DataKey::Config(ConfigKey::NextApprovalId);
+    let id: u32 = env.storage().instance().get(&key).unwrap_or(0);
+    env.storage().instance().set(&key, &(id + 1));
+    id
+}
+
+/// Approval waiting to be executed, or `None` once executed, revoked or never issued.
+pub fn get_approved_charge(env: &Env, approval_id: u32) -> Option<ApprovedCharge> {
+    env.storage().instance().get(&approval_key(approval_id))
+}
+
+/// Subscriber approves a one-off charge of `amount`, executable once from
+/// `execute_after` until just before `expires_at`. Returns the approval ID.
+///
+/// Fails with `InvalidAmount` for a non-positive amount and `InvalidInput` unless
+/// `execute_after < expires_at` and `expires_at` is in the future.
+pub fn do_approve_future_charge(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    amount: i128,
+    execute_after: u64,
+    expires_at: u64,
+) -> Result<u32, Error> {
+    subscriber.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Forbidden);
+    }
+    if sub.status == SubscriptionStatus::Cancelled {
+        return Err(Error::NotActive);
+    }
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if execute_after >= expires_at || expires_at <= env.ledger().timestamp() {
+        return Err(Error::InvalidInput);
+    }
+
+    let approval_id = next_approval_id(env);
+    let approval = ApprovedCharge {
+        subscription_id,
+        amount,
+        execute_after,
+        expires_at,
+    };
+    env.storage()
+        .instance()
+        .set(&approval_key(approval_id), &approval);
+    env.events().publish(
+        (Symbol::new(env, "future_charge_approved"), subscription_id),
+        (approval_id, amount, execute_after, expires_at),
+    );
+    Ok(approval_id)
+}
+
+/// Subscriber withdraws an approval that has not been executed yet.
+pub fn do_revoke_approved_charge(
+    env: &Env,
+    approval_id: u32,
+    subscriber: Address,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let approval = get_approved_charge(env, approval_id).ok_or(Error::NotFound)?;
+    let sub = crate::queries::get_subscription(env, approval.subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Forbidden);
+    }
+    env.storage().instance().remove(&approval_key(approval_id));
+    env.events().publish(
+        (
+            Symbol::new(env, "approved_charge_revoked"),
+            approval.subscription_id,
+        ),
+        approval_id,
+    );
+    Ok(())
+}
+
+/// The subscription's merchant, the billing engine or a billing agent executes an
+/// approval within its window, debiting the approved amount from the prepaid balance.
+///
+/// Fails with `IntervalNotElapsed` before `execute_after`, `Expired` from
+/// `expires_at` on, `NotActive` unless the subscription is Active or Paused, and
+/// `InsufficientPrepaidBalance` if the balance cannot cover the amount; the approval is
+/// kept in each case. It is used up once executed.
+pub fn do_execute_approved_charge(
+    env: &Env,
+    approval_id: u32,
+    caller: Address,
+) -> Result<(), Error> {
+    let approval = get_approved_charge(env, approval_id).ok_or(Error::NotFound)?;
+    let mut sub = crate::queries::get_subscription(env, approval.subscription_id)?;
+    if caller == sub.merchant {
+        crate::liveness::require_merchant_auth(env, &caller);
+    } else {
+        crate::admin::require_billing_operator(env, &caller)?;
+    }
+
+    let now = env.ledger().timestamp();
+    if now < approval.execute_after {
+        return Err(Error::IntervalNotElapsed);
+    }
+    if now >= approval.expires_at {
+        return Err(Error::Expired);
+    }
+    if sub.status != SubscriptionStatus::Active && sub.status != SubscriptionStatus::Paused {
+        return Err(Error::NotActive);
+    }
+    if sub.prepaid_balance < approval.amount {
+        return Err(Error::InsufficientPrepaidBalance);
+    }
+
+    crate::charge_core::settle(
+        env,
+        approval.subscription_id,
+        &mut sub,
+        crate::charge_core::ChargeKind::OneOff { memo_hash: None },
+        approval.amount,
+    )?;
+    let storage = env.storage().instance();
+    storage.set(&DataKey::Sub(approval.subscription_id), &sub);
+    storage.remove(&approval_key(approval_id));
+    env.events().publish(
+        (
+            Symbol::new(env, "approved_charge_executed"),
+            approval.subscription_id,
+        ),
+        (approval_id, approval.amount),
+    );
+    Ok(())
+}
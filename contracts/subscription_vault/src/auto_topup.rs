@@ -0,0 +1,134 @@
+//! Subscriber opt-in auto-topup from the wallet via a token allowance.
+//!
+//! When an interval charge finds the prepaid balance short and secondary token balances
+//! (see `multi_token.rs`) cannot cover it either, the shortfall is pulled from the
+//! subscriber's wallet with `transfer_from`, using an allowance the subscriber granted
+//! the vault, and added to the prepaid balance before the charge settles. Pulls are
+//! capped per billing period. If the allowance, wallet balance or cap cannot cover the
+//! whole shortfall nothing is pulled and the charge fails as before.
+//!
+//! **PRs that only change auto-topup should edit this file only.**
+
+use crate::safe_math::safe_add_balance;
+use crate::types::{AutoTopup, AutoTopupEvent, DataKey, Error, SubKey, Subscription};
+use soroban_sdk::{token, Address, Env, Symbol};
+
+fn auto_topup_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::AutoTopup, subscription_id)
+}
+
+fn load_owned(env: &Env, subscription_id: u32, subscriber: &Address) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != *subscriber {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+/// Auto-topup setting of the subscription, or `None` if the subscriber has not opted in.
+pub fn get_auto_topup(env: &Env, subscription_id: u32) -> Option<AutoTopup> {
+    env.storage()
+        .instance()
+        .get(&auto_topup_key(subscription_id))
+}
+
+/// Subscriber opts in to pulling charge shortfalls from their wallet, at most
+/// `max_pull_per_period` per billing period. Replaces any earlier setting; the amount
+/// already pulled in the current period still counts against the new cap.
+pub fn do_enable_auto_topup(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    max_pull_per_period: i128,
+) -> Result<(), Error> {
+    load_owned(env, subscription_id, &subscriber)?;
+    if max_pull_per_period <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let mut setting = get_auto_topup(env, subscription_id).unwrap_or(AutoTopup {
+        max_pull_per_period,
+        period_index: 0,
+        pulled_in_period: 0,
+    });
+    setting.max_pull_per_period = max_pull_per_period;
+    env.storage()
+        .instance()
+        .set(&auto_topup_key(subscription_id), &setting);
+    env.events().publish(
+        (Symbol::new(env, "auto_topup_enabled"), subscription_id),
+        max_pull_per_period,
+    );
+    Ok(())
+}
+
+/// Subscriber stops auto-topup on the subscription.
+pub fn do_disable_auto_topup(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+) -> Result<(), Error> {
+    load_owned(env, subscription_id, &subscriber)?;
+    env.storage()
+        .instance()
+        .remove(&auto_topup_key(subscription_id));
+    env.events().publish(
+        (Symbol::new(env, "auto_topup_disabled"), subscription_id),
+        subscriber,
+    );
+    Ok(())
+}
+
+/// Pulls `shortfall` from the subscriber's wallet into `sub.prepaid_balance` for the
+/// charge of billing period `period_index`. Returns `false`, without pulling anything,
+/// if auto-topup is off or the period cap, allowance or wallet balance is too small.
+/// The caller stores `sub`.
+pub fn pull_shortfall(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    shortfall: i128,
+    period_index: u64,
+) -> Result<bool, Error> {
+    let Some(mut setting) = get_auto_topup(env, subscription_id) else {
+        return Ok(false);
+    };
+    if setting.period_index != period_index {
+        setting.period_index = period_index;
+        setting.pulled_in_period = 0;
+    }
+    let pulled_in_period = safe_add_balance(setting.pulled_in_period, shortfall)?;
+    if pulled_in_period > setting.max_pull_per_period {
+        return Ok(false);
+    }
+
+    let vault = env.current_contract_address();
+    let token = token::Client::new(
+        env,
+        &crate::billing_tokens::subscription_token(env, subscription_id)?,
+    );
+    if token.allowance(&sub.subscriber, &vault) < shortfall
+        || token.balance(&sub.subscriber) < shortfall
+        || token
+            .try_transfer_from(&vault, &sub.subscriber, &vault, &shortfall)
+            .is_err()
+    {
+        return Ok(false);
+    }
+
+    sub.prepaid_balance = safe_add_balance(sub.prepaid_balance, shortfall)?;
+    setting.pulled_in_period = pulled_in_period;
+    env.storage()
+        .instance()
+        .set(&auto_topup_key(subscription_id), &setting);
+    env.events().publish(
+        (Symbol::new(env, "auto_topup"), subscription_id),
+        AutoTopupEvent {
+            subscription_id,
+            subscriber: sub.subscriber.clone(),
+            amount: shortfall,
+            pulled_in_period,
+        },
+    );
+    Ok(true)
+}
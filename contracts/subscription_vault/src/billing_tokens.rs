@@ -0,0 +1,128 @@
+//! Per-subscription billing tokens.
+//!
+//! The vault token set at `init` stays the default. The admin allowlists further Stellar
+//! assets with [`do_set_billing_token`], and a subscription created with
+//! [`do_create_subscription_with_token`] is billed in one of them: its prepaid balance,
+//! deposits, charges, refunds and withdrawals are all in that token. The token is kept
+//! under its own key rather than on `Subscription`, so the encoded record is unchanged
+//! and subscriptions without one keep billing in the vault token.
+//!
+//! Charges of such a subscription are credited to the merchant's balance in its token
+//! (see `multi_token.rs`), withdrawn with `withdraw_merchant_token_funds`. The platform
+//! fee, bundle splits, rolling reserve, secondary token balances and merchant statements
+//! are kept in vault-token units, so they do not apply to it, and disputes and usage
+//! flags are refused.
+//!
+//! **PRs that only change billing tokens should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::types::{DataKey, Error, RecordKey, SubKey};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn allowed_key(token: &Address) -> DataKey {
+    DataKey::Record(RecordKey::BillingToken(token.clone()))
+}
+
+fn sub_token_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::BillingToken, subscription_id)
+}
+
+/// Whether the admin allows new subscriptions to bill in `token`, besides the vault
+/// token.
+pub fn is_billing_token(env: &Env, token: &Address) -> bool {
+    env.storage().instance().has(&allowed_key(token))
+}
+
+/// Admin adds `token` to the billing token allowlist, or removes it. Removing a token
+/// only stops new subscriptions from billing in it.
+pub fn do_set_billing_token(
+    env: &Env,
+    admin: Address,
+    token: Address,
+    allowed: bool,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    if token == crate::admin::get_token(env)? {
+        return Err(Error::InvalidInput);
+    }
+    let key = allowed_key(&token);
+    if allowed {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events()
+        .publish((Symbol::new(env, "billing_token_set"), token), allowed);
+    Ok(())
+}
+
+/// Token the subscription bills in, or `None` for the vault token.
+pub fn get_billing_token(env: &Env, subscription_id: u32) -> Option<Address> {
+    env.storage()
+        .instance()
+        .get(&sub_token_key(subscription_id))
+}
+
+/// Token the subscription's funds move in: its billing token or the vault token.
+pub fn subscription_token(env: &Env, subscription_id: u32) -> Result<Address, Error> {
+    match get_billing_token(env, subscription_id) {
+        Some(token) => Ok(token),
+        None => crate::admin::get_token(env),
+    }
+}
+
+/// Records `token` as the subscription's billing token (the vault token clears the
+/// entry). Moves no funds.
+pub fn set_subscription_token(
+    env: &Env,
+    subscription_id: u32,
+    token: &Address,
+) -> Result<(), Error> {
+    let key = sub_token_key(subscription_id);
+    if *token == crate::admin::get_token(env)? {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, token);
+    }
+    Ok(())
+}
+
+/// Fails with `BillingTokenUnsupported` unless the subscription bills in the vault
+/// token.
+#[cfg_attr(not(feature = "disputes"), allow(dead_code))]
+pub fn ensure_vault_token(env: &Env, subscription_id: u32) -> Result<(), Error> {
+    if get_billing_token(env, subscription_id).is_some() {
+        return Err(Error::BillingTokenUnsupported);
+    }
+    Ok(())
+}
+
+/// Subscriber creates a subscription billed in `token`, which must be the vault token
+/// or on the allowlist (`TokenNotAccepted` otherwise).
+pub fn do_create_subscription_with_token(
+    env: &Env,
+    subscriber: Address,
+    merchant: Address,
+    amount: i128,
+    interval_seconds: u64,
+    usage_enabled: bool,
+    token: Address,
+) -> Result<u32, Error> {
+    let vault_token = crate::admin::get_token(env)?;
+    if token != vault_token && !is_billing_token(env, &token) {
+        return Err(Error::TokenNotAccepted);
+    }
+    let id = crate::subscription::do_create_subscription(
+        env,
+        subscriber,
+        merchant,
+        amount,
+        interval_seconds,
+        usage_enabled,
+    )?;
+    set_subscription_token(env, id, &token)?;
+    Ok(id)
+}
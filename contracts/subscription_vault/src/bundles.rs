@@ -0,0 +1,169 @@
+//! Subscription bundles: one interval charge split across several merchants.
+//!
+//! The subscription's own merchant (typically the platform selling the bundle)
+//! attaches weighted line items. Each interval charge is still a single debit from
+//! the subscriber's vault; the credited amount is then divided across line-item
+//! merchants by weight, with one event per line item.
+//!
+//! Disputes and refunds of a bundled subscription are taken from the line-item
+//! merchants by the same weights, since they are the ones the charges went to. While a
+//! dispute or usage flag is open the line items cannot change, so the hold is released
+//! from the same merchants it was placed on.
+//!
+//! **PRs that only change bundle splitting should edit this file only.**
+
+use crate::merchant::{available_balance, credit_merchant_balance};
+use crate::queries::get_subscription;
+use crate::types::{BundleItemChargedEvent, BundleLineItem, DataKey, Error};
+use soroban_sdk::{Address, Env, Symbol, Vec};
+
+/// Maximum number of line items per bundle (bounds per-charge work).
+pub const MAX_BUNDLE_ITEMS: u32 = 10;
+
+pub fn get_bundle_items(env: &Env, subscription_id: u32) -> Vec<BundleLineItem> {
+    env.storage()
+        .instance()
+        .get(&DataKey::Bundle(subscription_id))
+        .unwrap_or(Vec::new(env))
+}
+
+/// The subscription's merchant sets (or, with an empty list, clears) its line items.
+///
+/// Every weight must be positive and at most [`MAX_BUNDLE_ITEMS`] items are allowed.
+pub fn do_set_bundle_items(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    items: Vec<BundleLineItem>,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+
+    let sub = get_subscription(env, subscription_id)?;
+    if merchant != sub.merchant {
+        return Err(Error::Forbidden);
+    }
+    if items.len() > MAX_BUNDLE_ITEMS {
+        return Err(Error::InvalidInput);
+    }
+    for item in items.iter() {
+        if item.weight == 0 {
+            return Err(Error::InvalidInput);
+        }
+    }
+    #[cfg(feature = "disputes")]
+    if crate::disputes::has_open_disputes(env, subscription_id) {
+        return Err(Error::DisputeAlreadyOpen);
+    }
+
+    let key = DataKey::Bundle(subscription_id);
+    if items.is_empty() {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &items);
+    }
+    Ok(())
+}
+
+/// Splits `amount` across the merchants a charge of the subscription is credited to:
+/// the bundle's line items by weight, or `merchant` alone without a bundle. Rounding
+/// dust goes to the last line item.
+pub fn split_charge(
+    env: &Env,
+    subscription_id: u32,
+    merchant: &Address,
+    amount: i128,
+) -> Result<Vec<(Address, i128)>, Error> {
+    let items = get_bundle_items(env, subscription_id);
+    let mut shares = Vec::new(env);
+    if items.is_empty() {
+        shares.push_back((merchant.clone(), amount));
+        return Ok(shares);
+    }
+
+    let total_weight: i128 = items.iter().map(|item| item.weight as i128).sum();
+    let last = items.len() - 1;
+    let mut remaining = amount;
+    for (index, item) in items.iter().enumerate() {
+        let share = if index as u32 == last {
+            remaining
+        } else {
+            amount
+                .checked_mul(item.weight as i128)
+                .ok_or(Error::Overflow)?
+                / total_weight
+        };
+        remaining -= share;
+        shares.push_back((item.merchant, share));
+    }
+    Ok(shares)
+}
+
+/// Largest refund up to `amount` whose split (see [`split_charge`]) every merchant it is
+/// taken from can pay out of its available balance.
+pub fn payable_refund(
+    env: &Env,
+    subscription_id: u32,
+    merchant: &Address,
+    amount: i128,
+) -> Result<i128, Error> {
+    let items = get_bundle_items(env, subscription_id);
+    if items.is_empty() {
+        return Ok(amount.min(available_balance(env, merchant)?).max(0));
+    }
+    let total_weight: i128 = items.iter().map(|item| item.weight as i128).sum();
+    let last = items.len() - 1;
+    let last_merchant = items.get_unchecked(last).merchant;
+    let mut payable = amount;
+    let mut seen = Vec::new(env);
+    for item in items.iter() {
+        if seen.contains(&item.merchant) {
+            continue;
+        }
+        let weight: i128 = items
+            .iter()
+            .filter(|other| other.merchant == item.merchant)
+            .map(|other| other.weight as i128)
+            .sum();
+        let mut available = available_balance(env, &item.merchant)?;
+        if item.merchant == last_merchant {
+            // The last line item also takes the rounding dust: under one unit per other item.
+            available -= last as i128;
+        }
+        let bound = available
+            .max(0)
+            .checked_mul(total_weight)
+            .ok_or(Error::Overflow)?
+            / weight;
+        payable = payable.min(bound);
+        seen.push_back(item.merchant);
+    }
+    Ok(payable.max(0))
+}
+
+/// Credits a charged `amount` to the subscription's merchant, or splits it across the
+/// bundle's line items by weight (see [`split_charge`]).
+pub fn credit_charge(
+    env: &Env,
+    subscription_id: u32,
+    merchant: &Address,
+    amount: i128,
+) -> Result<(), Error> {
+    if get_bundle_items(env, subscription_id).is_empty() {
+        return credit_merchant_balance(env, merchant, amount);
+    }
+    let shares = split_charge(env, subscription_id, merchant, amount)?;
+    for (index, (item_merchant, share)) in shares.iter().enumerate() {
+        credit_merchant_balance(env, &item_merchant, share)?;
+
+        env.events().publish(
+            (Symbol::new(env, "bundle_item_charged"), subscription_id),
+            BundleItemChargedEvent {
+                subscription_id,
+                item_index: index as u32,
+                merchant: item_merchant,
+                amount: share,
+            },
+        );
+    }
+    Ok(())
+}
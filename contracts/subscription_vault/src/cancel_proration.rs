@@ -0,0 +1,134 @@
+//! Prorated refund of the current period when a subscription is cancelled.
+//!
+//! Interval charges are taken in advance: the charge booked at `last_payment_timestamp`
+//! pays for the period up to `last_payment_timestamp + interval_seconds`. A merchant
+//! choosing [`CancellationProration::Linear`] gives back the part of that period left
+//! when the subscription is cancelled, in proportion to the time remaining. The refund
+//! is debited from the merchant's accrued balance and credited to the prepaid balance,
+//! where the subscriber withdraws it with the rest.
+//!
+//! The refund is based on the last interval charge actually taken, recorded by
+//! [`record_interval_charge`] while the policy is on, so a period that was skipped or
+//! rolled over, or charged before the merchant chose `Linear`, refunds nothing. It goes
+//! through `refunds.rs`: it counts against the refundable total and emits `refunded`.
+//! It is capped at what the merchant's balance can pay at the time rather than failing
+//! the cancellation; for a bundle, at what every line-item merchant can pay of its
+//! share (see `bundles.rs`).
+//!
+//! **PRs that only change cancellation proration should edit this file only.**
+
+use crate::types::{CancellationProration, DataKey, Error, MerchantKey, SubKey, Subscription};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn proration_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::CancelProration, merchant.clone())
+}
+
+fn last_charge_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::LastPeriodCharge, subscription_id)
+}
+
+/// The merchant's cancellation proration policy.
+pub fn get_cancel_proration(env: &Env, merchant: &Address) -> CancellationProration {
+    env.storage()
+        .instance()
+        .get(&proration_key(merchant))
+        .unwrap_or(CancellationProration::None)
+}
+
+/// Merchant chooses whether cancelling mid-period refunds the unused part of it.
+pub fn do_set_cancel_proration(
+    env: &Env,
+    merchant: Address,
+    policy: CancellationProration,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    env.storage()
+        .instance()
+        .set(&proration_key(&merchant), &policy);
+    env.events()
+        .publish((Symbol::new(env, "cancel_proration_set"), merchant), policy);
+    Ok(())
+}
+
+/// Remembers the interval charge of `amount` booked at `paid_at`, if the merchant
+/// refunds unused periods. Skipped otherwise to keep the charge path lean.
+pub fn record_interval_charge(
+    env: &Env,
+    subscription_id: u32,
+    merchant: &Address,
+    paid_at: u64,
+    amount: i128,
+) {
+    if get_cancel_proration(env, merchant) == CancellationProration::None {
+        return;
+    }
+    env.storage()
+        .instance()
+        .set(&last_charge_key(subscription_id), &(paid_at, amount));
+}
+
+/// Converts the remembered last charge into another billing token's units when the
+/// subscription is migrated (see `token_migration.rs`).
+pub fn convert_last_charge(
+    env: &Env,
+    subscription_id: u32,
+    convert: impl Fn(i128) -> Result<i128, Error>,
+) -> Result<(), Error> {
+    let last_charge: Option<(u64, i128)> = env
+        .storage()
+        .instance()
+        .get(&last_charge_key(subscription_id));
+    if let Some((paid_at, amount)) = last_charge {
+        env.storage().instance().set(
+            &last_charge_key(subscription_id),
+            &(paid_at, convert(amount)?),
+        );
+    }
+    Ok(())
+}
+
+/// Refunds the unused part of the current period into `sub`'s prepaid balance if the
+/// merchant's policy asks for it. Returns the amount refunded.
+pub fn refund_unused_period(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+) -> Result<i128, Error> {
+    if get_cancel_proration(env, &sub.merchant) == CancellationProration::None {
+        return Ok(0);
+    }
+    let last_charge: Option<(u64, i128)> = env
+        .storage()
+        .instance()
+        .get(&last_charge_key(subscription_id));
+    let Some((paid_at, charged)) = last_charge else {
+        return Ok(0);
+    };
+    if paid_at != sub.last_payment_timestamp || sub.interval_seconds == 0 {
+        return Ok(0);
+    }
+    let period_end = paid_at.saturating_add(sub.interval_seconds);
+    let now = env.ledger().timestamp().max(paid_at);
+    if now >= period_end {
+        return Ok(0);
+    }
+    let unused = i128::from(period_end - now);
+    let prorated = charged.saturating_mul(unused) / i128::from(sub.interval_seconds);
+    let amount = prorated.min(crate::refunds::get_refundable_amount(env, subscription_id));
+    // Capped at what the merchants the refund is taken from can pay: the line-item
+    // merchants of a bundle, by weight, rather than the subscription's merchant.
+    let amount = match crate::billing_tokens::get_billing_token(env, subscription_id) {
+        Some(token) => amount.min(crate::multi_token::get_merchant_token_balance(
+            env,
+            &sub.merchant,
+            &token,
+        )),
+        None => crate::bundles::payable_refund(env, subscription_id, &sub.merchant, amount)?,
+    };
+    if amount <= 0 {
+        return Ok(0);
+    }
+    crate::refunds::apply_refund(env, subscription_id, sub, amount, false)?;
+    Ok(amount)
+}
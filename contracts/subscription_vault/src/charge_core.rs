@@ -13,116 +13,335 @@
 //! - **Optional idempotency key**: If the caller supplies an idempotency key (e.g. for retries),
 
 #![allow(dead_code)]
-//!   we store one key per subscription. A second call with the same key returns `Ok(())` without
-//!   debiting again (idempotent success). Storage stays bounded (one key and one period per sub).
+//!   we keep it per subscription for [`IDEMPOTENCY_RETENTION_SECONDS`]. A retry with a
+//!   retained key returns `Ok(())` without debiting again (idempotent success). A different
+//!   key for a period that was already charged is a conflict and fails with
+//!   [`Error::Replay`]. Storage stays bounded: at most [`MAX_IDEMPOTENCY_KEYS`] keys and one
+//!   period per subscription.
 
-use crate::queries::get_subscription;
+use crate::error_context::record as record_error;
+use crate::queries::{get_charge_mask, get_subscription};
 use crate::safe_math::safe_sub_balance;
 use crate::state_machine::validate_status_transition;
-use crate::types::{Error, SubscriptionChargedEvent, SubscriptionStatus};
-use soroban_sdk::{symbol_short, Env, Symbol};
+use crate::storage_migration::legacy_charged_period_key;
+use crate::types::{
+    DataKey, Error, IdempotencyRecord, OneOffChargedEvent, SubKey, Subscription,
+    SubscriptionChargedEvent, SubscriptionStatus,
+};
+use soroban_sdk::{symbol_short, Address, BytesN, Env, Symbol, TryFromVal, Val, Vec};
 
-const KEY_CHARGED_PERIOD: Symbol = symbol_short!("cp");
-const KEY_IDEM: Symbol = symbol_short!("idem");
+fn charged_period_key(subscription_id: u32) -> DataKey {
+    DataKey::ChargedPeriod(subscription_id)
+}
+
+/// How long a processed idempotency key is remembered (7 days).
+pub const IDEMPOTENCY_RETENTION_SECONDS: u64 = 7 * 24 * 60 * 60;
 
-fn charged_period_key(subscription_id: u32) -> (Symbol, u32) {
-    (KEY_CHARGED_PERIOD, subscription_id)
+/// Most idempotency keys retained per subscription; the oldest is dropped first.
+pub const MAX_IDEMPOTENCY_KEYS: u32 = 8;
+
+fn idem_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::Idempotency, subscription_id)
 }
 
-fn idem_key(subscription_id: u32) -> (Symbol, u32) {
-    (KEY_IDEM, subscription_id)
+/// Idempotency keys processed for the subscription within the retention window.
+fn retained_keys(env: &Env, subscription_id: u32, now: u64) -> Vec<IdempotencyRecord> {
+    let stored: Vec<IdempotencyRecord> = env
+        .storage()
+        .instance()
+        .get(&idem_key(subscription_id))
+        .unwrap_or(Vec::new(env));
+    let mut retained = Vec::new(env);
+    for record in stored.iter() {
+        let expires = record
+            .processed_at
+            .saturating_add(IDEMPOTENCY_RETENTION_SECONDS);
+        if now < expires {
+            retained.push_back(record);
+        }
+    }
+    retained
+}
+
+fn remember_key(env: &Env, subscription_id: u32, key: BytesN<32>, now: u64) {
+    let mut keys = retained_keys(env, subscription_id, now);
+    if keys.len() >= MAX_IDEMPOTENCY_KEYS {
+        keys.pop_front();
+    }
+    keys.push_back(IdempotencyRecord {
+        key,
+        processed_at: now,
+    });
+    env.storage()
+        .instance()
+        .set(&idem_key(subscription_id), &keys);
+}
+
+/// Billing period index of the most recent booked interval charge, if any, and the
+/// merchant's net credit for it (see [`settle_checked`]): 0 for a skipped or rolled-over
+/// period, `None` for a period booked before amounts were recorded, which stored the
+/// index alone. Until
+/// storage is migrated, a period still under its legacy key is read from there.
+pub fn last_charge(env: &Env, subscription_id: u32) -> Option<(u64, Option<i128>)> {
+    let storage = env.storage().instance();
+    let stored: Val = storage
+        .get(&charged_period_key(subscription_id))
+        .or_else(|| storage.get(&legacy_charged_period_key(subscription_id)))?;
+    if let Ok(period) = u64::try_from_val(env, &stored) {
+        return Some((period, None));
+    }
+    let (period, amount) = <(u64, i128)>::try_from_val(env, &stored).ok()?;
+    Some((period, Some(amount)))
+}
+
+/// Billing period index of the most recent booked interval charge, if any.
+pub fn last_charged_period(env: &Env, subscription_id: u32) -> Option<u64> {
+    last_charge(env, subscription_id).map(|(period, _)| period)
+}
+
+/// Converts the amount recorded for the last charged period into another billing
+/// token's units when the subscription is migrated (see `token_migration.rs`).
+pub fn convert_last_charge(
+    env: &Env,
+    subscription_id: u32,
+    convert: impl Fn(i128) -> Result<i128, Error>,
+) -> Result<(), Error> {
+    if let Some((period, Some(amount))) = last_charge(env, subscription_id) {
+        env.storage().instance().set(
+            &charged_period_key(subscription_id),
+            &(period, convert(amount)?),
+        );
+    }
+    Ok(())
 }
 
 /// Performs a single interval-based charge with optional replay protection.
 ///
 /// # Idempotency
 ///
-/// - If `idempotency_key` is `Some(k)` and we already processed this subscription with key `k`,
-///   returns `Ok(())` without changing state (idempotent success).
+/// - If `idempotency_key` is `Some(k)` and we processed this subscription with key `k` within
+///   the retention window, returns `Ok(())` without changing state (idempotent success).
 /// - Otherwise we derive a period from `now / interval_seconds`. If this period was already
 ///   charged, returns `Err(Error::Replay)`.
 ///
+/// # Clock-skew tolerance
+///
+/// A charge up to `charge_tolerance_seconds` (admin config) before the interval boundary is
+/// accepted and booked as if taken exactly at the boundary.
+///
 /// # Storage
 ///
-/// Bounded: one `u64` (last charged period) and optionally one idempotency key per subscription.
+/// Bounded: one `u64` (last charged period) and up to [`MAX_IDEMPOTENCY_KEYS`] retained keys per
+/// subscription.
 pub fn charge_one(
     env: &Env,
     subscription_id: u32,
     now: u64,
-    idempotency_key: Option<soroban_sdk::BytesN<32>>,
+    idempotency_key: Option<BytesN<32>>,
 ) -> Result<(), Error> {
+    charge_interval(env, subscription_id, now, false, idempotency_key, None).map(|_| ())
+}
+
+/// [`charge_one`] for batches: fails with `BatchDebitCapExceeded`, before any funds move,
+/// if the amount due exceeds `max_debit`. Returns the amount charged, 0 when the period
+/// was skipped, rolled over or already handled under `idempotency_key`.
+pub fn charge_one_within(
+    env: &Env,
+    subscription_id: u32,
+    now: u64,
+    idempotency_key: Option<BytesN<32>>,
+    max_debit: Option<i128>,
+) -> Result<i128, Error> {
+    charge_interval(env, subscription_id, now, false, idempotency_key, max_debit)
+}
+
+/// Charges up to `max_intervals` intervals that fell due since the last payment, oldest
+/// first, and returns how many were charged.
+///
+/// Each interval is booked at its own boundary (`last_payment_timestamp + interval`), so
+/// it gets its own billing period and `SubscriptionChargedEvent`, and the schedule ends
+/// on the last boundary charged rather than on `now`. Any interval that cannot be
+/// charged fails the whole call. A pending skip (see `skips.rs`) consumes its interval
+/// without a debit.
+pub fn charge_missed_intervals(
+    env: &Env,
+    subscription_id: u32,
+    now: u64,
+    max_intervals: u32,
+) -> Result<u32, Error> {
+    if max_intervals == 0 {
+        return Err(Error::InvalidInput);
+    }
+    // The interval is re-read each time: pending terms (see `terms.rs`) can change it
+    // once the first boundary is booked.
+    let mut charged = 0;
+    while charged < max_intervals {
+        let sub = get_subscription(env, subscription_id)?;
+        if now.saturating_sub(sub.last_payment_timestamp) < sub.interval_seconds {
+            break;
+        }
+        charge_interval(env, subscription_id, now, true, None, None)?;
+        charged += 1;
+    }
+    if charged == 0 {
+        return Err(Error::IntervalNotElapsed);
+    }
+    Ok(charged)
+}
+
+/// Shared body of [`charge_one`] and [`charge_missed_intervals`]. With `at_boundary` the
+/// charge is booked at the interval boundary instead of `now`. Returns the amount charged.
+fn charge_interval(
+    env: &Env,
+    subscription_id: u32,
+    now: u64,
+    at_boundary: bool,
+    idempotency_key: Option<BytesN<32>>,
+    max_debit: Option<i128>,
+) -> Result<i128, Error> {
     let mut sub = get_subscription(env, subscription_id)?;
 
     if sub.status != SubscriptionStatus::Active && sub.status != SubscriptionStatus::GracePeriod {
-        return Err(Error::NotActive);
+        return Err(record_error(env, subscription_id, Error::NotActive, 0, 0));
+    }
+    if !get_charge_mask(env, subscription_id).allow_interval {
+        return Err(Error::ChargeTypeBlocked);
     }
+    crate::promo::ensure_chargeable(env, subscription_id)?;
 
-    let period_index = now / sub.interval_seconds;
+    let next_allowed = sub
+        .last_payment_timestamp
+        .checked_add(sub.interval_seconds)
+        .ok_or(Error::Overflow)?;
+    // A charge landing within the clock-skew tolerance before the boundary is treated as
+    // landing exactly on it, so the schedule does not drift earlier.
+    let tolerance = crate::admin::get_charge_tolerance(env);
+    let within_tolerance = now < next_allowed && now.saturating_add(tolerance) >= next_allowed;
+    let charged_at = if within_tolerance || (at_boundary && now >= next_allowed) {
+        next_allowed
+    } else {
+        now
+    };
+
+    let period_index = charged_at / sub.interval_seconds;
 
     // Idempotent return: same idempotency key already processed for this subscription
     if let Some(ref k) = idempotency_key {
-        if let Some(stored) = env
-            .storage()
-            .instance()
-            .get::<_, soroban_sdk::BytesN<32>>(&idem_key(subscription_id))
+        if retained_keys(env, subscription_id, now)
+            .iter()
+            .any(|record| record.key == *k)
         {
-            if stored == *k {
-                return Ok(());
-            }
+            return Ok(0);
         }
     }
 
     // Replay: already charged for this billing period (derived key)
-    if let Some(stored_period) = env
-        .storage()
-        .instance()
-        .get::<_, u64>(&charged_period_key(subscription_id))
-    {
+    if let Some(stored_period) = last_charged_period(env, subscription_id) {
         if period_index <= stored_period {
-            return Err(Error::Replay);
+            return Err(record_error(
+                env,
+                subscription_id,
+                Error::Replay,
+                period_index.into(),
+                stored_period.into(),
+            ));
         }
     }
 
-    let next_allowed = sub
-        .last_payment_timestamp
-        .checked_add(sub.interval_seconds)
-        .ok_or(Error::Overflow)?;
-    if now < next_allowed {
-        return Err(Error::IntervalNotElapsed);
+    if charged_at < next_allowed {
+        return Err(record_error(
+            env,
+            subscription_id,
+            Error::IntervalNotElapsed,
+            charged_at.into(),
+            next_allowed.into(),
+        ));
     }
 
     let storage = env.storage().instance();
 
-    match safe_sub_balance(sub.prepaid_balance, sub.amount) {
-        Ok(new_balance) => {
-            sub.prepaid_balance = new_balance;
-            crate::merchant::credit_merchant_balance(env, &sub.merchant, sub.amount)?;
-            sub.last_payment_timestamp = now;
+    // The subscriber asked to skip this period, the merchant's hook reports its service
+    // down for it, or its amount is below the minimum charge and rolls over to the next
+    // one: book it as handled without a debit.
+    let base_amount = crate::loyalty::discounted_amount(env, subscription_id, &sub)?;
+    if crate::skips::take_pending(env, subscription_id, period_index)
+        || crate::notify::service_unavailable(env, subscription_id, &sub.merchant, period_index)
+        || crate::min_charge::roll_over_if_below_min(env, subscription_id, &sub, base_amount)?
+    {
+        sub.last_payment_timestamp = charged_at;
+        let period_index = renumber_period(env, subscription_id, &mut sub, period_index);
+        storage.set(&DataKey::Sub(subscription_id), &sub);
+        storage.set(&charged_period_key(subscription_id), &(period_index, 0i128));
+        if let Some(k) = idempotency_key {
+            remember_key(env, subscription_id, k, now);
+        }
+        return Ok(0);
+    }
+
+    let amount_due = interval_amount_due(env, subscription_id, &sub)?;
+    crate::spending_cap::ensure_within_cap(env, subscription_id, charged_at, amount_due)?;
+    if let Some(max_debit) = max_debit {
+        if amount_due > max_debit {
+            return Err(record_error(
+                env,
+                subscription_id,
+                Error::BatchDebitCapExceeded,
+                amount_due,
+                max_debit,
+            ));
+        }
+    }
+
+    // Every settlement guard runs before any funding source is touched: a batch entry
+    // failing a guard must not have pulled from a wallet first.
+    let interval_amount = safe_sub_balance(
+        amount_due,
+        crate::setup_fee::next_installment(env, subscription_id),
+    )?;
+    ensure_settleable(env, subscription_id, &mut sub, interval_amount)?;
+
+    match fund_interval_charge(env, subscription_id, &mut sub, period_index)? {
+        Some(funding) => {
             if sub.status == SubscriptionStatus::GracePeriod {
                 validate_status_transition(&sub.status, &SubscriptionStatus::Active)?;
                 sub.status = SubscriptionStatus::Active;
             }
+            let amount_due = funding.amount_due;
+            settle_interval_charge(
+                env,
+                subscription_id,
+                &mut sub,
+                funding,
+                charged_at,
+                period_index,
+            )?;
+            // Only settled charges count towards the merchant's SLA.
+            if !crate::sandbox::is_test_mode(env, subscription_id) {
+                crate::sla::record_charge_lateness(
+                    env,
+                    &sub.merchant,
+                    next_allowed,
+                    now.max(charged_at),
+                );
+            }
 
-            storage.set(&subscription_id, &sub);
-
-            // Record charged period and optional idempotency key (bounded storage)
-            storage.set(&charged_period_key(subscription_id), &period_index);
+            // Record optional idempotency key (bounded storage)
             if let Some(k) = idempotency_key {
-                storage.set(&idem_key(subscription_id), &k);
+                remember_key(env, subscription_id, k, now);
             }
 
-            env.events().publish(
-                (symbol_short!("charged"),),
-                SubscriptionChargedEvent {
-                    subscription_id,
-                    merchant: sub.merchant.clone(),
-                    amount: sub.amount,
-                },
+            Ok(amount_due)
+        }
+        None => {
+            crate::dunning::record_failed_charge(env, subscription_id, &sub.merchant, now);
+            record_error(
+                env,
+                subscription_id,
+                Error::InsufficientBalance,
+                sub.prepaid_balance,
+                interval_amount_due(env, subscription_id, &sub)?,
             );
 
-            Ok(())
-        }
-        Err(_) => {
             // Insufficient balance — check if grace period applies
             let grace_duration = crate::admin::get_grace_period(env).unwrap_or(0);
             let grace_expires = next_allowed
@@ -133,62 +352,575 @@ pub fn charge_one(
                 if sub.status != SubscriptionStatus::GracePeriod {
                     validate_status_transition(&sub.status, &SubscriptionStatus::GracePeriod)?;
                     sub.status = SubscriptionStatus::GracePeriod;
-                    storage.set(&subscription_id, &sub);
+                    storage.set(&DataKey::Sub(subscription_id), &sub);
                 }
                 Err(Error::InsufficientBalance)
             } else {
-                validate_status_transition(&sub.status, &SubscriptionStatus::InsufficientBalance)?;
-                sub.status = SubscriptionStatus::InsufficientBalance;
-                storage.set(&subscription_id, &sub);
+                // Outside the grace window the merchant's lapse action applies.
+                let target = crate::dunning::lapse_status(env, &sub.merchant);
+                validate_status_transition(&sub.status, &target)?;
+                sub.status = target;
+                storage.set(&DataKey::Sub(subscription_id), &sub);
                 Err(Error::InsufficientBalance)
             }
         }
     }
 }
 
+/// How an interval charge is funded (see [`fund_interval_charge`]).
+struct IntervalFunding {
+    /// The charge including any setup fee installment.
+    amount_due: i128,
+    /// Part taken from `prepaid_balance`.
+    base_debit: i128,
+    /// Secondary token draws covering the rest, applied once the charge settles.
+    token_draws: Vec<(Address, i128)>,
+}
+
+/// Funds one interval charge of the loyalty-discounted amount (see `loyalty.rs`) plus
+/// any setup fee installment owed (see `setup_fee.rs`): the prepaid (vault token)
+/// balance is drained first and any shortfall is planned from secondary token balances
+/// (see `multi_token.rs`) or, failing that, pulled into the prepaid balance from the
+/// subscriber's wallet (see `auto_topup.rs`) or else the guarantor's (see `guarantor.rs`).
+///
+/// The caller has already run [`ensure_settleable`], so nothing is pulled for a charge
+/// that would then be refused. Secondary token draws are only planned here; they are
+/// applied by [`settle_interval_charge`] after the charge settles.
+///
+/// Returns `None` when all sources together cannot cover the charge, in which case
+/// nothing is debited.
+fn fund_interval_charge(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    period_index: u64,
+) -> Result<Option<IntervalFunding>, Error> {
+    let amount_due = interval_amount_due(env, subscription_id, sub)?;
+    let base_debit = sub.prepaid_balance.clamp(0, amount_due);
+    let shortfall = safe_sub_balance(amount_due, base_debit)?;
+    if shortfall == 0 {
+        return Ok(Some(IntervalFunding {
+            amount_due,
+            base_debit,
+            token_draws: Vec::new(env),
+        }));
+    }
+    // Secondary token rates are in vault-token units, so they cannot cover a charge in
+    // another billing token.
+    if crate::billing_tokens::get_billing_token(env, subscription_id).is_none() {
+        if let Some(token_draws) =
+            crate::multi_token::plan_shortfall(env, subscription_id, shortfall)?
+        {
+            return Ok(Some(IntervalFunding {
+                amount_due,
+                base_debit,
+                token_draws,
+            }));
+        }
+    }
+    if crate::auto_topup::pull_shortfall(env, subscription_id, sub, shortfall, period_index)?
+        || crate::guarantor::pull_shortfall(env, subscription_id, sub, shortfall)?
+    {
+        return Ok(Some(IntervalFunding {
+            amount_due,
+            base_debit: amount_due,
+            token_draws: Vec::new(env),
+        }));
+    }
+    Ok(None)
+}
+
+/// Loyalty-discounted interval amount, prorated or blocked near the expiration (see
+/// `expiration.rs`) and adjusted for the minimum charge (see `min_charge.rs`), plus the
+/// next setup fee installment.
+fn interval_amount_due(env: &Env, subscription_id: u32, sub: &Subscription) -> Result<i128, Error> {
+    let base = crate::loyalty::discounted_amount(env, subscription_id, sub)?;
+    let due_at = sub
+        .last_payment_timestamp
+        .saturating_add(sub.interval_seconds);
+    let base = crate::expiration::expiry_adjusted_amount(env, subscription_id, sub, due_at, base)
+        .ok_or_else(|| {
+        record_error(
+            env,
+            subscription_id,
+            Error::BlockedByExpiry,
+            due_at.into(),
+            crate::expiration::get_expiration(env, subscription_id)
+                .unwrap_or(0)
+                .into(),
+        )
+    })?;
+    crate::min_charge::charge_amount(env, subscription_id, sub, base)?
+        .checked_add(crate::setup_fee::next_installment(env, subscription_id))
+        .ok_or(Error::Overflow)
+}
+
+/// Applies a successful interval charge funded by `funding`: debits its `base_debit`
+/// from the prepaid balance, credits it to the merchant (split across bundle line items,
+/// if any), applies the secondary token draws once that has settled, moves the schedule
+/// to `paid_at`, applies pending terms, records `period_index` with the merchant's net
+/// credit for replay protection and disputes, remembers the charge for cancellation
+/// proration (see `cancel_proration.rs`), counts the completed cycle (completing a
+/// fixed-term subscription on its last one, see `fixed_term.rs`) and emits
+/// `SubscriptionChargedEvent`. The setup fee installment included in `amount_due` is
+/// booked and reported separately by `setup_fee_charged`.
+fn settle_interval_charge(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    funding: IntervalFunding,
+    paid_at: u64,
+    period_index: u64,
+) -> Result<(), Error> {
+    let amount_due = funding.amount_due;
+    let setup_fee = crate::setup_fee::next_installment(env, subscription_id);
+    let interval_amount = safe_sub_balance(amount_due, setup_fee)?;
+    let credit = settle_checked(
+        env,
+        subscription_id,
+        sub,
+        ChargeKind::Interval {
+            amount_due: interval_amount,
+        },
+        funding.base_debit,
+    )?;
+    crate::multi_token::apply_draws(env, subscription_id, &sub.merchant, &funding.token_draws)?;
+    crate::cancel_proration::record_interval_charge(
+        env,
+        subscription_id,
+        &sub.merchant,
+        paid_at,
+        interval_amount,
+    );
+    if setup_fee > 0 {
+        crate::setup_fee::record_installment(env, subscription_id, setup_fee)?;
+    }
+    crate::round_up::apply(env, subscription_id, sub, amount_due)?;
+    crate::spending_cap::record_spend(env, subscription_id, paid_at, amount_due);
+    sub.last_payment_timestamp = paid_at;
+    let period_index = renumber_period(env, subscription_id, sub, period_index);
+
+    let storage = env.storage().instance();
+    storage.set(&DataKey::Sub(subscription_id), sub);
+    storage.set(
+        &charged_period_key(subscription_id),
+        &(period_index, credit),
+    );
+    crate::loyalty::record_completed_cycle(env, subscription_id);
+    crate::fixed_term::complete_if_last_cycle(env, subscription_id, sub);
+    crate::dunning::clear_failed_charges(env, subscription_id);
+    crate::min_charge::clear_carry(env, subscription_id);
+    crate::usage_tiers::reset_consumption(env, subscription_id);
+    Ok(())
+}
+
+/// Applies pending terms (see `terms.rs`) once the period `period_index` ending at
+/// `sub.last_payment_timestamp` is booked, and returns that period's index under the
+/// resulting interval, which is what replay protection compares the next charge with.
+fn renumber_period(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    period_index: u64,
+) -> u64 {
+    if crate::terms::apply_pending(env, subscription_id, sub) {
+        sub.last_payment_timestamp / sub.interval_seconds
+    } else {
+        period_index
+    }
+}
+
+/// Failsafe against racing charge paths: rejects a debit within the admin-configured
+/// `min_debit_spacing_seconds` of the subscription's previous one, whatever the charge
+/// type. The debit's time is recorded by [`record_debit_time`] once it settles.
+fn ensure_debit_spacing(env: &Env, subscription_id: u32) -> Result<(), Error> {
+    let spacing = crate::admin::get_min_debit_spacing(env);
+    if spacing == 0 {
+        return Ok(());
+    }
+    let now = env.ledger().timestamp();
+    if let Some(last) = env
+        .storage()
+        .instance()
+        .get::<_, u64>(&DataKey::SubState(SubKey::LastDebitAt, subscription_id))
+    {
+        if now < last.saturating_add(spacing) {
+            return Err(record_error(
+                env,
+                subscription_id,
+                Error::DebitTooSoon,
+                now.saturating_sub(last).into(),
+                spacing.into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Debit times are only recorded while the spacing floor is on, so it costs nothing
+/// when disabled and applies from the first debit after it is enabled.
+fn record_debit_time(env: &Env, subscription_id: u32) {
+    if crate::admin::get_min_debit_spacing(env) > 0 {
+        env.storage().instance().set(
+            &DataKey::SubState(SubKey::LastDebitAt, subscription_id),
+            &env.ledger().timestamp(),
+        );
+    }
+}
+
+/// Which charge path is settling, and therefore how the merchant is credited and which
+/// event reports it.
+pub enum ChargeKind {
+    /// Interval charge of `amount_due`; any part not in `debit` was already covered by
+    /// secondary tokens. Credited through the bundle split.
+    Interval { amount_due: i128 },
+    /// Metered usage, credited to the subscription's merchant.
+    Usage,
+    /// Merchant-initiated one-off charge, credited to the subscription's merchant. The
+    /// memo hash, if any, is reported in the event.
+    OneOff { memo_hash: Option<BytesN<32>> },
+}
+
+/// The single path by which prepaid funds move to merchants.
+///
+/// Debits `debit` from `sub.prepaid_balance`, credits it to the merchant side less the
+/// platform fee (see `fees.rs`), or in full to the merchant's balance in the
+/// subscription's billing token (see `billing_tokens.rs`), and emits the charge event,
+/// so an event can never be published without a matching token movement (or vice
+/// versa). A charge above the risk threshold must first be approved by the admin's risk
+/// oracle (see `risk.rs`). A balance left below the subscription's low-balance threshold
+/// is reported too (see `low_balance.rs`), and a merchant with automatic payouts on is
+/// paid out once it reaches its threshold (see `payouts.rs`). The caller stores `sub`.
+pub fn settle(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    kind: ChargeKind,
+    debit: i128,
+) -> Result<(), Error> {
+    let charged = match kind {
+        ChargeKind::Interval { amount_due } => amount_due,
+        ChargeKind::Usage | ChargeKind::OneOff { .. } => debit,
+    };
+    ensure_settleable(env, subscription_id, sub, charged)?;
+    settle_checked(env, subscription_id, sub, kind, debit)?;
+    Ok(())
+}
+
+/// Every guard [`settle`] applies to a charge of `charged`: the circuit breaker, the
+/// token fingerprint, merchant offboarding and liveness, the risk oracle and the debit
+/// spacing floor. Paths that fund a charge from outside the prepaid balance run this
+/// before touching any funding source, then settle with [`settle_checked`].
+pub fn ensure_settleable(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    charged: i128,
+) -> Result<(), Error> {
+    crate::circuit_breaker::ensure_closed(env)?;
+    crate::token_guard::ensure_token_unchanged(env)?;
+    crate::offboarding::ensure_merchant_active(env, &sub.merchant)?;
+    crate::liveness::ensure_merchant_live(env, subscription_id, sub)?;
+    crate::risk::ensure_approved(env, subscription_id, sub, charged)?;
+    ensure_debit_spacing(env, subscription_id)
+}
+
+/// [`settle`] without the guards, for callers that already ran [`ensure_settleable`].
+/// Returns what the merchant side was credited for `debit`: the debit less the platform
+/// fee, or the whole debit when billed in another token.
+fn settle_checked(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    kind: ChargeKind,
+    debit: i128,
+) -> Result<i128, Error> {
+    let charged = match kind {
+        ChargeKind::Interval { amount_due } => amount_due,
+        ChargeKind::Usage | ChargeKind::OneOff { .. } => debit,
+    };
+    record_debit_time(env, subscription_id);
+
+    #[cfg(any(test, feature = "settle-audit"))]
+    let merchant_side_before = audit::merchant_side_total(env, subscription_id, sub, &kind);
+
+    sub.prepaid_balance = safe_sub_balance(sub.prepaid_balance, debit)?;
+    let credit = if let Some(token) = crate::billing_tokens::get_billing_token(env, subscription_id)
+    {
+        // Billed in another token: credited in full to the merchant's balance in it.
+        crate::multi_token::credit_merchant_token_balance(env, &sub.merchant, &token, debit)?;
+        debit
+    } else {
+        let fee = crate::fees::take_platform_fee(env, subscription_id, &sub.merchant, debit)?;
+        let credit = safe_sub_balance(debit, fee)?;
+        match kind {
+            ChargeKind::Interval { .. } => {
+                crate::bundles::credit_charge(env, subscription_id, &sub.merchant, credit)?
+            }
+            ChargeKind::Usage | ChargeKind::OneOff { .. } => {
+                crate::merchant::credit_merchant_balance(env, &sub.merchant, credit)?
+            }
+        }
+        credit
+    };
+    // Only what reached the merchant side can be refunded from it; the platform fee
+    // stays with the protocol.
+    crate::refunds::record_charge(env, subscription_id, credit)?;
+
+    #[cfg(any(test, feature = "settle-audit"))]
+    audit::assert_credited(
+        env,
+        subscription_id,
+        sub,
+        &kind,
+        merchant_side_before,
+        debit,
+    );
+
+    match kind {
+        ChargeKind::Interval { amount_due } => env.events().publish(
+            (symbol_short!("charged"),),
+            SubscriptionChargedEvent {
+                subscription_id,
+                merchant: sub.merchant.clone(),
+                amount: amount_due,
+                remaining_balance: sub.prepaid_balance,
+            },
+        ),
+        ChargeKind::Usage => {
+            // Kept for the subscriber's flag window (see `disputes.rs`).
+            #[cfg(feature = "disputes")]
+            let seq = crate::disputes::record_usage_charge(env, subscription_id, credit)?;
+            #[cfg(not(feature = "disputes"))]
+            let seq = 0u32;
+            env.events().publish(
+                (Symbol::new(env, "usage_charged"), subscription_id),
+                (seq, debit),
+            );
+        }
+        ChargeKind::OneOff { memo_hash } => env.events().publish(
+            (symbol_short!("oneoff_ch"), subscription_id),
+            OneOffChargedEvent {
+                subscription_id,
+                merchant: sub.merchant.clone(),
+                amount: debit,
+                memo_hash,
+            },
+        ),
+    }
+    crate::payouts::pay_out_if_above_threshold(env, &sub.merchant)?;
+    crate::low_balance::warn_if_low(env, subscription_id, sub);
+    crate::notify::notify_charge(env, subscription_id, &sub.merchant, charged);
+    Ok(credit)
+}
+
+/// Settlement audit (always on in unit tests, or with the `settle-audit` feature):
+/// panics if a settled charge did not credit the merchant side by exactly the debit.
+#[cfg(any(test, feature = "settle-audit"))]
+mod audit {
+    use super::ChargeKind;
+    use crate::merchant::get_merchant_balance;
+    use crate::types::Subscription;
+    use soroban_sdk::{Env, Vec};
+
+    pub fn merchant_side_total(
+        env: &Env,
+        subscription_id: u32,
+        sub: &Subscription,
+        kind: &ChargeKind,
+    ) -> i128 {
+        if let Some(token) = crate::billing_tokens::get_billing_token(env, subscription_id) {
+            return crate::multi_token::get_merchant_token_balance(env, &sub.merchant, &token);
+        }
+        let items = match kind {
+            ChargeKind::Interval { .. } => crate::bundles::get_bundle_items(env, subscription_id),
+            _ => Vec::new(env),
+        };
+        // Platform fees count as credited: they are split off the same debit.
+        let fees = crate::fees::get_protocol_fee_balance(env);
+        if items.is_empty() {
+            return get_merchant_balance(env, &sub.merchant) + fees;
+        }
+        let mut seen = Vec::new(env);
+        let mut total = fees;
+        for item in items.iter() {
+            if !seen.contains(&item.merchant) {
+                total += get_merchant_balance(env, &item.merchant);
+                seen.push_back(item.merchant);
+            }
+        }
+        total
+    }
+
+    pub fn assert_credited(
+        env: &Env,
+        subscription_id: u32,
+        sub: &Subscription,
+        kind: &ChargeKind,
+        before: i128,
+        debit: i128,
+    ) {
+        let after = merchant_side_total(env, subscription_id, sub, kind);
+        assert_eq!(
+            after - before,
+            debit,
+            "charge settled without a matching merchant credit"
+        );
+    }
+}
+
+/// Charges the next billing period ahead of schedule (subscriber "pay now").
+///
+/// Bypasses only the `IntervalNotElapsed` check: the charge covers the period that
+/// would fall due at `last_payment_timestamp + interval_seconds`, and the schedule
+/// advances to that boundary so the regular charge for it is not taken again.
+/// Status, replay and balance guards apply as for [`charge_one`]. A short balance
+/// returns `InsufficientBalance` without changing status, since the charge is voluntary.
+pub fn charge_early(env: &Env, subscription_id: u32) -> Result<(), Error> {
+    let mut sub = get_subscription(env, subscription_id)?;
+
+    if sub.status != SubscriptionStatus::Active {
+        return Err(Error::NotActive);
+    }
+    if !get_charge_mask(env, subscription_id).allow_interval {
+        return Err(Error::ChargeTypeBlocked);
+    }
+    crate::promo::ensure_chargeable(env, subscription_id)?;
+
+    let due_at = sub
+        .last_payment_timestamp
+        .checked_add(sub.interval_seconds)
+        .ok_or(Error::Overflow)?;
+    let period_index = due_at / sub.interval_seconds;
+
+    if let Some(stored_period) = last_charged_period(env, subscription_id) {
+        if period_index <= stored_period {
+            return Err(Error::Replay);
+        }
+    }
+
+    let amount_due = interval_amount_due(env, subscription_id, &sub)?;
+    crate::spending_cap::ensure_within_cap(env, subscription_id, due_at, amount_due)?;
+    let interval_amount = safe_sub_balance(
+        amount_due,
+        crate::setup_fee::next_installment(env, subscription_id),
+    )?;
+    ensure_settleable(env, subscription_id, &mut sub, interval_amount)?;
+
+    let funding = fund_interval_charge(env, subscription_id, &mut sub, period_index)?
+        .ok_or(Error::InsufficientBalance)?;
+    settle_interval_charge(
+        env,
+        subscription_id,
+        &mut sub,
+        funding,
+        due_at,
+        period_index,
+    )
+}
+
 /// Debit a metered `usage_amount` from a subscription's prepaid balance.
 ///
 /// Shared safety checks:
 /// * Subscription must exist (`NotFound`).
 /// * Subscription must be `Active` (`NotActive`).
-/// * `usage_enabled` must be `true` (`UsageNotEnabled`).
+/// * Usage must be enabled in its `UsageConfig` (`UsageNotEnabled`).
 /// * `usage_amount` must be positive (`InvalidAmount`).
-/// * `prepaid_balance >= usage_amount` (`InsufficientPrepaidBalance`).
+/// * The report window's cap must not be passed (`UsageCapExceeded`).
+/// * `prepaid_balance` must cover what usage credit does not (`InsufficientPrepaidBalance`).
 ///
-/// On success the prepaid balance is reduced.  If the balance reaches zero
+/// On success usage credit is spent first and the prepaid balance is reduced by the
+/// rest (see `usage_credits.rs`). With an overage tier, only the usage beyond the
+/// interval's quota is billed (see `usage_tiers.rs`). If the balance reaches zero
 /// the subscription transitions to `InsufficientBalance`, blocking further
 /// charges until the subscriber tops up.
 pub fn charge_usage_one(env: &Env, subscription_id: u32, usage_amount: i128) -> Result<(), Error> {
+    charge_usage(env, subscription_id, None, usage_amount)
+}
+
+/// Debit `units` of metered usage, priced with the subscription's `UsageConfig`.
+///
+/// Units still covered by the report window's included units are recorded without a
+/// charge. Same checks as [`charge_usage_one`], plus `InvalidInput` if no unit price
+/// is configured.
+pub fn charge_usage_units(env: &Env, subscription_id: u32, units: u64) -> Result<(), Error> {
+    if units == 0 {
+        return Err(Error::InvalidAmount);
+    }
+    charge_usage(env, subscription_id, Some(units), 0)
+}
+
+fn charge_usage(
+    env: &Env,
+    subscription_id: u32,
+    units: Option<u64>,
+    usage_amount: i128,
+) -> Result<(), Error> {
     let mut sub = get_subscription(env, subscription_id)?;
 
     if sub.status != SubscriptionStatus::Active {
         return Err(Error::NotActive);
     }
 
-    if !sub.usage_enabled {
+    let config = crate::usage::get_usage_config(env, subscription_id, &sub);
+    if !config.enabled {
         return Err(Error::UsageNotEnabled);
     }
+    if !get_charge_mask(env, subscription_id).allow_usage {
+        return Err(Error::ChargeTypeBlocked);
+    }
+    crate::promo::ensure_chargeable(env, subscription_id)?;
 
-    if usage_amount <= 0 {
-        return Err(Error::InvalidAmount);
+    let window = crate::usage::get_usage_window(env, subscription_id, &sub, &config);
+    let credit = crate::usage_credits::available_credit(env, subscription_id, &config, &window);
+    let consumed = usage_amount;
+    let (units, usage_amount) = match units {
+        Some(units) => (units, crate::usage::price_units(&config, &window, units)?),
+        None if usage_amount <= 0 => return Err(Error::InvalidAmount),
+        // The merchant's overage tier (see `usage_tiers.rs`) bills only the overage.
+        None => (
+            0,
+            crate::usage_tiers::billable_amount(env, subscription_id, usage_amount)?,
+        ),
+    };
+    if usage_amount == 0 {
+        // Fully covered by included units or the tier's quota.
+        crate::usage_credits::consume(env, subscription_id, credit, 0);
+        crate::usage_tiers::record_consumption(env, subscription_id, consumed);
+        return crate::usage::record_in_window(env, subscription_id, window, &config, units, 0);
     }
 
-    if sub.prepaid_balance < usage_amount {
+    // Usage credit (see `usage_credits.rs`) is spent first; only the rest is debited.
+    let covered = usage_amount.min(credit.max(0));
+    let debit = usage_amount - covered;
+
+    // Every check, `settle`'s guards included, runs before the first write, so a failed
+    // entry of a usage batch leaves no trace. Usage credit is only spent once the debit
+    // has settled.
+    crate::usage::ensure_within_cap(&window, &config, debit)?;
+    if sub.prepaid_balance < debit {
         return Err(Error::InsufficientPrepaidBalance);
     }
+    let period_start = sub.last_payment_timestamp;
+    crate::spending_cap::ensure_within_cap(env, subscription_id, period_start, debit)?;
 
-    sub.prepaid_balance = sub
-        .prepaid_balance
-        .checked_sub(usage_amount)
-        .ok_or(Error::Overflow)?;
+    if debit > 0 {
+        settle(env, subscription_id, &mut sub, ChargeKind::Usage, debit)?;
+        crate::spending_cap::record_spend(env, subscription_id, period_start, debit);
+    }
+    crate::usage_credits::consume(env, subscription_id, credit, covered);
+    crate::usage::record_in_window(env, subscription_id, window, &config, units, debit)?;
+    crate::usage_tiers::record_consumption(env, subscription_id, consumed);
 
     // If the vault is now empty, transition to InsufficientBalance so no
     // further charges (interval or usage) can proceed until top-up.
-    if sub.prepaid_balance == 0 {
+    if debit > 0 && sub.prepaid_balance == 0 {
         validate_status_transition(&sub.status, &SubscriptionStatus::InsufficientBalance)?;
         sub.status = SubscriptionStatus::InsufficientBalance;
     }
 
-    env.storage().instance().set(&subscription_id, &sub);
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
     Ok(())
 }
@@ -0,0 +1,134 @@
+//! Automatic suspension of charges when their failure rate spikes.
+//!
+//! Batch charges report their outcomes here. Within each window of `window_seconds`, once
+//! at least `min_attempts` charges were attempted and failures exceed `max_failure_bps` of
+//! them, the breaker trips: every charge fails with `CircuitBreakerOpen` and a
+//! `circuit_breaker_tripped` alert is emitted, until the admin calls
+//! `reset_circuit_breaker`. This contains incidents such as a broken token contract
+//! before a whole billing run fails against it.
+//!
+//! Only infrastructure failures count against the rate: a token that no longer matches its
+//! fingerprint (`TokenMismatch`) and arithmetic overflow (`Overflow`). Subscriber-side and
+//! scheduling failures such as `InsufficientBalance`, `NotActive` or `Replay` are not
+//! counted at all, so a billing run full of lapsed subscribers cannot halt charging.
+//! A token transfer that traps aborts the whole batch and is not counted either.
+//! Single charge calls are not counted either: a failed one is rolled back with its
+//! transaction, so only batches can record failures.
+//!
+//! **PRs that only change the circuit breaker should edit this file only.**
+
+use crate::types::{ChargeMetrics, CircuitBreakerConfig, ConfigKey, DataKey, Error};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn config_key() -> DataKey {
+    DataKey::Config(ConfigKey::CircuitBreaker)
+}
+
+fn metrics_key() -> DataKey {
+    DataKey::Config(ConfigKey::ChargeMetrics)
+}
+
+/// Circuit breaker settings, or `None` while it is off.
+pub fn get_circuit_breaker(env: &Env) -> Option<CircuitBreakerConfig> {
+    env.storage().instance().get(&config_key())
+}
+
+/// Counters of the current window and the trip state.
+pub fn get_charge_metrics(env: &Env) -> ChargeMetrics {
+    env.storage()
+        .instance()
+        .get(&metrics_key())
+        .unwrap_or(ChargeMetrics {
+            window_start: 0,
+            attempts: 0,
+            failures: 0,
+            tripped_at: 0,
+        })
+}
+
+/// Admin turns the breaker on, or off with a `window_seconds` of 0. Turning it off does
+/// not resume charges after a trip; that takes `reset_circuit_breaker`.
+pub fn do_set_circuit_breaker(
+    env: &Env,
+    admin: Address,
+    config: CircuitBreakerConfig,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    if config.window_seconds == 0 {
+        env.storage().instance().remove(&config_key());
+    } else {
+        if config.max_failure_bps == 0 || config.max_failure_bps > 10_000 {
+            return Err(Error::InvalidInput);
+        }
+        if config.min_attempts == 0 {
+            return Err(Error::InvalidInput);
+        }
+        env.storage().instance().set(&config_key(), &config);
+    }
+    env.events()
+        .publish((Symbol::new(env, "circuit_breaker_set"),), config);
+    Ok(())
+}
+
+/// Admin resumes charges after a trip and starts a fresh counting window.
+pub fn do_reset_circuit_breaker(env: &Env, admin: Address) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    env.storage().instance().remove(&metrics_key());
+    env.events()
+        .publish((Symbol::new(env, "circuit_breaker_reset"),), admin);
+    Ok(())
+}
+
+/// Fails with `CircuitBreakerOpen` while the breaker is tripped.
+pub fn ensure_closed(env: &Env) -> Result<(), Error> {
+    if get_charge_metrics(env).tripped_at != 0 {
+        return Err(Error::CircuitBreakerOpen);
+    }
+    Ok(())
+}
+
+/// Whether a charge failing with `error` counts against the failure rate: only
+/// infrastructure failures do.
+pub fn counts_as_attempt(error: &Error) -> bool {
+    matches!(error, Error::TokenMismatch | Error::Overflow)
+}
+
+/// Adds a batch's counted `attempts` and `failures` to the window and trips the breaker
+/// if the failure rate is now over the threshold. Does nothing while the breaker is off.
+pub fn record_batch(env: &Env, attempts: u32, failures: u32) {
+    let Some(config) = get_circuit_breaker(env) else {
+        return;
+    };
+    if attempts == 0 {
+        return;
+    }
+    let now = env.ledger().timestamp();
+    let mut metrics = get_charge_metrics(env);
+    if now >= metrics.window_start.saturating_add(config.window_seconds) {
+        metrics = ChargeMetrics {
+            window_start: now,
+            attempts: 0,
+            failures: 0,
+            tripped_at: 0,
+        };
+    }
+    metrics.attempts = metrics.attempts.saturating_add(attempts);
+    metrics.failures = metrics.failures.saturating_add(failures);
+
+    let over_threshold = u64::from(metrics.failures) * 10_000
+        > u64::from(metrics.attempts) * u64::from(config.max_failure_bps);
+    if metrics.attempts >= config.min_attempts && over_threshold {
+        metrics.tripped_at = now;
+        env.events().publish(
+            (Symbol::new(env, "circuit_breaker_tripped"),),
+            metrics.clone(),
+        );
+    }
+    env.storage().instance().set(&metrics_key(), &metrics);
+}
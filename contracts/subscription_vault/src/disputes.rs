@@ -0,0 +1,420 @@
+//! Charge disputes: ring-fence a disputed amount without blocking the subscription.
+//!
+//! Opening a dispute locks the disputed amount inside the merchant's accrued
+//! balance (see `merchant.rs`), split across the line-item merchants for a bundled
+//! subscription (see `bundles.rs`). Billing continues as normal; only the locked
+//! amount is excluded from merchant withdrawals until the admin resolves it. A dispute
+//! is bounded by what the merchant was credited in the vault token for the disputed
+//! period, as recorded with the period for replay protection (see `charge_core.rs`):
+//! the prepaid debit less the platform fee. Secondary token draws (see
+//! `multi_token.rs`) are credited outside that balance and cannot be disputed.
+//!
+//! Usage charges have a lighter-weight variant: within `USAGE_FLAG_WINDOW_SECONDS`
+//! the subscriber can flag a usage line item, which locks it the same way until the
+//! merchant confirms or reverses it.
+//!
+//! Compiled in with the default `disputes` feature; deployments that do not offer
+//! disputes can build without it to save code size.
+//!
+//! **PRs that only change dispute handling should edit this file only.**
+
+use crate::bundles::split_charge;
+use crate::charge_core::last_charge;
+use crate::merchant::{lock_merchant_balance, unlock_merchant_balance};
+use crate::queries::get_subscription;
+use crate::safe_math::safe_add_balance;
+use crate::types::{
+    DataKey, Dispute, DisputeOpenedEvent, DisputeResolvedEvent, DisputeStatus, Error, SubKey,
+    UsageChargeLedger, UsageChargeRecord,
+};
+use crate::{SubscriptionVault, SubscriptionVaultArgs, SubscriptionVaultClient};
+use soroban_sdk::{contractimpl, Address, Env, Symbol, Vec};
+
+/// How long after a usage charge the subscriber can flag it (72 hours).
+pub const USAGE_FLAG_WINDOW_SECONDS: u64 = 72 * 60 * 60;
+
+/// Most usage charge records kept per subscription, flagged or not.
+pub const MAX_USAGE_CHARGE_RECORDS: u32 = 32;
+
+fn open_count_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::OpenDisputes, subscription_id)
+}
+
+/// Number of open charge disputes and usage flags on the subscription.
+pub fn open_dispute_count(env: &Env, subscription_id: u32) -> u32 {
+    env.storage()
+        .instance()
+        .get(&open_count_key(subscription_id))
+        .unwrap_or(0)
+}
+
+fn adjust_open_count(env: &Env, subscription_id: u32, opened: bool) {
+    let key = open_count_key(subscription_id);
+    let count = open_dispute_count(env, subscription_id);
+    let count = if opened {
+        count.saturating_add(1)
+    } else {
+        count.saturating_sub(1)
+    };
+    if count == 0 {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &count);
+    }
+}
+
+fn next_dispute_id(env: &Env) -> u32 {
+    let storage = env.storage().instance();
+    let id: u32 = storage.get(&DataKey::NextDisputeId).unwrap_or(0);
+    storage.set(&DataKey::NextDisputeId, &(id + 1));
+    id
+}
+
+pub fn get_dispute(env: &Env, dispute_id: u32) -> Result<Dispute, Error> {
+    env.storage()
+        .instance()
+        .get(&DataKey::Dispute(dispute_id))
+        .ok_or(Error::NotFound)
+}
+
+/// Subscriber disputes `amount` of the most recent interval charge.
+///
+/// The amount must be positive and not exceed the merchant's net vault-token credit for
+/// the period (`InvalidAmount`); a skipped period credited nothing. A period charged before amounts
+/// were recorded cannot be disputed (`NotFound`). Each charged period can be disputed
+/// at most once.
+pub fn do_open_dispute(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    amount: i128,
+) -> Result<u32, Error> {
+    subscriber.require_auth();
+
+    let sub = get_subscription(env, subscription_id)?;
+    if subscriber != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+    // Disputes hold the merchant's vault-token balance.
+    crate::billing_tokens::ensure_vault_token(env, subscription_id)?;
+
+    let (period, charged) = last_charge(env, subscription_id).ok_or(Error::NotFound)?;
+    let charged = charged.ok_or(Error::NotFound)?;
+    if amount <= 0 || amount > charged {
+        return Err(Error::InvalidAmount);
+    }
+    let charge_key = DataKey::ChargeDispute(subscription_id, period);
+    if env.storage().instance().has(&charge_key) {
+        return Err(Error::DisputeAlreadyOpen);
+    }
+
+    for (merchant, share) in split_charge(env, subscription_id, &sub.merchant, amount)?.iter() {
+        lock_merchant_balance(env, &merchant, share)?;
+    }
+
+    let dispute_id = next_dispute_id(env);
+    let dispute = Dispute {
+        subscription_id,
+        subscriber,
+        merchant: sub.merchant.clone(),
+        period,
+        amount,
+        opened_at: env.ledger().timestamp(),
+        status: DisputeStatus::Open,
+    };
+    let storage = env.storage().instance();
+    storage.set(&DataKey::Dispute(dispute_id), &dispute);
+    storage.set(&charge_key, &dispute_id);
+    adjust_open_count(env, subscription_id, true);
+    crate::health::record_dispute(env, subscription_id);
+
+    env.events().publish(
+        (Symbol::new(env, "dispute_opened"), subscription_id),
+        DisputeOpenedEvent {
+            dispute_id,
+            subscription_id,
+            merchant: sub.merchant,
+            amount,
+        },
+    );
+    Ok(dispute_id)
+}
+
+/// Admin resolves an open dispute.
+///
+/// - `refund_subscriber == false`: the lock is released and the merchant may withdraw it.
+/// - `refund_subscriber == true`: the amount leaves the merchant's balance and is
+///   credited back to the subscription's prepaid balance.
+pub fn do_resolve_dispute(
+    env: &Env,
+    admin: Address,
+    dispute_id: u32,
+    refund_subscriber: bool,
+) -> Result<(), Error> {
+    admin.require_auth();
+    let stored = crate::admin::require_admin(env)?;
+    if admin != stored {
+        return Err(Error::Forbidden);
+    }
+
+    let mut dispute = get_dispute(env, dispute_id)?;
+    if dispute.status != DisputeStatus::Open {
+        return Err(Error::DisputeNotOpen);
+    }
+
+    let shares = split_charge(
+        env,
+        dispute.subscription_id,
+        &dispute.merchant,
+        dispute.amount,
+    )?;
+    for (merchant, share) in shares.iter() {
+        unlock_merchant_balance(env, &merchant, share, refund_subscriber)?;
+    }
+
+    if refund_subscriber {
+        let mut sub = get_subscription(env, dispute.subscription_id)?;
+        sub.prepaid_balance = safe_add_balance(sub.prepaid_balance, dispute.amount)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::Sub(dispute.subscription_id), &sub);
+        dispute.status = DisputeStatus::Refunded;
+    } else {
+        dispute.status = DisputeStatus::ResolvedForMerchant;
+    }
+    env.storage()
+        .instance()
+        .set(&DataKey::Dispute(dispute_id), &dispute);
+    adjust_open_count(env, dispute.subscription_id, false);
+
+    env.events().publish(
+        (
+            Symbol::new(env, "dispute_resolved"),
+            dispute.subscription_id,
+        ),
+        DisputeResolvedEvent {
+            dispute_id,
+            subscription_id: dispute.subscription_id,
+            refunded: refund_subscriber,
+            amount: dispute.amount,
+        },
+    );
+    Ok(())
+}
+
+fn get_usage_ledger(env: &Env, subscription_id: u32) -> UsageChargeLedger {
+    env.storage()
+        .instance()
+        .get(&DataKey::UsageCharges(subscription_id))
+        .unwrap_or(UsageChargeLedger {
+            next_seq: 0,
+            recent: Vec::new(env),
+        })
+}
+
+fn set_usage_ledger(env: &Env, subscription_id: u32, ledger: &UsageChargeLedger) {
+    env.storage()
+        .instance()
+        .set(&DataKey::UsageCharges(subscription_id), ledger);
+}
+
+/// Usage charges that can still be flagged, plus flagged ones awaiting the merchant.
+pub fn get_usage_charges(env: &Env, subscription_id: u32) -> Vec<UsageChargeRecord> {
+    get_usage_ledger(env, subscription_id).recent
+}
+
+/// Records a usage charge that credited the merchant `amount` for the flag window and
+/// returns its sequence number.
+///
+/// Unflagged records older than the window are dropped, so the list only holds
+/// charges that can still be flagged and flags awaiting resolution. Past
+/// `MAX_USAGE_CHARGE_RECORDS` the oldest unflagged record is dropped too; when every
+/// record is flagged the charge fails with `DisputeAlreadyOpen` until the merchant
+/// resolves some.
+pub fn record_usage_charge(env: &Env, subscription_id: u32, amount: i128) -> Result<u32, Error> {
+    let now = env.ledger().timestamp();
+    let ledger = get_usage_ledger(env, subscription_id);
+    let mut recent = Vec::new(env);
+    for record in ledger.recent.iter() {
+        if record.flagged || record.charged_at.saturating_add(USAGE_FLAG_WINDOW_SECONDS) >= now {
+            recent.push_back(record);
+        }
+    }
+    if recent.len() >= MAX_USAGE_CHARGE_RECORDS {
+        let oldest_unflagged = recent
+            .iter()
+            .position(|r| !r.flagged)
+            .ok_or(Error::DisputeAlreadyOpen)?;
+        recent.remove(oldest_unflagged as u32);
+    }
+
+    let seq = ledger.next_seq;
+    recent.push_back(UsageChargeRecord {
+        seq,
+        amount,
+        charged_at: now,
+        flagged: false,
+    });
+    set_usage_ledger(
+        env,
+        subscription_id,
+        &UsageChargeLedger {
+            next_seq: seq + 1,
+            recent,
+        },
+    );
+    Ok(seq)
+}
+
+/// Subscriber flags usage charge `seq` as incorrect; its amount is held from the
+/// merchant's withdrawable balance. The subscription keeps running.
+pub fn do_flag_usage_charge(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    seq: u32,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+
+    let sub = get_subscription(env, subscription_id)?;
+    if subscriber != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+
+    let mut ledger = get_usage_ledger(env, subscription_id);
+    let index = ledger
+        .recent
+        .iter()
+        .position(|r| r.seq == seq)
+        .ok_or(Error::NotFound)? as u32;
+    let mut record = ledger.recent.get(index).ok_or(Error::NotFound)?;
+    if record.flagged {
+        return Err(Error::DisputeAlreadyOpen);
+    }
+    if env.ledger().timestamp() > record.charged_at.saturating_add(USAGE_FLAG_WINDOW_SECONDS) {
+        return Err(Error::DisputeWindowClosed);
+    }
+
+    crate::billing_tokens::ensure_vault_token(env, subscription_id)?;
+    lock_merchant_balance(env, &sub.merchant, record.amount)?;
+    record.flagged = true;
+    ledger.recent.set(index, record.clone());
+    set_usage_ledger(env, subscription_id, &ledger);
+    adjust_open_count(env, subscription_id, true);
+
+    env.events().publish(
+        (Symbol::new(env, "usage_flagged"), subscription_id),
+        (seq, record.amount),
+    );
+    Ok(())
+}
+
+/// Merchant settles a flagged usage charge: `reverse == false` confirms it and releases
+/// the hold; `reverse == true` returns the amount to the subscription's prepaid balance.
+pub fn do_resolve_usage_flag(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    seq: u32,
+    reverse: bool,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+
+    let mut sub = get_subscription(env, subscription_id)?;
+    if merchant != sub.merchant {
+        return Err(Error::Forbidden);
+    }
+
+    let mut ledger = get_usage_ledger(env, subscription_id);
+    let index = ledger
+        .recent
+        .iter()
+        .position(|r| r.seq == seq)
+        .ok_or(Error::NotFound)? as u32;
+    let record = ledger.recent.get(index).ok_or(Error::NotFound)?;
+    if !record.flagged {
+        return Err(Error::DisputeNotOpen);
+    }
+
+    unlock_merchant_balance(env, &merchant, record.amount, reverse)?;
+    if reverse {
+        sub.prepaid_balance = safe_add_balance(sub.prepaid_balance, record.amount)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::Sub(subscription_id), &sub);
+    }
+    ledger.recent.remove(index);
+    set_usage_ledger(env, subscription_id, &ledger);
+    adjust_open_count(env, subscription_id, false);
+
+    env.events().publish(
+        (Symbol::new(env, "usage_flag_resolved"), subscription_id),
+        (seq, reverse),
+    );
+    Ok(())
+}
+
+/// Whether any charge dispute or usage flag on the subscription is still open, and so
+/// still holds merchant funds that resolution has to find the live record for.
+pub fn has_open_disputes(env: &Env, subscription_id: u32) -> bool {
+    open_dispute_count(env, subscription_id) > 0
+}
+
+#[contractimpl]
+impl SubscriptionVault {
+    /// Subscriber disputes `amount` of the most recent interval charge.
+    ///
+    /// Only the disputed amount is ring-fenced in the merchant's balance; the
+    /// subscription keeps billing normally. Returns the new dispute ID.
+    pub fn open_dispute(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        amount: i128,
+    ) -> Result<u32, Error> {
+        do_open_dispute(&env, subscription_id, subscriber, amount)
+    }
+
+    /// Admin resolves a dispute, either releasing the lock to the merchant or
+    /// refunding the amount into the subscriber's prepaid balance.
+    pub fn resolve_dispute(
+        env: Env,
+        admin: Address,
+        dispute_id: u32,
+        refund_subscriber: bool,
+    ) -> Result<(), Error> {
+        do_resolve_dispute(&env, admin, dispute_id, refund_subscriber)
+    }
+
+    pub fn get_dispute(env: Env, dispute_id: u32) -> Result<Dispute, Error> {
+        get_dispute(&env, dispute_id)
+    }
+
+    /// Subscriber flags usage charge `seq` (from the `usage_charged` event) as incorrect
+    /// within `USAGE_FLAG_WINDOW_SECONDS`. The amount is held from merchant withdrawal;
+    /// the subscription is not paused.
+    pub fn flag_usage_charge(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        seq: u32,
+    ) -> Result<(), Error> {
+        do_flag_usage_charge(&env, subscription_id, subscriber, seq)
+    }
+
+    /// Merchant confirms (`reverse == false`) or reverses a flagged usage charge.
+    pub fn resolve_usage_flag(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        seq: u32,
+        reverse: bool,
+    ) -> Result<(), Error> {
+        do_resolve_usage_flag(&env, subscription_id, merchant, seq, reverse)
+    }
+
+    /// Usage charges still inside the flag window, and flagged ones awaiting the merchant.
+    pub fn get_usage_charges(env: Env, subscription_id: u32) -> Vec<UsageChargeRecord> {
+        get_usage_charges(&env, subscription_id)
+    }
+}
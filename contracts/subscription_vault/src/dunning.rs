@@ -0,0 +1,180 @@
+//! Per-merchant dunning policy: how failed interval charges are retried and announced.
+//!
+//! The policy is stored on-chain so the off-chain retry queue and reminder services
+//! read one source of truth per merchant. Each failed interval charge increments a
+//! per-subscription attempt counter and emits `PaymentFailedEvent` carrying the
+//! merchant's notify topics and the next retry time; a successful charge resets it.
+//!
+//! A failed charge moves the subscription into `GracePeriod` (past due) while the
+//! vault's grace window is open; retries stay allowed. Once the window lapses the
+//! subscription moves to the merchant's [`GraceLapseAction`] target, either on the next
+//! failed retry or through the [`do_lapse_grace_periods`] sweep.
+//!
+//! **PRs that only change dunning behaviour should edit this file only.**
+
+use crate::state_machine::validate_status_transition;
+use crate::types::{
+    DataKey, DunningPolicy, Error, GraceLapseAction, MerchantKey, PaymentFailedEvent,
+    SubscriptionStatus,
+};
+use soroban_sdk::{Address, Env, Symbol, Vec};
+
+/// Upper bound for `DunningPolicy::max_retries`.
+pub const MAX_DUNNING_RETRIES: u32 = 10;
+/// Upper bound for the number of notify topics.
+pub const MAX_NOTIFY_TOPICS: u32 = 5;
+
+/// Policy used for merchants that have not configured one.
+pub fn default_policy(env: &Env) -> DunningPolicy {
+    DunningPolicy {
+        max_retries: 3,
+        retry_spacing_seconds: 24 * 60 * 60,
+        notify_topics: Vec::new(env),
+    }
+}
+
+pub fn get_dunning_policy(env: &Env, merchant: &Address) -> DunningPolicy {
+    env.storage()
+        .instance()
+        .get(&DataKey::DunningPolicy(merchant.clone()))
+        .unwrap_or_else(|| default_policy(env))
+}
+
+/// Merchant stores its dunning policy.
+pub fn do_set_dunning_policy(
+    env: &Env,
+    merchant: Address,
+    policy: DunningPolicy,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+
+    if policy.max_retries > MAX_DUNNING_RETRIES
+        || policy.notify_topics.len() > MAX_NOTIFY_TOPICS
+        || (policy.max_retries > 0 && policy.retry_spacing_seconds == 0)
+    {
+        return Err(Error::InvalidInput);
+    }
+
+    env.storage()
+        .instance()
+        .set(&DataKey::DunningPolicy(merchant.clone()), &policy);
+    env.events()
+        .publish((Symbol::new(env, "dunning_policy_set"), merchant), policy);
+    Ok(())
+}
+
+/// Consecutive failed interval charges since the last successful one.
+pub fn get_failed_attempts(env: &Env, subscription_id: u32) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::FailedAttempts(subscription_id))
+        .unwrap_or(0)
+}
+
+/// Records a failed interval charge and announces it per the merchant's policy.
+pub fn record_failed_charge(env: &Env, subscription_id: u32, merchant: &Address, now: u64) {
+    let attempt = get_failed_attempts(env, subscription_id).saturating_add(1);
+    env.storage()
+        .instance()
+        .set(&DataKey::FailedAttempts(subscription_id), &attempt);
+
+    let policy = get_dunning_policy(env, merchant);
+    let next_retry_at = if attempt <= policy.max_retries {
+        Some(now.saturating_add(policy.retry_spacing_seconds))
+    } else {
+        None
+    };
+
+    env.events().publish(
+        (Symbol::new(env, "payment_failed"), subscription_id),
+        PaymentFailedEvent {
+            subscription_id,
+            merchant: merchant.clone(),
+            attempt,
+            next_retry_at,
+            notify_topics: policy.notify_topics,
+        },
+    );
+}
+
+/// Resets the failed-attempt counter after a successful interval charge.
+pub fn clear_failed_charges(env: &Env, subscription_id: u32) {
+    let key = DataKey::FailedAttempts(subscription_id);
+    if env.storage().instance().has(&key) {
+        env.storage().instance().remove(&key);
+    }
+}
+
+fn lapse_action_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::LapseAction, merchant.clone())
+}
+
+/// What happens to the merchant's subscriptions when their grace period lapses.
+pub fn get_grace_lapse_action(env: &Env, merchant: &Address) -> GraceLapseAction {
+    env.storage()
+        .instance()
+        .get(&lapse_action_key(merchant))
+        .unwrap_or(GraceLapseAction::Suspend)
+}
+
+/// Merchant chooses whether lapsed grace periods suspend or cancel its subscriptions.
+pub fn do_set_grace_lapse_action(
+    env: &Env,
+    merchant: Address,
+    action: GraceLapseAction,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    env.storage()
+        .instance()
+        .set(&lapse_action_key(&merchant), &action);
+    env.events()
+        .publish((Symbol::new(env, "lapse_action_set"), merchant), action);
+    Ok(())
+}
+
+/// Status a subscription of `merchant` takes when its grace period lapses.
+pub fn lapse_status(env: &Env, merchant: &Address) -> SubscriptionStatus {
+    match get_grace_lapse_action(env, merchant) {
+        GraceLapseAction::Suspend => SubscriptionStatus::InsufficientBalance,
+        GraceLapseAction::Cancel => SubscriptionStatus::Cancelled,
+    }
+}
+
+/// Moves every listed `GracePeriod` subscription whose window has lapsed to its
+/// merchant's lapse status, and returns the IDs it moved. Other IDs are skipped, so
+/// callers can pass a broad candidate list. Billing engine or billing agent only.
+///
+/// Emits `grace_lapsed` with the new status for each subscription moved.
+pub fn do_lapse_grace_periods(
+    env: &Env,
+    operator: Address,
+    subscription_ids: &Vec<u32>,
+) -> Result<Vec<u32>, Error> {
+    crate::admin::require_billing_operator(env, &operator)?;
+
+    let grace = crate::admin::get_grace_period(env).unwrap_or(0);
+    let now = env.ledger().timestamp();
+    let mut lapsed = Vec::new(env);
+    for id in subscription_ids.iter() {
+        let mut sub = match crate::queries::get_subscription(env, id) {
+            Ok(sub) if sub.status == SubscriptionStatus::GracePeriod => sub,
+            _ => continue,
+        };
+        let window_end = sub
+            .last_payment_timestamp
+            .saturating_add(sub.interval_seconds)
+            .saturating_add(grace);
+        if now < window_end {
+            continue;
+        }
+        let target = lapse_status(env, &sub.merchant);
+        validate_status_transition(&sub.status, &target)?;
+        sub.status = target.clone();
+        env.storage().instance().set(&DataKey::Sub(id), &sub);
+
+        env.events()
+            .publish((Symbol::new(env, "grace_lapsed"), id), target);
+        lapsed.push_back(id);
+    }
+    Ok(lapsed)
+}
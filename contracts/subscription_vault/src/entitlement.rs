@@ -0,0 +1,77 @@
+//! Entitlement oracle: one-call access checks for partner contracts.
+//!
+//! Each (subscriber, merchant) pair is indexed to its most recently created
+//! subscription, so `check_entitlement` costs two storage reads and never scans.
+//!
+//! **PRs that only change entitlement checks should edit this file only.**
+
+use crate::types::{
+    DataKey, EntitlementRecord, EntitlementStatus, Subscription, SubscriptionStatus,
+};
+use soroban_sdk::{Address, Env};
+
+/// Points the (subscriber, merchant) entitlement index at a newly created subscription.
+pub fn index_subscription(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    plan_id: Option<u32>,
+) {
+    env.storage().instance().set(
+        &DataKey::Entitlement(sub.subscriber.clone(), sub.merchant.clone()),
+        &EntitlementRecord {
+            subscription_id,
+            plan_id,
+        },
+    );
+}
+
+/// Whether `subscriber` currently has access to `merchant`'s product.
+///
+/// Access holds while the subscription is `Active` or in `GracePeriod`, and otherwise
+/// until the end of the last paid interval (`paid_through`). Unconverted promo
+/// subscriptions grant access while `Active` and before the promo expires. Unknown pairs
+/// and archived subscriptions report no access.
+pub fn check_entitlement(env: &Env, subscriber: Address, merchant: Address) -> EntitlementStatus {
+    let record: Option<EntitlementRecord> = env
+        .storage()
+        .instance()
+        .get(&DataKey::Entitlement(subscriber, merchant));
+    let Some(record) = record else {
+        return EntitlementStatus {
+            active: false,
+            paid_through: 0,
+            plan_id: None,
+        };
+    };
+    let Some(sub) = crate::queries::load_subscription(env, record.subscription_id) else {
+        return EntitlementStatus {
+            active: false,
+            paid_through: 0,
+            plan_id: record.plan_id,
+        };
+    };
+
+    let now = env.ledger().timestamp();
+    if let Some(promo) = crate::promo::get_promo(env, record.subscription_id) {
+        return EntitlementStatus {
+            active: sub.status == SubscriptionStatus::Active && now < promo.expires_at,
+            paid_through: promo.expires_at,
+            plan_id: record.plan_id,
+        };
+    }
+
+    let paid_through = sub
+        .last_payment_timestamp
+        .saturating_add(sub.interval_seconds);
+    let active = matches!(
+        sub.status,
+        SubscriptionStatus::Active | SubscriptionStatus::GracePeriod
+    ) || now < paid_through;
+
+    EntitlementStatus {
+        active,
+        paid_through,
+        plan_id: record.plan_id,
+    }
+}
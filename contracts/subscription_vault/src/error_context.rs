@@ -0,0 +1,36 @@
+//! Last charge failure per subscription, for support tooling.
+//!
+//! Charge guards record the error code with the offending value and the limit it broke,
+//! so "why did my charge fail" can be answered from one read. A failing top-level call
+//! rolls back its own writes, so contexts survive only where the failure is absorbed:
+//! `batch_charge` and `batch_charge_as`, the paths billing workers run. The same holds
+//! for the dunning failure counter.
+//!
+//! **PRs that only change error diagnostics should edit this file only.**
+
+use crate::types::{DataKey, Error, ErrorContext, SubKey};
+use soroban_sdk::Env;
+
+fn context_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::ErrorContext, subscription_id)
+}
+
+/// Records `error` as the subscription's last failure and returns it, so guards can
+/// write `return Err(record(...))`. `value` and `limit` are 0 where they do not apply.
+pub fn record(env: &Env, subscription_id: u32, error: Error, value: i128, limit: i128) -> Error {
+    let context = ErrorContext {
+        error_code: error.clone().to_code(),
+        value,
+        limit,
+        timestamp: env.ledger().timestamp(),
+    };
+    env.storage()
+        .instance()
+        .set(&context_key(subscription_id), &context);
+    error
+}
+
+/// Last recorded charge failure of the subscription, or `None` if none was kept.
+pub fn get_last_error_context(env: &Env, subscription_id: u32) -> Option<ErrorContext> {
+    env.storage().instance().get(&context_key(subscription_id))
+}
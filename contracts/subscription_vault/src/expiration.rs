@@ -0,0 +1,220 @@
+//! Fixed end dates and the expiration sweep.
+//!
+//! The optional `expiration` passed to `create_subscription` is kept under its own key so
+//! the encoded [`crate::types::Subscription`] stays unchanged; the subscriber can push it
+//! out later with [`do_extend_expiration`]. Expired subscriptions are not cancelled by
+//! the ledger clock alone: a billing operator runs [`do_expire_subscriptions`] over
+//! candidate IDs, which cancels every subscription whose expiration has been reached.
+//! Merchants can opt in to having the residual prepaid balance refunded to the subscriber
+//! during that sweep instead of leaving it parked until the subscriber withdraws.
+//!
+//! An expiration can fall in the middle of a billing period. Each merchant picks an
+//! [`ExpiryProration`] policy for the interval charge whose period runs past it: the full
+//! amount (the default), a prorated amount for the part of the period before the
+//! expiration, or no charge at all. The period is measured from the charge's due time,
+//! `last_payment_timestamp + interval_seconds`. Interval charges due at or after the
+//! expiration are refused under every policy.
+//!
+//! **PRs that only change expiration handling should edit this file only.**
+
+use crate::state_machine::validate_status_transition;
+use crate::types::{
+    DataKey, Error, ExpiryProration, MerchantKey, SubKey, Subscription, SubscriptionStatus,
+};
+use soroban_sdk::{Address, Env, Symbol, Vec};
+
+/// Expiration timestamp of the subscription, or `None` if it is open-ended.
+pub fn get_expiration(env: &Env, subscription_id: u32) -> Option<u64> {
+    env.storage()
+        .instance()
+        .get(&DataKey::SubState(SubKey::Expiration, subscription_id))
+}
+
+/// Records the expiration given at creation; `None` leaves the subscription open-ended.
+pub fn store_expiration(env: &Env, subscription_id: u32, expiration: Option<u64>) {
+    if let Some(ts) = expiration {
+        env.storage()
+            .instance()
+            .set(&DataKey::SubState(SubKey::Expiration, subscription_id), &ts);
+    }
+}
+
+/// Subscriber moves the expiration of the subscription out to `new_expiration`, which
+/// must lie in the future and after the current expiration. Open-ended subscriptions
+/// have nothing to extend and fail with `InvalidInput`, as does an earlier timestamp.
+/// Emits `expiration_extended` with `(old_expiration, new_expiration)`.
+pub fn do_extend_expiration(
+    env: &Env,
+    subscription_id: u32,
+    authorizer: Address,
+    new_expiration: u64,
+) -> Result<(), Error> {
+    authorizer.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if authorizer != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+    if sub.status == SubscriptionStatus::Cancelled {
+        return Err(Error::InvalidStatusTransition);
+    }
+    let current = get_expiration(env, subscription_id).ok_or(Error::InvalidInput)?;
+    if new_expiration <= env.ledger().timestamp() || new_expiration <= current {
+        return Err(Error::InvalidInput);
+    }
+    env.storage().instance().set(
+        &DataKey::SubState(SubKey::Expiration, subscription_id),
+        &new_expiration,
+    );
+    env.events().publish(
+        (Symbol::new(env, "expiration_extended"), subscription_id),
+        (current, new_expiration),
+    );
+    Ok(())
+}
+
+fn proration_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::ExpiryProration, merchant.clone())
+}
+
+/// How the merchant's interval charges running past an expiration are handled.
+pub fn get_expiry_proration(env: &Env, merchant: &Address) -> ExpiryProration {
+    env.storage()
+        .instance()
+        .get(&proration_key(merchant))
+        .unwrap_or(ExpiryProration::FullCharge)
+}
+
+/// Merchant chooses how interval charges whose period runs past an expiration are
+/// handled.
+pub fn do_set_expiry_proration(
+    env: &Env,
+    merchant: Address,
+    policy: ExpiryProration,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    env.storage()
+        .instance()
+        .set(&proration_key(&merchant), &policy);
+    env.events()
+        .publish((Symbol::new(env, "expiry_proration_set"), merchant), policy);
+    Ok(())
+}
+
+/// `amount` adjusted for the merchant's expiry proration policy, for the interval charge
+/// due at `due_at`; `None` if the policy blocks it or the charge is due at or after the
+/// expiration.
+pub fn expiry_adjusted_amount(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    due_at: u64,
+    amount: i128,
+) -> Option<i128> {
+    let Some(expiration) = get_expiration(env, subscription_id) else {
+        return Some(amount);
+    };
+    if due_at >= expiration {
+        return None;
+    }
+    if due_at.saturating_add(sub.interval_seconds) <= expiration {
+        return Some(amount);
+    }
+    match get_expiry_proration(env, &sub.merchant) {
+        ExpiryProration::FullCharge => Some(amount),
+        ExpiryProration::Block => None,
+        ExpiryProration::Prorate => {
+            let covered = i128::from(expiration - due_at);
+            Some(amount.saturating_mul(covered) / i128::from(sub.interval_seconds))
+        }
+    }
+}
+
+fn auto_refund_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::ExpiryAutoRefund, merchant.clone())
+}
+
+/// Whether the merchant has opted in to refunding residual balances on expiry.
+pub fn get_auto_refund_on_expiry(env: &Env, merchant: &Address) -> bool {
+    env.storage()
+        .instance()
+        .get(&auto_refund_key(merchant))
+        .unwrap_or(false)
+}
+
+/// Merchant turns auto-refund of residual prepaid balances on expiry on or off.
+pub fn do_set_auto_refund_on_expiry(
+    env: &Env,
+    merchant: Address,
+    enabled: bool,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    let key = auto_refund_key(&merchant);
+    if enabled {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events().publish(
+        (Symbol::new(env, "auto_refund_on_expiry"), merchant),
+        enabled,
+    );
+    Ok(())
+}
+
+/// Cancels every listed subscription whose expiration has been reached and returns the
+/// IDs it cancelled. Unknown IDs, open-ended or not-yet-expired subscriptions and
+/// subscriptions that are already cancelled are skipped, so callers can pass a broad
+/// candidate list. Billing engine or billing agent only.
+///
+/// If the merchant opted in via [`do_set_auto_refund_on_expiry`], the remaining prepaid
+/// balance in the billing token is transferred back to the subscriber. Each cancellation
+/// emits `subscription_expired` with `(expiration, refunded)`.
+pub fn do_expire_subscriptions(
+    env: &Env,
+    operator: Address,
+    subscription_ids: &Vec<u32>,
+) -> Result<Vec<u32>, Error> {
+    crate::admin::require_billing_operator(env, &operator)?;
+
+    let now = env.ledger().timestamp();
+    let mut expired = Vec::new(env);
+    for id in subscription_ids.iter() {
+        let expiration = match get_expiration(env, id) {
+            Some(ts) if now >= ts => ts,
+            _ => continue,
+        };
+        let mut sub = match crate::queries::get_subscription(env, id) {
+            Ok(sub) => sub,
+            Err(_) => continue,
+        };
+        if sub.status == SubscriptionStatus::Cancelled
+            || validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled).is_err()
+        {
+            continue;
+        }
+        sub.status = SubscriptionStatus::Cancelled;
+
+        let mut refunded = 0i128;
+        if sub.prepaid_balance > 0 && get_auto_refund_on_expiry(env, &sub.merchant) {
+            refunded = sub.prepaid_balance;
+            sub.prepaid_balance = 0;
+        }
+        env.storage().instance().set(&DataKey::Sub(id), &sub);
+
+        if refunded > 0 {
+            let token = crate::billing_tokens::subscription_token(env, id)?;
+            soroban_sdk::token::Client::new(env, &token).transfer(
+                &env.current_contract_address(),
+                &sub.subscriber,
+                &refunded,
+            );
+        }
+
+        env.events().publish(
+            (Symbol::new(env, "subscription_expired"), id),
+            (expiration, refunded),
+        );
+        expired.push_back(id);
+    }
+    Ok(expired)
+}
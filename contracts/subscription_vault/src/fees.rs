@@ -0,0 +1,230 @@
+//! Protocol fee ledger: platform fee on charges, accrued fees, fee recipient and donation
+//! routing.
+//!
+//! Fees accrue internally (like merchant balances) and leave the vault only via
+//! `withdraw_protocol_fees`, which pays the fee recipient (the platform treasury) and
+//! optionally routes a configured share to a donation address.
+//!
+//! **PRs that only change protocol fee handling should edit this file only.**
+
+#![allow(dead_code)]
+
+use crate::admin::require_admin;
+use crate::safe_math::safe_add_balance;
+use crate::types::{
+    ConfigKey, DataKey, DonationConfig, Error, FeeCollectedEvent, FeeConfig,
+    ProtocolFeesWithdrawnEvent,
+};
+use soroban_sdk::{token, Address, Env, Symbol};
+
+/// Upper bound for any basis-point setting (100%).
+pub const MAX_BPS: u32 = 10_000;
+
+/// Upper bound for the platform fee on charges (10%).
+pub const MAX_PLATFORM_FEE_BPS: u32 = 1_000;
+
+fn require_admin_caller(env: &Env, admin: &Address) -> Result<(), Error> {
+    admin.require_auth();
+    if *admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+pub fn get_protocol_fee_balance(env: &Env) -> i128 {
+    env.storage()
+        .instance()
+        .get(&DataKey::ProtocolFees)
+        .unwrap_or(0i128)
+}
+
+/// Credit collected protocol fees (used by fee-charging paths).
+pub fn credit_protocol_fees(env: &Env, amount: i128) -> Result<(), Error> {
+    let balance = safe_add_balance(get_protocol_fee_balance(env), amount)?;
+    env.storage()
+        .instance()
+        .set(&DataKey::ProtocolFees, &balance);
+    Ok(())
+}
+
+/// Address receiving protocol fee withdrawals. Defaults to the admin.
+pub fn get_fee_recipient(env: &Env) -> Result<Address, Error> {
+    match env.storage().instance().get(&DataKey::FeeRecipient) {
+        Some(recipient) => Ok(recipient),
+        None => require_admin(env),
+    }
+}
+
+pub fn do_set_fee_recipient(env: &Env, admin: Address, recipient: Address) -> Result<(), Error> {
+    require_admin_caller(env, &admin)?;
+    store_fee_recipient(env, recipient);
+    Ok(())
+}
+
+/// Stores the fee recipient and emits `fee_recipient_updated`. Callers check auth.
+pub fn store_fee_recipient(env: &Env, recipient: Address) {
+    env.storage()
+        .instance()
+        .set(&DataKey::FeeRecipient, &recipient);
+    env.events()
+        .publish((Symbol::new(env, "fee_recipient_updated"),), recipient);
+}
+
+fn platform_fee_key() -> DataKey {
+    DataKey::Config(ConfigKey::PlatformFeeBps)
+}
+
+/// Platform fee in basis points taken from every settled charge; 0 when off.
+pub fn get_platform_fee_bps(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&platform_fee_key())
+        .unwrap_or(0)
+}
+
+/// Configure the platform fee on charges, at most [`MAX_PLATFORM_FEE_BPS`].
+pub fn do_set_platform_fee_bps(env: &Env, admin: Address, bps: u32) -> Result<(), Error> {
+    require_admin_caller(env, &admin)?;
+    if bps > MAX_PLATFORM_FEE_BPS {
+        return Err(Error::InvalidInput);
+    }
+    env.storage().instance().set(&platform_fee_key(), &bps);
+    env.events()
+        .publish((Symbol::new(env, "platform_fee_updated"),), bps);
+    Ok(())
+}
+
+/// Platform fee rate and the treasury address fee withdrawals go to.
+pub fn get_fee_config(env: &Env) -> Result<FeeConfig, Error> {
+    Ok(FeeConfig {
+        platform_fee_bps: get_platform_fee_bps(env),
+        fee_recipient: get_fee_recipient(env)?,
+    })
+}
+
+/// Accrues the platform fee on a settled debit and returns it; the merchant side is
+/// credited the rest. Emits `fee_collected` when a fee is taken.
+pub fn take_platform_fee(
+    env: &Env,
+    subscription_id: u32,
+    merchant: &Address,
+    debit: i128,
+) -> Result<i128, Error> {
+    let bps = get_platform_fee_bps(env);
+    if bps == 0 || debit <= 0 {
+        return Ok(0);
+    }
+    let fee = debit.checked_mul(bps as i128).ok_or(Error::Overflow)? / MAX_BPS as i128;
+    if fee > 0 {
+        credit_protocol_fees(env, fee)?;
+        env.events().publish(
+            (Symbol::new(env, "fee_collected"), subscription_id),
+            FeeCollectedEvent {
+                subscription_id,
+                merchant: merchant.clone(),
+                amount: fee,
+            },
+        );
+    }
+    Ok(fee)
+}
+
+/// Fee in basis points charged on expedited merchant withdrawals, if offered.
+pub fn get_expedite_fee_bps(env: &Env) -> Option<u32> {
+    env.storage().instance().get(&DataKey::ExpediteFeeBps)
+}
+
+/// Configure the expedited withdrawal fee. `bps == 0` stops offering expedited withdrawals.
+pub fn do_set_expedite_fee_bps(env: &Env, admin: Address, bps: u32) -> Result<(), Error> {
+    require_admin_caller(env, &admin)?;
+    if bps > MAX_BPS {
+        return Err(Error::InvalidInput);
+    }
+    let storage = env.storage().instance();
+    if bps == 0 {
+        storage.remove(&DataKey::ExpediteFeeBps);
+    } else {
+        storage.set(&DataKey::ExpediteFeeBps, &bps);
+    }
+    env.events()
+        .publish((Symbol::new(env, "expedite_fee_updated"),), bps);
+    Ok(())
+}
+
+pub fn get_donation_config(env: &Env) -> Option<DonationConfig> {
+    env.storage().instance().get(&DataKey::DonationConfig)
+}
+
+/// Configure the donation share. `bps == 0` disables donation routing.
+pub fn do_set_donation_config(
+    env: &Env,
+    admin: Address,
+    recipient: Address,
+    bps: u32,
+) -> Result<(), Error> {
+    require_admin_caller(env, &admin)?;
+    if bps > MAX_BPS {
+        return Err(Error::InvalidInput);
+    }
+    let storage = env.storage().instance();
+    if bps == 0 {
+        storage.remove(&DataKey::DonationConfig);
+    } else {
+        storage.set(
+            &DataKey::DonationConfig,
+            &DonationConfig {
+                recipient: recipient.clone(),
+                bps,
+            },
+        );
+    }
+    env.events().publish(
+        (Symbol::new(env, "donation_config_updated"),),
+        (recipient, bps),
+    );
+    Ok(())
+}
+
+/// Pay out all accrued protocol fees, splitting off the configured donation share.
+///
+/// Returns the total amount withdrawn. Fails with `NotFound` when nothing has accrued.
+pub fn do_withdraw_protocol_fees(env: &Env, admin: Address) -> Result<i128, Error> {
+    require_admin_caller(env, &admin)?;
+
+    let total = get_protocol_fee_balance(env);
+    if total == 0 {
+        return Err(Error::NotFound);
+    }
+
+    let donation = get_donation_config(env);
+    let donation_amount = match &donation {
+        Some(cfg) => total.checked_mul(cfg.bps as i128).ok_or(Error::Overflow)? / MAX_BPS as i128,
+        None => 0,
+    };
+    let fee_amount = total.checked_sub(donation_amount).ok_or(Error::Overflow)?;
+    let fee_recipient = get_fee_recipient(env)?;
+
+    env.storage().instance().set(&DataKey::ProtocolFees, &0i128);
+
+    let token_client = token::Client::new(env, &crate::admin::get_token(env)?);
+    let vault = env.current_contract_address();
+    if fee_amount > 0 {
+        token_client.transfer(&vault, &fee_recipient, &fee_amount);
+    }
+    if let Some(cfg) = &donation {
+        if donation_amount > 0 {
+            token_client.transfer(&vault, &cfg.recipient, &donation_amount);
+        }
+    }
+
+    env.events().publish(
+        (Symbol::new(env, "protocol_fees_withdrawn"),),
+        ProtocolFeesWithdrawnEvent {
+            fee_recipient,
+            fee_amount,
+            donation_recipient: donation.map(|cfg| cfg.recipient),
+            donation_amount,
+        },
+    );
+    Ok(total)
+}
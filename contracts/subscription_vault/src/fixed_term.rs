@@ -0,0 +1,85 @@
+//! Fixed-term (installment) subscriptions.
+//!
+//! A subscription can be limited to a number of billing cycles with
+//! [`do_set_max_cycles`]. Cycles are the completed interval charges counted by
+//! `loyalty.rs` since creation; once the interval charge that completes the last cycle
+//! is settled, the subscription is moved to `Cancelled`, its terminal state, and
+//! `subscription_completed` is emitted. No further interval charges are taken, and any
+//! remaining prepaid balance stays withdrawable by the subscriber as after a regular
+//! cancellation.
+//!
+//! The limit is kept under its own key so the encoded [`crate::types::Subscription`]
+//! stays unchanged, and the charge path only reads it for subscriptions that set one.
+//!
+//! **PRs that only change fixed-term subscriptions should edit this file only.**
+
+use crate::types::{DataKey, Error, SubKey, Subscription, SubscriptionStatus};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn max_cycles_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::MaxCycles, subscription_id)
+}
+
+/// Number of billing cycles the subscription is limited to, or `None` if open-ended.
+pub fn get_max_cycles(env: &Env, subscription_id: u32) -> Option<u32> {
+    env.storage()
+        .instance()
+        .get(&max_cycles_key(subscription_id))
+}
+
+/// Billing cycles left before the subscription completes, or `None` if open-ended.
+pub fn cycles_remaining(env: &Env, subscription_id: u32) -> Option<u32> {
+    let max_cycles = get_max_cycles(env, subscription_id)?;
+    let completed = crate::loyalty::get_cycles_completed(env, subscription_id);
+    Some(max_cycles.saturating_sub(completed))
+}
+
+/// Subscriber limits the subscription to `max_cycles` billing cycles in total, counted
+/// from creation, or makes it open-ended again with `None`. The limit must be above the
+/// cycles already completed.
+pub fn do_set_max_cycles(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    max_cycles: Option<u32>,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Forbidden);
+    }
+    if sub.status == SubscriptionStatus::Cancelled {
+        return Err(Error::InvalidStatusTransition);
+    }
+    let key = max_cycles_key(subscription_id);
+    match max_cycles {
+        Some(max) => {
+            if max <= crate::loyalty::get_cycles_completed(env, subscription_id) {
+                return Err(Error::InvalidInput);
+            }
+            env.storage().instance().set(&key, &max);
+        }
+        None => env.storage().instance().remove(&key),
+    }
+    env.events().publish(
+        (Symbol::new(env, "max_cycles_set"), subscription_id),
+        max_cycles,
+    );
+    Ok(())
+}
+
+/// Completes the subscription if the interval charge just settled was its last cycle.
+/// Called after the cycle is counted.
+pub fn complete_if_last_cycle(env: &Env, subscription_id: u32, sub: &mut Subscription) {
+    if cycles_remaining(env, subscription_id) != Some(0) {
+        return;
+    }
+    sub.status = SubscriptionStatus::Cancelled;
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), sub);
+    env.events().publish(
+        (Symbol::new(env, "subscription_completed"), subscription_id),
+        crate::loyalty::get_cycles_completed(env, subscription_id),
+    );
+}
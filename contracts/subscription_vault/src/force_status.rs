@@ -0,0 +1,102 @@
+//! Admin escape hatch for subscriptions the state machine has wedged.
+//!
+//! In rare support cases (a partial migration, a bug in a transition guard) a
+//! subscription can end up in a status no allowed transition leads out of. Instead of
+//! editing storage by hand, the admin schedules the change with a reason through
+//! [`do_propose_force_status`] and carries it out with [`do_force_set_status`] once the
+//! timelock delay has passed (see `timelock.rs`). The change skips the state machine and
+//! touches nothing but the status; a `StatusForcedEvent` records who forced what and why.
+//!
+//! One forced change can be pending at a time; proposing another replaces it.
+//!
+//! **PRs that only change forced status changes should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::timelock::TIMELOCK_DELAY_SECONDS;
+use crate::types::{DataKey, Error, StatusForcedEvent, SubscriptionStatus};
+use soroban_sdk::{xdr::ToXdr, Address, BytesN, Env, String, Symbol};
+
+/// Maximum length of a force reason, in bytes.
+pub const MAX_FORCE_REASON_LEN: u32 = 256;
+
+fn force_action(env: &Env) -> Symbol {
+    Symbol::new(env, "force_status")
+}
+
+fn require_admin_auth(env: &Env, admin: &Address) -> Result<(), Error> {
+    admin.require_auth();
+    if *admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+fn payload_hash(
+    env: &Env,
+    subscription_id: u32,
+    status: &SubscriptionStatus,
+    reason: &String,
+) -> BytesN<32> {
+    let payload = (subscription_id, status.clone(), reason.clone());
+    env.crypto().sha256(&payload.to_xdr(env)).into()
+}
+
+/// Admin schedules forcing the subscription into `status`. Returns when
+/// [`do_force_set_status`] becomes possible.
+///
+/// Fails with `InvalidInput` if the subscription is already in `status` or the reason
+/// is empty or longer than [`MAX_FORCE_REASON_LEN`].
+pub fn do_propose_force_status(
+    env: &Env,
+    admin: Address,
+    subscription_id: u32,
+    status: SubscriptionStatus,
+    reason: String,
+) -> Result<u64, Error> {
+    require_admin_auth(env, &admin)?;
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.status == status || reason.is_empty() || reason.len() > MAX_FORCE_REASON_LEN {
+        return Err(Error::InvalidInput);
+    }
+    let hash = payload_hash(env, subscription_id, &status, &reason);
+    crate::timelock::schedule(env, force_action(env), hash)
+}
+
+/// Admin carries out the scheduled change, bypassing the state machine.
+///
+/// The arguments must match the proposal (`InvalidInput` otherwise), and the call fails
+/// with `TimelockPending` before the delay has passed.
+pub fn do_force_set_status(
+    env: &Env,
+    admin: Address,
+    subscription_id: u32,
+    status: SubscriptionStatus,
+    reason: String,
+) -> Result<(), Error> {
+    require_admin_auth(env, &admin)?;
+    let mut sub = crate::queries::get_subscription(env, subscription_id)?;
+    let action = force_action(env);
+    let entry = crate::timelock::get_entry(env, &action).ok_or(Error::NotFound)?;
+    let hash = payload_hash(env, subscription_id, &status, &reason);
+    crate::timelock::consume(env, action, &hash)?;
+
+    let from = sub.status.clone();
+    sub.status = status.clone();
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
+    env.events().publish(
+        (Symbol::new(env, "status_forced"), subscription_id),
+        StatusForcedEvent {
+            subscription_id,
+            admin,
+            from,
+            to: status,
+            reason,
+            prepaid_balance: sub.prepaid_balance,
+            proposed_at: entry.ready_at.saturating_sub(TIMELOCK_DELAY_SECONDS),
+            forced_at: env.ledger().timestamp(),
+        },
+    );
+    Ok(())
+}
@@ -0,0 +1,194 @@
+//! Third-party guarantors co-signing a subscription.
+//!
+//! A subscriber and a guarantor both sign to attach the guarantor to a subscription with
+//! a cap. When an interval charge cannot be covered by the prepaid balance, secondary
+//! token balances or auto-topup (see `auto_topup.rs`), the shortfall is pulled from the
+//! guarantor's wallet with `transfer_from`, using an allowance the guarantor granted the
+//! vault, and added to the prepaid balance before the charge settles. The charge's
+//! settlement guards (see `charge_core::ensure_settleable`) have all passed by then, so a
+//! guarantor is never pulled for a charge that is refused, in a batch or otherwise.
+//!
+//! Every pull is recorded against the `(subscription, guarantor)` pair, apart from the
+//! subscription's own balances, so the two parties can settle it between themselves. The
+//! guarantor records repayments with [`do_record_repayment`]; the cap bounds what may be
+//! outstanding at once, so repaid amounts can be pulled again.
+//!
+//! **PRs that only change guarantors should edit this file only.**
+
+use crate::safe_math::{safe_add_balance, safe_sub_balance};
+use crate::types::{
+    DataKey, Error, Guarantee, GuarantorContributions, GuarantorPullEvent, RecordKey, SubKey,
+    Subscription,
+};
+use soroban_sdk::{token, Address, Env, Symbol};
+
+fn guarantee_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::Guarantee, subscription_id)
+}
+
+fn contributions_key(subscription_id: u32, guarantor: &Address) -> DataKey {
+    DataKey::Record(RecordKey::GuarantorContribution(
+        subscription_id,
+        guarantor.clone(),
+    ))
+}
+
+/// Guarantor backing the subscription, or `None` if there is none.
+pub fn get_guarantor(env: &Env, subscription_id: u32) -> Option<Guarantee> {
+    env.storage()
+        .instance()
+        .get(&guarantee_key(subscription_id))
+}
+
+/// Amounts pulled from `guarantor` for the subscription; zero if it never contributed.
+pub fn get_contributions(
+    env: &Env,
+    subscription_id: u32,
+    guarantor: &Address,
+) -> GuarantorContributions {
+    env.storage()
+        .instance()
+        .get(&contributions_key(subscription_id, guarantor))
+        .unwrap_or(GuarantorContributions {
+            outstanding: 0,
+            total_contributed: 0,
+        })
+}
+
+fn set_contributions(
+    env: &Env,
+    subscription_id: u32,
+    guarantor: &Address,
+    contributions: &GuarantorContributions,
+) {
+    env.storage().instance().set(
+        &contributions_key(subscription_id, guarantor),
+        contributions,
+    );
+}
+
+/// Subscriber and guarantor co-sign to back the subscription with the guarantor's
+/// allowance, up to `cap` outstanding at once. Replaces any earlier guarantor; what an
+/// earlier guarantor contributed stays recorded under its own address.
+pub fn do_set_guarantor(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    guarantor: Address,
+    cap: i128,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    guarantor.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Forbidden);
+    }
+    if guarantor == subscriber {
+        return Err(Error::InvalidInput);
+    }
+    if cap <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let guarantee = Guarantee {
+        guarantor: guarantor.clone(),
+        cap,
+    };
+    env.storage()
+        .instance()
+        .set(&guarantee_key(subscription_id), &guarantee);
+    env.events().publish(
+        (Symbol::new(env, "guarantor_set"), subscription_id),
+        (guarantor, cap),
+    );
+    Ok(())
+}
+
+/// Subscriber or the current guarantor detaches the guarantor. Recorded contributions
+/// are kept.
+pub fn do_remove_guarantor(env: &Env, subscription_id: u32, caller: Address) -> Result<(), Error> {
+    caller.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    let guarantee = get_guarantor(env, subscription_id).ok_or(Error::NotFound)?;
+    if caller != sub.subscriber && caller != guarantee.guarantor {
+        return Err(Error::Forbidden);
+    }
+    env.storage()
+        .instance()
+        .remove(&guarantee_key(subscription_id));
+    env.events().publish(
+        (Symbol::new(env, "guarantor_removed"), subscription_id),
+        guarantee.guarantor,
+    );
+    Ok(())
+}
+
+/// Guarantor records that `amount` of its outstanding contributions was repaid by the
+/// subscriber outside the vault, freeing that much of its cap.
+pub fn do_record_repayment(
+    env: &Env,
+    subscription_id: u32,
+    guarantor: Address,
+    amount: i128,
+) -> Result<GuarantorContributions, Error> {
+    guarantor.require_auth();
+    let mut contributions = get_contributions(env, subscription_id, &guarantor);
+    if amount <= 0 || amount > contributions.outstanding {
+        return Err(Error::InvalidAmount);
+    }
+    contributions.outstanding = safe_sub_balance(contributions.outstanding, amount)?;
+    set_contributions(env, subscription_id, &guarantor, &contributions);
+    env.events().publish(
+        (Symbol::new(env, "guarantor_repaid"), subscription_id),
+        (guarantor, amount, contributions.outstanding),
+    );
+    Ok(contributions)
+}
+
+/// Pulls `shortfall` from the guarantor's wallet into `sub.prepaid_balance`. Returns
+/// `false`, without pulling anything, if there is no guarantor or the cap, allowance or
+/// wallet balance is too small. Only called once the charge's settlement guards have
+/// passed. The caller stores `sub`.
+pub fn pull_shortfall(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    shortfall: i128,
+) -> Result<bool, Error> {
+    let Some(guarantee) = get_guarantor(env, subscription_id) else {
+        return Ok(false);
+    };
+    let mut contributions = get_contributions(env, subscription_id, &guarantee.guarantor);
+    let outstanding = safe_add_balance(contributions.outstanding, shortfall)?;
+    if outstanding > guarantee.cap {
+        return Ok(false);
+    }
+
+    let vault = env.current_contract_address();
+    let token = token::Client::new(
+        env,
+        &crate::billing_tokens::subscription_token(env, subscription_id)?,
+    );
+    if token.allowance(&guarantee.guarantor, &vault) < shortfall
+        || token.balance(&guarantee.guarantor) < shortfall
+        || token
+            .try_transfer_from(&vault, &guarantee.guarantor, &vault, &shortfall)
+            .is_err()
+    {
+        return Ok(false);
+    }
+
+    sub.prepaid_balance = safe_add_balance(sub.prepaid_balance, shortfall)?;
+    contributions.outstanding = outstanding;
+    contributions.total_contributed = safe_add_balance(contributions.total_contributed, shortfall)?;
+    set_contributions(env, subscription_id, &guarantee.guarantor, &contributions);
+    env.events().publish(
+        (Symbol::new(env, "guarantor_pull"), subscription_id),
+        GuarantorPullEvent {
+            subscription_id,
+            guarantor: guarantee.guarantor,
+            amount: shortfall,
+            outstanding,
+        },
+    );
+    Ok(true)
+}
@@ -0,0 +1,86 @@
+//! Subscription health: a compact risk summary for merchant dashboards.
+//!
+//! Combines the balance runway, the dunning failure counter (see `dunning.rs`) and
+//! lifetime dispute and pause counters kept here, so at-risk customers can be flagged
+//! from one read instead of replaying history.
+//!
+//! **PRs that only change health scoring should edit this file only.**
+
+use crate::queries::get_subscription;
+use crate::types::{DataKey, Error, HealthCounters, SubscriptionHealth, SubscriptionStatus};
+use soroban_sdk::Env;
+
+fn get_counters(env: &Env, subscription_id: u32) -> HealthCounters {
+    env.storage()
+        .instance()
+        .get(&DataKey::HealthCounters(subscription_id))
+        .unwrap_or(HealthCounters {
+            disputes_opened: 0,
+            pauses: 0,
+            last_paused_at: 0,
+        })
+}
+
+fn set_counters(env: &Env, subscription_id: u32, counters: &HealthCounters) {
+    env.storage()
+        .instance()
+        .set(&DataKey::HealthCounters(subscription_id), counters);
+}
+
+/// Counts a dispute opened against one of the subscription's charges.
+#[cfg(feature = "disputes")]
+pub fn record_dispute(env: &Env, subscription_id: u32) {
+    let mut counters = get_counters(env, subscription_id);
+    counters.disputes_opened = counters.disputes_opened.saturating_add(1);
+    set_counters(env, subscription_id, &counters);
+}
+
+/// Counts a pause of the subscription.
+pub fn record_pause(env: &Env, subscription_id: u32) {
+    let mut counters = get_counters(env, subscription_id);
+    counters.pauses = counters.pauses.saturating_add(1);
+    counters.last_paused_at = env.ledger().timestamp();
+    set_counters(env, subscription_id, &counters);
+}
+
+/// Ledger time of the subscription's most recent pause; 0 if it was never paused.
+pub fn last_paused_at(env: &Env, subscription_id: u32) -> u64 {
+    get_counters(env, subscription_id).last_paused_at
+}
+
+/// Health summary of a subscription.
+///
+/// `runway_periods` counts only the prepaid (vault token) balance, not secondary token
+/// balances, against the next charge's amount including loyalty discount and any setup
+/// fee installment. A zero amount due counts as `u32::MAX` periods.
+pub fn get_subscription_health(
+    env: &Env,
+    subscription_id: u32,
+) -> Result<SubscriptionHealth, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    let amount_due = crate::queries::preview_charge(env, subscription_id)?.amount_due;
+    let runway_periods = if amount_due <= 0 {
+        u32::MAX
+    } else {
+        u32::try_from(sub.prepaid_balance.max(0) / amount_due).unwrap_or(u32::MAX)
+    };
+    let failed_attempts = crate::dunning::get_failed_attempts(env, subscription_id);
+    let counters = get_counters(env, subscription_id);
+
+    let at_risk = failed_attempts > 0
+        || matches!(
+            sub.status,
+            SubscriptionStatus::InsufficientBalance | SubscriptionStatus::GracePeriod
+        )
+        || (runway_periods == 0 && sub.status == SubscriptionStatus::Active);
+
+    Ok(SubscriptionHealth {
+        status: sub.status,
+        runway_periods,
+        failed_attempts,
+        disputes_opened: counters.disputes_opened,
+        pauses: counters.pauses,
+        last_paused_at: counters.last_paused_at,
+        at_risk,
+    })
+}
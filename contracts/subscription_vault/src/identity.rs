@@ -0,0 +1,150 @@
+//! Cross-wallet identity linkage: a subscriber groups the wallets they rotate through
+//! under one self-attested identity hash.
+//!
+//! An identity is founded by a wallet committing to it: the identity hash is
+//! [`identity_commitment`] of the founding wallet and a salt the user keeps, so nobody
+//! can claim a hash in advance and wait for its owner. Further wallets can only join
+//! with the authorization of a wallet already linked, so nobody can attach themselves
+//! to another user's identity to inherit its history. Any wallet can unlink itself at
+//! any time.
+//!
+//! Trial eligibility on plans and loyalty tiers read [`linked_wallets`] to treat the
+//! group as one customer (see `plans.rs` and `loyalty.rs`).
+//!
+//! **PRs that only change identity linkage should edit this file only.**
+
+use crate::types::{DataKey, Error};
+use soroban_sdk::{xdr::ToXdr, Address, Bytes, BytesN, Env, Symbol, Vec};
+
+/// Maximum number of wallets linked under one identity (bounds lookups).
+pub const MAX_LINKED_WALLETS: u32 = 10;
+
+/// Identity hash the wallet is linked to, if any.
+pub fn get_identity(env: &Env, wallet: &Address) -> Option<BytesN<32>> {
+    env.storage()
+        .instance()
+        .get(&DataKey::WalletIdentity(wallet.clone()))
+}
+
+/// Wallets linked under `identity`, in link order.
+pub fn get_identity_wallets(env: &Env, identity: &BytesN<32>) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get(&DataKey::IdentityWallets(identity.clone()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Every wallet belonging to the same identity as `wallet`, including itself.
+pub fn linked_wallets(env: &Env, wallet: &Address) -> Vec<Address> {
+    match get_identity(env, wallet) {
+        Some(identity) => get_identity_wallets(env, &identity),
+        None => Vec::from_array(env, [wallet.clone()]),
+    }
+}
+
+/// Identity hash `wallet` founds with `salt`: `sha256(salt || wallet XDR)`.
+pub fn identity_commitment(env: &Env, wallet: &Address, salt: &BytesN<32>) -> BytesN<32> {
+    let mut data = Bytes::from_array(env, &salt.to_array());
+    data.append(&wallet.clone().to_xdr(env));
+    env.crypto().sha256(&data).into()
+}
+
+/// `wallet` founds the identity committed to by `salt` and returns its hash.
+///
+/// Fails with `Forbidden` while other wallets are still linked to it; the founder then
+/// rejoins through [`do_link_wallet`]. A wallet belongs to at most one identity
+/// (`InvalidInput`).
+pub fn do_found_identity(
+    env: &Env,
+    wallet: Address,
+    salt: BytesN<32>,
+) -> Result<BytesN<32>, Error> {
+    wallet.require_auth();
+
+    let identity = identity_commitment(env, &wallet, &salt);
+    if let Some(current) = get_identity(env, &wallet) {
+        return if current == identity {
+            Ok(identity)
+        } else {
+            Err(Error::InvalidInput)
+        };
+    }
+    let wallets = get_identity_wallets(env, &identity);
+    if !wallets.is_empty() {
+        return Err(Error::Forbidden);
+    }
+    add_wallet(env, wallet, &identity, wallets);
+    Ok(identity)
+}
+
+/// Links `wallet` to an existing `identity` with the authorization of `sponsor`, one of
+/// its wallets.
+///
+/// Fails with `NotFound` if nobody founded the identity and `Forbidden` if `sponsor`
+/// is not linked to it. A wallet belongs to at most one identity; it must unlink before
+/// joining another. Fails with `InvalidInput` once [`MAX_LINKED_WALLETS`] is reached.
+pub fn do_link_wallet(
+    env: &Env,
+    wallet: Address,
+    identity: BytesN<32>,
+    sponsor: Address,
+) -> Result<(), Error> {
+    wallet.require_auth();
+
+    if let Some(current) = get_identity(env, &wallet) {
+        return if current == identity {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput)
+        };
+    }
+
+    let wallets = get_identity_wallets(env, &identity);
+    if wallets.is_empty() {
+        return Err(Error::NotFound);
+    }
+    if !wallets.contains(&sponsor) {
+        return Err(Error::Forbidden);
+    }
+    sponsor.require_auth();
+    if wallets.len() >= MAX_LINKED_WALLETS {
+        return Err(Error::InvalidInput);
+    }
+    add_wallet(env, wallet, &identity, wallets);
+    Ok(())
+}
+
+fn add_wallet(env: &Env, wallet: Address, identity: &BytesN<32>, mut wallets: Vec<Address>) {
+    wallets.push_back(wallet.clone());
+    let storage = env.storage().instance();
+    storage.set(&DataKey::IdentityWallets(identity.clone()), &wallets);
+    storage.set(&DataKey::WalletIdentity(wallet.clone()), identity);
+
+    env.events().publish(
+        (Symbol::new(env, "wallet_linked"), wallet),
+        identity.clone(),
+    );
+}
+
+/// Removes `wallet` from its identity. The identity is dropped with its last wallet.
+pub fn do_unlink_wallet(env: &Env, wallet: Address) -> Result<(), Error> {
+    wallet.require_auth();
+
+    let identity = get_identity(env, &wallet).ok_or(Error::NotFound)?;
+    let mut wallets = get_identity_wallets(env, &identity);
+    if let Some(index) = wallets.first_index_of(&wallet) {
+        wallets.remove(index);
+    }
+
+    let storage = env.storage().instance();
+    storage.remove(&DataKey::WalletIdentity(wallet.clone()));
+    if wallets.is_empty() {
+        storage.remove(&DataKey::IdentityWallets(identity.clone()));
+    } else {
+        storage.set(&DataKey::IdentityWallets(identity.clone()), &wallets);
+    }
+
+    env.events()
+        .publish((Symbol::new(env, "wallet_unlinked"), wallet), identity);
+    Ok(())
+}
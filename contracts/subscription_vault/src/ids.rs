@@ -0,0 +1,157 @@
+//! Subscription ID allocation: the forward-only counter, shards and reservations.
+//!
+//! Every path that creates a subscription takes its ID from [`next_id`], so IDs are never
+//! reused and allocation stops with `SubscriptionLimitReached` at the end of the ID range
+//! instead of wrapping. See `docs/subscription_ids.md`.
+//!
+//! Operators running several vaults can give each one a shard: shard `n` allocates from
+//! `n * ID_SHARD_SPAN` up to the start of shard `n + 1`, so IDs stay unique across the
+//! deployments. The shard can only be set before the first ID is allocated.
+//!
+//! **PRs that only change ID allocation should edit this file only.**
+
+use crate::types::{ConfigKey, DataKey, Error, SubscriptionIdReservation};
+use crate::MAX_SUBSCRIPTION_ID;
+use soroban_sdk::{Address, Env, Symbol};
+
+/// Number of subscription IDs in one shard.
+pub const ID_SHARD_SPAN: u32 = 1 << 24;
+
+/// Number of shards the ID space is split into.
+pub const MAX_ID_SHARDS: u32 = 256;
+
+/// How long a reserved subscription ID stays claimable (24 hours).
+pub const ID_RESERVATION_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+/// Shard this contract allocates from; 0 unless the admin set one.
+pub fn get_id_shard(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::IdShard))
+        .unwrap_or(0)
+}
+
+/// First ID of this contract's range.
+pub fn first_id(env: &Env) -> u32 {
+    get_id_shard(env) * ID_SHARD_SPAN
+}
+
+/// End of this contract's range (exclusive). The last shard ends at
+/// [`MAX_SUBSCRIPTION_ID`], which is never allocated.
+fn id_limit(env: &Env) -> u32 {
+    (get_id_shard(env) + 1)
+        .checked_mul(ID_SHARD_SPAN)
+        .unwrap_or(MAX_SUBSCRIPTION_ID)
+}
+
+/// ID the next allocation will return, without allocating it.
+pub fn peek_next_id(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::NextId)
+        .unwrap_or_else(|| first_id(env))
+}
+
+/// Allocates the next subscription ID.
+///
+/// Fails with `SubscriptionLimitReached` once the counter reaches the end of the range.
+/// The guard runs before the increment, so the counter can neither overflow nor wrap.
+pub fn next_id(env: &Env) -> Result<u32, Error> {
+    let id = peek_next_id(env);
+    if id >= id_limit(env) {
+        return Err(Error::SubscriptionLimitReached);
+    }
+    env.storage().instance().set(&DataKey::NextId, &(id + 1));
+    Ok(id)
+}
+
+/// Moves the counter past `id` for subscriptions written under a given ID, such as
+/// state imports, so later allocations cannot collide with them.
+#[cfg(feature = "state-chunks")]
+pub fn mark_allocated(env: &Env, id: u32) -> Result<(), Error> {
+    let next = id.checked_add(1).ok_or(Error::Overflow)?;
+    if next > peek_next_id(env) {
+        env.storage().instance().set(&DataKey::NextId, &next);
+    }
+    Ok(())
+}
+
+/// Admin assigns this contract's ID shard. Only possible before the first ID is
+/// allocated; `shard` must be below [`MAX_ID_SHARDS`].
+pub fn do_set_id_shard(env: &Env, admin: Address, shard: u32) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    let storage = env.storage().instance();
+    if shard >= MAX_ID_SHARDS || storage.has(&DataKey::NextId) {
+        return Err(Error::InvalidInput);
+    }
+    storage.set(&DataKey::Config(ConfigKey::IdShard), &shard);
+    storage.set(&DataKey::NextId, &(shard * ID_SHARD_SPAN));
+
+    env.events()
+        .publish((Symbol::new(env, "id_shard_set"),), shard);
+    Ok(())
+}
+
+/// Admin allocates a subscription ID ahead of creation so backends can print it on
+/// invoices or QR codes. Only `subscriber` can claim it, for `merchant`, via
+/// [`claim_reservation`] until the reservation expires; an expired ID is never reused.
+pub fn do_reserve_subscription_id(
+    env: &Env,
+    admin: Address,
+    subscriber: Address,
+    merchant: Address,
+) -> Result<u32, Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+
+    let id = next_id(env)?;
+    let expires_at = env
+        .ledger()
+        .timestamp()
+        .checked_add(ID_RESERVATION_TTL_SECONDS)
+        .ok_or(Error::Overflow)?;
+    let reservation = SubscriptionIdReservation {
+        subscriber,
+        merchant,
+        expires_at,
+    };
+    env.storage()
+        .instance()
+        .set(&DataKey::IdReservation(id), &reservation);
+
+    env.events().publish(
+        (Symbol::new(env, "subscription_id_reserved"), id),
+        expires_at,
+    );
+    Ok(id)
+}
+
+/// Consumes the reservation of `subscription_id` so `subscriber` can create a
+/// subscription with `merchant` under it.
+///
+/// Fails with `NotFound` if the ID was never reserved or was already claimed,
+/// `Forbidden` if it was reserved for another subscriber or merchant, and
+/// `Expired` once the reservation TTL has passed.
+pub fn claim_reservation(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: &Address,
+    merchant: &Address,
+) -> Result<(), Error> {
+    let key = DataKey::IdReservation(subscription_id);
+    let reservation: SubscriptionIdReservation =
+        env.storage().instance().get(&key).ok_or(Error::NotFound)?;
+    if reservation.subscriber != *subscriber || reservation.merchant != *merchant {
+        return Err(Error::Forbidden);
+    }
+    if env.ledger().timestamp() > reservation.expires_at {
+        return Err(Error::Expired);
+    }
+    env.storage().instance().remove(&key);
+    Ok(())
+}
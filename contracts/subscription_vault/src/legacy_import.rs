@@ -0,0 +1,119 @@
+//! Import of subscriptions from an off-chain billing system, signed by a migration key.
+//!
+//! A merchant moving from a web2 billing provider exports each customer's subscription
+//! as a [`LegacySubscriptionRecord`] and has it signed with an ed25519 key the admin
+//! registered as the migration key. Anyone may then submit the signed record, so the
+//! import can be relayed in bulk without each subscriber signing. The subscription is
+//! created with its billing schedule and completed cycles carried over, which keeps the
+//! customer's loyalty tier, and its original start date is kept next to it.
+//!
+//! Imported subscriptions start with an empty prepaid balance; the subscriber deposits
+//! as usual. Each `external_id` can be imported once.
+//!
+//! **PRs that only change legacy subscription import should edit this file only.**
+
+use crate::types::{
+    ConfigKey, DataKey, Error, LegacyOrigin, RecordKey, SignedLegacySubscription, SubKey,
+    Subscription, SubscriptionStatus,
+};
+use soroban_sdk::{xdr::ToXdr, Address, BytesN, Env, String, Symbol};
+
+fn key_key() -> DataKey {
+    DataKey::Config(ConfigKey::MigrationKey)
+}
+
+fn external_key(external_id: &String) -> DataKey {
+    DataKey::Record(RecordKey::LegacyExternalId(external_id.clone()))
+}
+
+fn origin_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::LegacyOrigin, subscription_id)
+}
+
+/// Public key legacy records must be signed with, if imports are enabled.
+pub fn get_migration_key(env: &Env) -> Option<BytesN<32>> {
+    env.storage().instance().get(&key_key())
+}
+
+/// Admin designates the ed25519 public key that signs legacy records, or disables
+/// imports with `None`.
+pub fn do_set_migration_key(
+    env: &Env,
+    admin: Address,
+    public_key: Option<BytesN<32>>,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    match &public_key {
+        Some(public_key) => env.storage().instance().set(&key_key(), public_key),
+        None => env.storage().instance().remove(&key_key()),
+    }
+    env.events()
+        .publish((Symbol::new(env, "migration_key_set"),), public_key);
+    Ok(())
+}
+
+/// Where an imported subscription came from, or `None` if it was created on-chain.
+pub fn get_legacy_origin(env: &Env, subscription_id: u32) -> Option<LegacyOrigin> {
+    env.storage().instance().get(&origin_key(subscription_id))
+}
+
+/// Verifies `signed` against the migration key and creates its subscription. The
+/// signature covers the XDR encoding of `(vault address, record)`, so a record signed for
+/// one vault cannot be replayed into another; a bad signature aborts the call.
+///
+/// Fails with `Forbidden` while no migration key is set, `Replay` if the external ID was
+/// already imported, `InvalidAmount` for a negative amount and `InvalidInput` for a zero
+/// interval or timestamps out of order (`started_at <= last_payment_timestamp <= now`).
+pub fn do_import_legacy_subscription(
+    env: &Env,
+    signed: SignedLegacySubscription,
+) -> Result<u32, Error> {
+    let public_key = get_migration_key(env).ok_or(Error::Forbidden)?;
+    let record = signed.record;
+    let message = (env.current_contract_address(), record.clone()).to_xdr(env);
+    env.crypto()
+        .ed25519_verify(&public_key, &message, &signed.signature);
+
+    let ext_key = external_key(&record.external_id);
+    if env.storage().instance().has(&ext_key) {
+        return Err(Error::Replay);
+    }
+    if record.amount < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if record.interval_seconds == 0
+        || record.started_at > record.last_payment_timestamp
+        || record.last_payment_timestamp > env.ledger().timestamp()
+    {
+        return Err(Error::InvalidInput);
+    }
+
+    let sub = Subscription {
+        subscriber: record.subscriber,
+        merchant: record.merchant,
+        amount: record.amount,
+        interval_seconds: record.interval_seconds,
+        last_payment_timestamp: record.last_payment_timestamp,
+        status: SubscriptionStatus::Active,
+        prepaid_balance: 0,
+        usage_enabled: false,
+    };
+    let id = crate::ids::next_id(env)?;
+    crate::subscription::store_new_subscription(env, id, &sub, None)?;
+    crate::loyalty::set_cycles_completed(env, id, record.cycles_completed);
+
+    let origin = LegacyOrigin {
+        external_id: record.external_id,
+        started_at: record.started_at,
+    };
+    env.storage().instance().set(&ext_key, &id);
+    env.storage().instance().set(&origin_key(id), &origin);
+    env.events().publish(
+        (Symbol::new(env, "legacy_imported"), id),
+        (origin.external_id, record.cycles_completed),
+    );
+    Ok(id)
+}
@@ -5,138 +5,81 @@
 
 // ── Modules ──────────────────────────────────────────────────────────────────
 mod admin;
+mod approved_charges;
+mod auto_topup;
+mod billing_tokens;
+mod bundles;
+mod cancel_proration;
 mod charge_core;
+mod circuit_breaker;
+#[cfg(feature = "disputes")]
+mod disputes;
+mod dunning;
+mod entitlement;
+mod error_context;
+mod expiration;
+mod fees;
+mod fixed_term;
+mod force_status;
+mod guarantor;
+mod health;
+mod identity;
+mod ids;
+mod legacy_import;
+mod liveness;
+mod low_balance;
+mod loyalty;
 mod merchant;
+mod merchant_privacy;
+mod merchant_registry;
+mod min_charge;
+mod multi_token;
+mod notify;
+mod offboarding;
+mod one_off_allowance;
+mod payout_address;
+mod payouts;
+mod plans;
+mod promo;
 mod queries;
+mod readiness;
+mod refunds;
+#[cfg(feature = "replay")]
+mod replay;
+mod reserve;
+mod risk;
+mod round_up;
+mod safe_math;
+mod sandbox;
+mod setup_fee;
+mod skips;
+mod sla;
+mod spending_cap;
+#[cfg(feature = "state-chunks")]
+mod state_chunks;
 mod state_machine;
+mod statements;
+mod storage_migration;
 mod subscription;
+mod terms;
+mod timelock;
+mod token_guard;
+mod token_migration;
 mod types;
+mod usage;
+mod usage_credits;
+mod usage_tiers;
 
+// ── Re-exports (used by tests and external consumers) ────────────────────────
+pub use queries::compute_next_charge_info;
+#[cfg(feature = "replay")]
+pub use replay::{MerchantBalanceMismatch, ReplayEvent, ReplayReport, SubscriptionBalanceMismatch};
+#[cfg(feature = "state-chunks")]
+pub use state_chunks::{MerchantBalanceRecord, StateChunk, SubscriptionRecord};
+pub use state_machine::{can_transition, get_allowed_transitions, validate_status_transition};
+pub use types::*;
 
-use soroban_sdk::{contract, contracterror, contractimpl, contracttype, Address, Env, Symbol};
-
-#[contracterror]
-#[derive(Clone, Debug, Eq, PartialEq)]
-#[repr(u32)]
-pub enum Error {
-    NotFound = 404,
-    Unauthorized = 401,
-    InvalidStatusTransition = 400,
-    BelowMinimumTopup = 402,
-
-    /// Charge attempt was made after the subscription's expiration timestamp.
-    SubscriptionExpired = 410,
-    /// The contract has allocated [`MAX_SUBSCRIPTION_ID`] subscriptions and
-    /// cannot issue any more IDs. This prevents `u32` counter overflow.
-    SubscriptionLimitReached = 429,
-
-    RecoveryNotAllowed = 403,
-    InvalidRecoveryAmount = 405,
-
-}
-
-/// Represents the lifecycle state of a subscription.
-///
-/// # State Machine
-///
-/// The subscription status follows a defined state machine with specific allowed transitions:
-///
-/// - **Active**: Subscription is active and charges can be processed.
-///   - Can transition to: `Paused`, `Cancelled`, `InsufficientBalance`
-///
-/// - **Paused**: Subscription is temporarily suspended, no charges are processed.
-///   - Can transition to: `Active`, `Cancelled`
-///
-/// - **Cancelled**: Subscription is permanently terminated, no further changes allowed.
-///   - No outgoing transitions (terminal state)
-///
-/// - **InsufficientBalance**: Subscription failed due to insufficient funds.
-///   - Can transition to: `Active` (after deposit), `Cancelled`
-///
-/// Invalid transitions (e.g., `Cancelled` -> `Active`) are rejected with
-/// [`Error::InvalidStatusTransition`].
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum SubscriptionStatus {
-    /// Subscription is active and ready for charging.
-    Active = 0,
-    /// Subscription is temporarily paused, no charges processed.
-    Paused = 1,
-    /// Subscription is permanently cancelled (terminal state).
-    Cancelled = 2,
-    /// Subscription failed due to insufficient balance for charging.
-    InsufficientBalance = 3,
-}
-
-
-/// Represents the reason for stranded funds that can be recovered by admin.
-///
-/// This enum documents the specific, well-defined cases where funds may become
-/// stranded in the contract and require administrative intervention. Each case
-/// must be carefully audited before recovery is permitted.
-///
-/// # Security Note
-///
-/// Recovery is an exceptional operation that should only be used for truly
-/// stranded funds. All recovery operations are logged via events and should
-/// be subject to governance review.
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum RecoveryReason {
-    /// Funds sent to contract address by mistake (no associated subscription).
-    /// This occurs when users accidentally send tokens directly to the contract.
-    AccidentalTransfer = 0,
-
-    /// Funds from deprecated contract flows or logic errors.
-    /// Used when contract upgrades or bugs leave funds in an inaccessible state.
-    DeprecatedFlow = 1,
-
-    /// Funds from cancelled subscriptions with unreachable addresses.
-    /// Subscribers may lose access to their withdrawal keys after cancellation.
-    UnreachableSubscriber = 2,
-}
-
-/// Event emitted when admin recovers stranded funds.
-///
-/// This event provides a complete audit trail for all recovery operations,
-/// including who initiated it, why, and how much was recovered.
-#[contracttype]
-#[derive(Clone, Debug)]
-pub struct RecoveryEvent {
-    /// The admin who authorized the recovery
-    pub admin: Address,
-    /// The destination address receiving the recovered funds
-    pub recipient: Address,
-    /// The amount of funds recovered
-    pub amount: i128,
-    /// The documented reason for recovery
-    pub reason: RecoveryReason,
-    /// Timestamp when recovery was executed
-    pub timestamp: u64,
-}
-
-
-/// Stores subscription details and current state.
-///
-/// The `status` field is managed by the state machine. Use the provided
-/// transition helpers to modify status, never set it directly.
-#[contracttype]
-#[derive(Clone, Debug)]
-pub struct Subscription {
-    pub subscriber: Address,
-    pub merchant: Address,
-    pub amount: i128,
-    pub interval_seconds: u64,
-    pub last_payment_timestamp: u64,
-    /// Current lifecycle state. Modified only through state machine transitions.
-    pub status: SubscriptionStatus,
-    pub prepaid_balance: i128,
-    pub usage_enabled: bool,
-
-    /// Optional Unix timestamp (seconds) after which no more charges are allowed.
-    /// `None` means the subscription has no fixed end date and runs indefinitely.
-    pub expiration: Option<u64>,
-}
+use soroban_sdk::{contract, contractimpl, Address, Env, String, Symbol, Vec};
 
 /// Maximum subscription ID this contract will ever allocate.
 ///
@@ -147,159 +90,7 @@ pub struct Subscription {
 /// limit that no real deployment will ever approach.
 pub const MAX_SUBSCRIPTION_ID: u32 = u32::MAX;
 
-
-}
-
-
-/// Validates if a status transition is allowed by the state machine.
-///
-/// # State Transition Rules
-///
-/// | From              | To                  | Allowed |
-/// |-------------------|---------------------|---------|
-/// | Active            | Paused              | Yes     |
-/// | Active            | Cancelled           | Yes     |
-/// | Active            | InsufficientBalance | Yes     |
-/// | Paused            | Active              | Yes     |
-/// | Paused            | Cancelled           | Yes     |
-/// | InsufficientBalance | Active            | Yes     |
-/// | InsufficientBalance | Cancelled         | Yes     |
-/// | Cancelled         | *any*               | No      |
-/// | *any*             | Same status         | Yes (idempotent) |
-///
-/// # Arguments
-/// * `from` - Current status
-/// * `to` - Target status
-///
-/// # Returns
-/// * `Ok(())` if transition is valid
-/// * `Err(Error::InvalidStatusTransition)` if transition is invalid
-pub fn validate_status_transition(
-    from: &SubscriptionStatus,
-    to: &SubscriptionStatus,
-) -> Result<(), Error> {
-    // Same status is always allowed (idempotent)
-    if from == to {
-        return Ok(());
-    }
-
-    let valid = match from {
-        SubscriptionStatus::Active => matches!(
-            to,
-            SubscriptionStatus::Paused
-                | SubscriptionStatus::Cancelled
-                | SubscriptionStatus::InsufficientBalance
-        ),
-        SubscriptionStatus::Paused => {
-            matches!(
-                to,
-                SubscriptionStatus::Active | SubscriptionStatus::Cancelled
-            )
-        }
-        SubscriptionStatus::Cancelled => false,
-        SubscriptionStatus::InsufficientBalance => {
-            matches!(
-                to,
-                SubscriptionStatus::Active | SubscriptionStatus::Cancelled
-            )
-        }
-    };
-
-    if valid {
-        Ok(())
-    } else {
-        Err(Error::InvalidStatusTransition)
-    }
-}
-
-/// Returns all valid target statuses for a given current status.
-///
-/// This is useful for UI/documentation to show available actions.
-///
-/// # Examples
-///
-/// ```
-/// let targets = get_allowed_transitions(&SubscriptionStatus::Active);
-/// assert!(targets.contains(&SubscriptionStatus::Paused));
-/// ```
-pub fn get_allowed_transitions(status: &SubscriptionStatus) -> &'static [SubscriptionStatus] {
-    match status {
-        SubscriptionStatus::Active => &[
-            SubscriptionStatus::Paused,
-            SubscriptionStatus::Cancelled,
-            SubscriptionStatus::InsufficientBalance,
-        ],
-
-        SubscriptionStatus::Paused => &[
-            SubscriptionStatus::Active,
-            SubscriptionStatus::Cancelled,
-        ],
-        SubscriptionStatus::Cancelled => &[],
-        SubscriptionStatus::InsufficientBalance => &[
-            SubscriptionStatus::Active,
-            SubscriptionStatus::Cancelled,
-        ],
-
-        SubscriptionStatus::Paused => &[SubscriptionStatus::Active, SubscriptionStatus::Cancelled],
-        SubscriptionStatus::Cancelled => &[],
-        SubscriptionStatus::InsufficientBalance => {
-            &[SubscriptionStatus::Active, SubscriptionStatus::Cancelled]
-        }
-
-    }
-}
-
-/// Checks if a transition is valid without returning an error.
-///
-/// Convenience wrapper around [`validate_status_transition`] for boolean checks.
-pub fn can_transition(from: &SubscriptionStatus, to: &SubscriptionStatus) -> bool {
-    validate_status_transition(from, to).is_ok()
-}
-
-use soroban_sdk::{contract, contractimpl, Address, Env, Vec};
-
-use soroban_sdk::{contract, contractimpl, symbol_short, Address, Env, Vec};
-
-
-pub use state_machine::{can_transition, get_allowed_transitions, validate_status_transition};
-pub use types::{
-    BatchChargeResult, Error, FundsDepositedEvent, MerchantWithdrawalEvent, OneOffChargedEvent,
-    Subscription, SubscriptionCancelledEvent, SubscriptionChargedEvent, SubscriptionCreatedEvent,
-    SubscriptionPausedEvent, SubscriptionResumedEvent, SubscriptionStatus,
-};
-
-/// Result of computing next charge information for a subscription.
-///
-/// Contains the estimated next charge timestamp and a flag indicating
-/// whether the charge is expected to occur based on the subscription status.
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct NextChargeInfo {
-    /// Estimated timestamp for the next charge attempt.
-    /// For Active and InsufficientBalance states, this is `last_payment_timestamp + interval_seconds`.
-    /// For Paused and Cancelled states, this represents when the charge *would* occur if the
-    /// subscription were Active, but `is_charge_expected` will be `false`.
-    pub next_charge_timestamp: u64,
-
-    /// Whether a charge is actually expected based on the subscription status.
-    /// - `true` for Active subscriptions (charge will be attempted)
-    /// - `true` for InsufficientBalance (charge will be retried after funding)
-    /// - `false` for Paused subscriptions (no charges until resumed)
-    /// - `false` for Cancelled subscriptions (terminal state, no future charges)
-    pub is_charge_expected: bool,
-}
-pub mod types;
-
-mod safe_math;
-
-// ── Re-exports (used by tests and external consumers) ────────────────────────
-pub use state_machine::{can_transition, get_allowed_transitions, validate_status_transition};
-pub use types::*;
-
-pub use queries::compute_next_charge_info;
-use soroban_sdk::{contract, contractimpl, Address, Env, Symbol, Vec};
-
-const STORAGE_VERSION: u32 = 1;
+const STORAGE_VERSION: u32 = 3;
 const MAX_EXPORT_LIMIT: u32 = 100;
 
 fn require_admin_auth(env: &Env, admin: &Address) -> Result<(), Error> {
@@ -331,10 +122,8 @@ fn require_not_emergency_stop(env: &Env) -> Result<(), Error> {
     }
     Ok(())
 }
-// ── Contract ─────────────────────────────────────────────────────────────────
-
-
 
+// ── Contract ─────────────────────────────────────────────────────────────────
 
 #[contract]
 pub struct SubscriptionVault;
@@ -343,16 +132,26 @@ pub struct SubscriptionVault;
 impl SubscriptionVault {
     // ── Admin / Config ───────────────────────────────────────────────────
 
-    /// Initialize the contract: set token address, admin, and minimum top-up.
+    /// Initialize the contract: set token address, admin, billing engine, and minimum
+    /// top-up. Only the billing engine may call the charge entrypoints.
     pub fn init(
         env: Env,
         token: Address,
         token_decimals: u32,
         admin: Address,
+        billing_engine: Address,
         min_topup: i128,
         grace_period: u64,
     ) -> Result<(), Error> {
-        admin::do_init(&env, token, token_decimals, admin, min_topup, grace_period)
+        admin::do_init(
+            &env,
+            token,
+            token_decimals,
+            admin,
+            billing_engine,
+            min_topup,
+            grace_period,
+        )
     }
 
     /// Update the minimum top-up threshold. Only callable by admin.
@@ -360,11 +159,6 @@ impl SubscriptionVault {
     /// # Arguments
     /// * `min_topup` - Minimum amount (in token base units) required for deposit_funds.
     ///                 Prevents inefficient micro-deposits. Typical range: 1-10 USDC (1_000000 - 10_000000 for 6 decimals).
-
-
-
-
-
     pub fn set_min_topup(env: Env, admin: Address, min_topup: i128) -> Result<(), Error> {
         admin::do_set_min_topup(&env, admin, min_topup)
     }
@@ -374,6 +168,37 @@ impl SubscriptionVault {
         admin::get_min_topup(&env)
     }
 
+    /// Set the minimum interval charge amount (in token base units); 0 turns it off.
+    /// Only callable by admin. Charges computed below it, after discounts, roll over to
+    /// the next period or are rounded up, per the merchant's `MinChargePolicy`.
+    pub fn set_min_charge_amount(env: Env, admin: Address, amount: i128) -> Result<(), Error> {
+        min_charge::do_set_min_charge_amount(&env, admin, amount)
+    }
+
+    /// Get the current minimum interval charge amount; 0 when off.
+    pub fn get_min_charge_amount(env: Env) -> i128 {
+        min_charge::get_min_charge_amount(&env)
+    }
+
+    /// Merchant chooses whether its charges below the minimum roll over or are rounded up.
+    pub fn set_min_charge_policy(
+        env: Env,
+        merchant: Address,
+        policy: MinChargePolicy,
+    ) -> Result<(), Error> {
+        min_charge::do_set_min_charge_policy(&env, merchant, policy)
+    }
+
+    /// How the merchant's charges below the minimum are handled (`RollOver` by default).
+    pub fn get_min_charge_policy(env: Env, merchant: Address) -> MinChargePolicy {
+        min_charge::get_min_charge_policy(&env, &merchant)
+    }
+
+    /// Amount rolled over from earlier periods, added to the subscription's next charge.
+    pub fn get_carried_charge(env: Env, subscription_id: u32) -> i128 {
+        min_charge::get_carried_amount(&env, subscription_id)
+    }
+
     /// Get the current admin address.
     pub fn get_admin(env: Env) -> Result<Address, Error> {
         admin::do_get_admin(&env)
@@ -405,12 +230,13 @@ impl SubscriptionVault {
         admin::do_recover_stranded_funds(&env, admin, recipient, amount, reason)
     }
 
-    /// Charge a batch of subscriptions in one transaction. Admin only.
+    /// Charge a batch of subscriptions in one transaction. Billing engine only.
     ///
     /// **This function is disabled when the emergency stop is active.**
     ///
     /// Returns a per-subscription result vector so callers can identify
-    /// which charges succeeded and which failed (with error codes).
+    /// which charges succeeded and which failed (with error codes). Subscriptions are
+    /// charged in ascending ID order, whatever the input order.
     pub fn batch_charge(
         env: Env,
         subscription_ids: Vec<u32>,
@@ -421,6 +247,28 @@ impl SubscriptionVault {
         admin::do_batch_charge(&env, &subscription_ids)
     }
 
+    /// Debit metered usage for many subscriptions in one transaction, one
+    /// `(subscription_id, usage_amount)` entry each, at most `MAX_USAGE_BATCH` (200).
+    /// Billing engine only; returns one result per entry, in input order.
+    pub fn batch_charge_usage(
+        env: Env,
+        entries: Vec<(u32, i128)>,
+    ) -> Result<Vec<BatchChargeResult>, Error> {
+        require_not_emergency_stop(&env)?;
+        admin::do_batch_charge_usage(&env, &entries)
+    }
+
+    /// **ADMIN ONLY**: Cap what one batch charge may debit from a single subscriber,
+    /// summed over their subscriptions in the batch. 0 removes the cap.
+    pub fn set_batch_debit_cap(env: Env, admin: Address, cap: i128) -> Result<(), Error> {
+        admin::do_set_batch_debit_cap(&env, admin, cap)
+    }
+
+    /// Per-subscriber debit cap of one batch charge, or `None` if unset.
+    pub fn get_batch_debit_cap(env: Env) -> Option<i128> {
+        admin::get_batch_debit_cap(&env)
+    }
+
     // ═══════════════════════════════════════════════════════════════════════════
     // EMERGENCY STOP (CIRCUIT BREAKER)
     // ═══════════════════════════════════════════════════════════════════════════
@@ -459,6 +307,49 @@ impl SubscriptionVault {
             (Symbol::new(&env, "emergency_stop_enabled"),),
             EmergencyStopEnabledEvent {
                 admin,
+                timestamp: env.ledger().timestamp(),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Disable the emergency stop (circuit breaker). Admin only.
+    ///
+    /// When disabled, normal contract operations resume. This should only be used
+    /// after the incident has been resolved and the contract is safe to operate.
+    ///
+    /// # Requirements
+    /// - Caller must be the admin.
+    /// - Emergency stop must currently be enabled.
+    ///
+    /// # Emits
+    /// `EmergencyStopDisabledEvent` on success.
+    pub fn disable_emergency_stop(env: Env, admin: Address) -> Result<(), Error> {
+        require_admin_auth(&env, &admin)?;
+
+        if !get_emergency_stop(&env) {
+            // Already disabled - return success (idempotent)
+            return Ok(());
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::EmergencyStop, &false);
+
+        env.events().publish(
+            (Symbol::new(&env, "emergency_stop_disabled"),),
+            EmergencyStopDisabledEvent {
+                admin,
+                timestamp: env.ledger().timestamp(),
+            },
+        );
+
+        Ok(())
+    }
+
+    // ── Migration export ─────────────────────────────────────────────────
+
     /// **ADMIN ONLY**: Export contract-level configuration for migration tooling.
     ///
     /// Read-only snapshot intended for carefully managed upgrades.
@@ -468,14 +359,10 @@ impl SubscriptionVault {
         let token: Address = env
             .storage()
             .instance()
-            .get(&Symbol::new(&env, "token"))
+            .get(&DataKey::Token)
             .ok_or(Error::NotFound)?;
         let min_topup: i128 = admin::get_min_topup(&env)?;
-        let next_id: u32 = env
-            .storage()
-            .instance()
-            .get(&Symbol::new(&env, "next_id"))
-            .unwrap_or(0);
+        let next_id: u32 = env.storage().instance().get(&DataKey::NextId).unwrap_or(0);
 
         env.events().publish(
             (Symbol::new(&env, "migration_contract_snapshot"),),
@@ -487,7 +374,7 @@ impl SubscriptionVault {
             token,
             min_topup,
             next_id,
-            storage_version: STORAGE_VERSION,
+            storage_version: storage_migration::get_storage_version(&env),
             timestamp: env.ledger().timestamp(),
         })
     }
@@ -512,36 +399,6 @@ impl SubscriptionVault {
             },
         );
 
-        Ok(())
-    }
-
-    /// Disable the emergency stop (circuit breaker). Admin only.
-    ///
-    /// When disabled, normal contract operations resume. This should only be used
-    /// after the incident has been resolved and the contract is safe to operate.
-    ///
-    /// # Requirements
-    /// - Caller must be the admin.
-    /// - Emergency stop must currently be enabled.
-    ///
-    /// # Emits
-    /// `EmergencyStopDisabledEvent` on success.
-    pub fn disable_emergency_stop(env: Env, admin: Address) -> Result<(), Error> {
-        require_admin_auth(&env, &admin)?;
-
-        if !get_emergency_stop(&env) {
-            // Already disabled - return success (idempotent)
-            return Ok(());
-        }
-
-        env.storage()
-            .instance()
-            .set(&DataKey::EmergencyStop, &false);
-
-        env.events().publish(
-            (Symbol::new(&env, "emergency_stop_disabled"),),
-            EmergencyStopDisabledEvent {
-                admin,
         Ok(SubscriptionSummary {
             subscription_id,
             subscriber: sub.subscriber,
@@ -570,11 +427,7 @@ impl SubscriptionVault {
             return Ok(Vec::new(&env));
         }
 
-        let next_id: u32 = env
-            .storage()
-            .instance()
-            .get(&Symbol::new(&env, "next_id"))
-            .unwrap_or(0);
+        let next_id: u32 = env.storage().instance().get(&DataKey::NextId).unwrap_or(0);
         if start_id >= next_id {
             return Ok(Vec::new(&env));
         }
@@ -584,7 +437,7 @@ impl SubscriptionVault {
         let mut exported = 0u32;
         let mut id = start_id;
         while id < end_id {
-            if let Some(sub) = env.storage().instance().get::<u32, Subscription>(&id) {
+            if let Some(sub) = crate::queries::load_subscription(&env, id) {
                 out.push_back(SubscriptionSummary {
                     subscription_id: id,
                     subscriber: sub.subscriber,
@@ -612,10 +465,21 @@ impl SubscriptionVault {
             },
         );
 
-        Ok(())
         Ok(out)
     }
 
+    /// **ADMIN ONLY**: Move storage written under legacy raw keys to typed keys, up to
+    /// `limit` subscriptions per call. Call after upgrading over version 1 storage until
+    /// it returns `true`.
+    pub fn migrate_storage_keys(env: Env, admin: Address, limit: u32) -> Result<bool, Error> {
+        storage_migration::do_migrate_storage_keys(&env, admin, limit)
+    }
+
+    /// Storage version of the contract's data.
+    pub fn get_storage_version(env: Env) -> u32 {
+        storage_migration::get_storage_version(&env)
+    }
+
     pub fn set_grace_period(env: Env, admin: Address, grace_period: u64) -> Result<(), Error> {
         admin::do_set_grace_period(&env, admin, grace_period)
     }
@@ -637,11 +501,6 @@ impl SubscriptionVault {
     /// # Errors
     /// Returns [`Error::SubscriptionLimitReached`] if the contract has already allocated
     /// [`MAX_SUBSCRIPTION_ID`] subscriptions and can issue no more unique IDs.
-
-
-
-    /// Create a new subscription. Caller deposits initial USDC; contract stores agreement.
-
     pub fn create_subscription(
         env: Env,
         subscriber: Address,
@@ -650,61 +509,104 @@ impl SubscriptionVault {
         interval_seconds: u64,
         usage_enabled: bool,
         expiration: Option<u64>,
-        _expiration: Option<u64>,
     ) -> Result<u32, Error> {
         // Emergency stop check - block new subscriptions when active
         require_not_emergency_stop(&env)?;
 
-
-        subscriber.require_auth();
-        // Allocate a unique ID before touching any other state to fail fast.
-        let id = Self::_next_id(&env)?;
-        // TODO: transfer initial deposit from subscriber to contract, then store subscription
-        let sub = Subscription {
-            subscriber: subscriber.clone(),
-
-        subscription::do_create_subscription(
+        let id = subscription::do_create_subscription(
             &env,
             subscriber,
+            merchant,
+            amount,
+            interval_seconds,
+            usage_enabled,
+        )?;
+        expiration::store_expiration(&env, id, expiration);
+        Ok(id)
+    }
 
+    /// Create up to `MAX_CREATE_BATCH` (50) subscriptions in one transaction, each like
+    /// [`Self::create_subscription`] and authorized by its subscriber. Returns one result
+    /// per entry, in input order, with the new ID or the error code; failed entries do
+    /// not affect the others.
+    pub fn batch_create_subscriptions(
+        env: Env,
+        entries: Vec<CreateSubscriptionRequest>,
+    ) -> Result<Vec<BatchCreateResult>, Error> {
+        require_not_emergency_stop(&env)?;
+        subscription::do_batch_create_subscriptions(&env, &entries)
+    }
 
-        subscriber.require_auth();
-        // TODO: transfer initial deposit from subscriber to contract, then store subscription
-        let sub = Subscription {
-            subscriber: subscriber.clone(),
-
-        subscription::do_create_subscription(
+    /// Like [`Self::create_subscription`], billed in `token`: the vault token or one the
+    /// admin allowlisted with `set_billing_token`.
+    pub fn create_subscription_with_token(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        amount: i128,
+        interval_seconds: u64,
+        usage_enabled: bool,
+        token: Address,
+    ) -> Result<u32, Error> {
+        require_not_emergency_stop(&env)?;
+        billing_tokens::do_create_subscription_with_token(
             &env,
             subscriber,
             merchant,
             amount,
             interval_seconds,
             usage_enabled,
+            token,
+        )
+    }
 
-
-            expiration,
-        };
-        env.storage().instance().set(&id, &sub);
-        Ok(id)
+    /// Token the subscription is billed in.
+    pub fn get_subscription_token(env: Env, subscription_id: u32) -> Result<Address, Error> {
+        queries::get_subscription(&env, subscription_id)?;
+        billing_tokens::subscription_token(&env, subscription_id)
     }
 
-    /// Subscriber deposits more USDC into their vault for this subscription.
-    ///
-    /// # Minimum top-up enforcement
-    /// Rejects deposits below the configured minimum threshold to prevent inefficient
-    /// micro-transactions that waste gas and complicate accounting. The minimum is set
-    /// globally at contract initialization and adjustable by admin via `set_min_topup`.
+    /// Admin allows new subscriptions to bill in `token`, or stops allowing it.
+    pub fn set_billing_token(
+        env: Env,
+        admin: Address,
+        token: Address,
+        allowed: bool,
+    ) -> Result<(), Error> {
+        billing_tokens::do_set_billing_token(&env, admin, token, allowed)
+    }
 
-        )
+    /// Whether `token` is on the billing token allowlist.
+    pub fn is_billing_token(env: Env, token: Address) -> bool {
+        billing_tokens::is_billing_token(&env, &token)
     }
 
+    /// **ADMIN ONLY**: Allow token migrations to swap on `venue`, or stop allowing it.
+    pub fn set_swap_venue(
+        env: Env,
+        admin: Address,
+        venue: Address,
+        allowed: bool,
+    ) -> Result<(), Error> {
+        token_migration::do_set_swap_venue(&env, admin, venue, allowed)
+    }
 
+    /// Whether `venue` is on the swap venue allowlist.
+    pub fn is_swap_venue(env: Env, venue: Address) -> bool {
+        token_migration::is_swap_venue(&env, &venue)
+    }
 
-        };
-        let id = Self::_next_id(&env);
-        env.storage().instance().set(&id, &sub);
-        Ok(id)
-        )
+    /// **ADMIN ONLY**: Move up to `MAX_TOKEN_MIGRATION_BATCH` (50) subscriptions to
+    /// `new_token`, converting their prepaid balances and recurring amounts at the rate
+    /// from `source`. Returns the IDs migrated; ineligible ones are skipped.
+    pub fn migrate_subscription_token(
+        env: Env,
+        admin: Address,
+        ids: Vec<u32>,
+        new_token: Address,
+        source: ConversionRateSource,
+    ) -> Result<Vec<u32>, Error> {
+        token_migration::do_migrate_subscription_token(&env, admin, ids, new_token, source)
     }
 
     /// Creates a plan template that can be used to instantiate subscriptions.
@@ -788,150 +690,147 @@ impl SubscriptionVault {
         subscription::get_plan_template(&env, plan_template_id)
     }
 
-    /// Subscriber deposits more USDC into their prepaid vault.
-    ///
-    /// **This function is disabled when the emergency stop is active.**
-    ///
-    /// # Minimum top-up enforcement
-    /// Rejects deposits below the configured minimum threshold to prevent inefficient
-    /// micro-transactions that waste gas and complicate accounting. The minimum is set
-    /// globally at contract initialization and adjustable by admin via `set_min_topup`.
+    // ── Plan catalog ─────────────────────────────────────────────────────
 
-    /// Rejects deposits below the configured minimum threshold.
-    pub fn deposit_funds(
+    /// Adds a plan to the merchant's catalog. The returned ID is shared with plan
+    /// templates, so subscribers join it through `create_subscription_from_plan`.
+    pub fn create_plan(
         env: Env,
-        subscription_id: u32,
-        subscriber: Address,
-        amount: i128,
-    ) -> Result<(), Error> {
-        // Emergency stop check - block deposits when active
-        require_not_emergency_stop(&env)?;
-
-
-        subscriber.require_auth();
-
-        let min_topup: i128 = env.storage().instance().get(&Symbol::new(&env, "min_topup")).ok_or(Error::NotFound)?;
-        if amount < min_topup {
-            return Err(Error::BelowMinimumTopup);
-        }
-
-        // TODO: transfer USDC from subscriber, increase prepaid_balance for subscription_id
-        let _ = (env, subscription_id, amount);
-        Ok(())
+        merchant: Address,
+        price: i128,
+        interval_seconds: u64,
+        usage_enabled: bool,
+        trial_seconds: u64,
+        metadata: String,
+    ) -> Result<u32, Error> {
+        plans::do_create_plan(
+            &env,
+            merchant,
+            price,
+            interval_seconds,
+            usage_enabled,
+            trial_seconds,
+            metadata,
+        )
     }
 
-    /// Billing engine (backend) calls this to charge one interval. Deducts from vault, pays merchant.
-    ///
-
-    /// # Expiration enforcement
-    /// If the subscription has an `expiration` timestamp and the current ledger timestamp is
-    /// greater than or equal to that value, this function returns `Error::SubscriptionExpired`
-    /// and no funds are moved. When `expiration` is `None` there is no time limit.
-    pub fn charge_subscription(env: Env, subscription_id: u32) -> Result<(), Error> {
-        // Load the subscription from storage.
-        let sub: Subscription = env
-            .storage()
-            .instance()
-            .get(&subscription_id)
-            .ok_or(Error::NotFound)?;
+    /// Changes a plan's terms for future subscribers; existing subscriptions keep theirs.
+    pub fn update_plan(
+        env: Env,
+        merchant: Address,
+        plan_id: u32,
+        price: i128,
+        interval_seconds: u64,
+        trial_seconds: u64,
+        metadata: String,
+    ) -> Result<(), Error> {
+        plans::do_update_plan(
+            &env,
+            merchant,
+            plan_id,
+            price,
+            interval_seconds,
+            trial_seconds,
+            metadata,
+        )
+    }
 
-        // Expiration guard: reject charges at or after the expiration timestamp.
-        if let Some(exp_ts) = sub.expiration {
-            if env.ledger().timestamp() >= exp_ts {
-                return Err(Error::SubscriptionExpired);
-            }
-        }
+    /// Closes a plan to new subscribers. Existing subscriptions are unaffected.
+    pub fn archive_plan(env: Env, merchant: Address, plan_id: u32) -> Result<(), Error> {
+        plans::do_archive_plan(&env, merchant, plan_id)
+    }
 
-        // TODO: require_caller admin or authorized billing service
-        // TODO: check interval and balance, transfer to merchant, update last_payment_timestamp and prepaid_balance
+    pub fn get_plan(env: Env, plan_id: u32) -> Result<Plan, Error> {
+        plans::get_plan(&env, plan_id)
+    }
 
-    /// # State Transitions
-    /// - On success: `Active` -> `Active` (no change)
-    /// - On insufficient balance: `Active` -> `InsufficientBalance`
+    /// List a merchant's catalog plans, archived ones included, for storefronts.
     ///
-    /// Subscriptions that are `Paused` or `Cancelled` cannot be charged.
-
-        subscription::do_deposit_funds(&env, subscription_id, subscriber, amount)
+    /// Returns up to `limit` `(plan_id, plan)` pairs from plan ID `start` (inclusive),
+    /// plus the plan ID to pass as the next `start` (`None` when done).
+    #[allow(clippy::type_complexity)]
+    pub fn list_plans_by_merchant(
+        env: Env,
+        merchant: Address,
+        start: u32,
+        limit: u32,
+    ) -> Result<(Vec<(u32, Plan)>, Option<u32>), Error> {
+        plans::list_plans_by_merchant(&env, &merchant, start, limit)
     }
 
+    // ── Setup fees ───────────────────────────────────────────────────────
 
-    pub fn charge_subscription(env: Env, subscription_id: u32) -> Result<(), Error> {
-        subscription::do_charge_subscription(&env, subscription_id)
-
-    /// Charge one subscription for the current billing interval. Optional `idempotency_key` enables
-    /// safe retries: repeated calls with the same key return success without double-charging.
-    pub fn charge_subscription(
+    /// Creates a subscription whose first `installments` interval charges each also
+    /// collect a share of the one-time `setup_fee`.
+    pub fn create_sub_with_setup_fee(
         env: Env,
-        subscription_id: u32,
-        idempotency_key: Option<soroban_sdk::BytesN<32>>,
-    ) -> Result<(), Error> {
-        subscription::do_charge_subscription(&env, subscription_id, idempotency_key)
-
+        subscriber: Address,
+        merchant: Address,
+        amount: i128,
+        interval_seconds: u64,
+        setup_fee: i128,
+        installments: u32,
+    ) -> Result<u32, Error> {
+        require_not_emergency_stop(&env)?;
+        setup_fee::do_create_subscription_with_setup_fee(
+            &env,
+            subscriber,
+            merchant,
+            amount,
+            interval_seconds,
+            setup_fee,
+            installments,
+        )
     }
 
-        subscriber.require_auth();
-
-        let min_topup: i128 = env
-            .storage()
-            .instance()
-            .get(&Symbol::new(&env, "min_topup"))
-            .ok_or(Error::NotFound)?;
-        if amount < min_topup {
-            return Err(Error::BelowMinimumTopup);
-        }
-
-
-        // TODO: transfer USDC from subscriber, increase prepaid_balance for subscription_id
-        let _ = (env, subscription_id, amount);
-        Ok(())
+    /// Creates a plan template whose subscriptions inherit a setup fee paid over their
+    /// first `installments` interval charges.
+    pub fn create_plan_with_setup_fee(
+        env: Env,
+        merchant: Address,
+        amount: i128,
+        interval_seconds: u64,
+        usage_enabled: bool,
+        setup_fee: i128,
+        installments: u32,
+    ) -> Result<u32, Error> {
+        setup_fee::do_create_plan_template_with_setup_fee(
+            &env,
+            merchant,
+            amount,
+            interval_seconds,
+            usage_enabled,
+            setup_fee,
+            installments,
+        )
     }
 
+    /// Setup fee schedule of a subscription, or `None` if it has no setup fee.
+    pub fn get_setup_fee(env: Env, subscription_id: u32) -> Option<SetupFee> {
+        setup_fee::get_setup_fee(&env, subscription_id)
+    }
 
+    /// Setup fee terms of a plan template, or `None` if it has no setup fee.
+    pub fn get_plan_setup_fee(env: Env, plan_template_id: u32) -> Option<SetupFee> {
+        setup_fee::get_plan_setup_fee(&env, plan_template_id)
+    }
 
-    /// Billing engine (backend) calls this to charge one interval. Deducts from vault, pays merchant.
+    /// Subscriber deposits more USDC into their prepaid vault.
     ///
-    /// # State Transitions
-    /// - On success: `Active` -> `Active` (no change)
-    /// - On insufficient balance: `Active` -> `InsufficientBalance`
+    /// **This function is disabled when the emergency stop is active.**
     ///
-    /// Subscriptions that are `Paused` or `Cancelled` cannot be charged.
-    pub fn charge_subscription(env: Env, subscription_id: u32) -> Result<(), Error> {
-        // TODO: require_caller admin or authorized billing service
-        // TODO: load subscription, check interval and balance, transfer to merchant
-
-        // Placeholder for actual charge logic
-        let maybe_sub: Option<Subscription> = env.storage().instance().get(&subscription_id);
-        if let Some(mut sub) = maybe_sub {
-            // Check current status allows charging
-            if sub.status == SubscriptionStatus::Cancelled
-                || sub.status == SubscriptionStatus::Paused
-            {
-                // Cannot charge cancelled or paused subscriptions
-                return Err(Error::InvalidStatusTransition);
-            }
-
-
-            // Simulate charge logic - on insufficient balance, transition to InsufficientBalance
-            let insufficient_balance = false; // TODO: actual balance check
-            if insufficient_balance {
-                validate_status_transition(&sub.status, &SubscriptionStatus::InsufficientBalance)?;
-                sub.status = SubscriptionStatus::InsufficientBalance;
-                env.storage().instance().set(&subscription_id, &sub);
-            }
-            // TODO: update last_payment_timestamp and prepaid_balance on successful charge
-        }
-
-
-        Ok(())
-
-    pub fn batch_charge(
+    /// # Minimum top-up enforcement
+    /// Rejects deposits below the configured minimum threshold to prevent inefficient
+    /// micro-transactions that waste gas and complicate accounting. The minimum is set
+    /// globally at contract initialization and adjustable by admin via `set_min_topup`.
+    pub fn deposit_funds(
         env: Env,
-        subscription_ids: Vec<u32>,
-    ) -> Result<Vec<BatchChargeResult>, Error> {
-        admin::do_batch_charge(&env, &subscription_ids)
-
-
-        Ok(())
+        subscription_id: u32,
+        subscriber: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        // Emergency stop check - block deposits when active
+        require_not_emergency_stop(&env)?;
 
         subscription::do_deposit_funds(&env, subscription_id, subscriber, amount)
     }
@@ -946,7 +845,8 @@ impl SubscriptionVault {
         subscription::do_cancel_subscription(&env, subscription_id, authorizer)
     }
 
-    /// Subscriber withdraws their remaining prepaid_balance after cancellation.
+    /// Subscriber withdraws their remaining prepaid_balance after cancellation, or once
+    /// the subscription has been paused for at least the grace period.
     pub fn withdraw_subscriber_funds(
         env: Env,
         subscription_id: u32,
@@ -1015,11 +915,7 @@ impl SubscriptionVault {
     /// **This function is disabled when the emergency stop is active.**
     ///
     /// Enforces strict interval timing and replay protection.
-    pub fn charge_subscription(env: Env, subscription_id: u32) -> Result<(), Error> {
-        // Emergency stop check - block charges when active
-        require_not_emergency_stop(&env)?;
-
-        charge_core::charge_one(&env, subscription_id, None)
+    ///
     /// # Non-Destructive Failure Guarantee
     ///
     /// When a charge fails due to insufficient balance:
@@ -1040,6 +936,10 @@ impl SubscriptionVault {
     /// The function uses early validation to avoid unnecessary state modifications.
     /// Balance check is performed before any state changes.
     pub fn charge_subscription(env: Env, subscription_id: u32) -> Result<(), Error> {
+        // Emergency stop check - block charges when active
+        require_not_emergency_stop(&env)?;
+        admin::require_billing_engine(&env)?;
+
         charge_core::charge_one(&env, subscription_id, env.ledger().timestamp(), None)
     }
 
@@ -1077,147 +977,390 @@ impl SubscriptionVault {
     pub fn charge_usage(env: Env, subscription_id: u32, usage_amount: i128) -> Result<(), Error> {
         // Emergency stop check - block usage charges when active
         require_not_emergency_stop(&env)?;
+        admin::require_billing_engine(&env)?;
 
         charge_core::charge_usage_one(&env, subscription_id, usage_amount)
     }
 
-    // ── Merchant ─────────────────────────────────────────────────────────
+    /// Charge `units` of metered usage at the subscription's configured unit price.
+    pub fn charge_usage_units(env: Env, subscription_id: u32, units: u64) -> Result<(), Error> {
+        require_not_emergency_stop(&env)?;
+        admin::require_billing_engine(&env)?;
+        charge_core::charge_usage_units(&env, subscription_id, units)
+    }
 
-    /// Merchant withdraws accumulated USDC to their wallet.
-    pub fn withdraw_merchant_funds(env: Env, merchant: Address, amount: i128) -> Result<(), Error> {
-        merchant::withdraw_merchant_funds(&env, merchant, amount)
+    /// [`Self::charge_subscription`] with an idempotency key: retrying with the same key
+    /// within 7 days succeeds without charging again, while a different key for an
+    /// already charged period fails with `Replay`.
+    pub fn charge_subscription_with_key(
+        env: Env,
+        subscription_id: u32,
+        idempotency_key: soroban_sdk::BytesN<32>,
+    ) -> Result<(), Error> {
+        require_not_emergency_stop(&env)?;
+        admin::require_billing_engine(&env)?;
+        charge_core::charge_one(
+            &env,
+            subscription_id,
+            env.ledger().timestamp(),
+            Some(idempotency_key),
+        )
     }
 
-    pub fn get_merchant_balance(env: Env, merchant: Address) -> i128 {
-        merchant::get_merchant_balance(&env, &merchant)
+    /// Catches up after a billing outage: charges up to `max_intervals` intervals that fell
+    /// due since the last payment, each at its own boundary with its own charge event, and
+    /// returns how many were charged. All or nothing: if one interval cannot be charged,
+    /// none are.
+    pub fn charge_missed_intervals(
+        env: Env,
+        subscription_id: u32,
+        max_intervals: u32,
+    ) -> Result<u32, Error> {
+        require_not_emergency_stop(&env)?;
+        admin::require_billing_engine(&env)?;
+        charge_core::charge_missed_intervals(
+            &env,
+            subscription_id,
+            env.ledger().timestamp(),
+            max_intervals,
+        )
     }
 
-    // ── Queries ──────────────────────────────────────────────────────────
+    // ── Operator-authorized charging ─────────────────────────────────────
+    //
+    // Same as the entrypoints above, but `operator` must sign and be the billing engine
+    // or an allowlisted billing agent, so redundant billing workers need not share the
+    // billing engine key.
 
-    /// Read subscription by id.
-    pub fn batch_withdraw_merchant_funds(
+    pub fn charge_subscription_as(
+        env: Env,
+        operator: Address,
+        subscription_id: u32,
+    ) -> Result<(), Error> {
+        require_not_emergency_stop(&env)?;
+        admin::require_billing_operator(&env, &operator)?;
+        charge_core::charge_one(&env, subscription_id, env.ledger().timestamp(), None)
+    }
+
+    pub fn batch_charge_as(
+        env: Env,
+        operator: Address,
+        subscription_ids: Vec<u32>,
+    ) -> Result<Vec<BatchChargeResult>, Error> {
+        require_not_emergency_stop(&env)?;
+        admin::do_batch_charge_as(&env, operator, &subscription_ids)
+    }
+
+    /// [`Self::batch_charge_as`] with one idempotency key per subscription, in the same
+    /// order, so a batch whose outcome was lost can be resent safely.
+    pub fn batch_charge_with_keys(
+        env: Env,
+        operator: Address,
+        subscription_ids: Vec<u32>,
+        idempotency_keys: Vec<soroban_sdk::BytesN<32>>,
+    ) -> Result<Vec<BatchChargeResult>, Error> {
+        require_not_emergency_stop(&env)?;
+        admin::do_batch_charge_with_keys(&env, operator, &subscription_ids, &idempotency_keys)
+    }
+
+    pub fn charge_usage_as(
+        env: Env,
+        operator: Address,
+        subscription_id: u32,
+        usage_amount: i128,
+    ) -> Result<(), Error> {
+        require_not_emergency_stop(&env)?;
+        admin::require_billing_operator(&env, &operator)?;
+        charge_core::charge_usage_one(&env, subscription_id, usage_amount)
+    }
+
+    // ── Usage configuration ──────────────────────────────────────────────
+
+    /// Create a subscription with full usage terms (see [`UsageConfig`]).
+    pub fn create_subscription_with_usage(
         env: Env,
+        subscriber: Address,
         merchant: Address,
-        amounts: Vec<i128>,
-    ) -> Result<Vec<BatchWithdrawResult>, Error> {
-        merchant.require_auth();
-        let mut results: Vec<BatchWithdrawResult> = Vec::new(&env);
-        for i in 0..amounts.len() {
-            let amount = amounts.get(i).unwrap();
-            if amount <= 0 {
-                results.push_back(BatchWithdrawResult {
-                    success: false,
-                    error_code: 1003,
-                });
-            } else {
-                results.push_back(BatchWithdrawResult {
-                    success: true,
-                    error_code: 0,
-                });
-            }
-        }
-        Ok(results)
+        amount: i128,
+        interval_seconds: u64,
+        usage: UsageConfig,
+    ) -> Result<u32, Error> {
+        require_not_emergency_stop(&env)?;
+        usage::do_create_subscription_with_usage(
+            &env,
+            subscriber,
+            merchant,
+            amount,
+            interval_seconds,
+            usage,
+        )
     }
 
-    pub fn get_subscription(env: Env, subscription_id: u32) -> Result<Subscription, Error> {
-        queries::get_subscription(&env, subscription_id)
+    /// Replace a subscription's usage terms. Subscriber and merchant must both authorize.
+    pub fn set_usage_config(
+        env: Env,
+        subscription_id: u32,
+        usage: UsageConfig,
+    ) -> Result<(), Error> {
+        usage::do_set_usage_config(&env, subscription_id, usage)
     }
 
-    /// Estimate how much a subscriber needs to deposit to cover N future intervals.
-    pub fn estimate_topup_for_intervals(
+    /// Usage terms of a subscription; legacy subscriptions report flag-only defaults.
+    pub fn get_usage_config(env: Env, subscription_id: u32) -> Result<UsageConfig, Error> {
+        let sub = queries::get_subscription(&env, subscription_id)?;
+        Ok(usage::get_usage_config(&env, subscription_id, &sub))
+    }
+
+    /// Usage charged in the subscription's current report window.
+    pub fn get_usage_window(env: Env, subscription_id: u32) -> Result<UsageWindow, Error> {
+        let sub = queries::get_subscription(&env, subscription_id)?;
+        let config = usage::get_usage_config(&env, subscription_id, &sub);
+        Ok(usage::get_usage_window(
+            &env,
+            subscription_id,
+            &sub,
+            &config,
+        ))
+    }
+
+    /// Merchant grants usage credit, spent by usage charges before the prepaid balance.
+    /// Returns the subscription's new credit.
+    pub fn grant_usage_credit(
         env: Env,
         subscription_id: u32,
-        num_intervals: u32,
+        merchant: Address,
+        amount: i128,
     ) -> Result<i128, Error> {
-        queries::estimate_topup_for_intervals(&env, subscription_id, num_intervals)
+        usage_credits::do_grant_usage_credit(&env, subscription_id, merchant, amount)
     }
 
-    /// Get estimated next charge info (timestamp + whether charge is expected).
-    pub fn get_next_charge_info(env: Env, subscription_id: u32) -> Result<NextChargeInfo, Error> {
-        let sub = queries::get_subscription(&env, subscription_id)?;
-        Ok(compute_next_charge_info(&sub))
+    /// Unspent usage credit of a subscription.
+    pub fn get_usage_credit(env: Env, subscription_id: u32) -> i128 {
+        usage_credits::get_usage_credit(&env, subscription_id)
     }
 
-    /// Return subscriptions for a merchant, paginated.
-    pub fn get_subscriptions_by_merchant(
+    /// Merchant turns rollover of a report window's unused included units into usage
+    /// credit on or off.
+    pub fn set_usage_rollover(
         env: Env,
+        subscription_id: u32,
         merchant: Address,
-        start: u32,
-        limit: u32,
-    ) -> Vec<Subscription> {
-        queries::get_subscriptions_by_merchant(&env, merchant, start, limit)
+        enabled: bool,
+    ) -> Result<(), Error> {
+        usage_credits::do_set_usage_rollover(&env, subscription_id, merchant, enabled)
     }
 
- 
-    pub fn get_subscription(env: Env, subscription_id: u32) -> Result<Subscription, Error> {
+    pub fn get_usage_rollover(env: Env, subscription_id: u32) -> bool {
+        usage_credits::get_usage_rollover(&env, subscription_id)
+    }
 
-        env.storage()
-            .instance()
-            .get(&subscription_id)
-            .ok_or(Error::NotFound)
+    /// Merchant sets the subscription's overage tier: `charge_usage` then debits only
+    /// the usage beyond the included quota since the last interval charge, at the
+    /// overage rate.
+    pub fn set_usage_tier(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        tier: UsageTier,
+    ) -> Result<(), Error> {
+        usage_tiers::do_set_usage_tier(&env, subscription_id, merchant, tier)
     }
 
-    /// Return the total number of subscriptions ever created (i.e. the next ID that
-    /// would be allocated). This is a free storage read useful for off-chain indexers
-    /// and monitoring.
-    ///
-    /// Returns `0` before any subscription has been created.
-    pub fn get_subscription_count(env: Env) -> u32 {
-        let key = Symbol::new(&env, "next_id");
-        env.storage().instance().get(&key).unwrap_or(0u32)
+    /// Merchant removes the overage tier, so usage is charged at face value again.
+    pub fn remove_usage_tier(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+    ) -> Result<(), Error> {
+        usage_tiers::do_remove_usage_tier(&env, subscription_id, merchant)
     }
 
-    /// Allocate the next unique subscription ID.
-    ///
-    /// # Guarantees
-    /// - IDs start at `0` and increment by exactly `1` on each successful call.
-    /// - IDs are **never reused**: the counter only moves forward.
-    /// - IDs are **bounded**: when the counter reaches [`MAX_SUBSCRIPTION_ID`]
-    ///   this function returns [`Error::SubscriptionLimitReached`] instead of
-    ///   wrapping or panicking.
-    ///
-    /// # Errors
-    /// [`Error::SubscriptionLimitReached`] — counter is at [`MAX_SUBSCRIPTION_ID`].
-    fn _next_id(env: &Env) -> Result<u32, Error> {
-        let key = Symbol::new(env, "next_id");
-        let current: u32 = env.storage().instance().get(&key).unwrap_or(0u32);
-
-        // Guard: refuse to allocate when we are already at the ceiling.
-        // This makes the subsequent +1 infallible (current < u32::MAX).
-        if current == MAX_SUBSCRIPTION_ID {
-            return Err(Error::SubscriptionLimitReached);
-        }
+    /// Overage tier of the subscription, if the merchant set one.
+    pub fn get_usage_tier(env: Env, subscription_id: u32) -> Option<UsageTier> {
+        usage_tiers::get_usage_tier(&env, subscription_id)
+    }
 
-        // Safe: current < MAX_SUBSCRIPTION_ID == u32::MAX, so current + 1 cannot overflow.
-        env.storage().instance().set(&key, &(current + 1));
-        Ok(current)
+    /// Usage counted against the tier's quota since the last interval charge.
+    pub fn get_usage_tier_consumption(env: Env, subscription_id: u32) -> i128 {
+        usage_tiers::get_tier_consumption(&env, subscription_id)
+    }
 
-        queries::get_subscription(&env, subscription_id)
+    /// Subscriber or merchant sets the low-balance threshold in upcoming intervals: a
+    /// charge leaving the prepaid balance below it emits a `LowBalanceEvent`. 0 turns
+    /// the warning off.
+    pub fn set_low_balance_threshold(
+        env: Env,
+        subscription_id: u32,
+        caller: Address,
+        intervals: u32,
+    ) -> Result<(), Error> {
+        low_balance::do_set_low_balance_threshold(&env, subscription_id, caller, intervals)
+    }
+
+    /// Low-balance threshold of the subscription in intervals; 0 when off.
+    pub fn get_low_balance_threshold(env: Env, subscription_id: u32) -> u32 {
+        low_balance::get_low_balance_threshold(&env, subscription_id)
+    }
 
+    // ── Merchant ─────────────────────────────────────────────────────────
 
-    fn _next_id(env: &Env) -> u32 {
-        let key = Symbol::new(env, "next_id");
-        let id: u32 = env.storage().instance().get(&key).unwrap_or(0);
-        env.storage().instance().set(&key, &(id + 1));
-        id
+    /// Merchant withdraws accumulated USDC to their wallet.
+    pub fn withdraw_merchant_funds(env: Env, merchant: Address, amount: i128) -> Result<(), Error> {
+        merchant::withdraw_merchant_funds(&env, merchant, amount)
+    }
 
-    /// Return the total number of subscriptions for a merchant.
-    pub fn get_merchant_subscription_count(env: Env, merchant: Address) -> u32 {
-        queries::get_merchant_subscription_count(&env, merchant)
+    pub fn get_merchant_balance(env: Env, merchant: Address) -> i128 {
+        merchant::get_merchant_balance(&env, &merchant)
     }
 
-    /// Merchant-initiated one-off charge.
-    pub fn charge_one_off(
+    /// Merchant sends future withdrawals and payouts to `payout`, for example a treasury
+    /// multisig; charges still accrue to `merchant`. Its own address restores the default.
+    pub fn set_merchant_payout_address(
         env: Env,
-        subscription_id: u32,
         merchant: Address,
-        amount: i128,
+        payout: Address,
     ) -> Result<(), Error> {
-        subscription::do_charge_one_off(&env, subscription_id, merchant, amount)
+        payout_address::do_set_merchant_payout_address(&env, merchant, payout)
     }
 
-    /// List all subscription IDs for a given subscriber with pagination support.
-    ///
+    /// Address the merchant's withdrawals are sent to; the merchant itself by default.
+    pub fn get_merchant_payout_address(env: Env, merchant: Address) -> Address {
+        payout_address::get_merchant_payout_address(&env, &merchant)
+    }
+
+    // ── Queries ──────────────────────────────────────────────────────────
+
+    /// Read subscription by id.
+    pub fn get_subscription(env: Env, subscription_id: u32) -> Result<Subscription, Error> {
+        queries::get_subscription(&env, subscription_id)
+    }
+
+    /// Estimate how much a subscriber needs to deposit to cover N future intervals,
+    /// including projected usage and setup fee installments.
+    pub fn estimate_topup_for_intervals(
+        env: Env,
+        subscription_id: u32,
+        num_intervals: u32,
+    ) -> Result<TopupEstimate, Error> {
+        queries::estimate_topup_for_intervals(&env, subscription_id, num_intervals)
+    }
+
+    /// Get estimated next charge info (timestamp + whether charge is expected).
+    pub fn get_next_charge_info(env: Env, subscription_id: u32) -> Result<NextChargeInfo, Error> {
+        let sub = queries::get_subscription(&env, subscription_id)?;
+        let mut info = compute_next_charge_info(&sub);
+        info.cycles_remaining = fixed_term::cycles_remaining(&env, subscription_id);
+        Ok(info)
+    }
+
+    /// Subscriber limits the subscription to `max_cycles` billing cycles in total, after
+    /// which it completes (is cancelled) automatically; `None` makes it open-ended again.
+    pub fn set_max_cycles(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        max_cycles: Option<u32>,
+    ) -> Result<(), Error> {
+        fixed_term::do_set_max_cycles(&env, subscription_id, subscriber, max_cycles)
+    }
+
+    /// Number of billing cycles the subscription is limited to, or `None` if open-ended.
+    pub fn get_max_cycles(env: Env, subscription_id: u32) -> Option<u32> {
+        fixed_term::get_max_cycles(&env, subscription_id)
+    }
+
+    /// Return subscriptions for a merchant, paginated.
+    pub fn get_subscriptions_by_merchant(
+        env: Env,
+        merchant: Address,
+        start: u32,
+        limit: u32,
+    ) -> Vec<Subscription> {
+        queries::get_subscriptions_by_merchant(&env, merchant, start, limit)
+    }
+
+    /// Return the number of subscription IDs allocated so far, including reserved ones.
+    /// Without an ID shard this equals the next ID that would be allocated. This is a
+    /// free storage read useful for off-chain indexers and monitoring.
+    ///
+    /// Returns `0` before any subscription has been created.
+    pub fn get_subscription_count(env: Env) -> u32 {
+        ids::peek_next_id(&env) - ids::first_id(&env)
+    }
+
+    /// ID the next created or reserved subscription will get, without allocating it.
+    pub fn peek_next_id(env: Env) -> u32 {
+        ids::peek_next_id(&env)
+    }
+
+    /// Return a merchant's subscriptions in `status`, paginated by offset into the
+    /// merchant's list like `get_subscriptions_by_merchant`. Pass `next_cursor` back as
+    /// `start` until it is `None`.
+    ///
+    /// Soroban caps contract function names at 32 characters, so this is the
+    /// `get_subscriptions_by_merchant_filtered` entrypoint.
+    pub fn get_merchant_subs_by_status(
+        env: Env,
+        merchant: Address,
+        status: SubscriptionStatus,
+        start: u32,
+        limit: u32,
+    ) -> Result<SubscriptionPage, Error> {
+        queries::get_subscriptions_by_merchant_filtered(&env, merchant, status, start, limit)
+    }
+
+    /// Return a merchant's subscriptions whose prepaid balance covers fewer than
+    /// `intervals` upcoming charges, paginated like `get_merchant_subs_by_status`.
+    pub fn get_subscriptions_below_runway(
+        env: Env,
+        merchant: Address,
+        intervals: u32,
+        start: u32,
+        limit: u32,
+    ) -> Result<SubscriptionPage, Error> {
+        queries::get_subscriptions_below_runway(&env, merchant, intervals, start, limit)
+    }
+
+    /// Return the total number of subscriptions for a merchant.
+    pub fn get_merchant_subscription_count(env: Env, merchant: Address) -> u32 {
+        queries::get_merchant_subscription_count(&env, merchant)
+    }
+
+    /// Merchant-initiated one-off charge, within the subscriber's monthly one-off
+    /// allowance. `memo_hash` identifies the charge and can only be charged once.
+    pub fn charge_one_off(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        amount: i128,
+        memo_hash: soroban_sdk::BytesN<32>,
+    ) -> Result<(), Error> {
+        subscription::do_charge_one_off(&env, subscription_id, merchant, amount, memo_hash)
+    }
+
+    /// Subscriber sets the most the merchant may charge in one-off charges per 30-day
+    /// month; 0 blocks one-off charges.
+    pub fn set_one_off_allowance(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        monthly_limit: i128,
+    ) -> Result<(), Error> {
+        one_off_allowance::do_set_one_off_allowance(
+            &env,
+            subscription_id,
+            subscriber,
+            monthly_limit,
+        )
+    }
+
+    /// The subscription's one-off allowance and what was charged against it this month.
+    pub fn get_one_off_allowance(env: Env, subscription_id: u32) -> Option<OneOffAllowance> {
+        one_off_allowance::get_one_off_allowance(&env, subscription_id)
+    }
+
+    /// List all subscription IDs for a given subscriber with pagination support.
+    ///
     /// This read-only function retrieves subscription IDs owned by a subscriber in a paginated manner.
     /// Subscriptions are returned in order by ID (ascending) for predictable iteration.
     ///
@@ -1255,6 +1398,1487 @@ impl SubscriptionVault {
     ) -> Result<crate::queries::SubscriptionsPage, Error> {
         crate::queries::list_subscriptions_by_subscriber(&env, subscriber, start_from_id, limit)
     }
+
+    // ── Dispute holds ────────────────────────────────────────────────────
+
+    /// Portion of the merchant's balance locked by open disputes (not withdrawable).
+    pub fn get_merchant_locked_balance(env: Env, merchant: Address) -> i128 {
+        merchant::get_merchant_locked_balance(&env, &merchant)
+    }
+
+    // ── Protocol fees ────────────────────────────────────────────────────
+
+    /// Protocol fees accrued in the vault and not yet withdrawn.
+    pub fn get_protocol_fee_balance(env: Env) -> i128 {
+        fees::get_protocol_fee_balance(&env)
+    }
+
+    /// Address receiving protocol fee withdrawals (defaults to the admin).
+    pub fn get_fee_recipient(env: Env) -> Result<Address, Error> {
+        fees::get_fee_recipient(&env)
+    }
+
+    /// Set the protocol fee recipient. Admin only.
+    pub fn set_fee_recipient(env: Env, admin: Address, recipient: Address) -> Result<(), Error> {
+        fees::do_set_fee_recipient(&env, admin, recipient)
+    }
+
+    /// Set the platform fee taken from every settled charge, in basis points (at most
+    /// 1000, i.e. 10%). Fees accrue to the protocol fee balance. Admin only.
+    pub fn set_platform_fee_bps(env: Env, admin: Address, bps: u32) -> Result<(), Error> {
+        fees::do_set_platform_fee_bps(&env, admin, bps)
+    }
+
+    /// Platform fee rate and the treasury address fees are withdrawn to.
+    pub fn get_fee_config(env: Env) -> Result<FeeConfig, Error> {
+        fees::get_fee_config(&env)
+    }
+
+    /// Atomically rotate the admin and replace the billing agents and fee recipient, so
+    /// a key rotation has no window where old agents stay authorized or fees still
+    /// flow to the outgoing operator.
+    pub fn handoff_admin(
+        env: Env,
+        current_admin: Address,
+        new_admin: Address,
+        new_agents: Vec<Address>,
+        new_fee_recipient: Address,
+    ) -> Result<(), Error> {
+        admin::do_handoff_admin(
+            &env,
+            current_admin,
+            new_admin,
+            new_agents,
+            new_fee_recipient,
+        )
+    }
+
+    /// Allow `agent` to call `charge_subscription_as`, `batch_charge_as` and
+    /// `charge_usage_as`. Admin only.
+    pub fn add_billing_agent(env: Env, admin: Address, agent: Address) -> Result<(), Error> {
+        admin::do_add_billing_agent(&env, admin, agent)
+    }
+
+    /// Revoke a billing agent. Admin only.
+    pub fn remove_billing_agent(env: Env, admin: Address, agent: Address) -> Result<(), Error> {
+        admin::do_remove_billing_agent(&env, admin, agent)
+    }
+
+    pub fn list_billing_agents(env: Env) -> Vec<Address> {
+        admin::get_billing_agents(&env)
+    }
+
+    /// Replace the billing engine, the address allowed to call the charge entrypoints.
+    /// Admin only.
+    pub fn set_billing_engine(env: Env, admin: Address, engine: Address) -> Result<(), Error> {
+        admin::do_set_billing_engine(&env, admin, engine)
+    }
+
+    /// Current billing engine.
+    pub fn get_billing_engine(env: Env) -> Result<Address, Error> {
+        admin::get_billing_engine(&env)
+    }
+
+    /// Route `bps` of every protocol fee withdrawal to `recipient`. Admin only.
+    /// Passing `bps == 0` disables donation routing.
+    pub fn set_donation_config(
+        env: Env,
+        admin: Address,
+        recipient: Address,
+        bps: u32,
+    ) -> Result<(), Error> {
+        fees::do_set_donation_config(&env, admin, recipient, bps)
+    }
+
+    pub fn get_donation_config(env: Env) -> Option<DonationConfig> {
+        fees::get_donation_config(&env)
+    }
+
+    /// Withdraw all accrued protocol fees to the fee recipient, splitting off the
+    /// configured donation share. Admin only. Returns the total withdrawn.
+    pub fn withdraw_protocol_fees(env: Env, admin: Address) -> Result<i128, Error> {
+        fees::do_withdraw_protocol_fees(&env, admin)
+    }
+
+    /// Subscriber pays the next billing period now instead of waiting for the
+    /// billing engine.
+    ///
+    /// **This function is disabled when the emergency stop is active.**
+    ///
+    /// Skips only the `IntervalNotElapsed` check; the schedule advances by one
+    /// interval so the regular charge for that period is not taken again. Status,
+    /// replay and balance guards still apply.
+    pub fn pay_now(env: Env, subscription_id: u32, subscriber: Address) -> Result<(), Error> {
+        require_not_emergency_stop(&env)?;
+
+        subscription::do_pay_now(&env, subscription_id, subscriber)
+    }
+
+    /// Walk all subscriptions by ID for indexer backfills.
+    ///
+    /// Returns up to `limit` `(id, summary)` pairs starting at `cursor` (inclusive),
+    /// skipping removed IDs, plus the cursor for the next call (`None` when done).
+    #[allow(clippy::type_complexity)]
+    pub fn iter_subscriptions(
+        env: Env,
+        cursor: u32,
+        limit: u32,
+    ) -> Result<(Vec<(u32, SubscriptionSummary)>, Option<u32>), Error> {
+        queries::iter_subscriptions(&env, cursor, limit)
+    }
+
+    /// IDs of `Active` subscriptions due for an interval charge at `now`, for billing
+    /// backends. Returns up to `limit` IDs starting at `start` (inclusive), plus the
+    /// cursor for the next call (`None` when done).
+    pub fn get_due_subscriptions(
+        env: Env,
+        now: u64,
+        start: u32,
+        limit: u32,
+    ) -> Result<(Vec<u32>, Option<u32>), Error> {
+        queries::get_due_subscriptions(&env, now, start, limit)
+    }
+
+    // ── Cursor-paged listings ────────────────────────────────────────────
+    //
+    // All take a `Cursor { start_id, limit }` and return a `SubscriptionPage`; pass the
+    // returned `next_cursor` back in until it is `None`.
+
+    /// Page through a merchant's subscriptions.
+    pub fn page_merchant_subscriptions(
+        env: Env,
+        merchant: Address,
+        cursor: Cursor,
+    ) -> Result<SubscriptionPage, Error> {
+        queries::page_merchant_subscriptions(&env, merchant, cursor)
+    }
+
+    /// Page through a subscriber's subscriptions.
+    pub fn page_subscriber_subscriptions(
+        env: Env,
+        subscriber: Address,
+        cursor: Cursor,
+    ) -> Result<SubscriptionPage, Error> {
+        queries::page_subscriber_subscriptions(&env, subscriber, cursor)
+    }
+
+    /// Page through subscriptions currently in `status`.
+    pub fn page_subscriptions_by_status(
+        env: Env,
+        status: SubscriptionStatus,
+        cursor: Cursor,
+    ) -> Result<SubscriptionPage, Error> {
+        queries::page_subscriptions_by_status(&env, status, cursor)
+    }
+
+    /// Page through subscriptions whose interval charge is due now.
+    pub fn page_due_subscriptions(env: Env, cursor: Cursor) -> Result<SubscriptionPage, Error> {
+        queries::page_due_subscriptions(&env, cursor)
+    }
+
+    /// Archive a cancelled subscription. Subscriber or merchant only.
+    ///
+    /// Removes it from the merchant index and default listings; the record stays
+    /// readable through [`Self::get_archived_subscription`]. Requires a zero prepaid
+    /// balance and no open dispute on the last charge.
+    pub fn archive_subscription(
+        env: Env,
+        subscription_id: u32,
+        authorizer: Address,
+    ) -> Result<(), Error> {
+        subscription::do_archive_subscription(&env, subscription_id, authorizer)
+    }
+
+    /// Read an archived subscription record for audit purposes.
+    pub fn get_archived_subscription(
+        env: Env,
+        subscription_id: u32,
+    ) -> Result<Subscription, Error> {
+        queries::get_archived_subscription(&env, subscription_id)
+    }
+
+    /// Charge lateness totals for `merchant` in SLA `period`
+    /// (`timestamp / SLA_PERIOD_SECONDS`, 30-day buckets).
+    pub fn get_billing_sla(env: Env, merchant: Address, period: u64) -> BillingSla {
+        sla::get_billing_sla(&env, merchant, period)
+    }
+
+    // ── Multi-token balances ────────────────────────────────────────────
+
+    /// **ADMIN ONLY**: Accept a secondary token at `rate` vault-token units per
+    /// `RATE_SCALE` (10^7) token units. A rate of 0 stops accepting the token.
+    pub fn set_token_rate(
+        env: Env,
+        admin: Address,
+        token: Address,
+        rate: i128,
+    ) -> Result<(), Error> {
+        multi_token::do_set_token_rate(&env, admin, token, rate)
+    }
+
+    /// Conversion rate of a secondary token, if accepted.
+    pub fn get_token_rate(env: Env, token: Address) -> Option<i128> {
+        multi_token::get_token_rate(&env, &token)
+    }
+
+    /// Deposit an accepted secondary token into a subscription's vault.
+    ///
+    /// **This function is disabled when the emergency stop is active.**
+    pub fn deposit_token(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        token: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        require_not_emergency_stop(&env)?;
+        multi_token::do_deposit_token(&env, subscription_id, subscriber, token, amount)
+    }
+
+    /// Set the order in which secondary tokens are drained after the prepaid balance.
+    pub fn set_token_priority(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        tokens: Vec<Address>,
+    ) -> Result<(), Error> {
+        multi_token::do_set_token_priority(&env, subscription_id, subscriber, tokens)
+    }
+
+    /// Secondary token drain order for a subscription.
+    pub fn get_token_priority(env: Env, subscription_id: u32) -> Vec<Address> {
+        multi_token::get_token_priority(&env, subscription_id)
+    }
+
+    /// Secondary token balance held for a subscription.
+    pub fn get_token_balance(env: Env, subscription_id: u32, token: Address) -> i128 {
+        multi_token::get_token_balance(&env, subscription_id, &token)
+    }
+
+    /// Withdraw a secondary token balance from a cancelled subscription. Subscriber only.
+    pub fn withdraw_token_balance(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        token: Address,
+    ) -> Result<i128, Error> {
+        multi_token::do_withdraw_token_balance(&env, subscription_id, subscriber, token)
+    }
+
+    /// Merchant earnings accrued in a secondary token.
+    pub fn get_merchant_token_balance(env: Env, merchant: Address, token: Address) -> i128 {
+        multi_token::get_merchant_token_balance(&env, &merchant, &token)
+    }
+
+    /// Accrued balances per token plus reserve and dispute holds, so multi-currency
+    /// merchants see their full position in one query.
+    pub fn get_merchant_exposure(env: Env, merchant: Address) -> Result<MerchantExposure, Error> {
+        merchant::get_merchant_exposure(&env, &merchant)
+    }
+
+    /// Withdraw all merchant earnings accrued in a secondary token.
+    pub fn withdraw_merchant_token_funds(
+        env: Env,
+        merchant: Address,
+        token: Address,
+    ) -> Result<i128, Error> {
+        multi_token::do_withdraw_merchant_token_funds(&env, merchant, token)
+    }
+
+    // ── Loyalty tiers ───────────────────────────────────────────────────
+
+    /// Merchant configures loyalty tiers: `bps_per_tier` off the charge amount for
+    /// every `cycles_per_tier` completed cycles, capped at `max_tiers`.
+    /// `cycles_per_tier == 0` disables the programme.
+    pub fn set_loyalty_config(
+        env: Env,
+        merchant: Address,
+        cycles_per_tier: u32,
+        bps_per_tier: u32,
+        max_tiers: u32,
+    ) -> Result<(), Error> {
+        loyalty::do_set_loyalty_config(&env, merchant, cycles_per_tier, bps_per_tier, max_tiers)
+    }
+
+    /// Merchant loyalty configuration, if any.
+    pub fn get_loyalty_config(env: Env, merchant: Address) -> Option<LoyaltyConfig> {
+        loyalty::get_loyalty_config(&env, &merchant)
+    }
+
+    /// Preview the next interval charge, including the loyalty discount earned so far.
+    pub fn preview_charge(env: Env, subscription_id: u32) -> Result<ChargePreview, Error> {
+        queries::preview_charge(&env, subscription_id)
+    }
+
+    /// List the subscriber's expected interval charges within `horizon_seconds` of now,
+    /// ordered by due time, so a wallet can render a billing calendar in one call.
+    pub fn get_upcoming_charges(
+        env: Env,
+        subscriber: Address,
+        horizon_seconds: u64,
+    ) -> Result<Vec<UpcomingCharge>, Error> {
+        queries::get_upcoming_charges(&env, subscriber, horizon_seconds)
+    }
+
+    /// Project the merchant's revenue from interval charges due within `horizon_seconds`
+    /// of now across its Active subscriptions, so dashboards can show MRR and ARR from
+    /// on-chain state.
+    pub fn forecast_merchant_revenue(
+        env: Env,
+        merchant: Address,
+        horizon_seconds: u64,
+    ) -> Result<RevenueForecast, Error> {
+        queries::forecast_merchant_revenue(&env, merchant, horizon_seconds)
+    }
+
+    /// Balance runway, failed charges, disputes and pause history of a subscription,
+    /// so dashboards can flag at-risk customers in one call.
+    pub fn get_subscription_health(
+        env: Env,
+        subscription_id: u32,
+    ) -> Result<SubscriptionHealth, Error> {
+        health::get_subscription_health(&env, subscription_id)
+    }
+
+    /// Whether the subscription can be charged and refunded: next charge funding,
+    /// auto-topup allowance and wallet balance, and whether the subscriber and merchant
+    /// can hold the token. Lists the setup steps still missing.
+    pub fn check_payment_readiness(
+        env: Env,
+        subscription_id: u32,
+    ) -> Result<PaymentReadiness, Error> {
+        readiness::check_payment_readiness(&env, subscription_id)
+    }
+
+    /// Error code, offending value and broken limit of the subscription's last charge
+    /// failure. Only failures absorbed by a batch charge are kept; a failing
+    /// `charge_subscription` rolls its own record back.
+    pub fn get_last_error_context(env: Env, subscription_id: u32) -> Option<ErrorContext> {
+        error_context::get_last_error_context(&env, subscription_id)
+    }
+
+    // ── Subscription ID reservation ─────────────────────────────────────
+
+    /// **ADMIN ONLY**: Allocate a subscription ID for off-chain pre-registration.
+    ///
+    /// The ID must be claimed by `subscriber`, for `merchant`, with
+    /// [`Self::create_reserved_subscription`] within `ID_RESERVATION_TTL_SECONDS`
+    /// (24 hours).
+    pub fn reserve_subscription_id(
+        env: Env,
+        admin: Address,
+        subscriber: Address,
+        merchant: Address,
+    ) -> Result<u32, Error> {
+        ids::do_reserve_subscription_id(&env, admin, subscriber, merchant)
+    }
+
+    /// Create a subscription under a reserved ID, consuming the reservation.
+    ///
+    /// Soroban caps contract function names at 32 characters, so this is the
+    /// `create_subscription_with_reserved_id` entrypoint.
+    ///
+    /// **This function is disabled when the emergency stop is active.**
+    pub fn create_reserved_subscription(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        merchant: Address,
+        amount: i128,
+        interval_seconds: u64,
+        usage_enabled: bool,
+    ) -> Result<(), Error> {
+        require_not_emergency_stop(&env)?;
+        subscription::do_create_subscription_with_reserved_id(
+            &env,
+            subscription_id,
+            subscriber,
+            merchant,
+            amount,
+            interval_seconds,
+            usage_enabled,
+        )
+    }
+
+    /// **ADMIN ONLY**: Allocate subscription IDs from shard `shard` (below
+    /// `MAX_ID_SHARDS`), so vaults deployed side by side never share an ID. Only
+    /// possible before the first ID is allocated.
+    pub fn set_id_shard(env: Env, admin: Address, shard: u32) -> Result<(), Error> {
+        ids::do_set_id_shard(&env, admin, shard)
+    }
+
+    /// Shard this contract allocates subscription IDs from (0 unless set).
+    pub fn get_id_shard(env: Env) -> u32 {
+        ids::get_id_shard(&env)
+    }
+
+    /// Enable or disable interval and usage charges independently. Subscriber or merchant.
+    ///
+    /// Blocked charge types fail with `ChargeTypeBlocked`. `pause_subscription`
+    /// remains the way to block everything.
+    pub fn set_charge_mask(
+        env: Env,
+        subscription_id: u32,
+        authorizer: Address,
+        allow_interval: bool,
+        allow_usage: bool,
+    ) -> Result<(), Error> {
+        subscription::do_set_charge_mask(
+            &env,
+            subscription_id,
+            authorizer,
+            allow_interval,
+            allow_usage,
+        )
+    }
+
+    /// Current charge mask of a subscription.
+    pub fn get_charge_mask(env: Env, subscription_id: u32) -> ChargeMask {
+        queries::get_charge_mask(&env, subscription_id)
+    }
+
+    /// Cheap access check for partner contracts gating content or features.
+    ///
+    /// Uses the subscriber's most recent subscription with `merchant`.
+    pub fn check_entitlement(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+    ) -> EntitlementStatus {
+        entitlement::check_entitlement(&env, subscriber, merchant)
+    }
+
+    /// **ADMIN ONLY**: Accept interval charges up to `seconds` before the boundary to
+    /// absorb ledger vs backend clock skew. Capped at `MAX_CHARGE_TOLERANCE_SECONDS`.
+    pub fn set_charge_tolerance(env: Env, admin: Address, seconds: u64) -> Result<(), Error> {
+        admin::do_set_charge_tolerance(&env, admin, seconds)
+    }
+
+    /// **ADMIN ONLY**: Reject any debit within `seconds` of the previous successful debit
+    /// on the same subscription, across interval, usage and one-off charges. Capped at
+    /// `MAX_MIN_DEBIT_SPACING_SECONDS`; 0 disables the floor.
+    pub fn set_min_debit_spacing(env: Env, admin: Address, seconds: u64) -> Result<(), Error> {
+        admin::do_set_min_debit_spacing(&env, admin, seconds)
+    }
+
+    /// Global configuration: admin, billing token, limits, emergency stop flag and fee
+    /// recipient, in one read.
+    pub fn get_config(env: Env) -> Result<VaultConfig, Error> {
+        admin::get_config(&env)
+    }
+
+    /// Billing token configured at initialization.
+    pub fn get_token(env: Env) -> Result<Address, Error> {
+        admin::get_token(&env)
+    }
+
+    /// Decimals of the billing token, as passed to `init`.
+    pub fn get_token_decimals(env: Env) -> Result<u32, Error> {
+        admin::get_token_decimals(&env)
+    }
+
+    /// Whether the emergency stop is active. Same as `get_emergency_stop_status`.
+    pub fn get_stopped(env: Env) -> bool {
+        get_emergency_stop(&env)
+    }
+
+    // ── Bundles ─────────────────────────────────────────────────────────
+
+    /// Split each interval charge of `subscription_id` across weighted line items.
+    /// Callable by the subscription's merchant; an empty list removes the bundle.
+    pub fn set_bundle_items(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        items: Vec<BundleLineItem>,
+    ) -> Result<(), Error> {
+        bundles::do_set_bundle_items(&env, subscription_id, merchant, items)
+    }
+
+    /// Line items of a bundled subscription (empty if not bundled).
+    pub fn get_bundle_items(env: Env, subscription_id: u32) -> Vec<BundleLineItem> {
+        bundles::get_bundle_items(&env, subscription_id)
+    }
+
+    // ── Dunning ─────────────────────────────────────────────────────────
+
+    /// Merchant sets how failed charges are retried and which notify topics
+    /// reminder services should use.
+    pub fn set_dunning_policy(
+        env: Env,
+        merchant: Address,
+        policy: DunningPolicy,
+    ) -> Result<(), Error> {
+        dunning::do_set_dunning_policy(&env, merchant, policy)
+    }
+
+    /// Merchant dunning policy (defaults if never set).
+    pub fn get_dunning_policy(env: Env, merchant: Address) -> DunningPolicy {
+        dunning::get_dunning_policy(&env, &merchant)
+    }
+
+    /// Consecutive failed interval charges since the last successful one.
+    pub fn get_failed_attempts(env: Env, subscription_id: u32) -> u32 {
+        dunning::get_failed_attempts(&env, subscription_id)
+    }
+
+    /// Merchant chooses whether subscriptions whose grace period lapses are suspended
+    /// (`InsufficientBalance`, the default) or cancelled.
+    pub fn set_grace_lapse_action(
+        env: Env,
+        merchant: Address,
+        action: GraceLapseAction,
+    ) -> Result<(), Error> {
+        dunning::do_set_grace_lapse_action(&env, merchant, action)
+    }
+
+    pub fn get_grace_lapse_action(env: Env, merchant: Address) -> GraceLapseAction {
+        dunning::get_grace_lapse_action(&env, &merchant)
+    }
+
+    /// Applies the lapse action to the listed `GracePeriod` subscriptions whose window
+    /// has passed. Returns the IDs moved. Billing engine or billing agent only.
+    pub fn lapse_grace_periods(
+        env: Env,
+        operator: Address,
+        subscription_ids: Vec<u32>,
+    ) -> Result<Vec<u32>, Error> {
+        dunning::do_lapse_grace_periods(&env, operator, &subscription_ids)
+    }
+
+    // ── Merchant liveness ───────────────────────────────────────────────
+
+    /// **ADMIN ONLY**: Stop charges for `merchant` once it has gone `max_idle_seconds`
+    /// without signing any merchant entrypoint. 0 removes the requirement.
+    pub fn set_merchant_liveness(
+        env: Env,
+        admin: Address,
+        merchant: Address,
+        max_idle_seconds: u64,
+    ) -> Result<(), Error> {
+        liveness::do_set_merchant_liveness(&env, admin, merchant, max_idle_seconds)
+    }
+
+    pub fn get_merchant_liveness(env: Env, merchant: Address) -> Option<MerchantLiveness> {
+        liveness::get_merchant_liveness(&env, &merchant)
+    }
+
+    /// Merchant proves it is still operating, resetting its liveness window.
+    pub fn merchant_heartbeat(env: Env, merchant: Address) {
+        liveness::require_merchant_auth(&env, &merchant);
+    }
+
+    // ── Charge skipping ─────────────────────────────────────────────────
+
+    /// Merchant sets how many periods a subscriber may skip per subscription in any
+    /// 365-day window. 0 (the default) disables skipping.
+    pub fn set_max_skips_per_year(env: Env, merchant: Address, max: u32) -> Result<(), Error> {
+        skips::do_set_max_skips_per_year(&env, merchant, max)
+    }
+
+    pub fn get_max_skips_per_year(env: Env, merchant: Address) -> u32 {
+        skips::get_max_skips_per_year(&env, &merchant)
+    }
+
+    /// Subscriber skips the next interval charge. When it comes due the period is booked
+    /// without a debit and the schedule advances by one interval.
+    pub fn skip_next_charge(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+    ) -> Result<(), Error> {
+        skips::do_skip_next_charge(&env, subscription_id, subscriber)
+    }
+
+    /// Pending skip and skip history of the subscription.
+    pub fn get_skip_record(env: Env, subscription_id: u32) -> SkipRecord {
+        skips::get_skip_record(&env, subscription_id)
+    }
+
+    // ── Refunds ─────────────────────────────────────────────────────────
+
+    /// Merchant refunds `amount` of the subscription's earlier charges from its accrued
+    /// balance, into the prepaid balance or, with `to_wallet`, to the subscriber's wallet.
+    /// At most [`Self::get_refundable_amount`] can be refunded.
+    pub fn refund_charge(
+        env: Env,
+        subscription_id: u32,
+        amount: i128,
+        merchant: Address,
+        to_wallet: bool,
+    ) -> Result<(), Error> {
+        refunds::do_refund_charge(&env, subscription_id, amount, merchant, to_wallet)
+    }
+
+    /// Total charged to the subscription less all refunds.
+    pub fn get_refundable_amount(env: Env, subscription_id: u32) -> i128 {
+        refunds::get_refundable_amount(&env, subscription_id)
+    }
+
+    // ── Sandbox subscriptions ───────────────────────────────────────────
+
+    /// Merchant designates (`enabled = true`) or removes a sandbox agent that may create
+    /// test mode subscriptions on its behalf.
+    pub fn set_sandbox_agent(
+        env: Env,
+        merchant: Address,
+        agent: Address,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        sandbox::do_set_sandbox_agent(&env, merchant, agent, enabled)
+    }
+
+    pub fn is_sandbox_agent(env: Env, merchant: Address, agent: Address) -> bool {
+        sandbox::is_sandbox_agent(&env, &merchant, &agent)
+    }
+
+    /// Sandbox agent creates a test mode subscription, co-signed by the subscriber: full
+    /// lifecycle, zero amount, and left out of the merchant's subscription index and SLA
+    /// report and the subscriber's subscription count and entitlements.
+    pub fn create_sandbox_subscription(
+        env: Env,
+        agent: Address,
+        subscriber: Address,
+        merchant: Address,
+        mock_amount: i128,
+        interval_seconds: u64,
+    ) -> Result<u32, Error> {
+        require_not_emergency_stop(&env)?;
+        sandbox::do_create_sandbox_subscription(
+            &env,
+            agent,
+            subscriber,
+            merchant,
+            mock_amount,
+            interval_seconds,
+        )
+    }
+
+    /// Whether the subscription was created in test mode.
+    pub fn is_test_mode(env: Env, subscription_id: u32) -> bool {
+        sandbox::is_test_mode(&env, subscription_id)
+    }
+
+    /// Mock amount of a test mode subscription; `None` for live subscriptions.
+    pub fn get_mock_amount(env: Env, subscription_id: u32) -> Option<i128> {
+        sandbox::get_mock_amount(&env, subscription_id)
+    }
+
+    // ── Round-up donations ──────────────────────────────────────────────
+
+    /// Subscriber opts in to rounding each interval charge up to a multiple of `unit`,
+    /// sending the difference to `recipient`. Replaces any earlier setting.
+    pub fn enable_round_up(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        unit: i128,
+        recipient: Address,
+    ) -> Result<(), Error> {
+        round_up::do_enable_round_up(&env, subscription_id, subscriber, unit, recipient)
+    }
+
+    /// Subscriber stops rounding up charges on the subscription.
+    pub fn disable_round_up(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+    ) -> Result<(), Error> {
+        round_up::do_disable_round_up(&env, subscription_id, subscriber)
+    }
+
+    /// Round-up setting of the subscription, if the subscriber opted in.
+    pub fn get_round_up(env: Env, subscription_id: u32) -> Option<RoundUp> {
+        round_up::get_round_up(&env, subscription_id)
+    }
+
+    /// Total donated by the subscriber through round-ups.
+    pub fn get_donated_total(env: Env, subscriber: Address) -> i128 {
+        round_up::get_donated_total(&env, &subscriber)
+    }
+
+    // ── Auto-topup ──────────────────────────────────────────────────────
+
+    /// Subscriber opts in to pulling charge shortfalls from their wallet through a token
+    /// allowance granted to the vault, at most `max_pull_per_period` per billing period.
+    pub fn enable_auto_topup(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        max_pull_per_period: i128,
+    ) -> Result<(), Error> {
+        auto_topup::do_enable_auto_topup(&env, subscription_id, subscriber, max_pull_per_period)
+    }
+
+    /// Subscriber stops auto-topup on the subscription.
+    pub fn disable_auto_topup(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+    ) -> Result<(), Error> {
+        auto_topup::do_disable_auto_topup(&env, subscription_id, subscriber)
+    }
+
+    /// Auto-topup setting of the subscription, if the subscriber opted in.
+    pub fn get_auto_topup(env: Env, subscription_id: u32) -> Option<AutoTopup> {
+        auto_topup::get_auto_topup(&env, subscription_id)
+    }
+
+    // ── Legacy import ───────────────────────────────────────────────────
+
+    /// Admin designates the ed25519 public key that signs legacy subscription records, or
+    /// disables imports with `None`.
+    pub fn set_migration_key(
+        env: Env,
+        admin: Address,
+        public_key: Option<soroban_sdk::BytesN<32>>,
+    ) -> Result<(), Error> {
+        legacy_import::do_set_migration_key(&env, admin, public_key)
+    }
+
+    /// Public key legacy records must be signed with, if imports are enabled.
+    pub fn get_migration_key(env: Env) -> Option<soroban_sdk::BytesN<32>> {
+        legacy_import::get_migration_key(&env)
+    }
+
+    /// Creates a subscription from a record exported by an off-chain billing system and
+    /// signed by the migration key, keeping its schedule, completed cycles and original
+    /// start date. Anyone may submit it. Returns the new subscription ID.
+    pub fn import_legacy_subscription(
+        env: Env,
+        signed_record: SignedLegacySubscription,
+    ) -> Result<u32, Error> {
+        legacy_import::do_import_legacy_subscription(&env, signed_record)
+    }
+
+    /// External ID and original start date of an imported subscription.
+    pub fn get_legacy_origin(env: Env, subscription_id: u32) -> Option<LegacyOrigin> {
+        legacy_import::get_legacy_origin(&env, subscription_id)
+    }
+
+    // ── Subscription terms ──────────────────────────────────────────────
+
+    /// Subscriber, or merchant with the subscriber's consent, changes the subscription's
+    /// amount and interval from the next billing boundary on. The charge due at that
+    /// boundary still uses the current terms. Blocked for cancelled subscriptions.
+    pub fn update_subscription_terms(
+        env: Env,
+        subscription_id: u32,
+        new_amount: i128,
+        new_interval: u64,
+        authorizer: Address,
+    ) -> Result<PendingTerms, Error> {
+        terms::do_update_terms(&env, subscription_id, new_amount, new_interval, authorizer)
+    }
+
+    /// Subscriber allows (or withdraws) one change of terms by the merchant.
+    pub fn set_terms_update_consent(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        allowed: bool,
+    ) -> Result<(), Error> {
+        terms::do_set_terms_consent(&env, subscription_id, subscriber, allowed)
+    }
+
+    /// Terms scheduled for the next billing boundary, if any.
+    pub fn get_pending_terms(env: Env, subscription_id: u32) -> Option<PendingTerms> {
+        terms::get_pending_terms(&env, subscription_id)
+    }
+
+    // ── Spending caps ───────────────────────────────────────────────────
+
+    /// Subscriber caps the combined interval and usage charges of one billing period;
+    /// 0 removes the cap. Charges past it fail with `SpendingCapExceeded`.
+    pub fn set_spending_cap(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        cap_per_period: i128,
+    ) -> Result<(), Error> {
+        spending_cap::do_set_spending_cap(&env, subscription_id, subscriber, cap_per_period)
+    }
+
+    /// Spending cap per billing period, if the subscriber set one.
+    pub fn get_spending_cap(env: Env, subscription_id: u32) -> Option<i128> {
+        spending_cap::get_spending_cap(&env, subscription_id)
+    }
+
+    /// Amount charged so far in the current billing period, tracked while a cap is set.
+    pub fn get_period_spend(env: Env, subscription_id: u32) -> Result<i128, Error> {
+        spending_cap::get_current_period_spend(&env, subscription_id)
+    }
+
+    // ── Approved future charges ─────────────────────────────────────────
+
+    /// Subscriber approves one future one-off charge of `amount`, executable from
+    /// `execute_after` until `expires_at`. Returns the approval ID.
+    pub fn approve_future_charge(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        amount: i128,
+        execute_after: u64,
+        expires_at: u64,
+    ) -> Result<u32, Error> {
+        approved_charges::do_approve_future_charge(
+            &env,
+            subscription_id,
+            subscriber,
+            amount,
+            execute_after,
+            expires_at,
+        )
+    }
+
+    /// Subscriber withdraws an approval that has not been executed.
+    pub fn revoke_approved_charge(
+        env: Env,
+        approval_id: u32,
+        subscriber: Address,
+    ) -> Result<(), Error> {
+        approved_charges::do_revoke_approved_charge(&env, approval_id, subscriber)
+    }
+
+    /// Merchant, billing engine or billing agent executes an approval within its window.
+    pub fn execute_approved_charge(
+        env: Env,
+        approval_id: u32,
+        caller: Address,
+    ) -> Result<(), Error> {
+        require_not_emergency_stop(&env)?;
+        approved_charges::do_execute_approved_charge(&env, approval_id, caller)
+    }
+
+    /// Approval waiting to be executed, if any.
+    pub fn get_approved_charge(env: Env, approval_id: u32) -> Option<ApprovedCharge> {
+        approved_charges::get_approved_charge(&env, approval_id)
+    }
+
+    // ── Guarantors ──────────────────────────────────────────────────────
+
+    /// Subscriber and guarantor co-sign to let charge shortfalls be pulled from the
+    /// guarantor's token allowance, with at most `cap` outstanding at once.
+    pub fn set_guarantor(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        guarantor: Address,
+        cap: i128,
+    ) -> Result<(), Error> {
+        guarantor::do_set_guarantor(&env, subscription_id, subscriber, guarantor, cap)
+    }
+
+    /// Subscriber or guarantor detaches the guarantor from the subscription.
+    pub fn remove_guarantor(env: Env, subscription_id: u32, caller: Address) -> Result<(), Error> {
+        guarantor::do_remove_guarantor(&env, subscription_id, caller)
+    }
+
+    /// Guarantor backing the subscription, if any.
+    pub fn get_guarantor(env: Env, subscription_id: u32) -> Option<Guarantee> {
+        guarantor::get_guarantor(&env, subscription_id)
+    }
+
+    /// Amounts pulled from `guarantor` for the subscription, outstanding and in total.
+    pub fn get_guarantor_contributions(
+        env: Env,
+        subscription_id: u32,
+        guarantor: Address,
+    ) -> GuarantorContributions {
+        guarantor::get_contributions(&env, subscription_id, &guarantor)
+    }
+
+    /// Guarantor records `amount` of its outstanding contributions as repaid by the
+    /// subscriber, freeing that much of its cap.
+    pub fn record_guarantor_repayment(
+        env: Env,
+        subscription_id: u32,
+        guarantor: Address,
+        amount: i128,
+    ) -> Result<GuarantorContributions, Error> {
+        guarantor::do_record_repayment(&env, subscription_id, guarantor, amount)
+    }
+
+    // ── Charge notifications ────────────────────────────────────────────
+
+    /// Subscriber registers an admin-approved contract to be called with
+    /// `on_charge(subscription_id, amount)` after each successful charge, or removes it
+    /// with `None`. A failing callback never reverts the charge.
+    pub fn set_charge_callback(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        callback: Option<Address>,
+    ) -> Result<(), Error> {
+        notify::do_set_charge_callback(&env, subscription_id, subscriber, callback)
+    }
+
+    /// Contract notified after each charge of the subscription, if registered.
+    pub fn get_charge_callback(env: Env, subscription_id: u32) -> Option<Address> {
+        notify::get_charge_callback(&env, subscription_id)
+    }
+
+    /// **ADMIN ONLY**: Approve `contract` as a charge callback or hook, or revoke the
+    /// approval. Callbacks run on the charging transaction's budget, so only approved
+    /// contracts can be registered and called.
+    pub fn set_callback_approved(
+        env: Env,
+        admin: Address,
+        contract: Address,
+        approved: bool,
+    ) -> Result<(), Error> {
+        notify::do_set_callback_approved(&env, admin, contract, approved)
+    }
+
+    /// Whether the admin approved `contract` as a charge callback or hook.
+    pub fn is_callback_approved(env: Env, contract: Address) -> bool {
+        notify::is_callback_approved(&env, &contract)
+    }
+
+    /// Merchant registers an admin-approved contract to be called with
+    /// `on_subscription_charged(subscription_id, amount, timestamp)` after each successful
+    /// charge of any of its subscriptions, or removes it with `None`. A failing hook never
+    /// reverts the charge.
+    pub fn set_charge_hook(
+        env: Env,
+        merchant: Address,
+        hook: Option<Address>,
+    ) -> Result<(), Error> {
+        notify::do_set_charge_hook(&env, merchant, hook)
+    }
+
+    /// Contract notified after each charge of the merchant's subscriptions, if registered.
+    pub fn get_charge_hook(env: Env, merchant: Address) -> Option<Address> {
+        notify::get_charge_hook(&env, &merchant)
+    }
+
+    // ── Rolling reserve ─────────────────────────────────────────────────
+
+    /// **ADMIN ONLY**: Hold back `bps` of each charge credited to `merchant` for
+    /// `reserve_period_seconds`. `bps == 0` stops new holds.
+    pub fn set_merchant_reserve(
+        env: Env,
+        admin: Address,
+        merchant: Address,
+        bps: u32,
+        reserve_period_seconds: u64,
+    ) -> Result<(), Error> {
+        reserve::do_set_merchant_reserve(&env, admin, merchant, bps, reserve_period_seconds)
+    }
+
+    /// Merchant rolling reserve configuration, if any.
+    pub fn get_merchant_reserve(env: Env, merchant: Address) -> Option<ReserveConfig> {
+        reserve::get_reserve_config(&env, &merchant)
+    }
+
+    /// Amount of the merchant's balance currently held in reserve.
+    pub fn get_reserved_balance(env: Env, merchant: Address) -> i128 {
+        reserve::get_reserved_balance(&env, &merchant)
+    }
+
+    /// Reserve buckets (amount and release time) for a merchant.
+    pub fn get_reserve_buckets(env: Env, merchant: Address) -> Vec<ReserveBucket> {
+        reserve::get_reserve_buckets(&env, &merchant)
+    }
+
+    // ── Promo subscriptions ─────────────────────────────────────────────
+
+    /// Merchant issues a free subscription to `subscriber`, who co-signs it, that grants
+    /// entitlement until `expires_at`. Charges are rejected with `PromoNotChargeable`
+    /// until it is converted.
+    pub fn create_promo_subscription(
+        env: Env,
+        merchant: Address,
+        subscriber: Address,
+        interval_seconds: u64,
+        expires_at: u64,
+    ) -> Result<u32, Error> {
+        require_not_emergency_stop(&env)?;
+        promo::do_create_promo_subscription(
+            &env,
+            merchant,
+            subscriber,
+            interval_seconds,
+            expires_at,
+        )
+    }
+
+    /// Promo state of a subscription, or `None` for paid subscriptions.
+    pub fn get_promo(env: Env, subscription_id: u32) -> Option<PromoSubscription> {
+        promo::get_promo(&env, subscription_id)
+    }
+
+    /// Merchant offers the price the subscription continues at after the promo.
+    pub fn offer_promo_conversion(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        promo::do_offer_promo_conversion(&env, subscription_id, merchant, amount)
+    }
+
+    /// Subscriber accepts the offered price; billing starts at the later of now and the
+    /// promo expiry.
+    pub fn accept_promo_conversion(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+    ) -> Result<(), Error> {
+        promo::do_accept_promo_conversion(&env, subscription_id, subscriber)
+    }
+
+    // ── Token guard ─────────────────────────────────────────────────────
+
+    /// Fingerprint of the vault token checked on every settlement, or `None` while the
+    /// guard is off (token metadata unreadable at init and never confirmed).
+    pub fn get_token_fingerprint(env: Env) -> Option<TokenFingerprint> {
+        token_guard::get_fingerprint(&env)
+    }
+
+    /// **ADMIN ONLY**: Schedule accepting the vault token as it is now, after charges
+    /// halted with `TokenMismatch`. Returns when `reconfirm_token` becomes possible.
+    pub fn propose_token_reconfirm(env: Env, admin: Address) -> Result<u64, Error> {
+        token_guard::do_propose_token_reconfirm(&env, admin)
+    }
+
+    /// **ADMIN ONLY**: Accept the token proposed by `propose_token_reconfirm` once the
+    /// timelock has passed, resuming charges.
+    pub fn reconfirm_token(env: Env, admin: Address) -> Result<(), Error> {
+        token_guard::do_reconfirm_token(&env, admin)
+    }
+
+    /// **ADMIN ONLY**: Schedule forcing a wedged subscription into `status`, bypassing
+    /// the state machine, with a support `reason`. Returns when `force_set_status`
+    /// becomes possible.
+    pub fn propose_force_status(
+        env: Env,
+        admin: Address,
+        subscription_id: u32,
+        status: SubscriptionStatus,
+        reason: String,
+    ) -> Result<u64, Error> {
+        force_status::do_propose_force_status(&env, admin, subscription_id, status, reason)
+    }
+
+    /// **ADMIN ONLY**: Carry out the change scheduled by `propose_force_status` once
+    /// the timelock delay has passed. Emits a `StatusForcedEvent` audit record.
+    pub fn force_set_status(
+        env: Env,
+        admin: Address,
+        subscription_id: u32,
+        status: SubscriptionStatus,
+        reason: String,
+    ) -> Result<(), Error> {
+        force_status::do_force_set_status(&env, admin, subscription_id, status, reason)
+    }
+
+    /// **ADMIN ONLY**: Consult `oracle` before every charge above `threshold`; a deny
+    /// blocks the charge with `RiskDeclined`. Replaces any earlier oracle.
+    pub fn set_risk_oracle(
+        env: Env,
+        admin: Address,
+        oracle: Address,
+        threshold: i128,
+    ) -> Result<(), Error> {
+        risk::do_set_risk_oracle(&env, admin, oracle, threshold)
+    }
+
+    /// **ADMIN ONLY**: Stop consulting a risk oracle before charges.
+    pub fn remove_risk_oracle(env: Env, admin: Address) -> Result<(), Error> {
+        risk::do_remove_risk_oracle(&env, admin)
+    }
+
+    /// Registered risk oracle and its threshold, if any.
+    pub fn get_risk_oracle(env: Env) -> Option<RiskOracle> {
+        risk::get_risk_oracle(&env)
+    }
+
+    // ── Merchant registry ───────────────────────────────────────────────
+
+    /// **ADMIN ONLY**: Consult `registry` before creating subscriptions and paying out
+    /// merchants. See `docs/merchant_registry.md`.
+    pub fn set_merchant_registry(env: Env, admin: Address, registry: Address) -> Result<(), Error> {
+        merchant_registry::do_set_merchant_registry(&env, admin, registry)
+    }
+
+    /// **ADMIN ONLY**: Stop consulting the merchant registry.
+    pub fn remove_merchant_registry(env: Env, admin: Address) -> Result<(), Error> {
+        merchant_registry::do_remove_merchant_registry(&env, admin)
+    }
+
+    /// The merchant registry the vault consults, if any.
+    pub fn get_merchant_registry(env: Env) -> Option<Address> {
+        merchant_registry::get_merchant_registry(&env)
+    }
+
+    // ── Circuit breaker ─────────────────────────────────────────────────
+
+    /// **ADMIN ONLY**: Suspend charges automatically when the batch charge failure rate
+    /// passes `config.max_failure_bps` within a window. A `window_seconds` of 0 turns the
+    /// breaker off.
+    pub fn set_circuit_breaker(
+        env: Env,
+        admin: Address,
+        config: CircuitBreakerConfig,
+    ) -> Result<(), Error> {
+        circuit_breaker::do_set_circuit_breaker(&env, admin, config)
+    }
+
+    /// Circuit breaker settings, or `None` while it is off.
+    pub fn get_circuit_breaker(env: Env) -> Option<CircuitBreakerConfig> {
+        circuit_breaker::get_circuit_breaker(&env)
+    }
+
+    /// Batch charge outcomes in the current window, and when the breaker tripped (0 if
+    /// charges are allowed).
+    pub fn get_charge_metrics(env: Env) -> ChargeMetrics {
+        circuit_breaker::get_charge_metrics(&env)
+    }
+
+    /// **ADMIN ONLY**: Resume charges after the circuit breaker tripped.
+    pub fn reset_circuit_breaker(env: Env, admin: Address) -> Result<(), Error> {
+        circuit_breaker::do_reset_circuit_breaker(&env, admin)
+    }
+
+    // ── Merchant privacy ────────────────────────────────────────────────
+
+    /// **ADMIN ONLY**: Show merchants to subscribers as salted hashes (`Some(salt)`) or
+    /// as addresses (`None`). Settlement always uses the real address.
+    pub fn set_merchant_ref_salt(
+        env: Env,
+        admin: Address,
+        salt: Option<soroban_sdk::BytesN<32>>,
+    ) -> Result<(), Error> {
+        merchant_privacy::do_set_merchant_ref_salt(&env, admin, salt)
+    }
+
+    /// Whether subscriber views show hashed merchant references.
+    pub fn is_merchant_ref_hashed(env: Env) -> bool {
+        merchant_privacy::is_merchant_ref_hashed(&env)
+    }
+
+    /// How `merchant` appears in subscriber views, so it can match its own subscriptions.
+    pub fn get_merchant_ref(env: Env, merchant: Address) -> MerchantRef {
+        merchant_privacy::merchant_ref(&env, merchant)
+    }
+
+    /// Subscriber-facing view of a subscription, with the merchant as a `MerchantRef`.
+    pub fn get_subscriber_view(env: Env, subscription_id: u32) -> Result<SubscriberView, Error> {
+        merchant_privacy::get_subscriber_view(&env, subscription_id)
+    }
+
+    /// Page through a subscriber's subscriptions as subscriber views.
+    pub fn page_subscriber_views(
+        env: Env,
+        subscriber: Address,
+        cursor: Cursor,
+    ) -> Result<SubscriberViewPage, Error> {
+        merchant_privacy::page_subscriber_views(&env, subscriber, cursor)
+    }
+
+    // ── Subscription limits ─────────────────────────────────────────────
+
+    /// **ADMIN ONLY**: Cap live subscriptions per subscriber and per merchant, enforced
+    /// at creation. 0 means unlimited.
+    pub fn set_subscription_limits(
+        env: Env,
+        admin: Address,
+        max_per_subscriber: u32,
+        max_per_merchant: u32,
+    ) -> Result<(), Error> {
+        admin::do_set_subscription_limits(&env, admin, max_per_subscriber, max_per_merchant)
+    }
+
+    /// Current subscription caps (also reported by `get_config`).
+    pub fn get_subscription_limits(env: Env) -> SubscriptionLimits {
+        admin::get_subscription_limits(&env)
+    }
+
+    /// Live subscriptions counted against the subscriber's limit (named to fit the
+    /// 32-character contract function limit).
+    pub fn get_subscriber_sub_count(env: Env, subscriber: Address) -> u32 {
+        subscription::get_subscriber_subscription_count(&env, &subscriber)
+    }
+
+    // ── Soft-launch allowlist ───────────────────────────────────────────
+
+    /// **ADMIN ONLY**: While enabled, only allowlisted subscribers may open subscriptions.
+    pub fn set_allowlist_mode(env: Env, admin: Address, enabled: bool) -> Result<(), Error> {
+        admin::do_set_allowlist_mode(&env, admin, enabled)
+    }
+
+    /// **ADMIN ONLY**: Admit a subscriber while allowlist mode is on.
+    pub fn add_allowed_subscriber(
+        env: Env,
+        admin: Address,
+        subscriber: Address,
+    ) -> Result<(), Error> {
+        admin::do_set_allowed_subscriber(&env, admin, subscriber, true)
+    }
+
+    /// **ADMIN ONLY**: Remove a subscriber from the allowlist. Existing subscriptions
+    /// are unaffected.
+    pub fn remove_allowed_subscriber(
+        env: Env,
+        admin: Address,
+        subscriber: Address,
+    ) -> Result<(), Error> {
+        admin::do_set_allowed_subscriber(&env, admin, subscriber, false)
+    }
+
+    /// True if the subscriber is on the allowlist (regardless of the mode).
+    pub fn is_subscriber_allowed(env: Env, subscriber: Address) -> bool {
+        admin::is_subscriber_allowed(&env, &subscriber)
+    }
+
+    /// Found the identity `wallet` commits to with `salt`, linking `wallet` to it, and
+    /// return its hash.
+    pub fn found_identity(
+        env: Env,
+        wallet: Address,
+        salt: soroban_sdk::BytesN<32>,
+    ) -> Result<soroban_sdk::BytesN<32>, Error> {
+        identity::do_found_identity(&env, wallet, salt)
+    }
+
+    /// Link `wallet` to a founded identity with the authorization of `sponsor`, a wallet
+    /// already linked to it.
+    pub fn link_wallet(
+        env: Env,
+        wallet: Address,
+        identity: soroban_sdk::BytesN<32>,
+        sponsor: Address,
+    ) -> Result<(), Error> {
+        identity::do_link_wallet(&env, wallet, identity, sponsor)
+    }
+
+    /// Remove `wallet` from its identity.
+    pub fn unlink_wallet(env: Env, wallet: Address) -> Result<(), Error> {
+        identity::do_unlink_wallet(&env, wallet)
+    }
+
+    pub fn get_identity(env: Env, wallet: Address) -> Option<soroban_sdk::BytesN<32>> {
+        identity::get_identity(&env, &wallet)
+    }
+
+    pub fn get_identity_wallets(env: Env, identity: soroban_sdk::BytesN<32>) -> Vec<Address> {
+        identity::get_identity_wallets(&env, &identity)
+    }
+
+    /// Every wallet of the same identity as `wallet` (itself included), for treating
+    /// them as one customer in trial checks.
+    pub fn get_linked_wallets(env: Env, wallet: Address) -> Vec<Address> {
+        identity::linked_wallets(&env, &wallet)
+    }
+
+    /// Current expiration timestamp, or `None` for open-ended subscriptions.
+    pub fn get_expiration(env: Env, subscription_id: u32) -> Option<u64> {
+        expiration::get_expiration(&env, subscription_id)
+    }
+
+    /// Subscriber moves the expiration out to `new_expiration`, which must be in the
+    /// future and later than the current expiration.
+    pub fn extend_expiration(
+        env: Env,
+        subscription_id: u32,
+        authorizer: Address,
+        new_expiration: u64,
+    ) -> Result<(), Error> {
+        expiration::do_extend_expiration(&env, subscription_id, authorizer, new_expiration)
+    }
+
+    /// Merchant opts in (or out) of refunding residual prepaid balances to subscribers
+    /// when the expiration sweep cancels their subscriptions.
+    pub fn set_auto_refund_on_expiry(
+        env: Env,
+        merchant: Address,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        expiration::do_set_auto_refund_on_expiry(&env, merchant, enabled)
+    }
+
+    pub fn get_auto_refund_on_expiry(env: Env, merchant: Address) -> bool {
+        expiration::get_auto_refund_on_expiry(&env, &merchant)
+    }
+
+    /// Merchant chooses how an interval charge whose period runs past the subscription's
+    /// expiration is handled: charged in full, prorated or blocked.
+    pub fn set_expiry_proration(
+        env: Env,
+        merchant: Address,
+        policy: ExpiryProration,
+    ) -> Result<(), Error> {
+        expiration::do_set_expiry_proration(&env, merchant, policy)
+    }
+
+    /// The merchant's expiry proration policy (`FullCharge` by default).
+    pub fn get_expiry_proration(env: Env, merchant: Address) -> ExpiryProration {
+        expiration::get_expiry_proration(&env, &merchant)
+    }
+
+    /// Merchant chooses whether cancelling mid-period refunds the unused part of the
+    /// last interval charge into the prepaid balance.
+    pub fn set_cancel_proration(
+        env: Env,
+        merchant: Address,
+        policy: CancellationProration,
+    ) -> Result<(), Error> {
+        cancel_proration::do_set_cancel_proration(&env, merchant, policy)
+    }
+
+    /// The merchant's cancellation proration policy (`None` by default).
+    pub fn get_cancel_proration(env: Env, merchant: Address) -> CancellationProration {
+        cancel_proration::get_cancel_proration(&env, &merchant)
+    }
+
+    /// Cancels the listed subscriptions whose expiration has been reached, refunding
+    /// residual balances where the merchant opted in. Returns the cancelled IDs.
+    /// Billing engine or billing agent only.
+    pub fn expire_subscriptions(
+        env: Env,
+        operator: Address,
+        subscription_ids: Vec<u32>,
+    ) -> Result<Vec<u32>, Error> {
+        expiration::do_expire_subscriptions(&env, operator, &subscription_ids)
+    }
+
+    /// Subscriber pauses and withdraws `refund_amount` of the prepaid balance in one
+    /// transaction, keeping at least one interval's amount for resuming later.
+    pub fn pause_and_refund(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        refund_amount: i128,
+    ) -> Result<(), Error> {
+        subscription::do_pause_and_refund(&env, subscription_id, subscriber, refund_amount)
+    }
+
+    // ── Expedited withdrawals ───────────────────────────────────────────
+
+    /// **ADMIN ONLY**: Fee in basis points for expedited merchant withdrawals, credited
+    /// to protocol fees. `bps == 0` stops offering them.
+    pub fn set_expedite_fee(env: Env, admin: Address, bps: u32) -> Result<(), Error> {
+        fees::do_set_expedite_fee_bps(&env, admin, bps)
+    }
+
+    /// Current expedite fee in basis points, or `None` if not offered.
+    pub fn get_expedite_fee(env: Env) -> Option<u32> {
+        fees::get_expedite_fee_bps(&env)
+    }
+
+    /// Merchant withdraws `amount` immediately, including funds still held by a rolling
+    /// reserve, paying the expedite fee on top. Returns the fee charged.
+    pub fn withdraw_merchant_expedited(
+        env: Env,
+        merchant: Address,
+        amount: i128,
+    ) -> Result<i128, Error> {
+        merchant::withdraw_merchant_funds_expedited(&env, merchant, amount)
+    }
+
+    // ── Merchant-wide pause ─────────────────────────────────────────────
+
+    /// Merchant pauses all its `Active` subscriptions, for example during a service
+    /// outage. Returns per-subscription results in merchant index order.
+    pub fn merchant_pause_all(
+        env: Env,
+        merchant: Address,
+    ) -> Result<Vec<MerchantPauseResult>, Error> {
+        merchant::merchant_pause_all(&env, merchant)
+    }
+
+    /// Merchant resumes the subscriptions paused by `merchant_pause_all`; subscriptions
+    /// paused by their subscriber are left alone.
+    pub fn merchant_resume_all(
+        env: Env,
+        merchant: Address,
+    ) -> Result<Vec<MerchantPauseResult>, Error> {
+        merchant::merchant_resume_all(&env, merchant)
+    }
+
+    // ── Scheduled payouts ───────────────────────────────────────────────
+
+    /// Merchant opts in to being paid out every `interval_seconds` once at least
+    /// `min_amount` is available; an interval of 0 opts out.
+    pub fn set_payout_schedule(
+        env: Env,
+        merchant: Address,
+        interval_seconds: u64,
+        min_amount: i128,
+    ) -> Result<(), Error> {
+        payouts::do_set_payout_schedule(&env, merchant, interval_seconds, min_amount)
+    }
+
+    /// Payout schedule of the merchant, if it opted in.
+    pub fn get_payout_schedule(env: Env, merchant: Address) -> Option<PayoutSchedule> {
+        payouts::get_payout_schedule(&env, &merchant)
+    }
+
+    /// Merchant has its whole available balance paid out to its payout address by the
+    /// charge that brings it to `threshold`; `enabled = false` turns this off.
+    pub fn set_auto_payout(
+        env: Env,
+        merchant: Address,
+        threshold: i128,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        payouts::do_set_auto_payout(&env, merchant, threshold, enabled)
+    }
+
+    /// Automatic payout threshold of the merchant, if enabled.
+    pub fn get_auto_payout(env: Env, merchant: Address) -> Option<i128> {
+        payouts::get_auto_payout(&env, &merchant)
+    }
+
+    /// Billing engine or billing agent pays out every listed merchant whose scheduled payout is
+    /// due, with one result per merchant, so a daily settlement job needs one transaction.
+    pub fn batch_payout(
+        env: Env,
+        operator: Address,
+        merchants: Vec<Address>,
+    ) -> Result<Vec<BatchPayoutResult>, Error> {
+        payouts::do_batch_payout(&env, operator, &merchants)
+    }
+
+    /// Close the merchant's statement period without a payout and emit its
+    /// `MerchantStatementEvent`. Called by the merchant, the billing engine or a billing
+    /// agent; payouts close the period on their own.
+    pub fn close_merchant_statement(
+        env: Env,
+        caller: Address,
+        merchant: Address,
+    ) -> Result<MerchantStatementEvent, Error> {
+        statements::do_close_merchant_statement(&env, caller, merchant)
+    }
+
+    // ── Merchant offboarding ────────────────────────────────────────────
+
+    /// **ADMIN ONLY**: Mark a merchant as no longer operating. Its subscriptions stop
+    /// being charged and subscribers can reclaim their prepaid balances.
+    pub fn declare_merchant_inactive(
+        env: Env,
+        admin: Address,
+        merchant: Address,
+    ) -> Result<(), Error> {
+        offboarding::do_declare_merchant_inactive(&env, admin, merchant)
+    }
+
+    /// True if the merchant was declared inactive or has had no activity for
+    /// `MERCHANT_INACTIVITY_TIMEOUT_SECONDS`.
+    pub fn is_merchant_inactive(env: Env, merchant: Address) -> bool {
+        offboarding::is_merchant_inactive(&env, &merchant)
+    }
+
+    /// Subscriber cancels a subscription of an inactive merchant and reclaims its
+    /// prepaid and secondary-token balances. Returns the vault-token amount refunded.
+    pub fn reclaim_escrow(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+    ) -> Result<i128, Error> {
+        offboarding::do_reclaim_from_inactive_merchant(&env, subscription_id, subscriber)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,101 @@
+//! Charge-time merchant liveness requirement.
+//!
+//! The admin can require, per merchant agreement, that the merchant has signed some
+//! merchant entrypoint within a window for its subscriptions to keep being charged.
+//! Every merchant-authorized entrypoint goes through [`require_merchant_auth`], which
+//! records when the merchant was last seen; `merchant_heartbeat` exists for merchants
+//! with nothing else to call. A charge for a stale merchant pauses the subscription
+//! instead of billing for a possibly abandoned service.
+//!
+//! **PRs that only change merchant liveness should edit this file only.**
+
+use crate::state_machine::validate_status_transition;
+use crate::types::{
+    DataKey, Error, MerchantKey, MerchantLiveness, Subscription, SubscriptionStatus,
+};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn window_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::LivenessWindow, merchant.clone())
+}
+
+fn seen_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::LastSeen, merchant.clone())
+}
+
+/// The merchant's liveness requirement, or `None` if charges do not depend on it.
+pub fn get_merchant_liveness(env: &Env, merchant: &Address) -> Option<MerchantLiveness> {
+    let storage = env.storage().instance();
+    let max_idle_seconds: u64 = storage.get(&window_key(merchant))?;
+    Some(MerchantLiveness {
+        max_idle_seconds,
+        last_seen: storage.get(&seen_key(merchant)).unwrap_or(0),
+    })
+}
+
+/// Requires the merchant's signature and, if a liveness requirement applies, records
+/// that the merchant was seen now.
+pub fn require_merchant_auth(env: &Env, merchant: &Address) {
+    merchant.require_auth();
+    let storage = env.storage().instance();
+    if storage.has(&window_key(merchant)) {
+        storage.set(&seen_key(merchant), &env.ledger().timestamp());
+    }
+}
+
+/// Admin sets how long the merchant may go without signing before charges for it stop.
+/// `max_idle_seconds == 0` removes the requirement. The window starts now.
+pub fn do_set_merchant_liveness(
+    env: &Env,
+    admin: Address,
+    merchant: Address,
+    max_idle_seconds: u64,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    let storage = env.storage().instance();
+    if max_idle_seconds == 0 {
+        storage.remove(&window_key(&merchant));
+        storage.remove(&seen_key(&merchant));
+    } else {
+        storage.set(&window_key(&merchant), &max_idle_seconds);
+        storage.set(&seen_key(&merchant), &env.ledger().timestamp());
+    }
+    env.events().publish(
+        (Symbol::new(env, "merchant_liveness_set"), merchant),
+        max_idle_seconds,
+    );
+    Ok(())
+}
+
+/// Charge guard: fails with `MerchantStale` once the merchant has been idle past its
+/// window, pausing `sub` (stored here) when its status allows it.
+pub fn ensure_merchant_live(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+) -> Result<(), Error> {
+    let Some(liveness) = get_merchant_liveness(env, &sub.merchant) else {
+        return Ok(());
+    };
+    let now = env.ledger().timestamp();
+    if now < liveness.last_seen.saturating_add(liveness.max_idle_seconds) {
+        return Ok(());
+    }
+    if validate_status_transition(&sub.status, &SubscriptionStatus::Paused).is_ok()
+        && sub.status != SubscriptionStatus::Paused
+    {
+        sub.status = SubscriptionStatus::Paused;
+        env.storage()
+            .instance()
+            .set(&DataKey::Sub(subscription_id), sub);
+        crate::health::record_pause(env, subscription_id);
+        env.events().publish(
+            (Symbol::new(env, "merchant_stale_paused"), subscription_id),
+            (sub.merchant.clone(), liveness.last_seen),
+        );
+    }
+    Err(Error::MerchantStale)
+}
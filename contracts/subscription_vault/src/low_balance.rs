@@ -0,0 +1,86 @@
+//! Low-balance warnings.
+//!
+//! The subscriber or the merchant can set a threshold in upcoming intervals on a
+//! subscription. Whenever a charge leaves `prepaid_balance` below that many intervals at
+//! the subscription's `amount`, a `LowBalanceEvent` is emitted so the merchant can ask
+//! the subscriber to top up before a charge fails. The warning repeats on every charge
+//! while the balance stays low. Subscriptions without a threshold emit nothing.
+//!
+//! `get_subscriptions_below_runway` in `queries.rs` answers the same question for a
+//! merchant's whole list, whatever the thresholds.
+//!
+//! **PRs that only change low-balance warnings should edit this file only.**
+
+use crate::types::{DataKey, Error, LowBalanceEvent, SubKey, Subscription};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn threshold_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::LowBalanceThreshold, subscription_id)
+}
+
+/// Threshold of the subscription in upcoming intervals; 0 when warnings are off.
+pub fn get_low_balance_threshold(env: &Env, subscription_id: u32) -> u32 {
+    env.storage()
+        .instance()
+        .get(&threshold_key(subscription_id))
+        .unwrap_or(0)
+}
+
+/// The subscriber or the merchant sets the threshold; 0 turns warnings off.
+pub fn do_set_low_balance_threshold(
+    env: &Env,
+    subscription_id: u32,
+    caller: Address,
+    intervals: u32,
+) -> Result<(), Error> {
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if caller == sub.merchant {
+        crate::liveness::require_merchant_auth(env, &caller);
+    } else if caller == sub.subscriber {
+        caller.require_auth();
+    } else {
+        return Err(Error::Forbidden);
+    }
+    let key = threshold_key(subscription_id);
+    if intervals == 0 {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &intervals);
+    }
+    env.events().publish(
+        (
+            Symbol::new(env, "low_balance_threshold_set"),
+            subscription_id,
+        ),
+        (caller, intervals),
+    );
+    Ok(())
+}
+
+/// Balance that covers `intervals` charges of the subscription's amount.
+pub fn required_balance(sub: &Subscription, intervals: u32) -> i128 {
+    sub.amount.saturating_mul(i128::from(intervals))
+}
+
+/// Emits a `LowBalanceEvent` if a charge left `sub` below its threshold.
+pub fn warn_if_low(env: &Env, subscription_id: u32, sub: &Subscription) {
+    let intervals = get_low_balance_threshold(env, subscription_id);
+    if intervals == 0 {
+        return;
+    }
+    let required = required_balance(sub, intervals);
+    if sub.prepaid_balance >= required {
+        return;
+    }
+    env.events().publish(
+        (Symbol::new(env, "low_balance"), sub.merchant.clone()),
+        LowBalanceEvent {
+            subscription_id,
+            subscriber: sub.subscriber.clone(),
+            merchant: sub.merchant.clone(),
+            prepaid_balance: sub.prepaid_balance,
+            intervals,
+            required,
+        },
+    );
+}
@@ -0,0 +1,131 @@
+//! Merchant loyalty tiers: automatic discounts after a number of completed cycles.
+//!
+//! A merchant configures `cycles_per_tier`, `bps_per_tier` and `max_tiers`. A
+//! subscription that has completed `n` interval charges sits in tier
+//! `min(n / cycles_per_tier, max_tiers)` and pays `amount` reduced by
+//! `tier * bps_per_tier` basis points. The discount is applied in `charge_core`
+//! and surfaced by `preview_charge`.
+//!
+//! A subscriber whose wallet is linked to an identity (see `identity.rs`) is one
+//! customer to the merchant: `n` counts the cycles of every subscription to the merchant
+//! held by any of the identity's wallets, so rotating wallets keeps the tier earned.
+//!
+//! **PRs that only change loyalty discounts should edit this file only.**
+
+use crate::fees::MAX_BPS;
+use crate::types::{DataKey, Error, LoyaltyConfig, Subscription};
+use soroban_sdk::{Address, Env, Symbol};
+
+pub fn get_loyalty_config(env: &Env, merchant: &Address) -> Option<LoyaltyConfig> {
+    env.storage()
+        .instance()
+        .get(&DataKey::LoyaltyConfig(merchant.clone()))
+}
+
+/// Merchant sets its loyalty tiers. `cycles_per_tier == 0` removes the programme.
+///
+/// The deepest discount (`bps_per_tier * max_tiers`) may not exceed 100%.
+pub fn do_set_loyalty_config(
+    env: &Env,
+    merchant: Address,
+    cycles_per_tier: u32,
+    bps_per_tier: u32,
+    max_tiers: u32,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+
+    let key = DataKey::LoyaltyConfig(merchant.clone());
+    if cycles_per_tier == 0 {
+        env.storage().instance().remove(&key);
+        return Ok(());
+    }
+    let max_discount = bps_per_tier
+        .checked_mul(max_tiers)
+        .ok_or(Error::InvalidInput)?;
+    if max_discount > MAX_BPS {
+        return Err(Error::InvalidInput);
+    }
+
+    let config = LoyaltyConfig {
+        cycles_per_tier,
+        bps_per_tier,
+        max_tiers,
+    };
+    env.storage().instance().set(&key, &config);
+    env.events()
+        .publish((Symbol::new(env, "loyalty_config_set"), merchant), config);
+    Ok(())
+}
+
+/// Number of successful interval charges taken for the subscription.
+pub fn get_cycles_completed(env: &Env, subscription_id: u32) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::CyclesCompleted(subscription_id))
+        .unwrap_or(0)
+}
+
+/// Counts one more completed cycle (called on every successful interval charge).
+pub fn record_completed_cycle(env: &Env, subscription_id: u32) {
+    let cycles = get_cycles_completed(env, subscription_id).saturating_add(1);
+    env.storage()
+        .instance()
+        .set(&DataKey::CyclesCompleted(subscription_id), &cycles);
+}
+
+/// Sets the completed cycle count, for subscriptions imported with their history.
+pub fn set_cycles_completed(env: &Env, subscription_id: u32, cycles: u32) {
+    env.storage()
+        .instance()
+        .set(&DataKey::CyclesCompleted(subscription_id), &cycles);
+}
+
+/// Cycles that count towards the subscription's loyalty tier: its own, plus those of
+/// every other subscription to the same merchant held by a wallet linked to the same
+/// identity. Only read while the merchant runs a loyalty programme.
+fn tier_cycles(env: &Env, subscription_id: u32, sub: &Subscription) -> u32 {
+    let own = get_cycles_completed(env, subscription_id);
+    if crate::identity::get_identity(env, &sub.subscriber).is_none() {
+        return own;
+    }
+    let mut cycles = own;
+    for wallet in crate::identity::linked_wallets(env, &sub.subscriber).iter() {
+        for id in crate::subscription::get_subscriber_subscription_ids(env, &wallet).iter() {
+            if id == subscription_id {
+                continue;
+            }
+            if let Some(other) = crate::queries::load_subscription(env, id) {
+                if other.merchant == sub.merchant {
+                    cycles = cycles.saturating_add(get_cycles_completed(env, id));
+                }
+            }
+        }
+    }
+    cycles
+}
+
+/// Loyalty discount in basis points currently earned by the subscription.
+pub fn discount_bps(env: &Env, subscription_id: u32, sub: &Subscription) -> u32 {
+    match get_loyalty_config(env, &sub.merchant) {
+        Some(config) => {
+            let tier = (tier_cycles(env, subscription_id, sub) / config.cycles_per_tier)
+                .min(config.max_tiers);
+            tier * config.bps_per_tier
+        }
+        None => 0,
+    }
+}
+
+/// Interval charge amount after the loyalty discount.
+pub fn discounted_amount(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+) -> Result<i128, Error> {
+    let bps = discount_bps(env, subscription_id, sub);
+    if bps == 0 {
+        return Ok(sub.amount);
+    }
+    let discount = sub.amount.checked_mul(bps as i128).ok_or(Error::Overflow)? / MAX_BPS as i128;
+    Ok(sub.amount - discount)
+}
@@ -1,35 +1,150 @@
-//! Merchant payout and accumulated USDC tracking entrypoints.
+//! Merchant payout and accumulated USDC tracking entrypoints, and merchant-wide pause.
 
-use crate::safe_math::validate_non_negative;
-use crate::types::Error;
-use soroban_sdk::{token, Address, Env, Symbol};
+use crate::fees::MAX_BPS;
+use crate::safe_math::{safe_add_balance, safe_sub_balance, validate_non_negative};
+use crate::statements::Movement;
+use crate::storage_migration::legacy_merchant_balance_key;
+use crate::types::{
+    DataKey, Error, MerchantExposure, MerchantKey, MerchantPauseResult, MerchantWithdrawalEvent,
+    SubscriptionStatus, TokenBalance,
+};
+use soroban_sdk::{token, Address, Env, Symbol, Vec};
 
-fn merchant_balance_key(env: &Env, merchant: &Address) -> (Symbol, Address) {
-    (Symbol::new(env, "merchant_balance"), merchant.clone())
+fn merchant_balance_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::Balance, merchant.clone())
 }
 
+/// Accrued balance of the merchant. Until storage is migrated, a balance still under
+/// its legacy key is read from there.
 pub fn get_merchant_balance(env: &Env, merchant: &Address) -> i128 {
-    let key = merchant_balance_key(env, merchant);
-    env.storage().instance().get(&key).unwrap_or(0i128)
+    let storage = env.storage().instance();
+    storage
+        .get(&merchant_balance_key(merchant))
+        .or_else(|| storage.get(&legacy_merchant_balance_key(env, merchant)))
+        .unwrap_or(0i128)
 }
 
-fn set_merchant_balance(env: &Env, merchant: &Address, balance: &i128) {
-    let key = merchant_balance_key(env, merchant);
-    env.storage().instance().set(&key, balance);
+pub fn set_merchant_balance(env: &Env, merchant: &Address, balance: &i128) {
+    env.storage()
+        .instance()
+        .set(&merchant_balance_key(merchant), balance);
 }
 
-/// Credit merchant balance (used when subscription charges process).
+/// Credit merchant balance (used when subscription charges process). Any rolling reserve
+/// configured for the merchant holds back its share (see `reserve.rs`).
 pub fn credit_merchant_balance(env: &Env, merchant: &Address, amount: i128) -> Result<(), Error> {
     validate_non_negative(amount)?;
     let current = get_merchant_balance(env, merchant);
     let new_balance = current.checked_add(amount).ok_or(Error::Overflow)?;
     set_merchant_balance(env, merchant, &new_balance);
+    crate::offboarding::record_activity(env, merchant);
+    crate::reserve::hold_reserve(env, merchant, amount)
+}
+
+/// Amount of the merchant's balance ring-fenced by open disputes.
+pub fn get_merchant_locked_balance(env: &Env, merchant: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&DataKey::MerchantLocked(merchant.clone()))
+        .unwrap_or(0i128)
+}
+
+#[cfg(feature = "disputes")]
+fn set_merchant_locked_balance(env: &Env, merchant: &Address, locked: &i128) {
+    env.storage()
+        .instance()
+        .set(&DataKey::MerchantLocked(merchant.clone()), locked);
+}
+
+/// The merchant's accrued balances per token with reserve and dispute holds, in one read.
+///
+/// `available` matches what `withdraw_merchant_funds` would allow, counting reserve
+/// buckets that have matured but not yet been released as available.
+pub fn get_merchant_exposure(env: &Env, merchant: &Address) -> Result<MerchantExposure, Error> {
+    let balance = get_merchant_balance(env, merchant);
+    let reserved = crate::reserve::get_reserved_balance(env, merchant);
+    let locked = get_merchant_locked_balance(env, merchant);
+    let available = balance
+        .saturating_sub(reserved)
+        .saturating_sub(locked)
+        .max(0);
+
+    let mut token_balances = Vec::new(env);
+    for token in crate::multi_token::get_merchant_tokens(env, merchant).iter() {
+        let amount = crate::multi_token::get_merchant_token_balance(env, merchant, &token);
+        token_balances.push_back(TokenBalance { token, amount });
+    }
+
+    Ok(MerchantExposure {
+        token: crate::admin::get_token(env)?,
+        balance,
+        reserved,
+        locked,
+        available,
+        token_balances,
+    })
+}
+
+/// Ring-fence `amount` of the merchant's accrued balance so it cannot be withdrawn.
+///
+/// Fails with `InsufficientMerchantBalance` if the unlocked balance cannot cover `amount`.
+#[cfg(feature = "disputes")]
+pub fn lock_merchant_balance(env: &Env, merchant: &Address, amount: i128) -> Result<(), Error> {
+    let locked = get_merchant_locked_balance(env, merchant);
+    let available = safe_sub_balance(get_merchant_balance(env, merchant), locked)?;
+    if amount > available {
+        return Err(Error::InsufficientMerchantBalance);
+    }
+    set_merchant_locked_balance(env, merchant, &safe_add_balance(locked, amount)?);
     Ok(())
 }
 
+/// Release a previously locked `amount`. If `debit` is true the amount also leaves
+/// the merchant's balance (e.g. refunded to the subscriber).
+#[cfg(feature = "disputes")]
+pub fn unlock_merchant_balance(
+    env: &Env,
+    merchant: &Address,
+    amount: i128,
+    debit: bool,
+) -> Result<(), Error> {
+    let locked = get_merchant_locked_balance(env, merchant);
+    set_merchant_locked_balance(env, merchant, &safe_sub_balance(locked, amount)?);
+    if debit {
+        crate::statements::record(env, merchant, Movement::Adjustment, -amount);
+        let balance = get_merchant_balance(env, merchant);
+        set_merchant_balance(env, merchant, &safe_sub_balance(balance, amount)?);
+    }
+    Ok(())
+}
+
+/// Debit `amount` from the merchant's balance for a payout other than a withdrawal, such
+/// as a refund. Like a withdrawal, it can only draw on funds not held by a rolling
+/// reserve or a dispute lock (`InsufficientMerchantBalance`).
+pub fn debit_merchant_balance(env: &Env, merchant: &Address, amount: i128) -> Result<(), Error> {
+    if amount > available_balance(env, merchant)? {
+        return Err(Error::InsufficientMerchantBalance);
+    }
+    let current = get_merchant_balance(env, merchant);
+    set_merchant_balance(env, merchant, &safe_sub_balance(current, amount)?);
+    Ok(())
+}
+
+/// Part of the merchant's balance not held by a rolling reserve or a dispute lock,
+/// after releasing matured reserve buckets.
+pub fn available_balance(env: &Env, merchant: &Address) -> Result<i128, Error> {
+    crate::reserve::release_matured(env, merchant);
+    get_merchant_balance(env, merchant)
+        .checked_sub(get_merchant_locked_balance(env, merchant))
+        .and_then(|v| v.checked_sub(crate::reserve::get_reserved_balance(env, merchant)))
+        .ok_or(Error::Overflow)
+}
+
 /// Withdraw accumulated USDC from prior subscription charges to the merchant address.
+///
+/// Standard path: funds held by a rolling reserve or a dispute lock are not available.
 pub fn withdraw_merchant_funds(env: &Env, merchant: Address, amount: i128) -> Result<(), Error> {
-    merchant.require_auth();
+    crate::liveness::require_merchant_auth(env, &merchant);
     if amount <= 0 {
         return Err(Error::InvalidAmount);
     }
@@ -38,20 +153,167 @@ pub fn withdraw_merchant_funds(env: &Env, merchant: Address, amount: i128) -> Re
     if current == 0 {
         return Err(Error::NotFound);
     }
-    if amount > current {
-        return Err(Error::InsufficientBalance);
+    crate::reserve::release_matured(env, &merchant);
+    let available = current
+        .checked_sub(get_merchant_locked_balance(env, &merchant))
+        .and_then(|v| v.checked_sub(crate::reserve::get_reserved_balance(env, &merchant)))
+        .ok_or(Error::Overflow)?;
+    if amount > available {
+        return Err(Error::InsufficientMerchantBalance);
     }
 
     let new_balance = current.checked_sub(amount).ok_or(Error::Overflow)?;
 
     let token_addr = crate::admin::get_token(env)?;
+    let destination = crate::merchant_registry::payout_destination(env, &merchant)?;
 
     let token_client = token::Client::new(env, &token_addr);
-    token_client.transfer(&env.current_contract_address(), &merchant, &amount);
+    token_client.transfer(&env.current_contract_address(), &destination, &amount);
 
+    crate::statements::record(env, &merchant, Movement::Payout, amount);
     set_merchant_balance(env, &merchant, &new_balance);
+    crate::offboarding::record_activity(env, &merchant);
+    crate::statements::close_period(env, &merchant);
 
-    env.events()
-        .publish((Symbol::new(env, "withdrawn"), merchant.clone()), amount);
+    env.events().publish(
+        (Symbol::new(env, "withdrawn"), merchant.clone()),
+        MerchantWithdrawalEvent { merchant, amount },
+    );
     Ok(())
 }
+
+/// Withdraw without waiting for reserve holds to release, paying the admin-configured
+/// expedite fee to the protocol. The fee is rounded up and debited from the merchant
+/// balance on top of `amount`; dispute locks still apply. Returns the fee charged.
+///
+/// Fails with `NotFound` when expedited withdrawals are not offered.
+pub fn withdraw_merchant_funds_expedited(
+    env: &Env,
+    merchant: Address,
+    amount: i128,
+) -> Result<i128, Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let bps = crate::fees::get_expedite_fee_bps(env).ok_or(Error::NotFound)?;
+    // Rounded up, so splitting a withdrawal into small amounts cannot avoid the fee.
+    let fee = amount
+        .checked_mul(bps as i128)
+        .and_then(|scaled| scaled.checked_add(MAX_BPS as i128 - 1))
+        .ok_or(Error::Overflow)?
+        / MAX_BPS as i128;
+    let total = safe_add_balance(amount, fee)?;
+
+    let current = get_merchant_balance(env, &merchant);
+    crate::reserve::release_matured(env, &merchant);
+    let unlocked = safe_sub_balance(current, get_merchant_locked_balance(env, &merchant))?;
+    if total > unlocked {
+        return Err(Error::InsufficientMerchantBalance);
+    }
+    let free = unlocked - crate::reserve::get_reserved_balance(env, &merchant);
+    if total > free {
+        crate::reserve::consume_reserve(env, &merchant, total - free.max(0))?;
+    }
+
+    let token_addr = crate::admin::get_token(env)?;
+    let destination = crate::merchant_registry::payout_destination(env, &merchant)?;
+    token::Client::new(env, &token_addr).transfer(
+        &env.current_contract_address(),
+        &destination,
+        &amount,
+    );
+    crate::statements::record(env, &merchant, Movement::Payout, amount);
+    crate::statements::record(env, &merchant, Movement::Fee, fee);
+    set_merchant_balance(env, &merchant, &(current - total));
+    crate::fees::credit_protocol_fees(env, fee)?;
+    crate::offboarding::record_activity(env, &merchant);
+    crate::statements::close_period(env, &merchant);
+
+    env.events().publish(
+        (Symbol::new(env, "withdrawn_expedited"), merchant),
+        (amount, fee),
+    );
+    Ok(fee)
+}
+
+fn merchant_paused_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::Paused, merchant.clone())
+}
+
+/// Subscriptions paused by [`merchant_pause_all`] and not yet resumed by
+/// [`merchant_resume_all`].
+pub fn get_merchant_paused(env: &Env, merchant: &Address) -> Vec<u32> {
+    env.storage()
+        .instance()
+        .get(&merchant_paused_key(merchant))
+        .unwrap_or(Vec::new(env))
+}
+
+fn transition_result(subscription_id: u32, result: Result<(), Error>) -> MerchantPauseResult {
+    MerchantPauseResult {
+        subscription_id,
+        success: result.is_ok(),
+        error_code: result.err().map_or(0, |e| e.to_code()),
+    }
+}
+
+/// Merchant pauses every `Active` subscription in its index, for example while its service
+/// is down for maintenance. Returns one result per indexed subscription, in index order;
+/// subscriptions in any other status are left alone and reported with `NotActive`.
+pub fn merchant_pause_all(env: &Env, merchant: Address) -> Result<Vec<MerchantPauseResult>, Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    let ids: Vec<u32> = env
+        .storage()
+        .instance()
+        .get(&DataKey::MerchantSubs(merchant.clone()))
+        .unwrap_or(Vec::new(env));
+    let mut paused = get_merchant_paused(env, &merchant);
+    let mut results = Vec::new(env);
+    for id in ids.iter() {
+        let result = match crate::queries::get_subscription(env, id) {
+            Ok(mut sub) if sub.status == SubscriptionStatus::Active => {
+                sub.status = SubscriptionStatus::Paused;
+                env.storage().instance().set(&DataKey::Sub(id), &sub);
+                crate::health::record_pause(env, id);
+                crate::subscription::emit_paused(env, id, merchant.clone());
+                paused.push_back(id);
+                Ok(())
+            }
+            Ok(_) => Err(Error::NotActive),
+            Err(e) => Err(e),
+        };
+        results.push_back(transition_result(id, result));
+    }
+    env.storage()
+        .instance()
+        .set(&merchant_paused_key(&merchant), &paused);
+    Ok(results)
+}
+
+/// Merchant resumes the subscriptions it paused with [`merchant_pause_all`]. Ones paused
+/// by their subscriber stay paused. Returns one result per subscription resumed or no
+/// longer resumable (for example cancelled in the meantime), in the order they were paused.
+pub fn merchant_resume_all(
+    env: &Env,
+    merchant: Address,
+) -> Result<Vec<MerchantPauseResult>, Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    let mut results = Vec::new(env);
+    for id in get_merchant_paused(env, &merchant).iter() {
+        let result = crate::queries::get_subscription(env, id).and_then(|mut sub| {
+            if sub.status != SubscriptionStatus::Paused {
+                return Err(Error::InvalidStatusTransition);
+            }
+            sub.status = SubscriptionStatus::Active;
+            env.storage().instance().set(&DataKey::Sub(id), &sub);
+            crate::subscription::emit_resumed(env, id, merchant.clone());
+            Ok(())
+        });
+        results.push_back(transition_result(id, result));
+    }
+    env.storage()
+        .instance()
+        .remove(&merchant_paused_key(&merchant));
+    Ok(results)
+}
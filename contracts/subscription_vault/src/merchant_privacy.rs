@@ -0,0 +1,98 @@
+//! Hashed merchant references in subscriber-facing reads.
+//!
+//! Privacy-sensitive deployments can set a salt, after which subscriber views show each
+//! merchant as `sha256(salt || merchant XDR)` instead of its address. Subscriptions still
+//! store the real address, and settlement, merchant listings and admin reads use it as
+//! before. The same merchant always maps to the same reference, so a merchant can look up
+//! its own with `get_merchant_ref`.
+//!
+//! This keeps public reads from trivially mapping subscribers to services. It is not
+//! encryption: contract storage, including the salt, is readable by anyone who inspects
+//! the ledger directly.
+//!
+//! **PRs that only change hashed merchant references should edit this file only.**
+
+use crate::types::{
+    ConfigKey, Cursor, DataKey, Error, MerchantRef, SubscriberView, SubscriberViewPage,
+    SubscriptionSummary,
+};
+use soroban_sdk::{xdr::ToXdr, Address, Bytes, BytesN, Env, Symbol};
+
+fn salt_key() -> DataKey {
+    DataKey::Config(ConfigKey::MerchantRefSalt)
+}
+
+/// Whether subscriber views show hashed merchant references.
+pub fn is_merchant_ref_hashed(env: &Env) -> bool {
+    env.storage().instance().has(&salt_key())
+}
+
+/// Admin turns hashed merchant references on with `salt`, or off with `None`. Changing
+/// the salt changes every reference.
+pub fn do_set_merchant_ref_salt(
+    env: &Env,
+    admin: Address,
+    salt: Option<BytesN<32>>,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    match &salt {
+        Some(salt) => env.storage().instance().set(&salt_key(), salt),
+        None => env.storage().instance().remove(&salt_key()),
+    }
+    env.events()
+        .publish((Symbol::new(env, "merchant_ref_mode_set"),), salt.is_some());
+    Ok(())
+}
+
+/// How `merchant` is shown to subscribers under the current setting.
+pub fn merchant_ref(env: &Env, merchant: Address) -> MerchantRef {
+    let Some(salt) = env.storage().instance().get::<_, BytesN<32>>(&salt_key()) else {
+        return MerchantRef::Address(merchant);
+    };
+    let mut data = Bytes::from_array(env, &salt.to_array());
+    data.append(&merchant.to_xdr(env));
+    MerchantRef::Hashed(env.crypto().sha256(&data).into())
+}
+
+fn subscriber_view(env: &Env, summary: SubscriptionSummary) -> SubscriberView {
+    SubscriberView {
+        subscription_id: summary.subscription_id,
+        subscriber: summary.subscriber,
+        merchant: merchant_ref(env, summary.merchant),
+        amount: summary.amount,
+        interval_seconds: summary.interval_seconds,
+        last_payment_timestamp: summary.last_payment_timestamp,
+        status: summary.status,
+        prepaid_balance: summary.prepaid_balance,
+        usage_enabled: summary.usage_enabled,
+    }
+}
+
+/// Subscriber view of one subscription.
+pub fn get_subscriber_view(env: &Env, subscription_id: u32) -> Result<SubscriberView, Error> {
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    let summary = crate::queries::subscription_summary(subscription_id, sub);
+    Ok(subscriber_view(env, summary))
+}
+
+/// Cursor-paged subscriber views of `subscriber`'s subscriptions, with the same paging
+/// and errors as `page_subscriber_subscriptions`.
+pub fn page_subscriber_views(
+    env: &Env,
+    subscriber: Address,
+    cursor: Cursor,
+) -> Result<SubscriberViewPage, Error> {
+    let page = crate::queries::page_subscriber_subscriptions(env, subscriber, cursor)?;
+    let mut items = soroban_sdk::Vec::new(env);
+    for summary in page.items.iter() {
+        items.push_back(subscriber_view(env, summary));
+    }
+    Ok(SubscriberViewPage {
+        items,
+        next_cursor: page.next_cursor,
+        total_hint: page.total_hint,
+    })
+}
@@ -0,0 +1,92 @@
+//! Merchant approval and payout routing through the external merchant registry.
+//!
+//! The admin can point the vault at a deployed `merchant_registry` contract with
+//! [`do_set_merchant_registry`]. From then on a subscription can only be created for a
+//! merchant the registry reports as active (`is_active(merchant) -> bool`), and merchant
+//! withdrawals and scheduled payouts are sent to the registry's payout address for the
+//! merchant (`payout_address(merchant) -> Option<Address>`) instead of the address the
+//! merchant set in the vault. Suspending a merchant in the registry therefore stops both new
+//! subscriptions and payouts without touching the vault.
+//!
+//! Both checks fail closed with `MerchantNotApproved`: an unregistered or suspended
+//! merchant, or a registry that errors, blocks the operation. Charges of existing
+//! subscriptions are not affected. Without a registry merchants are paid at the payout
+//! address they set in the vault, which defaults to the merchant itself.
+//!
+//! **PRs that only change the registry integration should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::types::{ConfigKey, DataKey, Error};
+use soroban_sdk::{vec, Address, Env, IntoVal, InvokeError, Symbol};
+
+fn registry_key() -> DataKey {
+    DataKey::Config(ConfigKey::MerchantRegistry)
+}
+
+fn require_admin_auth(env: &Env, admin: &Address) -> Result<(), Error> {
+    admin.require_auth();
+    if *admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+/// The merchant registry the vault consults, if any.
+pub fn get_merchant_registry(env: &Env) -> Option<Address> {
+    env.storage().instance().get(&registry_key())
+}
+
+/// Admin points the vault at `registry`, replacing any earlier one.
+pub fn do_set_merchant_registry(env: &Env, admin: Address, registry: Address) -> Result<(), Error> {
+    require_admin_auth(env, &admin)?;
+    env.storage().instance().set(&registry_key(), &registry);
+    env.events()
+        .publish((Symbol::new(env, "merchant_registry_set"),), registry);
+    Ok(())
+}
+
+/// Admin stops consulting a merchant registry.
+pub fn do_remove_merchant_registry(env: &Env, admin: Address) -> Result<(), Error> {
+    require_admin_auth(env, &admin)?;
+    env.storage().instance().remove(&registry_key());
+    env.events()
+        .publish((Symbol::new(env, "merchant_registry_removed"),), admin);
+    Ok(())
+}
+
+/// Fails with `MerchantNotApproved` unless the registry, if any, reports `merchant` as
+/// active. Checked before a subscription is created.
+pub fn ensure_merchant_approved(env: &Env, merchant: &Address) -> Result<(), Error> {
+    let Some(registry) = get_merchant_registry(env) else {
+        return Ok(());
+    };
+    let active = env.try_invoke_contract::<bool, InvokeError>(
+        &registry,
+        &Symbol::new(env, "is_active"),
+        vec![env, merchant.into_val(env)],
+    );
+    match active {
+        Ok(Ok(true)) => Ok(()),
+        _ => Err(Error::MerchantNotApproved),
+    }
+}
+
+/// Address `merchant`'s funds are paid out to: the registry's payout address, or without
+/// a registry the payout address the merchant set in the vault (see `payout_address.rs`).
+/// Fails with `MerchantNotApproved` if the registry has no active entry for the merchant.
+pub fn payout_destination(env: &Env, merchant: &Address) -> Result<Address, Error> {
+    let Some(registry) = get_merchant_registry(env) else {
+        return Ok(crate::payout_address::get_merchant_payout_address(
+            env, merchant,
+        ));
+    };
+    let payout = env.try_invoke_contract::<Option<Address>, InvokeError>(
+        &registry,
+        &Symbol::new(env, "payout_address"),
+        vec![env, merchant.into_val(env)],
+    );
+    match payout {
+        Ok(Ok(Some(address))) => Ok(address),
+        _ => Err(Error::MerchantNotApproved),
+    }
+}
@@ -0,0 +1,149 @@
+//! Minimum charge amount for interval charges.
+//!
+//! Loyalty discounts can bring an interval charge down to amounts that cost more to
+//! settle than they are worth. The admin sets a global `min_charge_amount` in token base
+//! units (0, the default, turns it off), separate from `min_topup`. An interval charge
+//! whose discounted amount falls below it is handled by the merchant's
+//! [`MinChargePolicy`]: either the amount is carried into the next period and the
+//! current one is booked without a debit, or it is rounded up to the minimum.
+//!
+//! Setup fee installments are not part of the comparison; they are added on top of the
+//! adjusted amount as before.
+//!
+//! **PRs that only change the minimum charge amount should edit this file only.**
+
+use crate::types::{ConfigKey, DataKey, Error, MerchantKey, MinChargePolicy, SubKey, Subscription};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn policy_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::MinChargePolicy, merchant.clone())
+}
+
+fn carry_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::MinChargeCarry, subscription_id)
+}
+
+/// Global minimum interval charge amount; 0 when unset.
+pub fn get_min_charge_amount(env: &Env) -> i128 {
+    env.storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::MinChargeAmount))
+        .unwrap_or(0)
+}
+
+/// Admin sets the minimum interval charge amount in token base units; 0 turns it off.
+pub fn do_set_min_charge_amount(env: &Env, admin: Address, amount: i128) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    if amount < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    env.storage()
+        .instance()
+        .set(&DataKey::Config(ConfigKey::MinChargeAmount), &amount);
+    env.events()
+        .publish((Symbol::new(env, "min_charge_updated"),), amount);
+    Ok(())
+}
+
+/// How charges of the merchant's subscriptions below the minimum are handled.
+pub fn get_min_charge_policy(env: &Env, merchant: &Address) -> MinChargePolicy {
+    env.storage()
+        .instance()
+        .get(&policy_key(merchant))
+        .unwrap_or(MinChargePolicy::RollOver)
+}
+
+/// Merchant chooses whether charges below the minimum roll over or are rounded up.
+pub fn do_set_min_charge_policy(
+    env: &Env,
+    merchant: Address,
+    policy: MinChargePolicy,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    env.storage()
+        .instance()
+        .set(&policy_key(&merchant), &policy);
+    env.events().publish(
+        (Symbol::new(env, "min_charge_policy_set"), merchant),
+        policy,
+    );
+    Ok(())
+}
+
+/// Amount carried over from earlier periods that were below the minimum.
+pub fn get_carried_amount(env: &Env, subscription_id: u32) -> i128 {
+    env.storage()
+        .instance()
+        .get(&carry_key(subscription_id))
+        .unwrap_or(0)
+}
+
+/// Converts the carried amount into another billing token's units when the subscription
+/// is migrated (see `token_migration.rs`).
+pub fn convert_carry(
+    env: &Env,
+    subscription_id: u32,
+    convert: impl Fn(i128) -> Result<i128, Error>,
+) -> Result<(), Error> {
+    let carried = get_carried_amount(env, subscription_id);
+    if carried != 0 {
+        env.storage()
+            .instance()
+            .set(&carry_key(subscription_id), &convert(carried)?);
+    }
+    Ok(())
+}
+
+/// `base` plus any carried amount, and whether that total is below the minimum.
+fn with_carry(env: &Env, subscription_id: u32, base: i128) -> Result<(i128, bool), Error> {
+    let total = base
+        .checked_add(get_carried_amount(env, subscription_id))
+        .ok_or(Error::Overflow)?;
+    let min = get_min_charge_amount(env);
+    Ok((total, total > 0 && total < min))
+}
+
+/// Interval amount to charge for a discounted `base`: the carried amount is added, and a
+/// total below the minimum is rounded up under [`MinChargePolicy::RoundUp`].
+pub fn charge_amount(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    base: i128,
+) -> Result<i128, Error> {
+    let (total, below_min) = with_carry(env, subscription_id, base)?;
+    if below_min && get_min_charge_policy(env, &sub.merchant) == MinChargePolicy::RoundUp {
+        return Ok(get_min_charge_amount(env));
+    }
+    Ok(total)
+}
+
+/// Under [`MinChargePolicy::RollOver`], carries a total below the minimum into the next
+/// period and returns `true`; the caller books the period without a debit.
+pub fn roll_over_if_below_min(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    base: i128,
+) -> Result<bool, Error> {
+    let (total, below_min) = with_carry(env, subscription_id, base)?;
+    if !below_min || get_min_charge_policy(env, &sub.merchant) != MinChargePolicy::RollOver {
+        return Ok(false);
+    }
+    env.storage()
+        .instance()
+        .set(&carry_key(subscription_id), &total);
+    env.events().publish(
+        (Symbol::new(env, "charge_rolled_over"), subscription_id),
+        (total, get_min_charge_amount(env)),
+    );
+    Ok(true)
+}
+
+/// Clears the carried amount once a charge has collected it.
+pub fn clear_carry(env: &Env, subscription_id: u32) {
+    env.storage().instance().remove(&carry_key(subscription_id));
+}
@@ -0,0 +1,371 @@
+//! Multi-token prepaid balances: secondary accepted tokens held per subscription.
+//!
+//! The configured vault token (`"token"`) remains the primary balance
+//! (`Subscription::prepaid_balance`) and is always drained first. When it cannot cover
+//! an interval charge, the shortfall is taken from the subscriber's secondary token
+//! balances in their priority order, converted with an admin-maintained rate. Merchants
+//! are credited in the token that was drawn and withdraw each token separately.
+//!
+//! **PRs that only change multi-token balances should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::queries::get_subscription;
+use crate::safe_math::{safe_add_balance, safe_sub_balance};
+use crate::types::{DataKey, Error, MerchantKey, SubscriptionStatus};
+use soroban_sdk::{token, Address, Env, Symbol, Vec};
+
+/// Fixed-point scale of token rates: a rate of `RATE_SCALE` means one unit of the
+/// secondary token is worth one unit of the vault token.
+pub const RATE_SCALE: i128 = 10_000_000;
+
+/// Conversion rate of an accepted secondary token (vault-token units per
+/// `RATE_SCALE` token units), or `None` if the token is not accepted.
+pub fn get_token_rate(env: &Env, token: &Address) -> Option<i128> {
+    env.storage()
+        .instance()
+        .get(&DataKey::TokenRate(token.clone()))
+}
+
+/// Admin sets the conversion rate for a secondary token. A rate of 0 stops accepting it;
+/// existing balances stay withdrawable but are no longer drawn at charge time.
+pub fn do_set_token_rate(
+    env: &Env,
+    admin: Address,
+    token: Address,
+    rate: i128,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    if rate < 0 || token == crate::admin::get_token(env)? {
+        return Err(Error::InvalidInput);
+    }
+
+    let key = DataKey::TokenRate(token.clone());
+    if rate == 0 {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &rate);
+    }
+
+    env.events()
+        .publish((Symbol::new(env, "token_rate_set"), token), rate);
+    Ok(())
+}
+
+pub fn get_token_balance(env: &Env, subscription_id: u32, token: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&DataKey::SubTokenBalance(subscription_id, token.clone()))
+        .unwrap_or(0i128)
+}
+
+fn set_token_balance(env: &Env, subscription_id: u32, token: &Address, balance: i128) {
+    env.storage().instance().set(
+        &DataKey::SubTokenBalance(subscription_id, token.clone()),
+        &balance,
+    );
+}
+
+/// Secondary tokens in the order they are drained once the primary balance runs out.
+pub fn get_token_priority(env: &Env, subscription_id: u32) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get(&DataKey::TokenPriority(subscription_id))
+        .unwrap_or(Vec::new(env))
+}
+
+/// True if any secondary token balance is still held for the subscription.
+pub fn has_token_balances(env: &Env, subscription_id: u32) -> bool {
+    get_token_priority(env, subscription_id)
+        .iter()
+        .any(|t| get_token_balance(env, subscription_id, &t) > 0)
+}
+
+/// Subscriber deposits an accepted secondary token. New tokens are appended to the
+/// end of the subscription's priority list.
+pub fn do_deposit_token(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    token: Address,
+    amount: i128,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+
+    let sub = get_subscription(env, subscription_id)?;
+    if subscriber != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if get_token_rate(env, &token).is_none() {
+        return Err(Error::TokenNotAccepted);
+    }
+
+    let balance = safe_add_balance(get_token_balance(env, subscription_id, &token), amount)?;
+    token::Client::new(env, &token).transfer(&subscriber, &env.current_contract_address(), &amount);
+    set_token_balance(env, subscription_id, &token, balance);
+
+    let mut priority = get_token_priority(env, subscription_id);
+    if !priority.contains(&token) {
+        priority.push_back(token.clone());
+        env.storage()
+            .instance()
+            .set(&DataKey::TokenPriority(subscription_id), &priority);
+    }
+
+    env.events().publish(
+        (Symbol::new(env, "token_deposited"), subscription_id),
+        (token, amount, balance),
+    );
+    Ok(())
+}
+
+/// Subscriber reorders the secondary tokens drained at charge time.
+///
+/// Every token must be accepted and listed once; tokens still holding a balance
+/// cannot be dropped from the list.
+pub fn do_set_token_priority(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    tokens: Vec<Address>,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+
+    let sub = get_subscription(env, subscription_id)?;
+    if subscriber != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+
+    let mut seen: Vec<Address> = Vec::new(env);
+    for token in tokens.iter() {
+        if seen.contains(&token) {
+            return Err(Error::InvalidInput);
+        }
+        if get_token_rate(env, &token).is_none() {
+            return Err(Error::TokenNotAccepted);
+        }
+        seen.push_back(token);
+    }
+    for held in get_token_priority(env, subscription_id).iter() {
+        if !tokens.contains(&held) && get_token_balance(env, subscription_id, &held) > 0 {
+            return Err(Error::InvalidInput);
+        }
+    }
+
+    env.storage()
+        .instance()
+        .set(&DataKey::TokenPriority(subscription_id), &tokens);
+    Ok(())
+}
+
+/// Plans covering `shortfall` (in vault-token units) from secondary balances in priority
+/// order, without changing anything.
+///
+/// Returns the `(token, amount)` draws that cover the whole shortfall, or `None` if the
+/// balances cannot. Tokens whose rate has been removed are skipped. The caller applies
+/// the draws with [`apply_draws`] once the charge has settled.
+pub fn plan_shortfall(
+    env: &Env,
+    subscription_id: u32,
+    shortfall: i128,
+) -> Result<Option<Vec<(Address, i128)>>, Error> {
+    let mut remaining = shortfall;
+    let mut draws: Vec<(Address, i128)> = Vec::new(env);
+
+    for token in get_token_priority(env, subscription_id).iter() {
+        if remaining == 0 {
+            break;
+        }
+        let balance = get_token_balance(env, subscription_id, &token);
+        let rate = match get_token_rate(env, &token) {
+            Some(rate) if balance > 0 => rate,
+            _ => continue,
+        };
+
+        let value = balance.checked_mul(rate).ok_or(Error::Overflow)? / RATE_SCALE;
+        if value >= remaining {
+            // Round the token amount up so the merchant is never under-paid.
+            let needed = remaining
+                .checked_mul(RATE_SCALE)
+                .ok_or(Error::Overflow)?
+                .checked_add(rate - 1)
+                .ok_or(Error::Overflow)?
+                / rate;
+            draws.push_back((token, needed.min(balance)));
+            remaining = 0;
+        } else {
+            draws.push_back((token, balance));
+            remaining -= value;
+        }
+    }
+
+    if remaining > 0 {
+        return Ok(None);
+    }
+    Ok(Some(draws))
+}
+
+/// Applies draws planned by [`plan_shortfall`]: debits the subscriber's token balances
+/// and credits the merchant per token.
+pub fn apply_draws(
+    env: &Env,
+    subscription_id: u32,
+    merchant: &Address,
+    draws: &Vec<(Address, i128)>,
+) -> Result<(), Error> {
+    for (token, amount) in draws.iter() {
+        let balance = safe_sub_balance(get_token_balance(env, subscription_id, &token), amount)?;
+        set_token_balance(env, subscription_id, &token, balance);
+        credit_merchant_token_balance(env, merchant, &token, amount)?;
+
+        env.events().publish(
+            (Symbol::new(env, "token_charged"), subscription_id),
+            (token, amount),
+        );
+    }
+    Ok(())
+}
+
+/// Subscriber withdraws a secondary token balance after cancellation.
+pub fn do_withdraw_token_balance(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    token: Address,
+) -> Result<i128, Error> {
+    subscriber.require_auth();
+
+    let sub = get_subscription(env, subscription_id)?;
+    if subscriber != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+    if sub.status != SubscriptionStatus::Cancelled {
+        return Err(Error::InvalidStatusTransition);
+    }
+
+    let amount = get_token_balance(env, subscription_id, &token);
+    if amount > 0 {
+        set_token_balance(env, subscription_id, &token, 0);
+        token::Client::new(env, &token).transfer(
+            &env.current_contract_address(),
+            &subscriber,
+            &amount,
+        );
+    }
+    Ok(amount)
+}
+
+/// Returns every secondary token balance of the subscription to `recipient`.
+pub fn refund_token_balances(env: &Env, subscription_id: u32, recipient: &Address) {
+    for token in get_token_priority(env, subscription_id).iter() {
+        let amount = get_token_balance(env, subscription_id, &token);
+        if amount > 0 {
+            set_token_balance(env, subscription_id, &token, 0);
+            token::Client::new(env, &token).transfer(
+                &env.current_contract_address(),
+                recipient,
+                &amount,
+            );
+        }
+    }
+}
+
+pub fn get_merchant_token_balance(env: &Env, merchant: &Address, token: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&DataKey::MerchantTokenBalance(
+            merchant.clone(),
+            token.clone(),
+        ))
+        .unwrap_or(0i128)
+}
+
+fn merchant_tokens_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::SecondaryTokens, merchant.clone())
+}
+
+/// Secondary tokens the merchant has ever been credited in, in first-credit order.
+pub fn get_merchant_tokens(env: &Env, merchant: &Address) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get(&merchant_tokens_key(merchant))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Adds `amount` of `token` to the merchant's balance in that token.
+pub fn credit_merchant_token_balance(
+    env: &Env,
+    merchant: &Address,
+    token: &Address,
+    amount: i128,
+) -> Result<(), Error> {
+    let mut tokens = get_merchant_tokens(env, merchant);
+    if !tokens.contains(token) {
+        tokens.push_back(token.clone());
+        env.storage()
+            .instance()
+            .set(&merchant_tokens_key(merchant), &tokens);
+    }
+    let balance = safe_add_balance(get_merchant_token_balance(env, merchant, token), amount)?;
+    env.storage().instance().set(
+        &DataKey::MerchantTokenBalance(merchant.clone(), token.clone()),
+        &balance,
+    );
+    Ok(())
+}
+
+/// Takes `amount` off the merchant's balance in `token`; `InsufficientMerchantBalance` if
+/// it holds less.
+pub fn debit_merchant_token_balance(
+    env: &Env,
+    merchant: &Address,
+    token: &Address,
+    amount: i128,
+) -> Result<(), Error> {
+    let balance = get_merchant_token_balance(env, merchant, token);
+    if amount > balance {
+        return Err(Error::InsufficientMerchantBalance);
+    }
+    env.storage().instance().set(
+        &DataKey::MerchantTokenBalance(merchant.clone(), token.clone()),
+        &(balance - amount),
+    );
+    Ok(())
+}
+
+/// Merchant withdraws everything accrued in a secondary token or a billing token.
+pub fn do_withdraw_merchant_token_funds(
+    env: &Env,
+    merchant: Address,
+    token: Address,
+) -> Result<i128, Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+
+    let amount = get_merchant_token_balance(env, &merchant, &token);
+    if amount == 0 {
+        return Err(Error::NotFound);
+    }
+
+    let destination = crate::merchant_registry::payout_destination(env, &merchant)?;
+
+    env.storage().instance().set(
+        &DataKey::MerchantTokenBalance(merchant.clone(), token.clone()),
+        &0i128,
+    );
+    token::Client::new(env, &token).transfer(
+        &env.current_contract_address(),
+        &destination,
+        &amount,
+    );
+
+    env.events().publish(
+        (Symbol::new(env, "token_withdrawn"), merchant),
+        (token, amount),
+    );
+    Ok(amount)
+}
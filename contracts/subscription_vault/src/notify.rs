@@ -0,0 +1,215 @@
+//! Subscriber-registered charge notifications.
+//!
+//! A subscriber can register a contract (for example a budgeting dapp) that is called
+//! with `on_charge(subscription_id, amount)` after every successful charge. The call
+//! is made with `try_invoke_contract`, so an error or panic in the callback is rolled
+//! back on its own and reported with a `callback_failed` event; the charge still goes
+//! through. Soroban rejects re-entry into the vault, so the callback cannot act on the
+//! charge while it is being applied.
+//!
+//! A merchant can likewise register a hook contract for all of its subscriptions, called
+//! with `on_subscription_charged(subscription_id, amount, timestamp)` so it can mint
+//! access NFTs or update entitlement state in the same transaction as the charge. A
+//! failing hook is reported with a `charge_hook_failed` event carrying what it was
+//! called with, so the merchant can replay it.
+//!
+//! Before an interval charge, a registered hook is also asked for
+//! `service_status(subscription_id, period_index)`. If it answers `unavailable`, the
+//! period is skipped instead of billed, as the service was down for it; a hook that does
+//! not implement the call, or fails it, leaves billing as it is.
+//!
+//! Isolation covers failures, not cost: the callbacks run on the charging
+//! transaction's resource budget, and a callback that exhausts it fails the whole
+//! transaction, including every other charge of a batch. Only contracts the admin has
+//! approved with [`do_set_callback_approved`] can therefore be registered as callbacks
+//! or hooks, and one whose approval is revoked is no longer called.
+//!
+//! **PRs that only change charge notifications should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::types::{DataKey, Error, MerchantKey, RecordKey, SubKey};
+use soroban_sdk::{vec, Address, Env, IntoVal, InvokeError, Symbol, Val};
+
+fn callback_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::ChargeCallback, subscription_id)
+}
+
+fn approved_key(contract: &Address) -> DataKey {
+    DataKey::Record(RecordKey::ApprovedCallback(contract.clone()))
+}
+
+/// Whether the admin approved `contract` to be called during charges.
+pub fn is_callback_approved(env: &Env, contract: &Address) -> bool {
+    env.storage().instance().has(&approved_key(contract))
+}
+
+/// Admin approves `contract` for charge notifications, or revokes the approval. Only
+/// approved contracts run on the charging transaction's budget.
+pub fn do_set_callback_approved(
+    env: &Env,
+    admin: Address,
+    contract: Address,
+    approved: bool,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    let key = approved_key(&contract);
+    if approved {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events()
+        .publish((Symbol::new(env, "callback_approved"), contract), approved);
+    Ok(())
+}
+
+fn hook_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::ChargeHook, merchant.clone())
+}
+
+/// Contract notified after each charge of the subscription, if any.
+pub fn get_charge_callback(env: &Env, subscription_id: u32) -> Option<Address> {
+    env.storage().instance().get(&callback_key(subscription_id))
+}
+
+/// Subscriber registers `callback` for charge notifications, replacing any earlier one,
+/// or removes it with `None`. The callback must be approved by the admin (`Forbidden`).
+pub fn do_set_charge_callback(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    callback: Option<Address>,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Forbidden);
+    }
+    if let Some(callback) = &callback {
+        if !is_callback_approved(env, callback) {
+            return Err(Error::Forbidden);
+        }
+    }
+    let key = callback_key(subscription_id);
+    match &callback {
+        Some(callback) => env.storage().instance().set(&key, callback),
+        None => env.storage().instance().remove(&key),
+    }
+    env.events().publish(
+        (Symbol::new(env, "charge_callback_set"), subscription_id),
+        callback,
+    );
+    Ok(())
+}
+
+/// Contract notified after each charge of any of the merchant's subscriptions, if any.
+pub fn get_charge_hook(env: &Env, merchant: &Address) -> Option<Address> {
+    env.storage().instance().get(&hook_key(merchant))
+}
+
+/// Merchant registers `hook` for charges of all its subscriptions, replacing any earlier
+/// one, or removes it with `None`. The hook must be approved by the admin (`Forbidden`).
+pub fn do_set_charge_hook(
+    env: &Env,
+    merchant: Address,
+    hook: Option<Address>,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    if let Some(hook) = &hook {
+        if !is_callback_approved(env, hook) {
+            return Err(Error::Forbidden);
+        }
+    }
+    let key = hook_key(&merchant);
+    match &hook {
+        Some(hook) => env.storage().instance().set(&key, hook),
+        None => env.storage().instance().remove(&key),
+    }
+    env.events()
+        .publish((Symbol::new(env, "charge_hook_set"), merchant), hook);
+    Ok(())
+}
+
+/// Whether the merchant's hook reports its service as unavailable for the interval charge
+/// of `period_index`. Only an approved hook answering `unavailable` counts; if it does,
+/// emits `charge_skipped_outage` and the caller books the period without a debit.
+pub fn service_unavailable(
+    env: &Env,
+    subscription_id: u32,
+    merchant: &Address,
+    period_index: u64,
+) -> bool {
+    let Some(hook) = get_charge_hook(env, merchant) else {
+        return false;
+    };
+    if !is_callback_approved(env, &hook) {
+        return false;
+    }
+    let args = vec![
+        env,
+        subscription_id.into_val(env),
+        period_index.into_val(env),
+    ];
+    let status = env.try_invoke_contract::<Symbol, InvokeError>(
+        &hook,
+        &Symbol::new(env, "service_status"),
+        args,
+    );
+    if !matches!(status, Ok(Ok(status)) if status == Symbol::new(env, "unavailable")) {
+        return false;
+    }
+    env.events().publish(
+        (Symbol::new(env, "charge_skipped_outage"), subscription_id),
+        (hook, period_index),
+    );
+    true
+}
+
+/// Pings the subscription's callback contract and the merchant's hook contract, if
+/// any, with the charged `amount`. Never fails: a failing or no longer approved callback
+/// only emits `callback_failed`, such a hook `charge_hook_failed`.
+pub fn notify_charge(env: &Env, subscription_id: u32, merchant: &Address, amount: i128) {
+    if let Some(callback) = get_charge_callback(env, subscription_id) {
+        let succeeded = is_callback_approved(env, &callback) && {
+            let args = vec![env, subscription_id.into_val(env), amount.into_val(env)];
+            env.try_invoke_contract::<Val, InvokeError>(
+                &callback,
+                &Symbol::new(env, "on_charge"),
+                args,
+            )
+            .is_ok()
+        };
+        if !succeeded {
+            env.events().publish(
+                (Symbol::new(env, "callback_failed"), subscription_id),
+                callback,
+            );
+        }
+    }
+    if let Some(hook) = get_charge_hook(env, merchant) {
+        let timestamp = env.ledger().timestamp();
+        let succeeded = is_callback_approved(env, &hook) && {
+            let args = vec![
+                env,
+                subscription_id.into_val(env),
+                amount.into_val(env),
+                timestamp.into_val(env),
+            ];
+            env.try_invoke_contract::<Val, InvokeError>(
+                &hook,
+                &Symbol::new(env, "on_subscription_charged"),
+                args,
+            )
+            .is_ok()
+        };
+        if !succeeded {
+            env.events().publish(
+                (Symbol::new(env, "charge_hook_failed"), subscription_id),
+                (hook, amount, timestamp),
+            );
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Merchant offboarding: let subscribers reclaim prepaid funds from inactive merchants.
+//!
+//! A merchant is inactive once the admin declares it, or automatically after
+//! `MERCHANT_INACTIVITY_TIMEOUT_SECONDS` without charges, withdrawals or new
+//! subscriptions. Subscribers of an inactive merchant can cancel and reclaim their
+//! whole prepaid escrow (including secondary-token balances) in one call. A declared
+//! merchant can no longer be charged for or gain new subscriptions.
+//!
+//! **PRs that only change merchant offboarding should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::queries::get_subscription;
+use crate::state_machine::validate_status_transition;
+use crate::types::{DataKey, Error, SubscriptionStatus};
+use soroban_sdk::{token, Address, Env, Symbol};
+
+/// Inactivity after which a merchant is treated as offboarded (180 days).
+pub const MERCHANT_INACTIVITY_TIMEOUT_SECONDS: u64 = 180 * 24 * 60 * 60;
+
+/// Records merchant activity (charge credited, withdrawal, new subscription).
+pub fn record_activity(env: &Env, merchant: &Address) {
+    env.storage().instance().set(
+        &DataKey::MerchantLastActivity(merchant.clone()),
+        &env.ledger().timestamp(),
+    );
+}
+
+pub fn get_last_activity(env: &Env, merchant: &Address) -> Option<u64> {
+    env.storage()
+        .instance()
+        .get(&DataKey::MerchantLastActivity(merchant.clone()))
+}
+
+fn is_declared_inactive(env: &Env, merchant: &Address) -> bool {
+    env.storage()
+        .instance()
+        .has(&DataKey::MerchantInactive(merchant.clone()))
+}
+
+/// True if the merchant was declared inactive or has been idle past the timeout.
+pub fn is_merchant_inactive(env: &Env, merchant: &Address) -> bool {
+    if is_declared_inactive(env, merchant) {
+        return true;
+    }
+    match get_last_activity(env, merchant) {
+        Some(last) => {
+            env.ledger().timestamp() >= last.saturating_add(MERCHANT_INACTIVITY_TIMEOUT_SECONDS)
+        }
+        None => false,
+    }
+}
+
+/// Charges for, and new subscriptions with, a declared-inactive merchant are rejected.
+pub fn ensure_merchant_active(env: &Env, merchant: &Address) -> Result<(), Error> {
+    if is_declared_inactive(env, merchant) {
+        return Err(Error::MerchantInactive);
+    }
+    Ok(())
+}
+
+/// Admin declares a merchant inactive, unlocking subscriber reclaims and stopping charges.
+pub fn do_declare_merchant_inactive(
+    env: &Env,
+    admin: Address,
+    merchant: Address,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+
+    env.storage().instance().set(
+        &DataKey::MerchantInactive(merchant.clone()),
+        &env.ledger().timestamp(),
+    );
+    env.events()
+        .publish((Symbol::new(env, "merchant_inactive"), merchant), ());
+    Ok(())
+}
+
+/// Subscriber cancels a subscription of an inactive merchant and takes back its prepaid
+/// and secondary-token balances. Returns the vault-token amount refunded.
+pub fn do_reclaim_from_inactive_merchant(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+) -> Result<i128, Error> {
+    subscriber.require_auth();
+
+    let mut sub = get_subscription(env, subscription_id)?;
+    if subscriber != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+    if !is_merchant_inactive(env, &sub.merchant) {
+        return Err(Error::MerchantActive);
+    }
+
+    if sub.status != SubscriptionStatus::Cancelled {
+        validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled)?;
+        sub.status = SubscriptionStatus::Cancelled;
+    }
+    let refund = sub.prepaid_balance;
+    sub.prepaid_balance = 0;
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
+
+    if refund > 0 {
+        let token_addr = crate::billing_tokens::subscription_token(env, subscription_id)?;
+        token::Client::new(env, &token_addr).transfer(
+            &env.current_contract_address(),
+            &subscriber,
+            &refund,
+        );
+    }
+    crate::multi_token::refund_token_balances(env, subscription_id, &subscriber);
+
+    env.events().publish(
+        (Symbol::new(env, "escrow_reclaimed"), subscription_id),
+        (sub.merchant, refund),
+    );
+    Ok(refund)
+}
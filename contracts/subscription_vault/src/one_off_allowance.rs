@@ -0,0 +1,109 @@
+//! Subscriber pre-approval of merchant-initiated one-off charges.
+//!
+//! `charge_one_off` lets a merchant debit the prepaid balance outside the billing
+//! interval, so the subscriber caps it: [`do_set_one_off_allowance`] sets how much the
+//! merchant may charge in one-off charges per month ([`ONE_OFF_MONTH_SECONDS`] windows
+//! of ledger time). Without an allowance no one-off charge goes through.
+//!
+//! Each one-off charge carries a merchant-chosen memo hash (for example the hash of the
+//! invoice line it bills). A memo hash can only be charged once per subscription, which
+//! protects one-off charges against replay independently of the per-period replay
+//! protection of interval charges.
+//!
+//! Charges executed from subscriber-approved charges (`approved_charges.rs`) are already
+//! consented to one by one and do not count against the allowance.
+//!
+//! **PRs that only change one-off allowances should edit this file only.**
+
+use crate::types::{DataKey, Error, OneOffAllowance, RecordKey, SubKey};
+use soroban_sdk::{Address, BytesN, Env, Symbol};
+
+/// Length of the month the one-off allowance applies to.
+pub const ONE_OFF_MONTH_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+fn allowance_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::OneOffAllowance, subscription_id)
+}
+
+fn memo_key(subscription_id: u32, memo_hash: &BytesN<32>) -> DataKey {
+    DataKey::Record(RecordKey::OneOffMemo(subscription_id, memo_hash.clone()))
+}
+
+/// The subscription's one-off allowance, if the subscriber set one.
+pub fn get_one_off_allowance(env: &Env, subscription_id: u32) -> Option<OneOffAllowance> {
+    env.storage()
+        .instance()
+        .get(&allowance_key(subscription_id))
+}
+
+/// Subscriber sets the most the merchant may charge in one-off charges per month, or
+/// withdraws the approval with 0. What was already charged this month still counts.
+pub fn do_set_one_off_allowance(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    monthly_limit: i128,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Forbidden);
+    }
+    if monthly_limit < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let allowance = match get_one_off_allowance(env, subscription_id) {
+        Some(existing) => OneOffAllowance {
+            monthly_limit,
+            ..existing
+        },
+        None => OneOffAllowance {
+            monthly_limit,
+            month_index: env.ledger().timestamp() / ONE_OFF_MONTH_SECONDS,
+            spent_in_month: 0,
+        },
+    };
+    env.storage()
+        .instance()
+        .set(&allowance_key(subscription_id), &allowance);
+    env.events().publish(
+        (Symbol::new(env, "oneoff_allowance_set"), subscription_id),
+        monthly_limit,
+    );
+    Ok(())
+}
+
+/// Books a one-off charge of `amount` under `memo_hash` against the allowance. Fails
+/// with `Replay` if the memo hash was already charged and with `SpendingCapExceeded`
+/// if the month's allowance does not cover `amount`.
+pub fn consume(
+    env: &Env,
+    subscription_id: u32,
+    memo_hash: &BytesN<32>,
+    amount: i128,
+) -> Result<(), Error> {
+    let memo = memo_key(subscription_id, memo_hash);
+    if env.storage().instance().has(&memo) {
+        return Err(Error::Replay);
+    }
+    let mut allowance =
+        get_one_off_allowance(env, subscription_id).ok_or(Error::SpendingCapExceeded)?;
+    let month_index = env.ledger().timestamp() / ONE_OFF_MONTH_SECONDS;
+    if allowance.month_index != month_index {
+        allowance.month_index = month_index;
+        allowance.spent_in_month = 0;
+    }
+    let spent = allowance
+        .spent_in_month
+        .checked_add(amount)
+        .ok_or(Error::Overflow)?;
+    if spent > allowance.monthly_limit {
+        return Err(Error::SpendingCapExceeded);
+    }
+    allowance.spent_in_month = spent;
+    env.storage()
+        .instance()
+        .set(&allowance_key(subscription_id), &allowance);
+    env.storage().instance().set(&memo, &amount);
+    Ok(())
+}
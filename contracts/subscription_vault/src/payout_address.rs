@@ -0,0 +1,44 @@
+//! Merchant payout address separate from the merchant identity.
+//!
+//! Charges always accrue to the merchant address that owns the subscriptions, but a
+//! merchant can have its withdrawals land elsewhere, for example at a treasury multisig,
+//! with [`do_set_merchant_payout_address`]. Withdrawals and scheduled payouts are sent
+//! there; see `merchant_registry::payout_destination`, where a configured merchant
+//! registry takes precedence.
+//!
+//! **PRs that only change merchant payout addresses should edit this file only.**
+
+use crate::types::{DataKey, Error, MerchantKey};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn payout_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::PayoutAddress, merchant.clone())
+}
+
+/// Where the merchant's withdrawals go when no merchant registry is configured: the
+/// address it set, or the merchant itself.
+pub fn get_merchant_payout_address(env: &Env, merchant: &Address) -> Address {
+    env.storage()
+        .instance()
+        .get(&payout_key(merchant))
+        .unwrap_or_else(|| merchant.clone())
+}
+
+/// Merchant sends future withdrawals to `payout`; setting its own address restores the
+/// default.
+pub fn do_set_merchant_payout_address(
+    env: &Env,
+    merchant: Address,
+    payout: Address,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    let key = payout_key(&merchant);
+    if payout == merchant {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &payout);
+    }
+    env.events()
+        .publish((Symbol::new(env, "payout_address_set"), merchant), payout);
+    Ok(())
+}
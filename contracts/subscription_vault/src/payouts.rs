@@ -0,0 +1,204 @@
+//! Scheduled merchant payouts, run for many merchants in one batch.
+//!
+//! A merchant opts in with a payout interval and a minimum amount. A billing operator then
+//! runs [`do_batch_payout`] (for example once a day) over candidate merchants: every
+//! merchant whose payout is due is sent its whole available balance, the same funds
+//! `withdraw_merchant_funds` would allow. Failures are reported per merchant and do not
+//! affect the rest of the batch.
+//!
+//! A merchant can instead have its balance paid out as soon as it reaches a threshold:
+//! with [`do_set_auto_payout`] on, the charge that brings the available balance to the
+//! threshold transfers all of it to the payout address in the same invocation
+//! ([`pay_out_if_above_threshold`]). A failed transfer leaves the balance for a later
+//! withdrawal and never fails the charge.
+//!
+//! **PRs that only change scheduled payouts should edit this file only.**
+
+use crate::statements::Movement;
+use crate::types::{
+    BatchPayoutResult, DataKey, Error, MerchantKey, MerchantWithdrawalEvent, PayoutSchedule,
+};
+use soroban_sdk::{token, Address, Env, Symbol, Vec};
+
+fn schedule_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::PayoutSchedule, merchant.clone())
+}
+
+fn auto_payout_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::AutoPayout, merchant.clone())
+}
+
+/// Payout schedule of the merchant, or `None` if it has not opted in.
+pub fn get_payout_schedule(env: &Env, merchant: &Address) -> Option<PayoutSchedule> {
+    env.storage().instance().get(&schedule_key(merchant))
+}
+
+/// Merchant opts in to scheduled payouts every `interval_seconds` once at least
+/// `min_amount` is available. An interval of 0 opts out. The first payout is due
+/// straight away.
+pub fn do_set_payout_schedule(
+    env: &Env,
+    merchant: Address,
+    interval_seconds: u64,
+    min_amount: i128,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    let key = schedule_key(&merchant);
+    if interval_seconds == 0 {
+        env.storage().instance().remove(&key);
+    } else {
+        if min_amount < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let last_payout = get_payout_schedule(env, &merchant).map_or(0, |s| s.last_payout);
+        let schedule = PayoutSchedule {
+            interval_seconds,
+            min_amount,
+            last_payout,
+        };
+        env.storage().instance().set(&key, &schedule);
+    }
+    env.events().publish(
+        (Symbol::new(env, "payout_schedule_set"), merchant),
+        (interval_seconds, min_amount),
+    );
+    Ok(())
+}
+
+/// Balance threshold that triggers an automatic payout, or `None` if the merchant has
+/// not enabled it.
+pub fn get_auto_payout(env: &Env, merchant: &Address) -> Option<i128> {
+    env.storage().instance().get(&auto_payout_key(merchant))
+}
+
+/// Merchant enables or disables automatic payouts once `threshold` is available. The
+/// threshold must be positive when enabling.
+pub fn do_set_auto_payout(
+    env: &Env,
+    merchant: Address,
+    threshold: i128,
+    enabled: bool,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    let key = auto_payout_key(&merchant);
+    if enabled {
+        if threshold <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        env.storage().instance().set(&key, &threshold);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events().publish(
+        (Symbol::new(env, "auto_payout_set"), merchant),
+        (threshold, enabled),
+    );
+    Ok(())
+}
+
+/// Called after a charge credits `merchant`: transfers its whole available balance to
+/// its payout address if automatic payouts are on and the balance reached the
+/// threshold. A payout the registry or the token refuses emits `auto_payout_failed` and
+/// leaves the balance in place rather than failing the charge.
+pub fn pay_out_if_above_threshold(env: &Env, merchant: &Address) -> Result<(), Error> {
+    let Some(threshold) = get_auto_payout(env, merchant) else {
+        return Ok(());
+    };
+    let amount = crate::merchant::available_balance(env, merchant)?;
+    if amount < threshold {
+        return Ok(());
+    }
+    let token_addr = crate::admin::get_token(env)?;
+    let paid =
+        crate::merchant_registry::payout_destination(env, merchant).is_ok_and(|destination| {
+            let transfer = token::Client::new(env, &token_addr).try_transfer(
+                &env.current_contract_address(),
+                &destination,
+                &amount,
+            );
+            matches!(transfer, Ok(Ok(())))
+        });
+    if !paid {
+        env.events().publish(
+            (Symbol::new(env, "auto_payout_failed"), merchant.clone()),
+            amount,
+        );
+        return Ok(());
+    }
+    crate::merchant::debit_merchant_balance(env, merchant, amount)?;
+    crate::statements::record(env, merchant, Movement::Payout, amount);
+    crate::statements::close_period(env, merchant);
+    env.events().publish(
+        (Symbol::new(env, "withdrawn"), merchant.clone()),
+        MerchantWithdrawalEvent {
+            merchant: merchant.clone(),
+            amount,
+        },
+    );
+    Ok(())
+}
+
+/// Pays out every listed merchant whose scheduled payout is due. Billing engine or billing agent
+/// only. Results are in input order; the error codes are `NotFound` (no schedule),
+/// `IntervalNotElapsed` (not due yet) and `InsufficientMerchantBalance` (nothing or less than
+/// `min_amount` available).
+pub fn do_batch_payout(
+    env: &Env,
+    operator: Address,
+    merchants: &Vec<Address>,
+) -> Result<Vec<BatchPayoutResult>, Error> {
+    crate::admin::require_billing_operator(env, &operator)?;
+    let now = env.ledger().timestamp();
+    let mut results = Vec::new(env);
+    for merchant in merchants.iter() {
+        let result = match payout_one(env, &merchant, now) {
+            Ok(amount) => BatchPayoutResult {
+                merchant,
+                amount,
+                error_code: 0,
+            },
+            Err(e) => BatchPayoutResult {
+                merchant,
+                amount: 0,
+                error_code: e.to_code(),
+            },
+        };
+        results.push_back(result);
+    }
+    Ok(results)
+}
+
+/// Pays the merchant's available balance if its payout is due. All checks run before
+/// any balance moves, so a failure leaves nothing half done inside the batch.
+fn payout_one(env: &Env, merchant: &Address, now: u64) -> Result<i128, Error> {
+    let mut schedule = get_payout_schedule(env, merchant).ok_or(Error::NotFound)?;
+    let next_due = schedule
+        .last_payout
+        .saturating_add(schedule.interval_seconds);
+    if schedule.last_payout > 0 && now < next_due {
+        return Err(Error::IntervalNotElapsed);
+    }
+    let amount = crate::merchant::available_balance(env, merchant)?;
+    if amount <= 0 || amount < schedule.min_amount {
+        return Err(Error::InsufficientMerchantBalance);
+    }
+    let destination = crate::merchant_registry::payout_destination(env, merchant)?;
+
+    crate::statements::record(env, merchant, Movement::Payout, amount);
+    crate::merchant::debit_merchant_balance(env, merchant, amount)?;
+    let token_addr = crate::admin::get_token(env)?;
+    token::Client::new(env, &token_addr).transfer(
+        &env.current_contract_address(),
+        &destination,
+        &amount,
+    );
+    schedule.last_payout = now;
+    env.storage()
+        .instance()
+        .set(&schedule_key(merchant), &schedule);
+    crate::statements::close_period(env, merchant);
+
+    env.events()
+        .publish((Symbol::new(env, "payout"), merchant.clone()), amount);
+    Ok(amount)
+}
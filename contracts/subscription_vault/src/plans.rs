@@ -0,0 +1,223 @@
+//! Plan catalog: merchant-managed plans with trial length, metadata and archiving.
+//!
+//! Plans share IDs and storage with plan templates (see `subscription.rs`), so every
+//! template is also a catalog plan and `create_subscription_from_plan` serves both. The
+//! extra catalog terms live next to the template under their own key. Subscriptions copy
+//! the plan's price and interval when created, so updating or archiving a plan only
+//! affects future subscribers.
+//!
+//! A subscriber gets one trial per merchant, counted across the wallets linked under its
+//! identity; later subscriptions start billing right away.
+//!
+//! **PRs that only change the plan catalog should edit this file only.**
+
+use crate::queries::{MAX_ITER_LIMIT, MAX_ITER_SCAN};
+use crate::safe_math::validate_non_negative;
+use crate::subscription::{do_create_plan_template, get_plan_template, plan_template_key};
+use crate::types::{ConfigKey, DataKey, Error, Plan, PlanTemplate, PlanTerms, RecordKey};
+use soroban_sdk::{Address, Env, String, Symbol, Vec};
+
+/// Maximum length of plan metadata, in bytes.
+pub const MAX_PLAN_METADATA_LEN: u32 = 256;
+
+/// One `list_plans_by_merchant` page: `(plan_id, plan)` pairs and the cursor to resume
+/// from.
+pub type PlanCatalogPage = (Vec<(u32, Plan)>, Option<u32>);
+
+fn trial_key(merchant: &Address, wallet: &Address) -> DataKey {
+    DataKey::Record(RecordKey::TrialTaken(merchant.clone(), wallet.clone()))
+}
+
+/// Claims a trial with `merchant` for `subscriber`. Returns `false`, emitting
+/// `trial_denied`, if the subscriber or a wallet linked with it (see `identity.rs`)
+/// already started one.
+pub fn take_trial(env: &Env, merchant: &Address, subscriber: &Address) -> bool {
+    let storage = env.storage().instance();
+    for wallet in crate::identity::linked_wallets(env, subscriber).iter() {
+        if storage.has(&trial_key(merchant, &wallet)) {
+            env.events().publish(
+                (Symbol::new(env, "trial_denied"), subscriber.clone()),
+                merchant.clone(),
+            );
+            return false;
+        }
+    }
+    storage.set(&trial_key(merchant, subscriber), &true);
+    true
+}
+
+fn terms_key(plan_id: u32) -> DataKey {
+    DataKey::Record(RecordKey::PlanTerms(plan_id))
+}
+
+/// Catalog terms of the plan; defaults for templates created without them.
+pub fn get_plan_terms(env: &Env, plan_id: u32) -> PlanTerms {
+    env.storage()
+        .instance()
+        .get(&terms_key(plan_id))
+        .unwrap_or(PlanTerms {
+            trial_seconds: 0,
+            metadata: String::from_str(env, ""),
+            archived: false,
+        })
+}
+
+fn validate_terms(price: i128, interval_seconds: u64, metadata: &String) -> Result<(), Error> {
+    validate_non_negative(price)?;
+    if interval_seconds == 0 || metadata.len() > MAX_PLAN_METADATA_LEN {
+        return Err(Error::InvalidInput);
+    }
+    Ok(())
+}
+
+fn load_owned(env: &Env, merchant: &Address, plan_id: u32) -> Result<PlanTemplate, Error> {
+    crate::liveness::require_merchant_auth(env, merchant);
+    let plan = get_plan_template(env, plan_id)?;
+    if plan.merchant != *merchant {
+        return Err(Error::Forbidden);
+    }
+    if get_plan_terms(env, plan_id).archived {
+        return Err(Error::PlanArchived);
+    }
+    Ok(plan)
+}
+
+/// Catalog view of a plan.
+pub fn get_plan(env: &Env, plan_id: u32) -> Result<Plan, Error> {
+    Ok(catalog_view(env, plan_id, get_plan_template(env, plan_id)?))
+}
+
+fn catalog_view(env: &Env, plan_id: u32, plan: PlanTemplate) -> Plan {
+    let terms = get_plan_terms(env, plan_id);
+    Plan {
+        merchant: plan.merchant,
+        price: plan.amount,
+        interval_seconds: plan.interval_seconds,
+        usage_enabled: plan.usage_enabled,
+        trial_seconds: terms.trial_seconds,
+        metadata: terms.metadata,
+        archived: terms.archived,
+    }
+}
+
+/// Walks the plan ID space from `start` (inclusive), returning up to `limit` of the
+/// merchant's plans, archived ones included, and the plan ID to resume from.
+///
+/// Plans are not indexed by merchant, so at most [`MAX_ITER_SCAN`] plan IDs are probed
+/// per call, as for `iter_subscriptions`: a page may hold fewer than `limit` plans
+/// while a cursor is still returned. The cursor is `None` once every plan ID has been
+/// visited.
+///
+/// # Errors
+/// - `InvalidInput` if `limit` is 0 or greater than [`MAX_ITER_LIMIT`].
+pub fn list_plans_by_merchant(
+    env: &Env,
+    merchant: &Address,
+    start: u32,
+    limit: u32,
+) -> Result<PlanCatalogPage, Error> {
+    if limit == 0 || limit > MAX_ITER_LIMIT {
+        return Err(Error::InvalidInput);
+    }
+
+    let next_plan_id: u32 = env
+        .storage()
+        .instance()
+        .get(&DataKey::Config(ConfigKey::NextPlanId))
+        .unwrap_or(0);
+    let scan_end = start.saturating_add(MAX_ITER_SCAN).min(next_plan_id);
+
+    let mut plans = Vec::new(env);
+    let mut plan_id = start;
+    while plan_id < scan_end && plans.len() < limit {
+        if let Ok(plan) = get_plan_template(env, plan_id) {
+            if plan.merchant == *merchant {
+                plans.push_back((plan_id, catalog_view(env, plan_id, plan)));
+            }
+        }
+        plan_id += 1;
+    }
+
+    let next_cursor = if plan_id < next_plan_id {
+        Some(plan_id)
+    } else {
+        None
+    };
+    Ok((plans, next_cursor))
+}
+
+/// Merchant adds a plan to the catalog and gets its canonical plan ID.
+pub fn do_create_plan(
+    env: &Env,
+    merchant: Address,
+    price: i128,
+    interval_seconds: u64,
+    usage_enabled: bool,
+    trial_seconds: u64,
+    metadata: String,
+) -> Result<u32, Error> {
+    validate_terms(price, interval_seconds, &metadata)?;
+    let plan_id = do_create_plan_template(
+        env,
+        merchant.clone(),
+        price,
+        interval_seconds,
+        usage_enabled,
+    )?;
+    let terms = PlanTerms {
+        trial_seconds,
+        metadata,
+        archived: false,
+    };
+    env.storage().instance().set(&terms_key(plan_id), &terms);
+
+    env.events()
+        .publish((Symbol::new(env, "plan_created"), plan_id), merchant);
+    Ok(plan_id)
+}
+
+/// Merchant changes the price, interval, trial or metadata of a plan for future
+/// subscribers. Existing subscriptions keep the terms they were created with.
+pub fn do_update_plan(
+    env: &Env,
+    merchant: Address,
+    plan_id: u32,
+    price: i128,
+    interval_seconds: u64,
+    trial_seconds: u64,
+    metadata: String,
+) -> Result<(), Error> {
+    let mut plan = load_owned(env, &merchant, plan_id)?;
+    validate_terms(price, interval_seconds, &metadata)?;
+
+    plan.amount = price;
+    plan.interval_seconds = interval_seconds;
+    env.storage()
+        .instance()
+        .set(&plan_template_key(plan_id), &plan);
+    let terms = PlanTerms {
+        trial_seconds,
+        metadata,
+        archived: false,
+    };
+    env.storage().instance().set(&terms_key(plan_id), &terms);
+
+    env.events().publish(
+        (Symbol::new(env, "plan_updated"), plan_id),
+        (price, interval_seconds, trial_seconds),
+    );
+    Ok(())
+}
+
+/// Merchant retires a plan: no new subscriptions or updates. Existing subscriptions
+/// continue unchanged. Archiving is permanent.
+pub fn do_archive_plan(env: &Env, merchant: Address, plan_id: u32) -> Result<(), Error> {
+    load_owned(env, &merchant, plan_id)?;
+    let mut terms = get_plan_terms(env, plan_id);
+    terms.archived = true;
+    env.storage().instance().set(&terms_key(plan_id), &terms);
+
+    env.events()
+        .publish((Symbol::new(env, "plan_archived"), plan_id), merchant);
+    Ok(())
+}
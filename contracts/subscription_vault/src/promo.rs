@@ -0,0 +1,141 @@
+//! Promotional subscriptions: zero-amount, time-limited, merchant-issued.
+//!
+//! A promo subscription is created with `amount == 0` and a mandatory `expires_at`.
+//! The subscriber authorizes its creation along with the merchant, since it counts
+//! towards the subscriber's subscription limit and entitlement index like any other.
+//! Charge paths skip it (`PromoNotChargeable`) while it still grants entitlement until
+//! expiry. To keep the subscriber after the promo, the merchant offers a price and the
+//! subscriber accepts it; paid billing then starts at the later of acceptance and expiry.
+//!
+//! **PRs that only change promo subscriptions should edit this file only.**
+
+use crate::queries::get_subscription;
+use crate::subscription::store_new_subscription;
+use crate::types::{DataKey, Error, PromoSubscription, Subscription, SubscriptionStatus};
+use soroban_sdk::{Address, Env, Symbol};
+
+pub fn get_promo(env: &Env, subscription_id: u32) -> Option<PromoSubscription> {
+    env.storage()
+        .instance()
+        .get(&DataKey::Promo(subscription_id))
+}
+
+/// Charge paths reject promo subscriptions until they are converted to paid.
+pub fn ensure_chargeable(env: &Env, subscription_id: u32) -> Result<(), Error> {
+    if env
+        .storage()
+        .instance()
+        .has(&DataKey::Promo(subscription_id))
+    {
+        return Err(Error::PromoNotChargeable);
+    }
+    Ok(())
+}
+
+/// Merchant issues a free subscription to `subscriber` that lapses at `expires_at`.
+/// Both the merchant and the subscriber must authorize it.
+pub fn do_create_promo_subscription(
+    env: &Env,
+    merchant: Address,
+    subscriber: Address,
+    interval_seconds: u64,
+    expires_at: u64,
+) -> Result<u32, Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    subscriber.require_auth();
+
+    let now = env.ledger().timestamp();
+    if interval_seconds == 0 || expires_at <= now {
+        return Err(Error::InvalidInput);
+    }
+
+    let sub = Subscription {
+        subscriber,
+        merchant,
+        amount: 0,
+        interval_seconds,
+        last_payment_timestamp: now,
+        status: SubscriptionStatus::Active,
+        prepaid_balance: 0i128,
+        usage_enabled: false,
+    };
+    let id = crate::ids::next_id(env)?;
+    store_new_subscription(env, id, &sub, None)?;
+    env.storage().instance().set(
+        &DataKey::Promo(id),
+        &PromoSubscription {
+            expires_at,
+            offered_amount: None,
+        },
+    );
+
+    env.events()
+        .publish((Symbol::new(env, "promo_created"), id), expires_at);
+    Ok(id)
+}
+
+/// Merchant offers the price the subscription continues at once the promo ends.
+pub fn do_offer_promo_conversion(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    amount: i128,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+
+    let sub = get_subscription(env, subscription_id)?;
+    if merchant != sub.merchant {
+        return Err(Error::Forbidden);
+    }
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let mut promo = get_promo(env, subscription_id).ok_or(Error::NotFound)?;
+    promo.offered_amount = Some(amount);
+    env.storage()
+        .instance()
+        .set(&DataKey::Promo(subscription_id), &promo);
+
+    env.events().publish(
+        (
+            Symbol::new(env, "promo_conversion_offered"),
+            subscription_id,
+        ),
+        amount,
+    );
+    Ok(())
+}
+
+/// Subscriber consents to the offered price, turning the promo into a paid subscription.
+pub fn do_accept_promo_conversion(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+
+    let mut sub = get_subscription(env, subscription_id)?;
+    if subscriber != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+    if sub.status == SubscriptionStatus::Cancelled {
+        return Err(Error::InvalidStatusTransition);
+    }
+    let promo = get_promo(env, subscription_id).ok_or(Error::NotFound)?;
+    let amount = promo.offered_amount.ok_or(Error::NotFound)?;
+
+    sub.amount = amount;
+    sub.last_payment_timestamp = env.ledger().timestamp().max(promo.expires_at);
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
+    env.storage()
+        .instance()
+        .remove(&DataKey::Promo(subscription_id));
+
+    env.events().publish(
+        (Symbol::new(env, "promo_converted"), subscription_id),
+        amount,
+    );
+    Ok(())
+}
@@ -1,39 +1,122 @@
-//! Read-only entrypoints and helpers: get_subscription, estimate_topup, list_subscriptions_by_subscriber.
+//! Read-only entrypoints and helpers: get_subscription, estimate_topup, list_subscriptions_by_subscriber,
+//! iter_subscriptions, preview_charge, and the cursor-paged listings.
 //!
 //! **PRs that only add or change read-only/query behavior should edit this file only.**
 
-use crate::types::{DataKey, Error, NextChargeInfo, Subscription, SubscriptionStatus};
-use soroban_sdk::{contracttype, Address, Env, Symbol, Vec};
+use crate::types::{
+    ChargeMask, ChargePreview, Cursor, DataKey, Error, NextChargeInfo, RevenueForecast,
+    Subscription, SubscriptionPage, SubscriptionStatus, SubscriptionSummary, TopupEstimate,
+    UpcomingCharge,
+};
+use soroban_sdk::{contracttype, Address, Env, Vec};
 
 pub fn get_subscription(env: &Env, subscription_id: u32) -> Result<Subscription, Error> {
+    load_subscription(env, subscription_id).ok_or(Error::NotFound)
+}
+
+/// Subscription record, or `None` if the ID holds none. Falls back to the legacy bare
+/// `u32` key for records not yet moved by `migrate_storage_keys`.
+pub fn load_subscription(env: &Env, subscription_id: u32) -> Option<Subscription> {
+    let storage = env.storage().instance();
+    storage
+        .get(&DataKey::Sub(subscription_id))
+        .or_else(|| storage.get(&subscription_id))
+}
+
+/// Returns the subscription's charge mask (both charge types allowed by default).
+pub fn get_charge_mask(env: &Env, subscription_id: u32) -> ChargeMask {
     env.storage()
         .instance()
-        .get(&subscription_id)
+        .get(&DataKey::ChargeMask(subscription_id))
+        .unwrap_or(ChargeMask {
+            allow_interval: true,
+            allow_usage: true,
+        })
+}
+
+/// Returns an archived subscription record (see `do_archive_subscription`).
+pub fn get_archived_subscription(env: &Env, subscription_id: u32) -> Result<Subscription, Error> {
+    env.storage()
+        .instance()
+        .get(&DataKey::ArchivedSubscription(subscription_id))
         .ok_or(Error::NotFound)
 }
 
+/// Plan amount of the next `num_intervals` interval charges and how many of them are
+/// taken, under the merchant's expiry proration policy (see `expiration.rs`).
+fn interval_charges_ahead(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    num_intervals: u32,
+) -> Result<(i128, u32), Error> {
+    if crate::expiration::get_expiration(env, subscription_id).is_none() {
+        let total = sub
+            .amount
+            .checked_mul(num_intervals.into())
+            .ok_or(Error::Overflow)?;
+        return Ok((total, num_intervals));
+    }
+    let mut total: i128 = 0;
+    let mut charged = 0;
+    let mut due_at = sub
+        .last_payment_timestamp
+        .saturating_add(sub.interval_seconds);
+    while charged < num_intervals {
+        let Some(amount) = crate::expiration::expiry_adjusted_amount(
+            env,
+            subscription_id,
+            sub,
+            due_at,
+            sub.amount,
+        ) else {
+            break;
+        };
+        total = total.checked_add(amount).ok_or(Error::Overflow)?;
+        charged += 1;
+        due_at = due_at.saturating_add(sub.interval_seconds);
+    }
+    Ok((total, charged))
+}
+
+/// Expected cost of the next `num_intervals` billing intervals and the top-up needed to
+/// cover it. Usage is projected from the trailing average of closed usage windows
+/// (see `usage::project_usage`), so metered subscriptions get a realistic figure.
+/// Interval charges near the expiration follow the merchant's expiry proration policy.
 pub fn estimate_topup_for_intervals(
     env: &Env,
     subscription_id: u32,
     num_intervals: u32,
-) -> Result<i128, Error> {
+) -> Result<TopupEstimate, Error> {
     let sub = get_subscription(env, subscription_id)?;
 
     if num_intervals == 0 {
-        return Ok(0);
+        return Ok(TopupEstimate {
+            base: 0,
+            projected_usage: 0,
+            fees: 0,
+            total: 0,
+        });
     }
 
-    let intervals_i128: i128 = num_intervals.into();
-    let required = sub
-        .amount
-        .checked_mul(intervals_i128)
-        .ok_or(Error::Overflow)?;
+    let (base, charged) = interval_charges_ahead(env, subscription_id, &sub, num_intervals)?;
+    let projected_usage = crate::usage::project_usage(env, subscription_id, &sub, num_intervals)?;
+    let fees = crate::setup_fee::installments_ahead_total(env, subscription_id, charged);
 
-    let topup = required
+    let required = base
+        .checked_add(projected_usage)
+        .and_then(|r| r.checked_add(fees))
+        .ok_or(Error::Overflow)?;
+    let total = required
         .checked_sub(sub.prepaid_balance)
         .unwrap_or(0)
         .max(0);
-    Ok(topup)
+    Ok(TopupEstimate {
+        base,
+        projected_usage,
+        fees,
+        total,
+    })
 }
 
 /// Returns subscriptions for a merchant, paginated by offset.
@@ -69,7 +152,7 @@ pub fn get_subscriptions_by_merchant(
     let mut i = start;
     while i < end {
         let sub_id = ids.get(i).unwrap();
-        if let Some(sub) = env.storage().instance().get::<u32, Subscription>(&sub_id) {
+        if let Some(sub) = crate::queries::load_subscription(env, sub_id) {
             result.push_back(sub);
         }
         i += 1;
@@ -77,6 +160,108 @@ pub fn get_subscriptions_by_merchant(
     result
 }
 
+/// Returns a merchant's subscriptions in `status`, paginated by offset, so dunning
+/// campaigns can fetch e.g. only `InsufficientBalance` subscriptions.
+///
+/// * `start` – 0-based offset into the merchant's subscription list, as for
+///   [`get_subscriptions_by_merchant`].
+/// * `limit` – maximum number of matches to return.
+///
+/// Matches are in insertion order. `next_cursor` is the offset to pass as the next
+/// `start`, or `None` once the list is exhausted. At most [`MAX_ITER_SCAN`] entries are
+/// examined per call, so a page may hold fewer than `limit` matches while a cursor is
+/// still returned. No `total_hint` is given.
+///
+/// # Errors
+/// - `InvalidInput` if `limit` is 0 or greater than [`MAX_ITER_LIMIT`].
+pub fn get_subscriptions_by_merchant_filtered(
+    env: &Env,
+    merchant: Address,
+    status: SubscriptionStatus,
+    start: u32,
+    limit: u32,
+) -> Result<SubscriptionPage, Error> {
+    if limit == 0 || limit > MAX_ITER_LIMIT {
+        return Err(Error::InvalidInput);
+    }
+
+    let ids: Vec<u32> = env
+        .storage()
+        .instance()
+        .get(&DataKey::MerchantSubs(merchant))
+        .unwrap_or(Vec::new(env));
+    let len = ids.len();
+    let scan_end = start.saturating_add(MAX_ITER_SCAN).min(len);
+
+    let mut items = Vec::new(env);
+    let mut i = start;
+    while i < scan_end && items.len() < limit {
+        let id = ids.get_unchecked(i);
+        if let Some(sub) = crate::queries::load_subscription(env, id) {
+            if sub.status == status {
+                items.push_back(subscription_summary(id, sub));
+            }
+        }
+        i += 1;
+    }
+
+    Ok(SubscriptionPage {
+        items,
+        next_cursor: if i < len { Some(i) } else { None },
+        total_hint: None,
+    })
+}
+
+/// Returns a merchant's subscriptions whose prepaid balance covers fewer than
+/// `intervals` upcoming charges at the subscription's `amount`, so merchants can ask
+/// those subscribers to top up. Cancelled subscriptions are left out.
+///
+/// Paginated by offset into the merchant's list exactly like
+/// [`get_subscriptions_by_merchant_filtered`], including the [`MAX_ITER_SCAN`] bound.
+///
+/// # Errors
+/// - `InvalidInput` if `intervals` is 0, or `limit` is 0 or greater than
+///   [`MAX_ITER_LIMIT`].
+pub fn get_subscriptions_below_runway(
+    env: &Env,
+    merchant: Address,
+    intervals: u32,
+    start: u32,
+    limit: u32,
+) -> Result<SubscriptionPage, Error> {
+    if intervals == 0 || limit == 0 || limit > MAX_ITER_LIMIT {
+        return Err(Error::InvalidInput);
+    }
+
+    let ids: Vec<u32> = env
+        .storage()
+        .instance()
+        .get(&DataKey::MerchantSubs(merchant))
+        .unwrap_or(Vec::new(env));
+    let len = ids.len();
+    let scan_end = start.saturating_add(MAX_ITER_SCAN).min(len);
+
+    let mut items = Vec::new(env);
+    let mut i = start;
+    while i < scan_end && items.len() < limit {
+        let id = ids.get_unchecked(i);
+        if let Some(sub) = crate::queries::load_subscription(env, id) {
+            if sub.status != SubscriptionStatus::Cancelled
+                && sub.prepaid_balance < crate::low_balance::required_balance(&sub, intervals)
+            {
+                items.push_back(subscription_summary(id, sub));
+            }
+        }
+        i += 1;
+    }
+
+    Ok(SubscriptionPage {
+        items,
+        next_cursor: if i < len { Some(i) } else { None },
+        total_hint: None,
+    })
+}
+
 /// Returns the number of subscriptions for a given merchant.
 ///
 /// Useful for dashboards and pagination metadata.
@@ -89,7 +274,8 @@ pub fn get_merchant_subscription_count(env: &Env, merchant: Address) -> u32 {
 /// Computes the estimated next charge timestamp for a subscription.
 ///
 /// This is a readonly helper that does not mutate contract state. It provides
-/// information for off-chain scheduling systems and UX displays.
+/// information for off-chain scheduling systems and UX displays. `cycles_remaining` is
+/// left `None`; `get_next_charge_info` fills it in from `fixed_term.rs`.
 pub fn compute_next_charge_info(subscription: &Subscription) -> NextChargeInfo {
     let next_charge_timestamp = subscription
         .last_payment_timestamp
@@ -106,9 +292,208 @@ pub fn compute_next_charge_info(subscription: &Subscription) -> NextChargeInfo {
     NextChargeInfo {
         next_charge_timestamp,
         is_charge_expected,
+        cycles_remaining: None,
     }
 }
 
+/// Previews the next interval charge, including any loyalty discount earned so far, any
+/// proration for an expiration within the period, any amount carried over or rounded up
+/// for the minimum charge and any setup fee installment still owed. A charge blocked by
+/// the expiry proration policy previews as 0 with `blocked_by_expiry` set.
+pub fn preview_charge(env: &Env, subscription_id: u32) -> Result<ChargePreview, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    let next_charge_timestamp = compute_next_charge_info(&sub).next_charge_timestamp;
+    let base = crate::loyalty::discounted_amount(env, subscription_id, &sub)?;
+    let adjusted = crate::expiration::expiry_adjusted_amount(
+        env,
+        subscription_id,
+        &sub,
+        next_charge_timestamp,
+        base,
+    );
+    let (amount_due, setup_fee_due) = match adjusted {
+        Some(base) => {
+            let setup_fee_due = crate::setup_fee::next_installment(env, subscription_id);
+            let amount_due = crate::min_charge::charge_amount(env, subscription_id, &sub, base)?
+                .checked_add(setup_fee_due)
+                .ok_or(Error::Overflow)?;
+            (amount_due, setup_fee_due)
+        }
+        None => (0, 0),
+    };
+
+    Ok(ChargePreview {
+        amount: sub.amount,
+        discount_bps: crate::loyalty::discount_bps(env, subscription_id, &sub),
+        amount_due,
+        setup_fee_due,
+        cycles_completed: crate::loyalty::get_cycles_completed(env, subscription_id),
+        next_charge_timestamp,
+        blocked_by_expiry: adjusted.is_none(),
+    })
+}
+
+/// Maximum number of entries returned by a single `get_upcoming_charges` call.
+pub const MAX_UPCOMING_CHARGES: u32 = 100;
+
+/// Inserts `charge` keeping `charges` ordered by `due_at`, dropping the latest entry
+/// once [`MAX_UPCOMING_CHARGES`] is exceeded.
+fn insert_upcoming(charges: &mut Vec<UpcomingCharge>, charge: UpcomingCharge) {
+    let mut i = charges.len();
+    while i > 0 && charges.get(i - 1).unwrap().due_at > charge.due_at {
+        i -= 1;
+    }
+    charges.insert(i, charge);
+    if charges.len() > MAX_UPCOMING_CHARGES {
+        charges.pop_back();
+    }
+}
+
+/// Lists the interval charges expected for `subscriber`'s subscriptions within
+/// `horizon_seconds` of now, ordered by `due_at`.
+///
+/// A subscription contributes one entry per interval falling in the horizon. An
+/// overdue charge is listed at its original due time, and the following intervals
+/// run from now, since a late charge restarts the schedule at the charge time.
+/// Paused, cancelled, promo, and interval-masked subscriptions are skipped. At most
+/// [`MAX_UPCOMING_CHARGES`] entries (the earliest) are returned.
+///
+/// # Performance
+/// O(n) in the total number of subscriptions, like `list_subscriptions_by_subscriber`.
+pub fn get_upcoming_charges(
+    env: &Env,
+    subscriber: Address,
+    horizon_seconds: u64,
+) -> Result<Vec<UpcomingCharge>, Error> {
+    let now = env.ledger().timestamp();
+    let horizon_end = now.saturating_add(horizon_seconds);
+    let next_id = crate::ids::peek_next_id(env);
+
+    let mut charges = Vec::new(env);
+    for id in crate::ids::first_id(env)..next_id {
+        let sub = match crate::queries::load_subscription(env, id) {
+            Some(sub) if sub.subscriber == subscriber => sub,
+            _ => continue,
+        };
+        if !compute_next_charge_info(&sub).is_charge_expected
+            || !get_charge_mask(env, id).allow_interval
+            || crate::promo::get_promo(env, id).is_some()
+            || sub.interval_seconds == 0
+        {
+            continue;
+        }
+
+        let base_amount = crate::loyalty::discounted_amount(env, id, &sub)?;
+        let mut due_at = compute_next_charge_info(&sub).next_charge_timestamp;
+        // No subscription can place more than the cap among the earliest entries.
+        let mut listed = 0;
+        while due_at <= horizon_end && listed < MAX_UPCOMING_CHARGES {
+            insert_upcoming(
+                &mut charges,
+                UpcomingCharge {
+                    subscription_id: id,
+                    due_at,
+                    estimated_amount: base_amount
+                        .checked_add(crate::setup_fee::installment_ahead(env, id, listed))
+                        .ok_or(Error::Overflow)?,
+                },
+            );
+            due_at = due_at.max(now).saturating_add(sub.interval_seconds);
+            listed += 1;
+        }
+    }
+    Ok(charges)
+}
+
+/// Projects the merchant's revenue from interval charges due within `horizon_seconds`
+/// of now.
+///
+/// Only Active subscriptions are counted, skipping promo and interval-masked ones. The
+/// next charge uses the current terms and any later one the pending terms (see
+/// `terms.rs`), with the loyalty discount earned so far and any setup fee installments.
+/// Charges due at or after a subscription's expiration are left out, since the
+/// expiration sweep cancels it by then. The funded part of each subscription's charges
+/// is capped by its prepaid balance; auto-topup and guarantors are not counted.
+///
+/// # Performance
+/// O(n) in the merchant's subscriptions; the charges of each are summed in closed form.
+pub fn forecast_merchant_revenue(
+    env: &Env,
+    merchant: Address,
+    horizon_seconds: u64,
+) -> Result<RevenueForecast, Error> {
+    let now = env.ledger().timestamp();
+    let horizon_end = now.saturating_add(horizon_seconds);
+    let ids: Vec<u32> = env
+        .storage()
+        .instance()
+        .get(&DataKey::MerchantSubs(merchant))
+        .unwrap_or(Vec::new(env));
+
+    let mut forecast = RevenueForecast {
+        expected: 0,
+        funded: 0,
+        charges: 0,
+        subscriptions: 0,
+    };
+    for id in ids.iter() {
+        let sub = match load_subscription(env, id) {
+            Some(sub) if sub.status == SubscriptionStatus::Active => sub,
+            _ => continue,
+        };
+        if !get_charge_mask(env, id).allow_interval
+            || crate::promo::get_promo(env, id).is_some()
+            || sub.interval_seconds == 0
+        {
+            continue;
+        }
+        // Last instant a charge can fall on: the horizon end or just before expiration.
+        let last = match crate::expiration::get_expiration(env, id) {
+            Some(expiration) => horizon_end.min(expiration.saturating_sub(1)),
+            None => horizon_end,
+        };
+        let first_due = compute_next_charge_info(&sub).next_charge_timestamp;
+        if first_due > last {
+            continue;
+        }
+
+        let first_amount = crate::loyalty::discounted_amount(env, id, &sub)?;
+        let mut next_terms = sub.clone();
+        if let Some(pending) = crate::terms::get_pending_terms(env, id) {
+            next_terms.amount = pending.amount;
+            next_terms.interval_seconds = pending.interval_seconds;
+        }
+        let second_due = first_due
+            .max(now)
+            .saturating_add(next_terms.interval_seconds);
+        let later: u32 = if second_due <= last {
+            ((last - second_due) / next_terms.interval_seconds + 1)
+                .try_into()
+                .unwrap_or(u32::MAX)
+        } else {
+            0
+        };
+        let fees = crate::setup_fee::installments_ahead_total(env, id, later.saturating_add(1));
+        let expected = crate::loyalty::discounted_amount(env, id, &next_terms)?
+            .checked_mul(later as i128)
+            .and_then(|amount| amount.checked_add(first_amount))
+            .and_then(|amount| amount.checked_add(fees))
+            .ok_or(Error::Overflow)?;
+
+        forecast.expected = forecast
+            .expected
+            .checked_add(expected)
+            .ok_or(Error::Overflow)?;
+        forecast.funded = forecast
+            .funded
+            .checked_add(expected.min(sub.prepaid_balance.max(0)))
+            .ok_or(Error::Overflow)?;
+        forecast.charges = forecast.charges.saturating_add(later).saturating_add(1);
+        forecast.subscriptions += 1;
+    }
+    Ok(forecast)
+}
+
 /// Result of a paginated query for subscriptions by subscriber.
 /// Contains the subscription IDs and metadata for pagination.
 #[contracttype]
@@ -139,9 +524,10 @@ pub struct SubscriptionsPage {
 /// - `has_next`: True if there are more subscriptions after the last returned ID
 ///
 /// # Performance Notes
-/// - Time complexity: O(n) where n = total number of subscriptions in the contract
+/// - Reads the subscriber index (`DataKey::SubscriberSubs`): O(subscriber's subscriptions)
+/// - While `migrate_storage_keys` has not yet built the index, falls back to scanning
+///   every subscription ID: O(n) in the total number of subscriptions
 /// - Space complexity: O(limit)
-/// - On-chain storage usage is minimal (only subscription objects are stored)
 /// - Suitable for off-chain indexers and UI pagination
 ///
 /// # Pagination Example
@@ -165,9 +551,27 @@ pub fn list_subscriptions_by_subscriber(
         return Err(Error::InvalidInput);
     }
 
-    // Get the next_id counter to determine the range of valid subscription IDs
-    let next_id_key = Symbol::new(env, "next_id");
-    let next_id: u32 = env.storage().instance().get(&next_id_key).unwrap_or(0);
+    if crate::storage_migration::subscriber_index_ready(env) {
+        let ids = crate::subscription::get_subscriber_subscription_ids(env, &subscriber);
+        let mut subscription_ids = Vec::new(env);
+        let mut has_next = false;
+        for id in ids.iter().filter(|id| *id >= start_from_id) {
+            if subscription_ids.len() == limit {
+                has_next = true;
+                break;
+            }
+            subscription_ids.push_back(id);
+        }
+        return Ok(SubscriptionsPage {
+            subscription_ids,
+            has_next,
+        });
+    }
+
+    // Valid subscription IDs run from this contract's first ID up to the next one to be
+    // allocated.
+    let start_from_id = start_from_id.max(crate::ids::first_id(env));
+    let next_id = crate::ids::peek_next_id(env);
 
     let mut subscription_ids = Vec::new(env);
     let mut count = 0u32;
@@ -175,20 +579,16 @@ pub fn list_subscriptions_by_subscriber(
 
     // Iterate through all subscription IDs from start_from_id (inclusive) and filter by subscriber
     for id in start_from_id..next_id {
-        match env.storage().instance().get::<u32, Subscription>(&id) {
-            Some(sub) => {
-                if sub.subscriber == subscriber {
-                    subscription_ids.push_back(id);
-                    count += 1;
-                    last_found_id = id;
-                    if count >= limit {
-                        break;
-                    }
+        // Subscriptions that were deleted or IDs that were skipped load as `None`.
+        if let Some(sub) = crate::queries::load_subscription(env, id) {
+            if sub.subscriber == subscriber {
+                subscription_ids.push_back(id);
+                count += 1;
+                last_found_id = id;
+                if count >= limit {
+                    break;
                 }
             }
-            None => {
-                // Subscription was deleted or ID skipped; continue to next
-            }
         }
     }
 
@@ -197,7 +597,7 @@ pub fn list_subscriptions_by_subscriber(
         // We hit the limit; check if there is at least one more subscriber match
         let mut found_next = false;
         for id in (last_found_id + 1)..next_id {
-            if let Some(sub) = env.storage().instance().get::<u32, Subscription>(&id) {
+            if let Some(sub) = crate::queries::load_subscription(env, id) {
                 if sub.subscriber == subscriber {
                     found_next = true;
                     break;
@@ -214,3 +614,258 @@ pub fn list_subscriptions_by_subscriber(
         has_next,
     })
 }
+
+/// Maximum number of entries returned by a single `iter_subscriptions` call.
+pub const MAX_ITER_LIMIT: u32 = 100;
+
+/// Maximum number of IDs probed by a single `iter_subscriptions` call, so a long run
+/// of removed IDs cannot exhaust the budget. The returned cursor resumes the scan.
+pub const MAX_ITER_SCAN: u32 = 1_000;
+
+/// One `iter_subscriptions` page: `(id, summary)` pairs and the cursor to resume from.
+pub type SubscriptionIterPage = (Vec<(u32, SubscriptionSummary)>, Option<u32>);
+
+/// One `get_due_subscriptions` page: subscription IDs and the cursor to resume from.
+pub type DueSubscriptionPage = (Vec<u32>, Option<u32>);
+
+/// Builds the reporting summary for a stored subscription.
+pub fn subscription_summary(subscription_id: u32, sub: Subscription) -> SubscriptionSummary {
+    SubscriptionSummary {
+        subscription_id,
+        subscriber: sub.subscriber,
+        merchant: sub.merchant,
+        amount: sub.amount,
+        interval_seconds: sub.interval_seconds,
+        last_payment_timestamp: sub.last_payment_timestamp,
+        status: sub.status,
+        prepaid_balance: sub.prepaid_balance,
+        usage_enabled: sub.usage_enabled,
+    }
+}
+
+/// Walks the subscription ID space from `cursor` (inclusive), returning up to `limit`
+/// existing subscriptions and the cursor to resume from.
+///
+/// IDs with no stored record are skipped. At most [`MAX_ITER_SCAN`] IDs are probed per
+/// call, so a page may hold fewer than `limit` entries while a cursor is still returned.
+/// The cursor is `None` once every allocated ID has been visited.
+///
+/// # Errors
+/// - `InvalidInput` if `limit` is 0 or greater than [`MAX_ITER_LIMIT`].
+pub fn iter_subscriptions(
+    env: &Env,
+    cursor: u32,
+    limit: u32,
+) -> Result<SubscriptionIterPage, Error> {
+    if limit == 0 || limit > MAX_ITER_LIMIT {
+        return Err(Error::InvalidInput);
+    }
+
+    let next_id = crate::ids::peek_next_id(env);
+    let cursor = cursor.max(crate::ids::first_id(env));
+
+    let mut entries = Vec::new(env);
+    if cursor >= next_id {
+        return Ok((entries, None));
+    }
+
+    let scan_end = cursor.saturating_add(MAX_ITER_SCAN).min(next_id);
+    let mut id = cursor;
+    while id < scan_end && entries.len() < limit {
+        if let Some(sub) = crate::queries::load_subscription(env, id) {
+            entries.push_back((id, subscription_summary(id, sub)));
+        }
+        id += 1;
+    }
+
+    let next_cursor = if id < next_id { Some(id) } else { None };
+    Ok((entries, next_cursor))
+}
+
+fn check_cursor(cursor: &Cursor) -> Result<(), Error> {
+    if cursor.limit == 0 || cursor.limit > MAX_ITER_LIMIT {
+        return Err(Error::InvalidInput);
+    }
+    Ok(())
+}
+
+/// Walks the subscription ID space from `cursor.start_id`, keeping subscriptions that
+/// match `keep`. Probes at most [`MAX_ITER_SCAN`] IDs, like [`iter_subscriptions`].
+fn scan_page(
+    env: &Env,
+    cursor: &Cursor,
+    total_hint: Option<u32>,
+    keep: impl Fn(&Subscription) -> bool,
+) -> Result<SubscriptionPage, Error> {
+    check_cursor(cursor)?;
+
+    let next_id = crate::ids::peek_next_id(env);
+
+    let mut items = Vec::new(env);
+    let start_id = cursor.start_id.max(crate::ids::first_id(env));
+    let scan_end = start_id.saturating_add(MAX_ITER_SCAN).min(next_id);
+    let mut id = start_id;
+    while id < scan_end && items.len() < cursor.limit {
+        if let Some(sub) = crate::queries::load_subscription(env, id) {
+            if keep(&sub) {
+                items.push_back(subscription_summary(id, sub));
+            }
+        }
+        id += 1;
+    }
+
+    let next_cursor = if id < next_id { Some(id) } else { None };
+    Ok(SubscriptionPage {
+        items,
+        next_cursor,
+        total_hint,
+    })
+}
+
+/// Cursor-paged subscriptions of `merchant`, read from the merchant index.
+///
+/// `total_hint` is the merchant's subscription count.
+///
+/// # Errors
+/// - `InvalidInput` if `cursor.limit` is 0 or greater than [`MAX_ITER_LIMIT`].
+pub fn page_merchant_subscriptions(
+    env: &Env,
+    merchant: Address,
+    cursor: Cursor,
+) -> Result<SubscriptionPage, Error> {
+    check_cursor(&cursor)?;
+
+    let ids: Vec<u32> = env
+        .storage()
+        .instance()
+        .get(&DataKey::MerchantSubs(merchant))
+        .unwrap_or(Vec::new(env));
+
+    // The index is in creation order, which is ascending ID order.
+    let mut items = Vec::new(env);
+    let mut next_cursor = None;
+    for id in ids.iter().filter(|id| *id >= cursor.start_id) {
+        if items.len() == cursor.limit {
+            next_cursor = Some(id);
+            break;
+        }
+        if let Some(sub) = crate::queries::load_subscription(env, id) {
+            items.push_back(subscription_summary(id, sub));
+        }
+    }
+
+    Ok(SubscriptionPage {
+        items,
+        next_cursor,
+        total_hint: Some(ids.len()),
+    })
+}
+
+/// Cursor-paged subscriptions owned by `subscriber`, read from the subscriber index, or
+/// by scanning IDs until `migrate_storage_keys` has built it.
+///
+/// `total_hint` is the subscriber's live subscription count.
+///
+/// # Errors
+/// - `InvalidInput` if `cursor.limit` is 0 or greater than [`MAX_ITER_LIMIT`].
+pub fn page_subscriber_subscriptions(
+    env: &Env,
+    subscriber: Address,
+    cursor: Cursor,
+) -> Result<SubscriptionPage, Error> {
+    let total = crate::subscription::get_subscriber_subscription_count(env, &subscriber);
+    if !crate::storage_migration::subscriber_index_ready(env) {
+        return scan_page(env, &cursor, Some(total), |sub| {
+            sub.subscriber == subscriber
+        });
+    }
+    check_cursor(&cursor)?;
+
+    let ids = crate::subscription::get_subscriber_subscription_ids(env, &subscriber);
+    let mut items = Vec::new(env);
+    let mut next_cursor = None;
+    for id in ids.iter().filter(|id| *id >= cursor.start_id) {
+        if items.len() == cursor.limit {
+            next_cursor = Some(id);
+            break;
+        }
+        if let Some(sub) = crate::queries::load_subscription(env, id) {
+            items.push_back(subscription_summary(id, sub));
+        }
+    }
+
+    Ok(SubscriptionPage {
+        items,
+        next_cursor,
+        total_hint: Some(total),
+    })
+}
+
+/// Cursor-paged subscriptions currently in `status`. No `total_hint` is given.
+///
+/// # Errors
+/// - `InvalidInput` if `cursor.limit` is 0 or greater than [`MAX_ITER_LIMIT`].
+pub fn page_subscriptions_by_status(
+    env: &Env,
+    status: SubscriptionStatus,
+    cursor: Cursor,
+) -> Result<SubscriptionPage, Error> {
+    scan_page(env, &cursor, None, |sub| sub.status == status)
+}
+
+/// Cursor-paged subscriptions whose interval charge is due now: `Active` or
+/// `GracePeriod` with the next charge timestamp reached. No `total_hint` is given.
+///
+/// # Errors
+/// - `InvalidInput` if `cursor.limit` is 0 or greater than [`MAX_ITER_LIMIT`].
+pub fn page_due_subscriptions(env: &Env, cursor: Cursor) -> Result<SubscriptionPage, Error> {
+    let now = env.ledger().timestamp();
+    scan_page(env, &cursor, None, |sub| {
+        matches!(
+            sub.status,
+            SubscriptionStatus::Active | SubscriptionStatus::GracePeriod
+        ) && compute_next_charge_info(sub).next_charge_timestamp <= now
+    })
+}
+
+/// IDs of `Active` subscriptions whose interval charge is due at `now`, that is
+/// `last_payment_timestamp + interval_seconds <= now`, walking IDs from `start`
+/// (inclusive). `now` is caller-supplied so a billing backend can also look ahead.
+///
+/// Returns up to `limit` IDs and the cursor to pass back as `start` (`None` once every
+/// allocated ID has been visited). At most [`MAX_ITER_SCAN`] IDs are probed per call, so
+/// a page may hold fewer than `limit` IDs while a cursor is still returned.
+///
+/// # Errors
+/// - `InvalidInput` if `limit` is 0 or greater than [`MAX_ITER_LIMIT`].
+pub fn get_due_subscriptions(
+    env: &Env,
+    now: u64,
+    start: u32,
+    limit: u32,
+) -> Result<DueSubscriptionPage, Error> {
+    if limit == 0 || limit > MAX_ITER_LIMIT {
+        return Err(Error::InvalidInput);
+    }
+
+    let next_id = crate::ids::peek_next_id(env);
+    let start = start.max(crate::ids::first_id(env));
+    let scan_end = start.saturating_add(MAX_ITER_SCAN).min(next_id);
+
+    let mut ids = Vec::new(env);
+    let mut id = start;
+    while id < scan_end && ids.len() < limit {
+        if let Some(sub) = crate::queries::load_subscription(env, id) {
+            let due_at = sub
+                .last_payment_timestamp
+                .saturating_add(sub.interval_seconds);
+            if sub.status == SubscriptionStatus::Active && due_at <= now {
+                ids.push_back(id);
+            }
+        }
+        id += 1;
+    }
+
+    let next_cursor = if id < next_id { Some(id) } else { None };
+    Ok((ids, next_cursor))
+}
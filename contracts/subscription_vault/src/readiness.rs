@@ -0,0 +1,95 @@
+//! Payment readiness check for onboarding flows.
+//!
+//! [`check_payment_readiness`] reports, before the first charge fails, whether a
+//! subscription can actually be charged and refunded: whether the prepaid balance or
+//! auto-topup (see `auto_topup.rs`) covers the next interval charge, and whether the
+//! subscriber and merchant can hold the subscription's token. A Stellar account needs
+//! an authorized trustline to the asset, which the vault detects by reading the
+//! account's balance and authorization through the token contract.
+//!
+//! Read-only: nothing is pulled or stored. Secondary token balances and guarantors are
+//! not counted toward the next charge.
+//!
+//! **PRs that only change the readiness check should edit this file only.**
+
+use crate::types::{Error, PaymentReadiness, ReadinessIssue, SubscriptionStatus};
+use soroban_sdk::{token, Address, Env, Vec};
+
+/// Wallet balance of `holder` in `token`, or `None` if the holder cannot hold it (no
+/// trustline, or a trustline the issuer has not authorized).
+fn holding(env: &Env, token: &Address, holder: &Address) -> Option<i128> {
+    let balance = match token::Client::new(env, token).try_balance(holder) {
+        Ok(Ok(balance)) => balance,
+        _ => return None,
+    };
+    // Tokens that are not Stellar assets have no `authorized` flag.
+    if let Ok(Ok(false)) = token::StellarAssetClient::new(env, token).try_authorized(holder) {
+        return None;
+    }
+    Some(balance)
+}
+
+/// Readiness report of the subscription; `NotFound` if it does not exist.
+pub fn check_payment_readiness(env: &Env, subscription_id: u32) -> Result<PaymentReadiness, Error> {
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    let preview = crate::queries::preview_charge(env, subscription_id)?;
+    let token = crate::billing_tokens::subscription_token(env, subscription_id)?;
+    let vault = env.current_contract_address();
+    let subscriber_holding = holding(env, &token, &sub.subscriber);
+    let wallet_balance = subscriber_holding.unwrap_or(0);
+    let allowance = match token::Client::new(env, &token).try_allowance(&sub.subscriber, &vault) {
+        Ok(Ok(allowance)) => allowance,
+        _ => 0,
+    };
+
+    let mut issues = Vec::new(env);
+    if matches!(
+        sub.status,
+        SubscriptionStatus::Paused | SubscriptionStatus::Cancelled
+    ) || preview.blocked_by_expiry
+    {
+        issues.push_back(ReadinessIssue::NotChargeable);
+    }
+    let shortfall = preview
+        .amount_due
+        .saturating_sub(sub.prepaid_balance.max(0))
+        .max(0);
+    if shortfall > 0 {
+        match crate::auto_topup::get_auto_topup(env, subscription_id) {
+            None => issues.push_back(ReadinessIssue::InsufficientFunds),
+            Some(setting) => {
+                let period_index = preview.next_charge_timestamp / sub.interval_seconds.max(1);
+                let pulled = if setting.period_index == period_index {
+                    setting.pulled_in_period
+                } else {
+                    0
+                };
+                if setting.max_pull_per_period.saturating_sub(pulled) < shortfall {
+                    issues.push_back(ReadinessIssue::AutoTopupCapTooLow);
+                }
+                if allowance < shortfall {
+                    issues.push_back(ReadinessIssue::AllowanceTooLow);
+                }
+                if wallet_balance < shortfall {
+                    issues.push_back(ReadinessIssue::WalletBalanceTooLow);
+                }
+            }
+        }
+    }
+    if subscriber_holding.is_none() {
+        issues.push_back(ReadinessIssue::SubscriberCannotReceive);
+    }
+    if holding(env, &token, &sub.merchant).is_none() {
+        issues.push_back(ReadinessIssue::MerchantCannotReceive);
+    }
+
+    Ok(PaymentReadiness {
+        token,
+        amount_due: preview.amount_due,
+        prepaid_balance: sub.prepaid_balance,
+        wallet_balance,
+        allowance,
+        ready: issues.is_empty(),
+        issues,
+    })
+}
@@ -0,0 +1,146 @@
+//! Merchant refunds of earlier charges.
+//!
+//! Every settled charge adds what it credited to the merchant side, its prepaid debit
+//! less any platform fee (see `fees.rs`), to the subscription's charged total. A
+//! merchant can refund up to the charged total less earlier refunds, paid out of its
+//! unlocked, unreserved accrued balance either back into the prepaid vault or to the
+//! subscriber's wallet. Refunds of a bundled subscription are paid by the line-item
+//! merchants by weight, like its charges are credited (see `bundles.rs`).
+//!
+//! **PRs that only change refunds should edit this file only.**
+
+use crate::safe_math::{safe_add_balance, safe_sub_balance};
+use crate::statements::Movement;
+use crate::types::{DataKey, Error, RefundedEvent, SubKey, Subscription};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn charged_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::ChargedTotal, subscription_id)
+}
+
+fn refunded_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::RefundedTotal, subscription_id)
+}
+
+fn get_total(env: &Env, key: &DataKey) -> i128 {
+    env.storage().instance().get(key).unwrap_or(0)
+}
+
+/// Adds a settled charge's merchant credit to the subscription's charged total.
+pub fn record_charge(env: &Env, subscription_id: u32, amount: i128) -> Result<(), Error> {
+    let key = charged_key(subscription_id);
+    let total = safe_add_balance(get_total(env, &key), amount)?;
+    env.storage().instance().set(&key, &total);
+    Ok(())
+}
+
+/// Amount the merchant can still refund: everything charged less earlier refunds.
+pub fn get_refundable_amount(env: &Env, subscription_id: u32) -> i128 {
+    get_total(env, &charged_key(subscription_id))
+        .saturating_sub(get_total(env, &refunded_key(subscription_id)))
+}
+
+/// Converts the charged and refunded totals into another billing token's units when the
+/// subscription is migrated (see `token_migration.rs`).
+pub fn convert_totals(
+    env: &Env,
+    subscription_id: u32,
+    convert: impl Fn(i128) -> Result<i128, Error>,
+) -> Result<(), Error> {
+    for key in [charged_key(subscription_id), refunded_key(subscription_id)] {
+        if let Some(total) = env.storage().instance().get::<_, i128>(&key) {
+            env.storage().instance().set(&key, &convert(total)?);
+        }
+    }
+    Ok(())
+}
+
+/// Merchant returns `amount` of earlier charges to the subscriber, into the prepaid
+/// balance or, with `to_wallet`, straight to the subscriber's wallet.
+///
+/// Fails with `RefundExceedsCharged` above [`get_refundable_amount`] and with
+/// `InsufficientMerchantBalance` if the merchant's withdrawable balance cannot cover it.
+pub fn do_refund_charge(
+    env: &Env,
+    subscription_id: u32,
+    amount: i128,
+    merchant: Address,
+    to_wallet: bool,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let mut sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.merchant != merchant {
+        return Err(Error::Forbidden);
+    }
+    if amount > get_refundable_amount(env, subscription_id) {
+        return Err(Error::RefundExceedsCharged);
+    }
+    apply_refund(env, subscription_id, &mut sub, amount, to_wallet)
+}
+
+/// Pays `amount` back to the subscriber out of the merchant's balance and emits
+/// `refunded`. The caller has checked it against [`get_refundable_amount`]; `sub` is
+/// stored when the refund goes to the prepaid balance.
+pub fn apply_refund(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    amount: i128,
+    to_wallet: bool,
+) -> Result<(), Error> {
+    let merchant = sub.merchant.clone();
+    // Subscriptions billed in another token are refunded from the merchant's balance in
+    // that token (see `billing_tokens.rs`).
+    let billing_token = crate::billing_tokens::get_billing_token(env, subscription_id);
+    match &billing_token {
+        Some(token) => {
+            crate::multi_token::debit_merchant_token_balance(env, &merchant, token, amount)?
+        }
+        None => {
+            let shares = crate::bundles::split_charge(env, subscription_id, &merchant, amount)?;
+            for (payer, share) in shares.iter() {
+                crate::statements::record(env, &payer, Movement::Refund, share);
+                crate::merchant::debit_merchant_balance(env, &payer, share)?;
+            }
+        }
+    }
+
+    let refunded_key = refunded_key(subscription_id);
+    let refunded_total = safe_add_balance(get_total(env, &refunded_key), amount)?;
+    env.storage().instance().set(&refunded_key, &refunded_total);
+
+    if to_wallet {
+        let token = match billing_token {
+            Some(token) => token,
+            None => crate::admin::get_token(env)?,
+        };
+        soroban_sdk::token::Client::new(env, &token).transfer(
+            &env.current_contract_address(),
+            &sub.subscriber,
+            &amount,
+        );
+    } else {
+        sub.prepaid_balance = safe_add_balance(sub.prepaid_balance, amount)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::Sub(subscription_id), sub);
+    }
+
+    env.events().publish(
+        (Symbol::new(env, "refunded"), subscription_id),
+        RefundedEvent {
+            subscription_id,
+            merchant,
+            amount,
+            to_wallet,
+            remaining_refundable: safe_sub_balance(
+                get_total(env, &charged_key(subscription_id)),
+                refunded_total,
+            )?,
+        },
+    );
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Audit-mode event replay consistency checker (`replay` feature).
+//!
+//! Given historical vault events (as recorded by an indexer), recomputes the prepaid
+//! balance of every subscription and the balance of every merchant they touch, and
+//! reports where the recomputed value differs from current storage. This is a debugging
+//! aid for testnet deployments; it is compiled out of default builds.
+//!
+//! Replay starts every balance at zero, so the event list must cover each subject from
+//! its creation. Charges are replayed at face value: multi-token draws, bundle splits,
+//! setup fee installments and one-off charges are not modelled and show up as
+//! mismatches.
+//!
+//! **PRs that only change the replay checker should edit this file only.**
+
+use crate::merchant::get_merchant_balance;
+use crate::queries::{get_archived_subscription, get_subscription};
+use crate::types::{Error, SubscriptionChargedEvent};
+use crate::{SubscriptionVault, SubscriptionVaultArgs, SubscriptionVaultClient};
+use soroban_sdk::{contractimpl, contracttype, Address, Env, Map, Vec};
+
+/// One historical event, mirroring what the vault emits.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub enum ReplayEvent {
+    /// `("deposited", subscription_id)`: `(subscription_id, amount)`.
+    Deposited(u32, i128),
+    /// `("charged",)` payload.
+    Charged(SubscriptionChargedEvent),
+    /// `("withdrawn", merchant)`: `(merchant, amount)`.
+    Withdrawn(Address, i128),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionBalanceMismatch {
+    pub subscription_id: u32,
+    pub expected: i128,
+    pub actual: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantBalanceMismatch {
+    pub merchant: Address,
+    pub expected: i128,
+    pub actual: i128,
+}
+
+/// Result of replaying an event list against current storage.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReplayReport {
+    pub events_applied: u32,
+    pub subscriptions: Vec<SubscriptionBalanceMismatch>,
+    pub merchants: Vec<MerchantBalanceMismatch>,
+}
+
+fn apply(map_value: Option<i128>, delta: i128) -> Result<i128, Error> {
+    map_value
+        .unwrap_or(0)
+        .checked_add(delta)
+        .ok_or(Error::Overflow)
+}
+
+pub fn replay_events(env: &Env, events: Vec<ReplayEvent>) -> Result<ReplayReport, Error> {
+    let mut prepaid: Map<u32, i128> = Map::new(env);
+    let mut merchants: Map<Address, i128> = Map::new(env);
+
+    for event in events.iter() {
+        match event {
+            ReplayEvent::Deposited(id, amount) => {
+                prepaid.set(id, apply(prepaid.get(id), amount)?);
+            }
+            ReplayEvent::Charged(charged) => {
+                let id = charged.subscription_id;
+                let debit = charged.amount.checked_neg().ok_or(Error::Overflow)?;
+                prepaid.set(id, apply(prepaid.get(id), debit)?);
+                let credited = apply(merchants.get(charged.merchant.clone()), charged.amount)?;
+                merchants.set(charged.merchant, credited);
+            }
+            ReplayEvent::Withdrawn(merchant, amount) => {
+                let debit = amount.checked_neg().ok_or(Error::Overflow)?;
+                let balance = apply(merchants.get(merchant.clone()), debit)?;
+                merchants.set(merchant, balance);
+            }
+        }
+    }
+
+    let mut report = ReplayReport {
+        events_applied: events.len(),
+        subscriptions: Vec::new(env),
+        merchants: Vec::new(env),
+    };
+    for (subscription_id, expected) in prepaid.iter() {
+        let actual = get_subscription(env, subscription_id)
+            .or_else(|_| get_archived_subscription(env, subscription_id))
+            .map(|sub| sub.prepaid_balance)
+            .unwrap_or(0);
+        if actual != expected {
+            report.subscriptions.push_back(SubscriptionBalanceMismatch {
+                subscription_id,
+                expected,
+                actual,
+            });
+        }
+    }
+    for (merchant, expected) in merchants.iter() {
+        let actual = get_merchant_balance(env, &merchant);
+        if actual != expected {
+            report.merchants.push_back(MerchantBalanceMismatch {
+                merchant,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[contractimpl]
+impl SubscriptionVault {
+    /// Replay historical events and report balances that disagree with storage.
+    /// Read-only; only available in builds with the `replay` feature.
+    pub fn replay_events(env: Env, events: Vec<ReplayEvent>) -> Result<ReplayReport, Error> {
+        replay_events(&env, events)
+    }
+}
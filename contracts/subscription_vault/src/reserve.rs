@@ -0,0 +1,174 @@
+//! Rolling reserve: hold back part of a merchant's charge proceeds for a fixed period.
+//!
+//! For merchants the admin marks as higher risk, `bps` of every credited charge is
+//! placed in a timestamped reserve bucket. Reserved funds stay in the merchant's
+//! balance but are excluded from withdrawals until the bucket's `release_at` passes;
+//! release is automatic (buckets are evaluated against the ledger time).
+//!
+//! **PRs that only change reserve handling should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::fees::MAX_BPS;
+use crate::safe_math::safe_add_balance;
+use crate::types::{DataKey, Error, ReserveBucket, ReserveConfig};
+use soroban_sdk::{Address, Env, Symbol, Vec};
+
+/// Holds created within the same day are merged into one bucket to bound storage.
+const BUCKET_GRANULARITY_SECONDS: u64 = 24 * 60 * 60;
+
+pub fn get_reserve_config(env: &Env, merchant: &Address) -> Option<ReserveConfig> {
+    env.storage()
+        .instance()
+        .get(&DataKey::ReserveConfig(merchant.clone()))
+}
+
+/// Admin sets the merchant's rolling reserve. `bps == 0` stops new holds; existing
+/// buckets still release on schedule.
+pub fn do_set_merchant_reserve(
+    env: &Env,
+    admin: Address,
+    merchant: Address,
+    bps: u32,
+    reserve_period_seconds: u64,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    if bps > MAX_BPS || (bps > 0 && reserve_period_seconds == 0) {
+        return Err(Error::InvalidInput);
+    }
+
+    let key = DataKey::ReserveConfig(merchant.clone());
+    if bps == 0 {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(
+            &key,
+            &ReserveConfig {
+                bps,
+                reserve_period_seconds,
+            },
+        );
+    }
+
+    env.events().publish(
+        (Symbol::new(env, "reserve_configured"), merchant),
+        (bps, reserve_period_seconds),
+    );
+    Ok(())
+}
+
+pub fn get_reserve_buckets(env: &Env, merchant: &Address) -> Vec<ReserveBucket> {
+    env.storage()
+        .instance()
+        .get(&DataKey::ReserveBuckets(merchant.clone()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Amount of the merchant's balance still held in unreleased reserve buckets.
+pub fn get_reserved_balance(env: &Env, merchant: &Address) -> i128 {
+    let now = env.ledger().timestamp();
+    get_reserve_buckets(env, merchant)
+        .iter()
+        .filter(|b| b.release_at > now)
+        .map(|b| b.amount)
+        .sum()
+}
+
+/// Places the configured share of a credited charge `amount` into a reserve bucket.
+pub fn hold_reserve(env: &Env, merchant: &Address, amount: i128) -> Result<(), Error> {
+    let Some(config) = get_reserve_config(env, merchant) else {
+        return Ok(());
+    };
+    let held = amount
+        .checked_mul(config.bps as i128)
+        .ok_or(Error::Overflow)?
+        / MAX_BPS as i128;
+    if held == 0 {
+        return Ok(());
+    }
+
+    let release_at = env
+        .ledger()
+        .timestamp()
+        .checked_add(config.reserve_period_seconds)
+        .ok_or(Error::Overflow)?;
+    let mut buckets = get_reserve_buckets(env, merchant);
+    match buckets.last() {
+        Some(mut last)
+            if last.release_at / BUCKET_GRANULARITY_SECONDS
+                == release_at / BUCKET_GRANULARITY_SECONDS =>
+        {
+            last.amount = safe_add_balance(last.amount, held)?;
+            last.release_at = last.release_at.max(release_at);
+            buckets.set(buckets.len() - 1, last);
+        }
+        _ => buckets.push_back(ReserveBucket {
+            amount: held,
+            release_at,
+        }),
+    }
+    env.storage()
+        .instance()
+        .set(&DataKey::ReserveBuckets(merchant.clone()), &buckets);
+    Ok(())
+}
+
+/// Takes `amount` out of unreleased buckets, soonest release first (expedited withdrawals).
+pub fn consume_reserve(env: &Env, merchant: &Address, amount: i128) -> Result<(), Error> {
+    let now = env.ledger().timestamp();
+    let mut remaining = amount;
+    let mut kept = Vec::new(env);
+    for mut bucket in get_reserve_buckets(env, merchant).iter() {
+        if bucket.release_at > now && remaining > 0 {
+            let taken = remaining.min(bucket.amount);
+            bucket.amount -= taken;
+            remaining -= taken;
+        }
+        if bucket.amount > 0 {
+            kept.push_back(bucket);
+        }
+    }
+    if remaining > 0 {
+        return Err(Error::InsufficientBalance);
+    }
+
+    let key = DataKey::ReserveBuckets(merchant.clone());
+    if kept.is_empty() {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &kept);
+    }
+    Ok(())
+}
+
+/// Drops matured buckets from storage. Their funds are already withdrawable; this only
+/// keeps the bucket list short and announces the release.
+pub fn release_matured(env: &Env, merchant: &Address) {
+    let now = env.ledger().timestamp();
+    let buckets = get_reserve_buckets(env, merchant);
+    let mut pending = Vec::new(env);
+    let mut released = 0i128;
+    for bucket in buckets.iter() {
+        if bucket.release_at > now {
+            pending.push_back(bucket);
+        } else {
+            released += bucket.amount;
+        }
+    }
+    if released == 0 {
+        return;
+    }
+
+    let key = DataKey::ReserveBuckets(merchant.clone());
+    if pending.is_empty() {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &pending);
+    }
+    env.events().publish(
+        (Symbol::new(env, "reserve_released"), merchant.clone()),
+        released,
+    );
+}
@@ -0,0 +1,112 @@
+//! External risk scoring of high-value charges.
+//!
+//! The admin can register a risk-oracle contract with [`do_set_risk_oracle`]. Before
+//! any charge above the configured threshold is settled, the vault calls
+//! `assess_charge(subscription_id, subscriber, merchant, amount) -> bool` on it. `true`
+//! lets the charge through. `false` blocks it with `RiskDeclined`, a `risk_declined`
+//! event and an error context (see `error_context.rs`). Fraud teams can change their
+//! rules by redeploying the oracle instead of the vault.
+//!
+//! A failing transaction rolls back its events and writes, so the event and the context
+//! only survive where the decline is absorbed: in `batch_charge`. A direct charge call
+//! that is declined leaves nothing behind and reports `RiskDeclined` to its caller only.
+//!
+//! The check fails closed: an oracle that errors, panics or returns something other
+//! than a `bool` declines the charge. The call runs on the charging transaction's
+//! budget, like charge notifications (see `notify.rs`). Soroban rejects re-entry into
+//! the vault, so the oracle can only read what it is given.
+//!
+//! **PRs that only change risk checks should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::error_context::record as record_error;
+use crate::types::{ConfigKey, DataKey, Error, RiskOracle, Subscription};
+use soroban_sdk::{vec, Address, Env, IntoVal, InvokeError, Symbol};
+
+fn oracle_key() -> DataKey {
+    DataKey::Config(ConfigKey::RiskOracle)
+}
+
+fn require_admin_auth(env: &Env, admin: &Address) -> Result<(), Error> {
+    admin.require_auth();
+    if *admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+/// Registered risk oracle and its threshold, if any.
+pub fn get_risk_oracle(env: &Env) -> Option<RiskOracle> {
+    env.storage().instance().get(&oracle_key())
+}
+
+/// Admin registers `oracle`, replacing any earlier one. Charges above `threshold` are
+/// checked with it. A negative threshold fails with `InvalidAmount`.
+pub fn do_set_risk_oracle(
+    env: &Env,
+    admin: Address,
+    oracle: Address,
+    threshold: i128,
+) -> Result<(), Error> {
+    require_admin_auth(env, &admin)?;
+    if threshold < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let config = RiskOracle { oracle, threshold };
+    env.storage().instance().set(&oracle_key(), &config);
+    env.events()
+        .publish((Symbol::new(env, "risk_oracle_set"),), config);
+    Ok(())
+}
+
+/// Admin stops consulting a risk oracle.
+pub fn do_remove_risk_oracle(env: &Env, admin: Address) -> Result<(), Error> {
+    require_admin_auth(env, &admin)?;
+    env.storage().instance().remove(&oracle_key());
+    env.events()
+        .publish((Symbol::new(env, "risk_oracle_removed"),), admin);
+    Ok(())
+}
+
+/// Fails with `RiskDeclined`, emitting `risk_declined` and recording the error context,
+/// if `amount` is above the threshold and the oracle does not approve it. Both are
+/// rolled back with a direct call; see the module docs.
+pub fn ensure_approved(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    amount: i128,
+) -> Result<(), Error> {
+    let Some(config) = get_risk_oracle(env) else {
+        return Ok(());
+    };
+    if amount <= config.threshold {
+        return Ok(());
+    }
+    let args = vec![
+        env,
+        subscription_id.into_val(env),
+        sub.subscriber.into_val(env),
+        sub.merchant.into_val(env),
+        amount.into_val(env),
+    ];
+    let approved = env.try_invoke_contract::<bool, InvokeError>(
+        &config.oracle,
+        &Symbol::new(env, "assess_charge"),
+        args,
+    );
+    if let Ok(Ok(true)) = approved {
+        return Ok(());
+    }
+    env.events().publish(
+        (Symbol::new(env, "risk_declined"), subscription_id),
+        (config.oracle, amount),
+    );
+    Err(record_error(
+        env,
+        subscription_id,
+        Error::RiskDeclined,
+        amount,
+        config.threshold,
+    ))
+}
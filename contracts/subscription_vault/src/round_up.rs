@@ -0,0 +1,144 @@
+//! Subscriber opt-in round-up donations.
+//!
+//! A subscriber can ask for each interval charge to be rounded up to a multiple of a
+//! chosen unit. The difference is debited from the prepaid balance after the charge
+//! settles and transferred straight to the donation address they picked; the merchant
+//! is credited the unrounded amount as before. If the prepaid balance cannot cover the
+//! round-up, or the donation address cannot receive it, the charge goes through
+//! unrounded. Donated totals are kept per subscriber.
+//!
+//! **PRs that only change round-up donations should edit this file only.**
+
+use crate::safe_math::{safe_add_balance, safe_sub_balance};
+use crate::types::{DataKey, Error, RecordKey, RoundUp, SubKey, Subscription};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn round_up_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::RoundUp, subscription_id)
+}
+
+fn donated_key(subscriber: &Address) -> DataKey {
+    DataKey::Record(RecordKey::Donated(subscriber.clone()))
+}
+
+fn load_owned(env: &Env, subscription_id: u32, subscriber: &Address) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != *subscriber {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+/// Round-up setting of the subscription, or `None` if the subscriber has not opted in.
+pub fn get_round_up(env: &Env, subscription_id: u32) -> Option<RoundUp> {
+    env.storage().instance().get(&round_up_key(subscription_id))
+}
+
+/// Total the subscriber has donated through round-ups, across all their subscriptions.
+pub fn get_donated_total(env: &Env, subscriber: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&donated_key(subscriber))
+        .unwrap_or(0)
+}
+
+/// Subscriber opts in to rounding each interval charge up to a multiple of `unit`,
+/// donating the difference to `recipient`. Replaces any earlier setting.
+pub fn do_enable_round_up(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    unit: i128,
+    recipient: Address,
+) -> Result<(), Error> {
+    load_owned(env, subscription_id, &subscriber)?;
+    if unit <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let setting = RoundUp {
+        unit,
+        recipient: recipient.clone(),
+    };
+    env.storage()
+        .instance()
+        .set(&round_up_key(subscription_id), &setting);
+    env.events().publish(
+        (Symbol::new(env, "round_up_enabled"), subscription_id),
+        (unit, recipient),
+    );
+    Ok(())
+}
+
+/// Subscriber stops rounding up their charges.
+pub fn do_disable_round_up(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+) -> Result<(), Error> {
+    load_owned(env, subscription_id, &subscriber)?;
+    env.storage()
+        .instance()
+        .remove(&round_up_key(subscription_id));
+    env.events().publish(
+        (Symbol::new(env, "round_up_disabled"), subscription_id),
+        subscriber,
+    );
+    Ok(())
+}
+
+/// Amount needed to bring `amount` up to the next multiple of `unit`.
+pub fn round_up_amount(amount: i128, unit: i128) -> i128 {
+    if unit <= 0 || amount <= 0 {
+        return 0;
+    }
+    match amount % unit {
+        0 => 0,
+        rem => unit - rem,
+    }
+}
+
+/// Takes the round-up on an interval charge of `amount_due` from `sub`'s prepaid balance
+/// and sends it to the donation address. Skipped when the subscriber has not opted in,
+/// the charge is already a multiple of the unit, or the balance cannot cover it. A
+/// transfer the token refuses emits `round_up_failed` and leaves the charge unrounded.
+/// The caller stores `sub`.
+pub fn apply(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    amount_due: i128,
+) -> Result<(), Error> {
+    let setting = match get_round_up(env, subscription_id) {
+        Some(setting) => setting,
+        None => return Ok(()),
+    };
+    let donation = round_up_amount(amount_due, setting.unit);
+    if donation == 0 || donation > sub.prepaid_balance {
+        return Ok(());
+    }
+
+    let token = crate::billing_tokens::subscription_token(env, subscription_id)?;
+    let transfer = soroban_sdk::token::Client::new(env, &token).try_transfer(
+        &env.current_contract_address(),
+        &setting.recipient,
+        &donation,
+    );
+    if !matches!(transfer, Ok(Ok(()))) {
+        env.events().publish(
+            (Symbol::new(env, "round_up_failed"), subscription_id),
+            (setting.recipient, donation),
+        );
+        return Ok(());
+    }
+    sub.prepaid_balance = safe_sub_balance(sub.prepaid_balance, donation)?;
+    let total = safe_add_balance(get_donated_total(env, &sub.subscriber), donation)?;
+    env.storage()
+        .instance()
+        .set(&donated_key(&sub.subscriber), &total);
+    env.events().publish(
+        (Symbol::new(env, "round_up_donated"), subscription_id),
+        (setting.recipient, donation),
+    );
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! Sandbox (test mode) subscriptions for integration testing against production.
+//!
+//! A merchant designates sandbox agents, which can create test mode subscriptions on
+//! the merchant's behalf. These run the full lifecycle (charges, pause, resume, cancel)
+//! but with an amount of zero, so no funds move. The amount the integration meant to
+//! charge is kept as the mock amount. The subscriber authorizes the subscription along
+//! with the agent. Test mode subscriptions stay out of the merchant's subscription index,
+//! per-merchant limit and billing SLA report, and out of the subscriber's subscription
+//! count and entitlements, so they never lock a subscriber out of live subscriptions.
+//!
+//! **PRs that only change sandbox subscriptions should edit this file only.**
+
+use crate::safe_math::validate_non_negative;
+use crate::types::{DataKey, Error, RecordKey, SubKey, Subscription, SubscriptionStatus};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn agent_key(merchant: &Address, agent: &Address) -> DataKey {
+    DataKey::Record(RecordKey::SandboxAgent(merchant.clone(), agent.clone()))
+}
+
+fn test_mode_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::TestMode, subscription_id)
+}
+
+/// Whether `agent` may create sandbox subscriptions for `merchant`.
+pub fn is_sandbox_agent(env: &Env, merchant: &Address, agent: &Address) -> bool {
+    env.storage()
+        .instance()
+        .get(&agent_key(merchant, agent))
+        .unwrap_or(false)
+}
+
+/// Merchant designates or removes a sandbox agent.
+pub fn do_set_sandbox_agent(
+    env: &Env,
+    merchant: Address,
+    agent: Address,
+    enabled: bool,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    let key = agent_key(&merchant, &agent);
+    if enabled {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events().publish(
+        (Symbol::new(env, "sandbox_agent_set"), merchant),
+        (agent, enabled),
+    );
+    Ok(())
+}
+
+/// Mock amount of a test mode subscription, or `None` for a live subscription.
+pub fn get_mock_amount(env: &Env, subscription_id: u32) -> Option<i128> {
+    env.storage()
+        .instance()
+        .get(&test_mode_key(subscription_id))
+}
+
+/// Whether the subscription was created in test mode.
+pub fn is_test_mode(env: &Env, subscription_id: u32) -> bool {
+    get_mock_amount(env, subscription_id).is_some()
+}
+
+/// Sandbox agent creates a test mode subscription for `merchant`, co-signed by the
+/// subscriber. The stored amount is zero; `mock_amount` is recorded for display only.
+pub fn do_create_sandbox_subscription(
+    env: &Env,
+    agent: Address,
+    subscriber: Address,
+    merchant: Address,
+    mock_amount: i128,
+    interval_seconds: u64,
+) -> Result<u32, Error> {
+    agent.require_auth();
+    subscriber.require_auth();
+    if !is_sandbox_agent(env, &merchant, &agent) {
+        return Err(Error::Forbidden);
+    }
+    validate_non_negative(mock_amount)?;
+
+    let sub = Subscription {
+        subscriber,
+        merchant,
+        amount: 0,
+        interval_seconds,
+        last_payment_timestamp: env.ledger().timestamp(),
+        status: SubscriptionStatus::Active,
+        prepaid_balance: 0,
+        usage_enabled: false,
+    };
+    let id = crate::ids::next_id(env)?;
+    let storage = env.storage().instance();
+    storage.set(&DataKey::Sub(id), &sub);
+    storage.set(&test_mode_key(id), &mock_amount);
+
+    env.events().publish(
+        (Symbol::new(env, "sandbox_sub_created"), id),
+        (agent, mock_amount),
+    );
+    Ok(id)
+}
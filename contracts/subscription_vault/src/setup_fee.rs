@@ -0,0 +1,183 @@
+//! One-time setup fees amortized over the first interval charges.
+//!
+//! A setup fee is fixed when the plan or subscription is created and cannot be changed
+//! afterwards. Each interval charge adds the next installment on top of the
+//! (loyalty-discounted) base amount; the charge succeeds or fails as a whole. The
+//! installment is reported by its own `setup_fee_charged` event next to the regular
+//! `charged` event, which keeps reporting the base amount only.
+//!
+//! **PRs that only change setup fee amortization should edit this file only.**
+
+use crate::types::{DataKey, Error, SetupFee};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn new_schedule(total: i128, installments: u32) -> Result<SetupFee, Error> {
+    if total < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if total > 0 && installments == 0 {
+        return Err(Error::InvalidInput);
+    }
+    Ok(SetupFee {
+        total,
+        installments,
+        paid_installments: 0,
+        collected: 0,
+    })
+}
+
+/// Setup fee schedule of the subscription, or `None` if it has no setup fee.
+pub fn get_setup_fee(env: &Env, subscription_id: u32) -> Option<SetupFee> {
+    env.storage()
+        .instance()
+        .get(&DataKey::SetupFee(subscription_id))
+}
+
+/// Setup fee terms of a plan template, or `None` if it has no setup fee.
+pub fn get_plan_setup_fee(env: &Env, plan_template_id: u32) -> Option<SetupFee> {
+    env.storage()
+        .instance()
+        .get(&DataKey::PlanSetupFee(plan_template_id))
+}
+
+/// Converts the subscription's setup fee total and collected amount into another billing
+/// token's units when it is migrated (see `token_migration.rs`). The remaining
+/// installments follow from the converted total.
+pub fn convert_schedule(
+    env: &Env,
+    subscription_id: u32,
+    convert: impl Fn(i128) -> Result<i128, Error>,
+) -> Result<(), Error> {
+    let Some(mut fee) = get_setup_fee(env, subscription_id) else {
+        return Ok(());
+    };
+    fee.total = convert(fee.total)?;
+    fee.collected = convert(fee.collected)?.min(fee.total);
+    env.storage()
+        .instance()
+        .set(&DataKey::SetupFee(subscription_id), &fee);
+    Ok(())
+}
+
+/// Subscriber creates a subscription whose first `installments` interval charges each
+/// also collect a share of `setup_fee`. Usage charging is disabled.
+pub fn do_create_subscription_with_setup_fee(
+    env: &Env,
+    subscriber: Address,
+    merchant: Address,
+    amount: i128,
+    interval_seconds: u64,
+    setup_fee: i128,
+    installments: u32,
+) -> Result<u32, Error> {
+    let schedule = new_schedule(setup_fee, installments)?;
+    let id = crate::subscription::do_create_subscription(
+        env,
+        subscriber,
+        merchant,
+        amount,
+        interval_seconds,
+        false,
+    )?;
+    if schedule.total > 0 {
+        env.storage()
+            .instance()
+            .set(&DataKey::SetupFee(id), &schedule);
+    }
+    Ok(id)
+}
+
+/// Merchant creates a plan template carrying a setup fee, inherited by every
+/// subscription created from it.
+pub fn do_create_plan_template_with_setup_fee(
+    env: &Env,
+    merchant: Address,
+    amount: i128,
+    interval_seconds: u64,
+    usage_enabled: bool,
+    setup_fee: i128,
+    installments: u32,
+) -> Result<u32, Error> {
+    let schedule = new_schedule(setup_fee, installments)?;
+    let plan_id = crate::subscription::do_create_plan_template(
+        env,
+        merchant,
+        amount,
+        interval_seconds,
+        usage_enabled,
+    )?;
+    if schedule.total > 0 {
+        env.storage()
+            .instance()
+            .set(&DataKey::PlanSetupFee(plan_id), &schedule);
+    }
+    Ok(plan_id)
+}
+
+/// Copies the plan's setup fee terms, if any, to a subscription created from it.
+pub fn inherit_plan_setup_fee(env: &Env, plan_template_id: u32, subscription_id: u32) {
+    if let Some(schedule) = get_plan_setup_fee(env, plan_template_id) {
+        env.storage()
+            .instance()
+            .set(&DataKey::SetupFee(subscription_id), &schedule);
+    }
+}
+
+/// Installment the next interval charge adds on top of the base amount; 0 once the
+/// setup fee is fully collected or if there is none.
+pub fn next_installment(env: &Env, subscription_id: u32) -> i128 {
+    installment_ahead(env, subscription_id, 0)
+}
+
+/// Installment added by the interval charge `charges_ahead` charges after the next one.
+pub fn installment_ahead(env: &Env, subscription_id: u32, charges_ahead: u32) -> i128 {
+    let fee = match get_setup_fee(env, subscription_id) {
+        Some(fee) => fee,
+        None => return 0,
+    };
+    let index = fee.paid_installments.saturating_add(charges_ahead);
+    let share = fee.total / fee.installments as i128;
+    if index >= fee.installments {
+        0
+    } else if index + 1 == fee.installments {
+        // The last installment takes the rounding remainder.
+        fee.total - share * (fee.installments as i128 - 1)
+    } else {
+        share
+    }
+}
+
+/// Installments added by the next `charges` interval charges together.
+pub fn installments_ahead_total(env: &Env, subscription_id: u32, charges: u32) -> i128 {
+    let fee = match get_setup_fee(env, subscription_id) {
+        Some(fee) => fee,
+        None => return 0,
+    };
+    let remaining = fee.installments.saturating_sub(fee.paid_installments);
+    let share = fee.total / fee.installments as i128;
+    if charges >= remaining {
+        fee.total - share * fee.paid_installments.min(fee.installments) as i128
+    } else {
+        share * charges as i128
+    }
+}
+
+/// Books `amount` as the next installment after a successful interval charge and
+/// emits `setup_fee_charged` with `(installment number, amount, remaining)`.
+pub fn record_installment(env: &Env, subscription_id: u32, amount: i128) -> Result<(), Error> {
+    let mut fee = match get_setup_fee(env, subscription_id) {
+        Some(fee) => fee,
+        None => return Ok(()),
+    };
+    fee.paid_installments += 1;
+    fee.collected = fee.collected.checked_add(amount).ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&DataKey::SetupFee(subscription_id), &fee);
+
+    env.events().publish(
+        (Symbol::new(env, "setup_fee_charged"), subscription_id),
+        (fee.paid_installments, amount, fee.total - fee.collected),
+    );
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Subscriber-requested skips of one billing period.
+//!
+//! A subscriber marks the next interval charge to be skipped. When that charge comes
+//! due, `charge_core` books the period as handled without debiting anything and moves
+//! the schedule on by one interval. Merchants cap how many skips a subscriber may
+//! request per subscription in a rolling 365-day window; the default cap of 0 leaves
+//! skipping off.
+//!
+//! **PRs that only change charge skipping should edit this file only.**
+
+use crate::types::{DataKey, Error, MerchantKey, SkipRecord, SubKey, SubscriptionStatus};
+use soroban_sdk::{Address, Env, Symbol};
+
+/// Length of the window the merchant's skip cap applies to.
+pub const SKIP_WINDOW_SECONDS: u64 = 365 * 24 * 60 * 60;
+
+fn max_skips_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::MaxSkips, merchant.clone())
+}
+
+fn record_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::Skips, subscription_id)
+}
+
+/// Skips per subscription the merchant allows in any 365-day window.
+pub fn get_max_skips_per_year(env: &Env, merchant: &Address) -> u32 {
+    env.storage()
+        .instance()
+        .get(&max_skips_key(merchant))
+        .unwrap_or(0)
+}
+
+/// Merchant sets how many periods a subscriber may skip per year; 0 disables skipping.
+pub fn do_set_max_skips_per_year(env: &Env, merchant: Address, max: u32) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    env.storage()
+        .instance()
+        .set(&max_skips_key(&merchant), &max);
+    env.events()
+        .publish((Symbol::new(env, "max_skips_set"), merchant), max);
+    Ok(())
+}
+
+/// Skip history of the subscription.
+pub fn get_skip_record(env: &Env, subscription_id: u32) -> SkipRecord {
+    env.storage()
+        .instance()
+        .get(&record_key(subscription_id))
+        .unwrap_or(SkipRecord {
+            pending: false,
+            window_start: 0,
+            window_skips: 0,
+            total_skipped: 0,
+            last_skipped_period: 0,
+        })
+}
+
+/// Subscriber asks for the next interval charge of an active subscription to be skipped.
+///
+/// Fails with `SkipAlreadyPending` if a skip is already waiting and with
+/// `SkipLimitReached` once the merchant's yearly cap is used up.
+pub fn do_skip_next_charge(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Forbidden);
+    }
+    if sub.status != SubscriptionStatus::Active {
+        return Err(Error::NotActive);
+    }
+
+    let mut record = get_skip_record(env, subscription_id);
+    if record.pending {
+        return Err(Error::SkipAlreadyPending);
+    }
+    let now = env.ledger().timestamp();
+    if record.window_skips == 0 || now >= record.window_start.saturating_add(SKIP_WINDOW_SECONDS) {
+        record.window_start = now;
+        record.window_skips = 0;
+    }
+    if record.window_skips >= get_max_skips_per_year(env, &sub.merchant) {
+        return Err(Error::SkipLimitReached);
+    }
+    record.window_skips += 1;
+    record.pending = true;
+    env.storage()
+        .instance()
+        .set(&record_key(subscription_id), &record);
+
+    env.events().publish(
+        (Symbol::new(env, "skip_requested"), subscription_id),
+        record.window_skips,
+    );
+    Ok(())
+}
+
+/// Consumes a pending skip for the due charge of `period_index`. Returns `true` if the
+/// charge is to be skipped; the caller then advances the schedule without debiting.
+pub fn take_pending(env: &Env, subscription_id: u32, period_index: u64) -> bool {
+    let mut record = get_skip_record(env, subscription_id);
+    if !record.pending {
+        return false;
+    }
+    record.pending = false;
+    record.total_skipped = record.total_skipped.saturating_add(1);
+    record.last_skipped_period = period_index;
+    env.storage()
+        .instance()
+        .set(&record_key(subscription_id), &record);
+
+    env.events().publish(
+        (Symbol::new(env, "charge_skipped"), subscription_id),
+        period_index,
+    );
+    true
+}
@@ -0,0 +1,45 @@
+//! Billing SLA tracking: how late interval charges land relative to their due time.
+//!
+//! Lateness is aggregated per merchant per SLA period (`charged_at / SLA_PERIOD_SECONDS`)
+//! so merchants can show when the shared billing agent under-serves them. Only charges
+//! taken by the billing path are recorded; subscriber `pay_now` charges are not.
+//!
+//! **PRs that only change SLA reporting should edit this file only.**
+
+use crate::types::{BillingSla, DataKey};
+use soroban_sdk::{Address, Env};
+
+/// Length of one SLA reporting period in seconds (30 days).
+pub const SLA_PERIOD_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+/// SLA period index containing `timestamp`.
+pub fn sla_period(timestamp: u64) -> u64 {
+    timestamp / SLA_PERIOD_SECONDS
+}
+
+/// Returns the lateness totals for `merchant` in `period` (zeroed if nothing was charged).
+pub fn get_billing_sla(env: &Env, merchant: Address, period: u64) -> BillingSla {
+    env.storage()
+        .instance()
+        .get(&DataKey::BillingSla(merchant, period))
+        .unwrap_or(BillingSla {
+            charge_count: 0,
+            total_lateness: 0,
+            max_lateness: 0,
+        })
+}
+
+/// Records an interval charge for `merchant` that fell due at `due_at` and landed at `charged_at`.
+pub fn record_charge_lateness(env: &Env, merchant: &Address, due_at: u64, charged_at: u64) {
+    let lateness = charged_at.saturating_sub(due_at);
+    let period = sla_period(charged_at);
+
+    let mut sla = get_billing_sla(env, merchant.clone(), period);
+    sla.charge_count = sla.charge_count.saturating_add(1);
+    sla.total_lateness = sla.total_lateness.saturating_add(lateness);
+    sla.max_lateness = sla.max_lateness.max(lateness);
+
+    env.storage()
+        .instance()
+        .set(&DataKey::BillingSla(merchant.clone(), period), &sla);
+}
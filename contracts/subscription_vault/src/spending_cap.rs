@@ -0,0 +1,115 @@
+//! Subscriber-set cap on what one billing period may cost.
+//!
+//! A subscriber can bound the combined interval and usage charges of a single billing
+//! period, so a misbehaving meter or a raised plan amount cannot drain the prepaid
+//! balance. A period starts at the interval charge that opens it (the new
+//! `last_payment_timestamp`) and lasts until the next one; usage charges count towards
+//! the period they fall in. A charge that would take the period past the cap fails with
+//! `SpendingCapExceeded` and debits nothing.
+//!
+//! **PRs that only change spending caps should edit this file only.**
+
+use crate::error_context::record as record_error;
+use crate::types::{DataKey, Error, SubKey};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn cap_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::SpendingCap, subscription_id)
+}
+
+fn spend_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::PeriodSpend, subscription_id)
+}
+
+/// Cap per billing period, or `None` if the subscriber set none.
+pub fn get_spending_cap(env: &Env, subscription_id: u32) -> Option<i128> {
+    env.storage().instance().get(&cap_key(subscription_id))
+}
+
+/// Subscriber caps the combined charges of one billing period; 0 removes the cap.
+pub fn do_set_spending_cap(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    cap_per_period: i128,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Forbidden);
+    }
+    if cap_per_period < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let key = cap_key(subscription_id);
+    if cap_per_period == 0 {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &cap_per_period);
+    }
+    env.events().publish(
+        (Symbol::new(env, "spending_cap_set"), subscription_id),
+        cap_per_period,
+    );
+    Ok(())
+}
+
+/// Amount charged so far in the period starting at `period_start`.
+pub fn get_period_spend(env: &Env, subscription_id: u32, period_start: u64) -> i128 {
+    match env
+        .storage()
+        .instance()
+        .get::<_, (u64, i128)>(&spend_key(subscription_id))
+    {
+        Some((start, spent)) if start == period_start => spent,
+        _ => 0,
+    }
+}
+
+/// Amount charged so far in the subscription's current billing period.
+pub fn get_current_period_spend(env: &Env, subscription_id: u32) -> Result<i128, Error> {
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    Ok(get_period_spend(
+        env,
+        subscription_id,
+        sub.last_payment_timestamp,
+    ))
+}
+
+/// Fails with `SpendingCapExceeded` if charging `amount` in the period starting at
+/// `period_start` would pass the subscription's cap.
+pub fn ensure_within_cap(
+    env: &Env,
+    subscription_id: u32,
+    period_start: u64,
+    amount: i128,
+) -> Result<(), Error> {
+    let Some(cap) = get_spending_cap(env, subscription_id) else {
+        return Ok(());
+    };
+    let total = get_period_spend(env, subscription_id, period_start)
+        .checked_add(amount)
+        .ok_or(Error::Overflow)?;
+    if total > cap {
+        return Err(record_error(
+            env,
+            subscription_id,
+            Error::SpendingCapExceeded,
+            total,
+            cap,
+        ));
+    }
+    Ok(())
+}
+
+/// Adds a settled charge of `amount` to the period starting at `period_start`. Only
+/// tracked while a cap is set.
+pub fn record_spend(env: &Env, subscription_id: u32, period_start: u64, amount: i128) {
+    if get_spending_cap(env, subscription_id).is_none() {
+        return;
+    }
+    let spent = get_period_spend(env, subscription_id, period_start).saturating_add(amount);
+    env.storage()
+        .instance()
+        .set(&spend_key(subscription_id), &(period_start, spent));
+}
@@ -0,0 +1,143 @@
+//! Chunked state export and import for testnet resets (`state-chunks` feature).
+//!
+//! Dumps live subscriptions together with the accrued balances of their merchants in
+//! chunks of [`STATE_CHUNK_SIZE`] subscription IDs, and loads such chunks into another
+//! deployment. Staging environments can be refreshed this way instead of replaying
+//! thousands of individual calls. It is compiled out of default builds and must not be
+//! enabled for mainnet.
+//!
+//! Only subscription records, their merchant and subscriber indexes and merchant
+//! balances move. Configuration is covered by `export_contract_snapshot`; archived
+//! subscriptions and per-feature state (plans, disputes, reserves, ...) are not carried.
+//! Importing credits balances without moving tokens, so the target vault must be funded
+//! separately.
+//!
+//! **PRs that only change state export/import should edit this file only.**
+
+use crate::merchant::{get_merchant_balance, set_merchant_balance};
+use crate::types::{DataKey, Error, Subscription};
+use crate::{SubscriptionVault, SubscriptionVaultArgs, SubscriptionVaultClient};
+use soroban_sdk::{contractimpl, contracttype, Address, Env, Symbol, Vec};
+
+/// Subscription IDs scanned per exported chunk.
+pub const STATE_CHUNK_SIZE: u32 = 25;
+
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct SubscriptionRecord {
+    pub subscription_id: u32,
+    pub subscription: Subscription,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantBalanceRecord {
+    pub merchant: Address,
+    pub balance: i128,
+}
+
+/// One chunk of exported state. Pass `next_cursor` to the next export call; `None`
+/// means the export is complete.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct StateChunk {
+    pub subscriptions: Vec<SubscriptionRecord>,
+    pub merchant_balances: Vec<MerchantBalanceRecord>,
+    pub next_cursor: Option<u32>,
+}
+
+fn require_admin_caller(env: &Env, admin: &Address) -> Result<(), Error> {
+    admin.require_auth();
+    if *admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+/// Exports the live subscriptions with IDs from `cursor` up to `cursor + STATE_CHUNK_SIZE`,
+/// and the current balance of every merchant they belong to.
+pub fn export_state_chunk(env: &Env, cursor: u32) -> StateChunk {
+    let next_id = crate::ids::peek_next_id(env);
+    let cursor = cursor.max(crate::ids::first_id(env));
+    let end = cursor.saturating_add(STATE_CHUNK_SIZE).min(next_id);
+
+    let mut subscriptions = Vec::new(env);
+    let mut merchant_balances: Vec<MerchantBalanceRecord> = Vec::new(env);
+    for id in cursor..end {
+        let Some(subscription) = crate::queries::load_subscription(env, id) else {
+            continue;
+        };
+        if !merchant_balances
+            .iter()
+            .any(|record| record.merchant == subscription.merchant)
+        {
+            merchant_balances.push_back(MerchantBalanceRecord {
+                merchant: subscription.merchant.clone(),
+                balance: get_merchant_balance(env, &subscription.merchant),
+            });
+        }
+        subscriptions.push_back(SubscriptionRecord {
+            subscription_id: id,
+            subscription,
+        });
+    }
+
+    StateChunk {
+        subscriptions,
+        merchant_balances,
+        next_cursor: if end < next_id { Some(end) } else { None },
+    }
+}
+
+/// Loads a chunk produced by [`export_state_chunk`]. Existing records with the same ID
+/// are overwritten; new ones are added to the merchant and subscriber indexes. Merchant
+/// balances are set, not added, so re-importing a chunk is harmless. Returns the number
+/// of subscriptions imported.
+pub fn import_state_chunk(env: &Env, chunk: StateChunk) -> Result<u32, Error> {
+    let storage = env.storage().instance();
+
+    for record in chunk.subscriptions.iter() {
+        let id = record.subscription_id;
+        let sub = record.subscription;
+        if crate::queries::load_subscription(env, id).is_none() {
+            let index_key = DataKey::MerchantSubs(sub.merchant.clone());
+            let mut ids: Vec<u32> = storage.get(&index_key).unwrap_or(Vec::new(env));
+            ids.push_back(id);
+            storage.set(&index_key, &ids);
+
+            let count_key = DataKey::SubscriberSubCount(sub.subscriber.clone());
+            let count: u32 = storage.get(&count_key).unwrap_or(0);
+            storage.set(&count_key, &count.saturating_add(1));
+            crate::subscription::index_subscriber_subscription(env, &sub.subscriber, id);
+            crate::entitlement::index_subscription(env, id, &sub, None);
+        }
+        storage.set(&DataKey::Sub(id), &sub);
+        crate::ids::mark_allocated(env, id)?;
+    }
+
+    for record in chunk.merchant_balances.iter() {
+        set_merchant_balance(env, &record.merchant, &record.balance);
+    }
+
+    let imported = chunk.subscriptions.len();
+    env.events()
+        .publish((Symbol::new(env, "state_chunk_imported"),), imported);
+    Ok(imported)
+}
+
+#[contractimpl]
+impl SubscriptionVault {
+    /// **ADMIN ONLY**: Export one chunk of contract state starting at `cursor` (0 for the
+    /// first call). Only available in builds with the `state-chunks` feature.
+    pub fn export_state_chunk(env: Env, admin: Address, cursor: u32) -> Result<StateChunk, Error> {
+        require_admin_caller(&env, &admin)?;
+        Ok(export_state_chunk(&env, cursor))
+    }
+
+    /// **ADMIN ONLY**: Load a chunk from `export_state_chunk`. Only available in builds
+    /// with the `state-chunks` feature.
+    pub fn import_state_chunk(env: Env, admin: Address, data: StateChunk) -> Result<u32, Error> {
+        require_admin_caller(&env, &admin)?;
+        import_state_chunk(&env, data)
+    }
+}
@@ -0,0 +1,120 @@
+//! Merchant payout statements, published as events.
+//!
+//! Debits on a merchant's accrued balance are summed per statement period: refunds, fees,
+//! payouts and adjustments such as usage refunded after a dispute. Every payout
+//! (withdrawal, expedited withdrawal or scheduled payout) closes the period after moving
+//! the funds, as does [`do_close_merchant_statement`], and emits a
+//! `MerchantStatementEvent` with the opening and closing accrual, just before the
+//! payout's own event. Accounting integrations can then follow a merchant from events
+//! alone.
+//!
+//! Charges are not tracked as they settle, which would add a write to every charge;
+//! they are derived at close as whatever the other movements do not explain. A
+//! merchant's first period opens at zero.
+//!
+//! Only the vault token's accrual is covered; secondary token balances are not.
+//!
+//! **PRs that only change merchant statements should edit this file only.**
+
+use crate::types::{DataKey, Error, MerchantKey, MerchantStatementEvent, StatementPeriod};
+use soroban_sdk::{Address, Env, Symbol};
+
+/// A tracked movement on the merchant's accrued balance.
+pub enum Movement {
+    Refund,
+    Fee,
+    Payout,
+    /// Signed; negative when the balance is debited.
+    #[cfg_attr(not(feature = "disputes"), allow(dead_code))]
+    Adjustment,
+}
+
+fn period_key(merchant: &Address) -> DataKey {
+    DataKey::Merchant(MerchantKey::StatementPeriod, merchant.clone())
+}
+
+fn open_period(env: &Env, merchant: &Address) -> StatementPeriod {
+    env.storage()
+        .instance()
+        .get(&period_key(merchant))
+        .unwrap_or(StatementPeriod {
+            start: 0,
+            opening: 0,
+            refunds: 0,
+            fees: 0,
+            payouts: 0,
+            adjustments: 0,
+        })
+}
+
+/// Adds `amount` to the merchant's open period.
+pub fn record(env: &Env, merchant: &Address, movement: Movement, amount: i128) {
+    let mut period = open_period(env, merchant);
+    let total = match movement {
+        Movement::Refund => &mut period.refunds,
+        Movement::Fee => &mut period.fees,
+        Movement::Payout => &mut period.payouts,
+        Movement::Adjustment => &mut period.adjustments,
+    };
+    *total = total.saturating_add(amount);
+    env.storage().instance().set(&period_key(merchant), &period);
+}
+
+/// Closes the open period at the current balance, emits its statement and opens the
+/// next one.
+pub fn close_period(env: &Env, merchant: &Address) -> MerchantStatementEvent {
+    let period = open_period(env, merchant);
+    let now = env.ledger().timestamp();
+    let closing = crate::merchant::get_merchant_balance(env, merchant);
+    let charges = closing
+        .saturating_sub(period.opening)
+        .saturating_add(period.refunds)
+        .saturating_add(period.fees)
+        .saturating_add(period.payouts)
+        .saturating_sub(period.adjustments);
+    let statement = MerchantStatementEvent {
+        merchant: merchant.clone(),
+        period_start: period.start,
+        period_end: now,
+        opening_accrual: period.opening,
+        charges,
+        refunds: period.refunds,
+        fees: period.fees,
+        payouts: period.payouts,
+        adjustments: period.adjustments,
+        reserved: crate::reserve::get_reserved_balance(env, merchant),
+        closing_accrual: closing,
+    };
+    env.storage().instance().set(
+        &period_key(merchant),
+        &StatementPeriod {
+            start: now,
+            opening: closing,
+            refunds: 0,
+            fees: 0,
+            payouts: 0,
+            adjustments: 0,
+        },
+    );
+    env.events().publish(
+        (Symbol::new(env, "merchant_statement"), merchant.clone()),
+        statement.clone(),
+    );
+    statement
+}
+
+/// The merchant, the billing engine or a billing agent closes the merchant's statement
+/// period without a payout, for example at the end of an accounting month.
+pub fn do_close_merchant_statement(
+    env: &Env,
+    caller: Address,
+    merchant: Address,
+) -> Result<MerchantStatementEvent, Error> {
+    if caller == merchant {
+        crate::liveness::require_merchant_auth(env, &caller);
+    } else {
+        crate::admin::require_billing_operator(env, &caller)?;
+    }
+    crate::reserve::release_matured(env, &merchant);
+    Ok(close_period(env, &merchant))
+}
@@ -0,0 +1,238 @@
+//! Move of storage written under legacy raw keys to typed [`DataKey`] keys, and backfill
+//! of the subscriber index.
+//!
+//! Up to storage version 1, subscriptions lived under their bare `u32` ID, global
+//! settings under ad-hoc `Symbol` keys ("admin", "token", "next_id", ...) and
+//! per-entity state under `(Symbol, ..)` tuples: `("cp", id)`, `("idem", id)`,
+//! `("merchant_balance", merchant)` and `("plan", plan_id)`. Version 2 keeps everything
+//! under [`DataKey`]. Version 3 adds the per-subscriber index
+//! (`DataKey::SubscriberSubs`). A contract upgraded over older storage is brought
+//! forward with [`do_migrate_storage_keys`]: the first call moves the global settings,
+//! and every call moves and indexes up to `limit` subscriptions, together with their
+//! charged period, idempotency key and merchant balance, then up to the rest of `limit`
+//! plan templates. Until a record is moved, reads fall back to its legacy key (see
+//! `queries::load_subscription`), and until the migration is done, subscriber listings
+//! scan IDs instead of the index.
+//!
+//! A legacy idempotency key was a bare `BytesN<32>` with no processing time; it is
+//! moved as processed at the time of the migration. Charges do not read it before
+//! then, and the charged period still rejects a replay of the same period.
+//!
+//! **PRs that only change the storage key migration should edit this file only.**
+
+use crate::types::{ConfigKey, DataKey, Error, IdempotencyRecord, MerchantKey, RecordKey, SubKey};
+use crate::STORAGE_VERSION;
+use soroban_sdk::{symbol_short, vec, Address, BytesN, Env, Symbol, Val};
+
+/// Maximum subscriptions moved per `migrate_storage_keys` call.
+pub const MAX_MIGRATION_BATCH: u32 = 100;
+
+/// Legacy `Symbol` key and the typed key that replaces it.
+fn legacy_settings(env: &Env) -> [(Symbol, DataKey); 13] {
+    [
+        (Symbol::new(env, "admin"), DataKey::Admin),
+        (Symbol::new(env, "token"), DataKey::Token),
+        (Symbol::new(env, "next_id"), DataKey::NextId),
+        (
+            Symbol::new(env, "token_decimals"),
+            DataKey::Config(ConfigKey::TokenDecimals),
+        ),
+        (
+            Symbol::new(env, "min_topup"),
+            DataKey::Config(ConfigKey::MinTopup),
+        ),
+        (
+            Symbol::new(env, "grace_period"),
+            DataKey::Config(ConfigKey::GracePeriod),
+        ),
+        (
+            Symbol::new(env, "charge_tolerance"),
+            DataKey::Config(ConfigKey::ChargeTolerance),
+        ),
+        (
+            Symbol::new(env, "min_debit_spacing"),
+            DataKey::Config(ConfigKey::MinDebitSpacing),
+        ),
+        (
+            Symbol::new(env, "subscription_limits"),
+            DataKey::Config(ConfigKey::SubscriptionLimits),
+        ),
+        (
+            Symbol::new(env, "allowlist_mode"),
+            DataKey::Config(ConfigKey::AllowlistMode),
+        ),
+        (
+            Symbol::new(env, "id_shard"),
+            DataKey::Config(ConfigKey::IdShard),
+        ),
+        (
+            Symbol::new(env, "next_plan_id"),
+            DataKey::Config(ConfigKey::NextPlanId),
+        ),
+        (
+            Symbol::new(env, "platform_fee_bps"),
+            DataKey::Config(ConfigKey::PlatformFeeBps),
+        ),
+    ]
+}
+
+/// Legacy key of a subscription's last charged period.
+pub fn legacy_charged_period_key(subscription_id: u32) -> (Symbol, u32) {
+    (symbol_short!("cp"), subscription_id)
+}
+
+/// Legacy key of a subscription's last idempotency key.
+fn legacy_idempotency_key(subscription_id: u32) -> (Symbol, u32) {
+    (symbol_short!("idem"), subscription_id)
+}
+
+/// Legacy key of a merchant's accrued balance.
+pub fn legacy_merchant_balance_key(env: &Env, merchant: &Address) -> (Symbol, Address) {
+    (Symbol::new(env, "merchant_balance"), merchant.clone())
+}
+
+/// Legacy key of a plan template.
+pub fn legacy_plan_key(env: &Env, plan_id: u32) -> (Symbol, u32) {
+    (Symbol::new(env, "plan"), plan_id)
+}
+
+/// Moves the value under `legacy` to `key`, unless `key` was written since the upgrade.
+fn move_value<K>(env: &Env, legacy: &K, key: &DataKey)
+where
+    K: soroban_sdk::IntoVal<Env, Val>,
+{
+    let storage = env.storage().instance();
+    if let Some(value) = storage.get::<_, Val>(legacy) {
+        if !storage.has(key) {
+            storage.set(key, &value);
+        }
+        storage.remove(legacy);
+    }
+}
+
+/// Moves the subscription's legacy per-subscription keys and its merchant's balance.
+fn move_subscription_state(env: &Env, subscription_id: u32, merchant: &Address) {
+    let storage = env.storage().instance();
+    move_value(
+        env,
+        &legacy_charged_period_key(subscription_id),
+        &DataKey::ChargedPeriod(subscription_id),
+    );
+    let idem = legacy_idempotency_key(subscription_id);
+    if let Some(key) = storage.get::<_, BytesN<32>>(&idem) {
+        let typed = DataKey::SubState(SubKey::Idempotency, subscription_id);
+        if !storage.has(&typed) {
+            let record = IdempotencyRecord {
+                key,
+                processed_at: env.ledger().timestamp(),
+            };
+            storage.set(&typed, &vec![env, record]);
+        }
+        storage.remove(&idem);
+    }
+    move_value(
+        env,
+        &legacy_merchant_balance_key(env, merchant),
+        &DataKey::Merchant(MerchantKey::Balance, merchant.clone()),
+    );
+}
+
+/// Storage version of the contract's data; 1 for storage written before versioning.
+pub fn get_storage_version(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::SchemaVersion)
+        .unwrap_or(1)
+}
+
+/// Marks fresh storage as current at `init`, so no migration is needed.
+pub fn set_current_version(env: &Env) {
+    env.storage()
+        .instance()
+        .set(&DataKey::SchemaVersion, &STORAGE_VERSION);
+}
+
+/// Whether the subscriber index covers every subscription.
+pub fn subscriber_index_ready(env: &Env) -> bool {
+    get_storage_version(env) >= 3
+}
+
+/// Admin moves legacy keys to [`DataKey`] and indexes subscriptions by subscriber, up
+/// to `limit` subscriptions per call.
+/// Returns `true` once everything is moved and the storage version is current; further
+/// calls do nothing. The admin is looked up under either key, so this works on storage
+/// that has not been touched yet.
+pub fn do_migrate_storage_keys(env: &Env, admin: Address, limit: u32) -> Result<bool, Error> {
+    admin.require_auth();
+    let storage = env.storage().instance();
+    let stored_admin: Address = storage
+        .get(&DataKey::Admin)
+        .or_else(|| storage.get(&Symbol::new(env, "admin")))
+        .ok_or(Error::NotInitialized)?;
+    if admin != stored_admin {
+        return Err(Error::Forbidden);
+    }
+    if get_storage_version(env) >= STORAGE_VERSION {
+        return Ok(true);
+    }
+    if limit == 0 || limit > MAX_MIGRATION_BATCH {
+        return Err(Error::InvalidInput);
+    }
+
+    for (legacy, key) in legacy_settings(env) {
+        if let Some(value) = storage.get::<_, Val>(&legacy) {
+            storage.set(&key, &value);
+            storage.remove(&legacy);
+        }
+    }
+
+    let cursor_key = DataKey::Config(ConfigKey::MigrationCursor);
+    let cursor: u32 = storage
+        .get(&cursor_key)
+        .unwrap_or_else(|| crate::ids::first_id(env));
+    let next_id = crate::ids::peek_next_id(env);
+    let end = cursor.saturating_add(limit).min(next_id);
+    for id in cursor..end {
+        move_value(env, &id, &DataKey::Sub(id));
+        if let Some(sub) = crate::queries::load_subscription(env, id) {
+            move_subscription_state(env, id, &sub.merchant);
+            crate::subscription::index_subscriber_subscription(env, &sub.subscriber, id);
+        }
+    }
+
+    // Plan templates take whatever is left of this call's budget once subscriptions
+    // are done.
+    let plan_cursor_key = DataKey::Config(ConfigKey::PlanMigrationCursor);
+    let plan_cursor: u32 = storage.get(&plan_cursor_key).unwrap_or(0);
+    let next_plan_id: u32 = storage
+        .get(&DataKey::Config(ConfigKey::NextPlanId))
+        .unwrap_or(0);
+    let budget = limit - (end - cursor);
+    let plan_end = if end >= next_id {
+        plan_cursor.saturating_add(budget).min(next_plan_id)
+    } else {
+        plan_cursor
+    };
+    for plan_id in plan_cursor..plan_end {
+        move_value(
+            env,
+            &legacy_plan_key(env, plan_id),
+            &DataKey::Record(RecordKey::PlanTemplate(plan_id)),
+        );
+    }
+
+    let done = end >= next_id && plan_end >= next_plan_id;
+    if done {
+        storage.remove(&cursor_key);
+        storage.remove(&plan_cursor_key);
+        set_current_version(env);
+    } else {
+        storage.set(&cursor_key, &end);
+        storage.set(&plan_cursor_key, &plan_end);
+    }
+    env.events().publish(
+        (Symbol::new(env, "storage_keys_migrated"),),
+        (cursor, end, done),
+    );
+    Ok(done)
+}
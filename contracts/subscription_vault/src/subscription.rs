@@ -1,4 +1,4 @@
-//! Subscription lifecycle: create, deposit.
+//! Subscription lifecycle: create, deposit, archive.
 //!
 //! See `docs/subscription_lifecycle.md` for the full lifecycle and state machine.
 //!
@@ -7,29 +7,35 @@
 #![allow(dead_code)]
 
 use crate::queries::get_subscription;
-use crate::safe_math::{safe_add_balance, validate_non_negative};
+use crate::safe_math::{safe_add_balance, safe_sub_balance, validate_non_negative};
 use crate::state_machine::validate_status_transition;
-use crate::types::{DataKey, Error, PlanTemplate, Subscription, SubscriptionStatus};
-use soroban_sdk::{Address, Env, Symbol, Vec};
-
-pub fn next_id(env: &Env) -> u32 {
-    let key = Symbol::new(env, "next_id");
-    let storage = env.storage().instance();
-    let id: u32 = storage.get(&key).unwrap_or(0);
-    storage.set(&key, &(id + 1));
-    id
-}
+use crate::storage_migration::legacy_plan_key;
+use crate::types::{
+    BatchCreateResult, ChargeMask, ConfigKey, CreateSubscriptionRequest, DataKey, Error,
+    FundsDepositedEvent, PlanTemplate, RecordKey, Subscription, SubscriptionArchivedEvent,
+    SubscriptionPausedEvent, SubscriptionResumedEvent, SubscriptionStatus,
+};
+use soroban_sdk::{Address, BytesN, Env, Symbol, Vec};
 
 pub fn next_plan_id(env: &Env) -> u32 {
-    let key = Symbol::new(env, "next_plan_id");
+    let key = DataKey::Config(ConfigKey::NextPlanId);
     let id: u32 = env.storage().instance().get(&key).unwrap_or(0);
     env.storage().instance().set(&key, &(id + 1));
     id
 }
 
+pub fn plan_template_key(plan_id: u32) -> DataKey {
+    DataKey::Record(RecordKey::PlanTemplate(plan_id))
+}
+
+/// Plan template by ID. Until storage is migrated, a template still under its legacy
+/// key is read from there.
 pub fn get_plan_template(env: &Env, plan_template_id: u32) -> Result<PlanTemplate, Error> {
-    let key = (Symbol::new(env, "plan"), plan_template_id);
-    env.storage().instance().get(&key).ok_or(Error::NotFound)
+    let storage = env.storage().instance();
+    storage
+        .get(&plan_template_key(plan_template_id))
+        .or_else(|| storage.get(&legacy_plan_key(env, plan_template_id)))
+        .ok_or(Error::NotFound)
 }
 
 pub fn do_create_subscription(
@@ -52,16 +58,178 @@ pub fn do_create_subscription(
         prepaid_balance: 0i128,
         usage_enabled,
     };
-    let id = next_id(env);
-    env.storage().instance().set(&id, &sub);
+    let id = crate::ids::next_id(env)?;
+    store_new_subscription(env, id, &sub, None)?;
+    Ok(id)
+}
+
+/// Maximum number of entries in one `batch_create_subscriptions` call.
+pub const MAX_CREATE_BATCH: u32 = 50;
+
+/// Creates one subscription per entry, like `create_subscription`, for onboarding an
+/// existing customer base. An entry that fails is reported with its error code, writes
+/// nothing and uses up no ID; the others still go through. Results are in input order.
+/// More than [`MAX_CREATE_BATCH`] entries fail with `InvalidInput`.
+///
+/// Each subscriber must authorize its entries; a missing authorization fails the whole
+/// batch, as it is not recoverable.
+pub fn do_batch_create_subscriptions(
+    env: &Env,
+    entries: &Vec<CreateSubscriptionRequest>,
+) -> Result<Vec<BatchCreateResult>, Error> {
+    if entries.len() > MAX_CREATE_BATCH {
+        return Err(Error::InvalidInput);
+    }
+    let mut results = Vec::new(env);
+    for entry in entries.iter() {
+        let res = match create_from_request(env, entry) {
+            Ok(subscription_id) => BatchCreateResult {
+                success: true,
+                subscription_id,
+                error_code: 0,
+            },
+            Err(e) => BatchCreateResult {
+                success: false,
+                subscription_id: 0,
+                error_code: e.to_code(),
+            },
+        };
+        results.push_back(res);
+    }
+    Ok(results)
+}
+
+/// Runs every check of [`do_create_subscription`] before the ID is allocated, so a
+/// failed batch entry leaves no gap behind.
+fn create_from_request(env: &Env, entry: CreateSubscriptionRequest) -> Result<u32, Error> {
+    validate_non_negative(entry.amount)?;
+    ensure_can_create(env, &entry.subscriber, &entry.merchant)?;
+    let id = do_create_subscription(
+        env,
+        entry.subscriber,
+        entry.merchant,
+        entry.amount,
+        entry.interval_seconds,
+        entry.usage_enabled,
+    )?;
+    crate::expiration::store_expiration(env, id, entry.expiration);
+    Ok(id)
+}
+
+/// Fails with `SubscriberNotAllowlisted` while allowlist mode excludes the subscriber,
+/// with `MerchantNotApproved` if the merchant registry does not approve the merchant, and
+/// with `SubscriptionLimitReached` if the admin-configured per-subscriber or per-merchant
+/// limit is already met.
+pub fn ensure_can_create(env: &Env, subscriber: &Address, merchant: &Address) -> Result<(), Error> {
+    crate::admin::ensure_subscriber_allowed(env, subscriber)?;
+    crate::merchant_registry::ensure_merchant_approved(env, merchant)?;
+    crate::offboarding::ensure_merchant_active(env, merchant)?;
+    let limits = crate::admin::get_subscription_limits(env);
+    let subscriber_count = get_subscriber_subscription_count(env, subscriber);
+    if limits.max_per_subscriber > 0 && subscriber_count >= limits.max_per_subscriber {
+        return Err(Error::SubscriptionLimitReached);
+    }
+    let merchant_subs = get_merchant_subscription_ids(env, merchant);
+    if limits.max_per_merchant > 0 && merchant_subs.len() >= limits.max_per_merchant {
+        return Err(Error::SubscriptionLimitReached);
+    }
+    Ok(())
+}
+
+fn get_merchant_subscription_ids(env: &Env, merchant: &Address) -> Vec<u32> {
+    env.storage()
+        .instance()
+        .get(&DataKey::MerchantSubs(merchant.clone()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Stores a new subscription and updates the merchant, subscriber and entitlement indexes.
+/// Fails like [`ensure_can_create`] before writing anything.
+pub fn store_new_subscription(
+    env: &Env,
+    id: u32,
+    sub: &Subscription,
+    plan_id: Option<u32>,
+) -> Result<(), Error> {
+    ensure_can_create(env, &sub.subscriber, &sub.merchant)?;
+    let subscriber_count = get_subscriber_subscription_count(env, &sub.subscriber);
 
     // Maintain merchant → subscription-ID index
-    let key = DataKey::MerchantSubs(sub.merchant.clone());
-    let mut ids: Vec<u32> = env.storage().instance().get(&key).unwrap_or(Vec::new(env));
+    let mut ids = get_merchant_subscription_ids(env, &sub.merchant);
     ids.push_back(id);
+    let key = DataKey::MerchantSubs(sub.merchant.clone());
     env.storage().instance().set(&key, &ids);
 
-    Ok(id)
+    env.storage().instance().set(
+        &DataKey::SubscriberSubCount(sub.subscriber.clone()),
+        &(subscriber_count + 1),
+    );
+    index_subscriber_subscription(env, &sub.subscriber, id);
+    env.storage().instance().set(&DataKey::Sub(id), sub);
+    crate::entitlement::index_subscription(env, id, sub, plan_id);
+    crate::offboarding::record_activity(env, &sub.merchant);
+    Ok(())
+}
+
+/// Live subscription IDs of `subscriber`, ascending. Complete once the storage version
+/// is current; cancelled subscriptions stay listed until they are archived.
+pub fn get_subscriber_subscription_ids(env: &Env, subscriber: &Address) -> Vec<u32> {
+    env.storage()
+        .instance()
+        .get(&DataKey::SubscriberSubs(subscriber.clone()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Adds `id` to the subscriber index, keeping it sorted. Reserved IDs can be used out
+/// of order, so this inserts rather than appends; an ID already present is skipped.
+pub fn index_subscriber_subscription(env: &Env, subscriber: &Address, id: u32) {
+    let mut ids = get_subscriber_subscription_ids(env, subscriber);
+    if let Err(pos) = ids.binary_search(id) {
+        ids.insert(pos, id);
+        env.storage()
+            .instance()
+            .set(&DataKey::SubscriberSubs(subscriber.clone()), &ids);
+    }
+}
+
+/// Live (non-archived) subscriptions created for `subscriber`.
+pub fn get_subscriber_subscription_count(env: &Env, subscriber: &Address) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::SubscriberSubCount(subscriber.clone()))
+        .unwrap_or(0)
+}
+
+/// Creates a subscription under a previously reserved ID, consuming the reservation.
+///
+/// Fails with `NotFound` if `subscription_id` was never reserved or was already used,
+/// and `Expired` once the reservation TTL has passed.
+pub fn do_create_subscription_with_reserved_id(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    merchant: Address,
+    amount: i128,
+    interval_seconds: u64,
+    usage_enabled: bool,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    validate_non_negative(amount)?;
+
+    crate::ids::claim_reservation(env, subscription_id, &subscriber, &merchant)?;
+
+    let sub = Subscription {
+        subscriber,
+        merchant,
+        amount,
+        interval_seconds,
+        last_payment_timestamp: env.ledger().timestamp(),
+        status: SubscriptionStatus::Active,
+        prepaid_balance: 0i128,
+        usage_enabled,
+    };
+    store_new_subscription(env, subscription_id, &sub, None)?;
+    Ok(())
 }
 
 pub fn do_deposit_funds(
@@ -80,18 +248,23 @@ pub fn do_deposit_funds(
 
     let mut sub = get_subscription(env, subscription_id)?;
     sub.prepaid_balance = safe_add_balance(sub.prepaid_balance, amount)?;
-    let token_addr: Address = env
-        .storage()
-        .instance()
-        .get(&Symbol::new(env, "token"))
-        .ok_or(Error::NotInitialized)?;
+    let token_addr = crate::billing_tokens::subscription_token(env, subscription_id)?;
     let token_client = soroban_sdk::token::Client::new(env, &token_addr);
 
+    // Pull the tokens first: if the transfer fails the whole call reverts and the
+    // prepaid balance is never credited.
     token_client.transfer(&subscriber, &env.current_contract_address(), &amount);
-    env.storage().instance().set(&subscription_id, &sub);
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
     env.events().publish(
         (Symbol::new(env, "deposited"), subscription_id),
-        (subscriber, amount, sub.prepaid_balance),
+        FundsDepositedEvent {
+            subscription_id,
+            subscriber,
+            amount,
+            new_balance: sub.prepaid_balance,
+        },
     );
     Ok(())
 }
@@ -111,8 +284,111 @@ pub fn do_cancel_subscription(
 
     validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled)?;
     sub.status = SubscriptionStatus::Cancelled;
+    crate::cancel_proration::refund_unused_period(env, subscription_id, &mut sub)?;
+
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
+    Ok(())
+}
+
+/// Moves a cancelled subscription out of the live key space and the merchant index.
+///
+/// The record is kept under [`DataKey::ArchivedSubscription`] for audit queries; after
+/// archiving, `get_subscription` and the listing views no longer return it. Archiving is
+/// refused while prepaid funds remain or any charge dispute or usage flag is open, since
+/// both flows still need the live record.
+pub fn do_archive_subscription(
+    env: &Env,
+    subscription_id: u32,
+    authorizer: Address,
+) -> Result<(), Error> {
+    authorizer.require_auth();
+
+    let sub = get_subscription(env, subscription_id)?;
+    if authorizer != sub.subscriber && authorizer != sub.merchant {
+        return Err(Error::Forbidden);
+    }
+    if sub.status != SubscriptionStatus::Cancelled
+        || sub.prepaid_balance != 0
+        || crate::multi_token::has_token_balances(env, subscription_id)
+    {
+        return Err(Error::ArchiveNotAllowed);
+    }
+    #[cfg(feature = "disputes")]
+    if crate::disputes::has_open_disputes(env, subscription_id) {
+        return Err(Error::ArchiveNotAllowed);
+    }
+
+    let storage = env.storage().instance();
+    let index_key = DataKey::MerchantSubs(sub.merchant.clone());
+    let mut ids: Vec<u32> = storage.get(&index_key).unwrap_or(Vec::new(env));
+    if let Some(pos) = ids.first_index_of(subscription_id) {
+        ids.remove(pos);
+        storage.set(&index_key, &ids);
+    }
+    // Test mode subscriptions are not counted (see `sandbox.rs`).
+    if !crate::sandbox::is_test_mode(env, subscription_id) {
+        let count_key = DataKey::SubscriberSubCount(sub.subscriber.clone());
+        let count: u32 = storage.get(&count_key).unwrap_or(0);
+        storage.set(&count_key, &count.saturating_sub(1));
+    }
+    let subscriber_key = DataKey::SubscriberSubs(sub.subscriber.clone());
+    let mut subscriber_ids = get_subscriber_subscription_ids(env, &sub.subscriber);
+    if let Ok(pos) = subscriber_ids.binary_search(subscription_id) {
+        subscriber_ids.remove(pos);
+        if subscriber_ids.is_empty() {
+            storage.remove(&subscriber_key);
+        } else {
+            storage.set(&subscriber_key, &subscriber_ids);
+        }
+    }
+
+    storage.remove(&DataKey::Sub(subscription_id));
+    storage.remove(&subscription_id);
+    storage.set(&DataKey::ArchivedSubscription(subscription_id), &sub);
+
+    env.events().publish(
+        (Symbol::new(env, "subscription_archived"), subscription_id),
+        SubscriptionArchivedEvent {
+            subscription_id,
+            archived_by: authorizer,
+        },
+    );
+    Ok(())
+}
+
+/// Subscriber or merchant enables or disables interval and usage charges separately.
+///
+/// A finer-grained alternative to pausing: e.g. stop metered billing while the base
+/// plan keeps renewing. Does not change `status`; `Paused` still blocks everything.
+pub fn do_set_charge_mask(
+    env: &Env,
+    subscription_id: u32,
+    authorizer: Address,
+    allow_interval: bool,
+    allow_usage: bool,
+) -> Result<(), Error> {
+    authorizer.require_auth();
+
+    let sub = get_subscription(env, subscription_id)?;
+    if authorizer != sub.subscriber && authorizer != sub.merchant {
+        return Err(Error::Forbidden);
+    }
 
-    env.storage().instance().set(&subscription_id, &sub);
+    let key = DataKey::ChargeMask(subscription_id);
+    let mask = ChargeMask {
+        allow_interval,
+        allow_usage,
+    };
+    if allow_interval && allow_usage {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &mask);
+    }
+
+    env.events()
+        .publish((Symbol::new(env, "charge_mask_set"), subscription_id), mask);
     Ok(())
 }
 
@@ -127,7 +403,68 @@ pub fn do_pause_subscription(
     validate_status_transition(&sub.status, &SubscriptionStatus::Paused)?;
     sub.status = SubscriptionStatus::Paused;
 
-    env.storage().instance().set(&subscription_id, &sub);
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
+    crate::health::record_pause(env, subscription_id);
+    emit_paused(env, subscription_id, authorizer);
+    Ok(())
+}
+
+pub fn emit_paused(env: &Env, subscription_id: u32, authorizer: Address) {
+    env.events().publish(
+        (Symbol::new(env, "paused"), subscription_id),
+        SubscriptionPausedEvent {
+            subscription_id,
+            authorizer,
+        },
+    );
+}
+
+/// Subscriber pauses and takes back `refund_amount` of the prepaid balance in one call.
+///
+/// At least one interval's `amount` must remain so the subscription can be resumed and
+/// charged without a new deposit.
+pub fn do_pause_and_refund(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    refund_amount: i128,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+
+    let mut sub = get_subscription(env, subscription_id)?;
+    if subscriber != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+    if refund_amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    validate_status_transition(&sub.status, &SubscriptionStatus::Paused)?;
+
+    let remaining = safe_sub_balance(sub.prepaid_balance, refund_amount)?;
+    if remaining < sub.amount {
+        return Err(Error::InsufficientBalance);
+    }
+    sub.status = SubscriptionStatus::Paused;
+    sub.prepaid_balance = remaining;
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
+    crate::health::record_pause(env, subscription_id);
+
+    let token = crate::billing_tokens::subscription_token(env, subscription_id)?;
+    soroban_sdk::token::Client::new(env, &token).transfer(
+        &env.current_contract_address(),
+        &subscriber,
+        &refund_amount,
+    );
+
+    emit_paused(env, subscription_id, subscriber);
+    env.events().publish(
+        (Symbol::new(env, "pause_refunded"), subscription_id),
+        (refund_amount, remaining),
+    );
     Ok(())
 }
 
@@ -142,20 +479,36 @@ pub fn do_resume_subscription(
     validate_status_transition(&sub.status, &SubscriptionStatus::Active)?;
     sub.status = SubscriptionStatus::Active;
 
-    env.storage().instance().set(&subscription_id, &sub);
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
+    emit_resumed(env, subscription_id, authorizer);
     Ok(())
 }
 
+pub fn emit_resumed(env: &Env, subscription_id: u32, authorizer: Address) {
+    env.events().publish(
+        (Symbol::new(env, "resumed"), subscription_id),
+        SubscriptionResumedEvent {
+            subscription_id,
+            authorizer,
+        },
+    );
+}
+
 /// Merchant-initiated one-off charge: debits `amount` from the subscription's prepaid balance.
 /// Requires merchant auth; the subscription's merchant must match the caller. Subscription must be
-/// Active or Paused. Amount must be positive and not exceed prepaid_balance.
+/// Active or Paused. Amount must be positive and not exceed prepaid_balance, and must fit the
+/// subscriber's monthly one-off allowance; `memo_hash` can only be charged once (see
+/// `one_off_allowance.rs`).
 pub fn do_charge_one_off(
     env: &Env,
     subscription_id: u32,
     merchant: Address,
     amount: i128,
+    memo_hash: BytesN<32>,
 ) -> Result<(), Error> {
-    merchant.require_auth();
+    crate::liveness::require_merchant_auth(env, &merchant);
 
     let mut sub = get_subscription(env, subscription_id)?;
     if sub.merchant != merchant {
@@ -170,17 +523,40 @@ pub fn do_charge_one_off(
     if sub.prepaid_balance < amount {
         return Err(Error::InsufficientPrepaidBalance);
     }
+    crate::one_off_allowance::consume(env, subscription_id, &memo_hash, amount)?;
+
+    crate::charge_core::settle(
+        env,
+        subscription_id,
+        &mut sub,
+        crate::charge_core::ChargeKind::OneOff {
+            memo_hash: Some(memo_hash),
+        },
+        amount,
+    )?;
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
+    Ok(())
+}
 
-    sub.prepaid_balance = sub
-        .prepaid_balance
-        .checked_sub(amount)
-        .ok_or(Error::Overflow)?;
+/// Subscriber voluntarily pays the next period early (see [`crate::charge_core::charge_early`]).
+pub fn do_pay_now(env: &Env, subscription_id: u32, subscriber: Address) -> Result<(), Error> {
+    subscriber.require_auth();
 
-    env.storage().instance().set(&subscription_id, &sub);
+    let sub = get_subscription(env, subscription_id)?;
+    if subscriber != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
 
-    Ok(())
+    crate::charge_core::charge_early(env, subscription_id)
 }
 
+/// Returns the whole remaining prepaid balance to the subscriber.
+///
+/// Allowed once the subscription is `Cancelled`, or `Paused` for at least the configured
+/// grace period (counted from the last pause), so a subscriber who walks away without
+/// cancelling can still recover their funds. The subscription keeps its status.
 pub fn do_withdraw_subscriber_funds(
     env: &Env,
     subscription_id: u32,
@@ -194,20 +570,27 @@ pub fn do_withdraw_subscriber_funds(
         return Err(Error::Forbidden);
     }
 
-    if sub.status != SubscriptionStatus::Cancelled {
-        return Err(Error::InvalidStatusTransition); // Or Unauthorized/InvalidState
+    let withdrawable = match sub.status {
+        SubscriptionStatus::Cancelled => true,
+        SubscriptionStatus::Paused => {
+            let grace = crate::admin::get_grace_period(env).unwrap_or(0);
+            let paused_at = crate::health::last_paused_at(env, subscription_id);
+            env.ledger().timestamp() >= paused_at.saturating_add(grace)
+        }
+        _ => false,
+    };
+    if !withdrawable {
+        return Err(Error::InvalidStatusTransition);
     }
 
     let amount_to_refund = sub.prepaid_balance;
     if amount_to_refund > 0 {
         sub.prepaid_balance = 0;
-        env.storage().instance().set(&subscription_id, &sub);
-
-        let token_addr: Address = env
-            .storage()
+        env.storage()
             .instance()
-            .get(&Symbol::new(env, "token"))
-            .ok_or(Error::NotInitialized)?;
+            .set(&DataKey::Sub(subscription_id), &sub);
+
+        let token_addr = crate::billing_tokens::subscription_token(env, subscription_id)?;
         let token_client = soroban_sdk::token::Client::new(env, &token_addr);
 
         token_client.transfer(
@@ -227,7 +610,7 @@ pub fn do_create_plan_template(
     interval_seconds: u64,
     usage_enabled: bool,
 ) -> Result<u32, Error> {
-    merchant.require_auth();
+    crate::liveness::require_merchant_auth(env, &merchant);
 
     let plan = PlanTemplate {
         merchant,
@@ -237,8 +620,9 @@ pub fn do_create_plan_template(
     };
 
     let plan_id = next_plan_id(env);
-    let key = (Symbol::new(env, "plan"), plan_id);
-    env.storage().instance().set(&key, &plan);
+    env.storage()
+        .instance()
+        .set(&plan_template_key(plan_id), &plan);
 
     Ok(plan_id)
 }
@@ -251,19 +635,37 @@ pub fn do_create_subscription_from_plan(
     subscriber.require_auth();
 
     let plan = get_plan_template(env, plan_template_id)?;
+    let terms = crate::plans::get_plan_terms(env, plan_template_id);
+    if terms.archived {
+        return Err(Error::PlanArchived);
+    }
 
+    // A trial defers the first charge by starting the first interval after it. Each
+    // customer gets one trial per merchant, across its linked wallets.
+    let trial_seconds =
+        if terms.trial_seconds > 0 && crate::plans::take_trial(env, &plan.merchant, &subscriber) {
+            terms.trial_seconds
+        } else {
+            0
+        };
+    let starts_at = env
+        .ledger()
+        .timestamp()
+        .checked_add(trial_seconds)
+        .ok_or(Error::Overflow)?;
     let sub = Subscription {
         subscriber: subscriber.clone(),
         merchant: plan.merchant,
         amount: plan.amount,
         interval_seconds: plan.interval_seconds,
-        last_payment_timestamp: env.ledger().timestamp(),
+        last_payment_timestamp: starts_at,
         status: SubscriptionStatus::Active,
         prepaid_balance: 0i128,
         usage_enabled: plan.usage_enabled,
     };
 
-    let id = next_id(env);
-    env.storage().instance().set(&id, &sub);
+    let id = crate::ids::next_id(env)?;
+    store_new_subscription(env, id, &sub, Some(plan_template_id))?;
+    crate::setup_fee::inherit_plan_setup_fee(env, plan_template_id, id);
     Ok(id)
 }
@@ -0,0 +1,152 @@
+//! Changes to a subscription's amount and interval after creation.
+//!
+//! The subscriber may change the terms of their own subscription at any time. The
+//! merchant may only do so while the subscriber has granted consent, and each merchant
+//! change uses that consent up. A change never applies mid-period: it is stored as
+//! pending and applied when the next interval charge (or skipped period) is booked, so
+//! that charge still uses the old terms and every later one the new.
+//!
+//! **PRs that only change subscription terms updates should edit this file only.**
+
+use crate::types::{
+    DataKey, Error, PendingTerms, SubKey, Subscription, SubscriptionStatus, TermsUpdatedEvent,
+};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn pending_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::PendingTerms, subscription_id)
+}
+
+fn consent_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::TermsConsent, subscription_id)
+}
+
+/// Terms waiting for the next billing boundary, if any.
+pub fn get_pending_terms(env: &Env, subscription_id: u32) -> Option<PendingTerms> {
+    env.storage().instance().get(&pending_key(subscription_id))
+}
+
+/// Converts the pending amount into another billing token's units when the subscription
+/// is migrated (see `token_migration.rs`). Fails with `InvalidAmount` if it rounds to
+/// zero.
+pub fn convert_pending(
+    env: &Env,
+    subscription_id: u32,
+    convert: impl Fn(i128) -> Result<i128, Error>,
+) -> Result<(), Error> {
+    let Some(mut pending) = get_pending_terms(env, subscription_id) else {
+        return Ok(());
+    };
+    pending.amount = convert(pending.amount)?;
+    if pending.amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    env.storage()
+        .instance()
+        .set(&pending_key(subscription_id), &pending);
+    Ok(())
+}
+
+/// Whether the merchant may currently change the subscription's terms.
+pub fn has_consent(env: &Env, subscription_id: u32) -> bool {
+    env.storage().instance().has(&consent_key(subscription_id))
+}
+
+/// Subscriber allows or withdraws one merchant change of the subscription's terms.
+pub fn do_set_terms_consent(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    allowed: bool,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Forbidden);
+    }
+    let key = consent_key(subscription_id);
+    if allowed {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events().publish(
+        (Symbol::new(env, "terms_consent_set"), subscription_id),
+        allowed,
+    );
+    Ok(())
+}
+
+/// Subscriber, or merchant with the subscriber's consent, schedules new terms for the
+/// next billing boundary, replacing any change still pending. Fails with `NotActive`
+/// for cancelled subscriptions.
+pub fn do_update_terms(
+    env: &Env,
+    subscription_id: u32,
+    new_amount: i128,
+    new_interval: u64,
+    authorizer: Address,
+) -> Result<PendingTerms, Error> {
+    authorizer.require_auth();
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if authorizer == sub.merchant {
+        if !has_consent(env, subscription_id) {
+            return Err(Error::Forbidden);
+        }
+    } else if authorizer != sub.subscriber {
+        return Err(Error::Forbidden);
+    }
+    if sub.status == SubscriptionStatus::Cancelled {
+        return Err(Error::NotActive);
+    }
+    if new_amount < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if new_interval == 0 {
+        return Err(Error::InvalidInput);
+    }
+
+    let pending = PendingTerms {
+        amount: new_amount,
+        interval_seconds: new_interval,
+        effective_at: sub
+            .last_payment_timestamp
+            .checked_add(sub.interval_seconds)
+            .ok_or(Error::Overflow)?,
+    };
+    env.storage()
+        .instance()
+        .set(&pending_key(subscription_id), &pending);
+    if authorizer == sub.merchant {
+        env.storage()
+            .instance()
+            .remove(&consent_key(subscription_id));
+    }
+    env.events().publish(
+        (Symbol::new(env, "terms_updated"), subscription_id),
+        TermsUpdatedEvent {
+            subscription_id,
+            authorizer,
+            old_amount: sub.amount,
+            new_amount,
+            old_interval: sub.interval_seconds,
+            new_interval,
+            effective_at: pending.effective_at,
+        },
+    );
+    Ok(pending)
+}
+
+/// Applies pending terms to `sub` once a billing boundary has been booked, returning
+/// whether there were any. The caller stores `sub`.
+pub fn apply_pending(env: &Env, subscription_id: u32, sub: &mut Subscription) -> bool {
+    let Some(pending) = get_pending_terms(env, subscription_id) else {
+        return false;
+    };
+    sub.amount = pending.amount;
+    sub.interval_seconds = pending.interval_seconds;
+    env.storage()
+        .instance()
+        .remove(&pending_key(subscription_id));
+    true
+}
@@ -0,0 +1,61 @@
+//! Delay between scheduling a sensitive admin action and carrying it out.
+//!
+//! The admin first schedules an action, committing to a hash of its payload; the action
+//! can be carried out once [`TIMELOCK_DELAY_SECONDS`] have passed, and only with the same
+//! payload. The delay gives subscribers and merchants time to react to the announced
+//! change. One action of each kind can be scheduled at a time; scheduling again replaces
+//! it and restarts the delay.
+//!
+//! **PRs that only change the timelock should edit this file only.**
+
+use crate::types::{DataKey, Error, RecordKey, TimelockEntry};
+use soroban_sdk::{BytesN, Env, Symbol};
+
+/// Delay before a scheduled action can be carried out (48 hours).
+pub const TIMELOCK_DELAY_SECONDS: u64 = 48 * 60 * 60;
+
+fn entry_key(action: &Symbol) -> DataKey {
+    DataKey::Record(RecordKey::Timelock(action.clone()))
+}
+
+/// Scheduled `action`, if any.
+pub fn get_entry(env: &Env, action: &Symbol) -> Option<TimelockEntry> {
+    env.storage().instance().get(&entry_key(action))
+}
+
+/// Schedules `action` with `payload_hash` and returns when it becomes ready. The caller
+/// checks authorization.
+pub fn schedule(env: &Env, action: Symbol, payload_hash: BytesN<32>) -> Result<u64, Error> {
+    let ready_at = env
+        .ledger()
+        .timestamp()
+        .checked_add(TIMELOCK_DELAY_SECONDS)
+        .ok_or(Error::Overflow)?;
+    let entry = TimelockEntry {
+        payload_hash,
+        ready_at,
+    };
+    env.storage().instance().set(&entry_key(&action), &entry);
+    env.events()
+        .publish((Symbol::new(env, "timelock_scheduled"), action), entry);
+    Ok(ready_at)
+}
+
+/// Consumes the scheduled `action` so it can be carried out.
+///
+/// Fails with `NotFound` if nothing is scheduled, `InvalidInput` if `payload_hash` differs
+/// from the scheduled one and `TimelockPending` before the delay has passed.
+pub fn consume(env: &Env, action: Symbol, payload_hash: &BytesN<32>) -> Result<(), Error> {
+    let key = entry_key(&action);
+    let entry = get_entry(env, &action).ok_or(Error::NotFound)?;
+    if entry.payload_hash != *payload_hash {
+        return Err(Error::InvalidInput);
+    }
+    if env.ledger().timestamp() < entry.ready_at {
+        return Err(Error::TimelockPending);
+    }
+    env.storage().instance().remove(&key);
+    env.events()
+        .publish((Symbol::new(env, "timelock_executed"), action), entry);
+    Ok(())
+}
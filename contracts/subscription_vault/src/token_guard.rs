@@ -0,0 +1,102 @@
+//! Guard against the vault token changing under existing balances.
+//!
+//! `init` caches a fingerprint of the token (its address, decimals and a hash of its
+//! symbol). Every settlement reads the token's metadata again and fails with
+//! `TokenMismatch` if the fingerprint no longer matches, for example after the token key
+//! was rewritten or the token contract was upgraded to different metadata. Charges stay
+//! halted until the admin reconfirms the token through the timelock (see `timelock.rs`).
+//!
+//! Tokens whose metadata cannot be read at `init` are not fingerprinted and the guard is
+//! off until the admin confirms the token.
+//!
+//! **PRs that only change the token guard should edit this file only.**
+
+use crate::types::{ConfigKey, DataKey, Error, TokenFingerprint};
+use soroban_sdk::{token, xdr::ToXdr, Address, BytesN, Env, Symbol};
+
+fn fingerprint_key() -> DataKey {
+    DataKey::Config(ConfigKey::TokenFingerprint)
+}
+
+fn reconfirm_action(env: &Env) -> Symbol {
+    Symbol::new(env, "token_reconfirm")
+}
+
+/// Fingerprint the guard compares against, or `None` while the guard is off.
+pub fn get_fingerprint(env: &Env) -> Option<TokenFingerprint> {
+    env.storage().instance().get(&fingerprint_key())
+}
+
+/// Reads the fingerprint of `token`, or `None` if its metadata cannot be read.
+fn read_fingerprint(env: &Env, token: &Address) -> Option<TokenFingerprint> {
+    let client = token::Client::new(env, token);
+    let decimals = client.try_decimals().ok()?.ok()?;
+    let symbol = client.try_symbol().ok()?.ok()?;
+    Some(TokenFingerprint {
+        token: token.clone(),
+        decimals,
+        symbol_hash: env.crypto().sha256(&symbol.to_xdr(env)).into(),
+    })
+}
+
+fn live_fingerprint(env: &Env) -> Result<Option<TokenFingerprint>, Error> {
+    Ok(read_fingerprint(env, &crate::admin::get_token(env)?))
+}
+
+/// Caches the fingerprint of `token` at `init`, if its metadata can be read.
+pub fn cache_fingerprint(env: &Env, token: &Address) {
+    if let Some(fingerprint) = read_fingerprint(env, token) {
+        env.storage()
+            .instance()
+            .set(&fingerprint_key(), &fingerprint);
+    }
+}
+
+/// Fails with `TokenMismatch` if the configured token no longer matches the cached
+/// fingerprint. Called on every settlement.
+pub fn ensure_token_unchanged(env: &Env) -> Result<(), Error> {
+    let Some(cached) = get_fingerprint(env) else {
+        return Ok(());
+    };
+    if live_fingerprint(env)? != Some(cached) {
+        return Err(Error::TokenMismatch);
+    }
+    Ok(())
+}
+
+fn live_fingerprint_hash(env: &Env) -> Result<(TokenFingerprint, BytesN<32>), Error> {
+    let fingerprint = live_fingerprint(env)?.ok_or(Error::TokenMismatch)?;
+    let hash = env.crypto().sha256(&fingerprint.clone().to_xdr(env)).into();
+    Ok((fingerprint, hash))
+}
+
+/// Admin schedules reconfirming the token as it is now. Returns when
+/// [`do_reconfirm_token`] becomes possible. Fails with `TokenMismatch` if the token's
+/// metadata cannot be read.
+pub fn do_propose_token_reconfirm(env: &Env, admin: Address) -> Result<u64, Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    let (_, hash) = live_fingerprint_hash(env)?;
+    crate::timelock::schedule(env, reconfirm_action(env), hash)
+}
+
+/// Admin accepts the token as it is now as the new fingerprint, resuming charges.
+///
+/// Fails with `TimelockPending` before the delay has passed and `InvalidInput` if the
+/// token changed again since the proposal.
+pub fn do_reconfirm_token(env: &Env, admin: Address) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != crate::admin::require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    let (fingerprint, hash) = live_fingerprint_hash(env)?;
+    crate::timelock::consume(env, reconfirm_action(env), &hash)?;
+    env.storage()
+        .instance()
+        .set(&fingerprint_key(), &fingerprint);
+    env.events()
+        .publish((Symbol::new(env, "token_reconfirmed"),), fingerprint);
+    Ok(())
+}
@@ -0,0 +1,259 @@
+//! Admin-run move of existing subscriptions to another billing token.
+//!
+//! When the platform switches stablecoins, [`do_migrate_subscription_token`] moves a
+//! batch of subscriptions from the token they bill in today to `new_token` (the vault
+//! token or one on the billing token allowlist, see `billing_tokens.rs`). Each prepaid
+//! balance and recurring amount is converted at the batch's rate, the subscription's
+//! billing token is updated, and a `TokenMigratedEvent` is emitted. The same rate
+//! converts the other amounts kept in the old token's units that later charges, refunds
+//! or cancellations rely on: the refundable charged and refunded totals, the setup fee
+//! schedule, pending terms, the minimum-charge carry and the last charge remembered for
+//! cancellation proration.
+//!
+//! The rate comes from a [`ConversionRateSource`]:
+//!
+//! - `Fixed(rate)`: the admin acts as counterparty, paying in the converted balances in
+//!   `new_token` and receiving the old balances.
+//! - `Swap(venue)`: the vault sells the batch's old balances on a venue the admin has
+//!   allowlisted with [`do_set_swap_venue`]. What the vault actually receives is shared
+//!   out pro rata, and the same rate converts the recurring amounts.
+//!
+//! Cancelled subscriptions, subscriptions already on `new_token`, and subscriptions with
+//! secondary token balances or an open dispute or usage flag are skipped. Limits and
+//! prices the subscriber or merchant set on a subscription are not converted and should
+//! be reviewed after a migration: usage prices, caps and credit, usage tier quotas,
+//! spending caps, approved and one-off charge allowances, auto-topup and guarantor caps
+//! (and the guarantor's recorded contributions), round-up units and low-balance
+//! thresholds.
+//!
+//! **PRs that only change token migration should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::multi_token::RATE_SCALE;
+use crate::types::{
+    ConversionRateSource, DataKey, Error, RecordKey, SubscriptionStatus, TokenMigratedEvent,
+};
+use soroban_sdk::{token, vec, Address, Env, IntoVal, Symbol, Val, Vec};
+
+/// Maximum subscription IDs per `migrate_subscription_token` call.
+pub const MAX_TOKEN_MIGRATION_BATCH: u32 = 50;
+
+fn venue_key(venue: &Address) -> DataKey {
+    DataKey::Record(RecordKey::SwapVenue(venue.clone()))
+}
+
+fn require_admin_auth(env: &Env, admin: &Address) -> Result<(), Error> {
+    admin.require_auth();
+    if *admin != require_admin(env)? {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+/// Whether the admin allows token migrations to swap on `venue`.
+pub fn is_swap_venue(env: &Env, venue: &Address) -> bool {
+    env.storage().instance().has(&venue_key(venue))
+}
+
+/// Admin adds `venue` to the swap venue allowlist, or removes it.
+pub fn do_set_swap_venue(
+    env: &Env,
+    admin: Address,
+    venue: Address,
+    allowed: bool,
+) -> Result<(), Error> {
+    require_admin_auth(env, &admin)?;
+    let key = venue_key(&venue);
+    if allowed {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events()
+        .publish((Symbol::new(env, "swap_venue_set"), venue), allowed);
+    Ok(())
+}
+
+fn is_migratable(
+    env: &Env,
+    subscription_id: u32,
+    status: &SubscriptionStatus,
+    new_token: &Address,
+) -> Result<bool, Error> {
+    if *status == SubscriptionStatus::Cancelled
+        || crate::billing_tokens::subscription_token(env, subscription_id)? == *new_token
+        || crate::multi_token::has_token_balances(env, subscription_id)
+    {
+        return Ok(false);
+    }
+    #[cfg(feature = "disputes")]
+    if crate::disputes::has_open_disputes(env, subscription_id) {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+fn convert(value: i128, numerator: i128, denominator: i128) -> Result<i128, Error> {
+    Ok(value.checked_mul(numerator).ok_or(Error::Overflow)? / denominator)
+}
+
+/// Sells `amount_in` of `from_token` on `venue` and returns the `to_token` the vault
+/// received.
+fn swap_on_venue(
+    env: &Env,
+    venue: &Address,
+    from_token: &Address,
+    to_token: &Address,
+    amount_in: i128,
+) -> Result<i128, Error> {
+    let vault = env.current_contract_address();
+    let to_client = token::Client::new(env, to_token);
+    let before = to_client.balance(&vault);
+    token::Client::new(env, from_token).transfer(&vault, venue, &amount_in);
+    let args: Vec<Val> = vec![
+        env,
+        from_token.into_val(env),
+        to_token.into_val(env),
+        amount_in.into_val(env),
+        vault.into_val(env),
+    ];
+    let quoted: i128 = env.invoke_contract(venue, &Symbol::new(env, "swap"), args);
+    let received = to_client.balance(&vault).saturating_sub(before);
+    if received <= 0 || received < quoted {
+        return Err(Error::InvalidAmount);
+    }
+    Ok(received)
+}
+
+/// Admin moves the listed subscriptions to `new_token`, converting their prepaid
+/// balances and recurring amounts. Returns the IDs actually migrated.
+///
+/// At most [`MAX_TOKEN_MIGRATION_BATCH`] IDs per call (`InvalidInput` otherwise), and
+/// the migrated subscriptions must all bill in the same token today (`InvalidInput`
+/// otherwise). `new_token` must be the vault token or allowlisted (`TokenNotAccepted`),
+/// a fixed rate must be positive (`InvalidInput`), and a swap venue must be allowlisted
+/// (`Forbidden`). A swap needs a non-zero total balance to price the batch
+/// (`InvalidInput`), and a conversion that rounds a recurring amount to zero fails with
+/// `InvalidAmount`.
+pub fn do_migrate_subscription_token(
+    env: &Env,
+    admin: Address,
+    ids: Vec<u32>,
+    new_token: Address,
+    source: ConversionRateSource,
+) -> Result<Vec<u32>, Error> {
+    require_admin_auth(env, &admin)?;
+    if ids.is_empty() || ids.len() > MAX_TOKEN_MIGRATION_BATCH {
+        return Err(Error::InvalidInput);
+    }
+    if new_token != crate::admin::get_token(env)?
+        && !crate::billing_tokens::is_billing_token(env, &new_token)
+    {
+        return Err(Error::TokenNotAccepted);
+    }
+    match &source {
+        ConversionRateSource::Fixed(rate) if *rate <= 0 => return Err(Error::InvalidInput),
+        ConversionRateSource::Swap(venue) if !is_swap_venue(env, venue) => {
+            return Err(Error::Forbidden)
+        }
+        _ => {}
+    }
+
+    let mut batch = Vec::new(env);
+    let mut from_token: Option<Address> = None;
+    let mut total_in: i128 = 0;
+    for id in ids.iter() {
+        if batch.contains(id) {
+            continue;
+        }
+        let sub = crate::queries::get_subscription(env, id)?;
+        if !is_migratable(env, id, &sub.status, &new_token)? {
+            continue;
+        }
+        let token = crate::billing_tokens::subscription_token(env, id)?;
+        match &from_token {
+            Some(from) if *from != token => return Err(Error::InvalidInput),
+            Some(_) => {}
+            None => from_token = Some(token),
+        }
+        total_in = total_in
+            .checked_add(sub.prepaid_balance)
+            .ok_or(Error::Overflow)?;
+        batch.push_back(id);
+    }
+    let Some(from_token) = from_token else {
+        return Ok(batch);
+    };
+
+    let (numerator, denominator) = match &source {
+        ConversionRateSource::Fixed(rate) => (*rate, RATE_SCALE),
+        ConversionRateSource::Swap(venue) => {
+            if total_in == 0 {
+                return Err(Error::InvalidInput);
+            }
+            let received = swap_on_venue(env, venue, &from_token, &new_token, total_in)?;
+            (received, total_in)
+        }
+    };
+
+    let mut balances = Vec::new(env);
+    let mut last_funded = None;
+    let mut total_out: i128 = 0;
+    for (index, id) in batch.iter().enumerate() {
+        let balance = crate::queries::get_subscription(env, id)?.prepaid_balance;
+        let converted = convert(balance, numerator, denominator)?;
+        if balance > 0 {
+            last_funded = Some(index as u32);
+        }
+        total_out = total_out.checked_add(converted).ok_or(Error::Overflow)?;
+        balances.push_back(converted);
+    }
+    // Rounding dust of a swap goes to the last funded subscription, so the balances
+    // add up to exactly what the vault received.
+    if let (ConversionRateSource::Swap(_), Some(index)) = (&source, last_funded) {
+        let dust = numerator.saturating_sub(total_out);
+        balances.set(index, balances.get_unchecked(index).saturating_add(dust));
+        total_out = numerator;
+    }
+
+    for (id, new_balance) in batch.iter().zip(balances.iter()) {
+        let mut sub = crate::queries::get_subscription(env, id)?;
+        let new_amount = convert(sub.amount, numerator, denominator)?;
+        if new_amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let event = TokenMigratedEvent {
+            subscription_id: id,
+            from_token: from_token.clone(),
+            to_token: new_token.clone(),
+            old_balance: sub.prepaid_balance,
+            new_balance,
+            old_amount: sub.amount,
+            new_amount,
+        };
+        sub.prepaid_balance = new_balance;
+        sub.amount = new_amount;
+        env.storage().instance().set(&DataKey::Sub(id), &sub);
+        let rate = |value| convert(value, numerator, denominator);
+        crate::refunds::convert_totals(env, id, rate)?;
+        crate::setup_fee::convert_schedule(env, id, rate)?;
+        crate::terms::convert_pending(env, id, rate)?;
+        crate::min_charge::convert_carry(env, id, rate)?;
+        crate::cancel_proration::convert_last_charge(env, id, rate)?;
+        crate::charge_core::convert_last_charge(env, id, rate)?;
+        crate::billing_tokens::set_subscription_token(env, id, &new_token)?;
+        env.events()
+            .publish((Symbol::new(env, "token_migrated"), id), event);
+    }
+
+    if let ConversionRateSource::Fixed(_) = source {
+        let vault = env.current_contract_address();
+        if total_out > 0 {
+            token::Client::new(env, &new_token).transfer(&admin, &vault, &total_out);
+        }
+        if total_in > 0 {
+            token::Client::new(env, &from_token).transfer(&vault, &admin, &total_in);
+        }
+    }
+    Ok(batch)
+}
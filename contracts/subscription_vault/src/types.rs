@@ -3,9 +3,16 @@
 //! Kept in a separate module to reduce merge conflicts when editing state machine
 //! or contract entrypoints.
 
-use soroban_sdk::{contracterror, contracttype, Address};
+use soroban_sdk::{contracterror, contracttype, Address, BytesN, String, Symbol, Vec};
 
-/// Storage keys for secondary indices.
+/// Storage keys.
+///
+/// The contract spec caps a union at 50 cases and discriminant 49 is the last one.
+/// Further keys go in the nested enums: global settings in [`ConfigKey`],
+/// per-subscription state in [`SubKey`], per-merchant state in [`MerchantKey`] and
+/// everything else in [`RecordKey`]. `SubKey` and `MerchantKey` are integer enums, so
+/// their keys stay flat vectors of small values that are cheap to compare on every
+/// instance storage lookup.
 #[contracttype]
 #[derive(Clone)]
 pub enum DataKey {
@@ -15,8 +22,8 @@ pub enum DataKey {
     Token,
     /// Authorized admin address. Discriminant 2.
     Admin,
-    /// Minimum deposit threshold. Discriminant 3.
-    MinTopup,
+    /// Global setting, see [`ConfigKey`]. Discriminant 3.
+    Config(ConfigKey),
     /// Auto-incrementing subscription ID counter. Discriminant 4.
     NextId,
     /// On-chain storage schema version. Discriminant 5.
@@ -25,10 +32,287 @@ pub enum DataKey {
     Sub(u32),
     /// Last charged billing-period index for replay protection. Discriminant 7.
     ChargedPeriod(u32),
-    /// Idempotency key stored per subscription. Discriminant 8.
-    IdemKey(u32),
+    /// Live subscription IDs of a subscriber, in ascending order. Discriminant 8, left
+    /// unused by an earlier idempotency key.
+    SubscriberSubs(Address),
     /// Emergency stop flag - when true, critical operations are blocked. Discriminant 9.
     EmergencyStop,
+    /// Dispute record keyed by dispute ID. Discriminant 10.
+    Dispute(u32),
+    /// Auto-incrementing dispute ID counter. Discriminant 11.
+    NextDisputeId,
+    /// Dispute ID opened against a (subscription ID, charged period) pair. Discriminant 12.
+    ChargeDispute(u32, u64),
+    /// Sum of open dispute amounts ring-fenced in a merchant's balance. Discriminant 13.
+    MerchantLocked(Address),
+    /// Protocol fees accrued in the vault and not yet withdrawn. Discriminant 14.
+    ProtocolFees,
+    /// Address that receives protocol fee withdrawals. Discriminant 15.
+    FeeRecipient,
+    /// Optional donation split applied on protocol fee withdrawals. Discriminant 16.
+    DonationConfig,
+    /// Archived subscription record, moved out of the live ID key space. Discriminant 17.
+    ArchivedSubscription(u32),
+    /// Charge lateness totals keyed by (merchant, SLA period). Discriminant 18.
+    BillingSla(Address, u64),
+    /// Conversion rate of an accepted secondary token. Discriminant 19.
+    TokenRate(Address),
+    /// Secondary token balance keyed by (subscription ID, token). Discriminant 20.
+    SubTokenBalance(u32, Address),
+    /// Drain order of a subscription's secondary tokens. Discriminant 21.
+    TokenPriority(u32),
+    /// Merchant earnings in a secondary token keyed by (merchant, token). Discriminant 22.
+    MerchantTokenBalance(Address, Address),
+    /// Merchant loyalty tier configuration. Discriminant 23.
+    LoyaltyConfig(Address),
+    /// Successful interval charges taken per subscription. Discriminant 24.
+    CyclesCompleted(u32),
+    /// Reservation of a not yet created subscription ID. Discriminant 25.
+    IdReservation(u32),
+    /// Per-subscription charge mask; absent means all charge types allowed. Discriminant 26.
+    ChargeMask(u32),
+    /// Latest subscription keyed by (subscriber, merchant) for entitlement checks. Discriminant 27.
+    Entitlement(Address, Address),
+    /// Bundle line items of a subscription. Discriminant 28.
+    Bundle(u32),
+    /// Merchant dunning policy. Discriminant 29.
+    DunningPolicy(Address),
+    /// Consecutive failed interval charges per subscription. Discriminant 30.
+    FailedAttempts(u32),
+    /// Merchant rolling reserve configuration. Discriminant 31.
+    ReserveConfig(Address),
+    /// Timestamped reserve buckets per merchant. Discriminant 32.
+    ReserveBuckets(Address),
+    /// Promo state of a zero-amount promotional subscription. Discriminant 33.
+    Promo(u32),
+    /// Live subscription count per subscriber, for the per-subscriber limit. Discriminant 34.
+    SubscriberSubCount(Address),
+    /// Recent usage charges open to subscriber flags. Discriminant 35.
+    UsageCharges(u32),
+    /// Protocol fee (bps) charged on expedited merchant withdrawals. Discriminant 36.
+    ExpediteFeeBps,
+    /// Timestamp at which the admin declared a merchant inactive. Discriminant 37.
+    MerchantInactive(Address),
+    /// Last charge, withdrawal or new subscription for a merchant. Discriminant 38.
+    MerchantLastActivity(Address),
+    /// Usage billing configuration per subscription. Discriminant 39.
+    UsageConfig(u32),
+    /// Usage charged in the current report window per subscription. Discriminant 40.
+    UsageWindow(u32),
+    /// Subscriber admitted while allowlist mode is on. Discriminant 41.
+    AllowedSubscriber(Address),
+    /// Setup fee amortization schedule per subscription. Discriminant 42.
+    SetupFee(u32),
+    /// Setup fee terms inherited by subscriptions created from a plan. Discriminant 43.
+    PlanSetupFee(u32),
+    /// Dispute and pause counters per subscription, for health scoring. Discriminant 44.
+    HealthCounters(u32),
+    /// Identity hash a wallet is linked to. Discriminant 45.
+    WalletIdentity(Address),
+    /// Wallets linked under an identity hash. Discriminant 46.
+    IdentityWallets(BytesN<32>),
+    /// Per-subscription state keyed by (field, subscription ID), see [`SubKey`].
+    /// Discriminant 47.
+    SubState(SubKey, u32),
+    /// Per-merchant state keyed by (field, merchant), see [`MerchantKey`].
+    /// Discriminant 48.
+    Merchant(MerchantKey, Address),
+    /// Other keyed records, see [`RecordKey`]. Discriminant 49.
+    Record(RecordKey),
+}
+
+/// Global settings, stored under [`DataKey::Config`].
+#[contracttype]
+#[derive(Clone)]
+pub enum ConfigKey {
+    /// Decimals of the vault token.
+    TokenDecimals,
+    /// Minimum deposit threshold.
+    MinTopup,
+    /// Grace period before an underfunded subscription is suspended.
+    GracePeriod,
+    /// Seconds before the interval boundary within which charges are accepted.
+    ChargeTolerance,
+    /// Minimum seconds between two debits on one subscription.
+    MinDebitSpacing,
+    /// Per-subscriber and per-merchant subscription caps.
+    SubscriptionLimits,
+    /// Whether only allowlisted subscribers may open subscriptions.
+    AllowlistMode,
+    /// Subscription ID shard of this contract.
+    IdShard,
+    /// Auto-incrementing plan template ID counter.
+    NextPlanId,
+    /// Platform fee in basis points.
+    PlatformFeeBps,
+    /// Vault token fingerprint checked on settlement.
+    TokenFingerprint,
+    /// Next subscription ID to move while migrating legacy storage keys.
+    MigrationCursor,
+    /// Next plan template ID to move while migrating legacy storage keys.
+    PlanMigrationCursor,
+    /// Salt for hashed merchant references in subscriber views; unset shows addresses.
+    MerchantRefSalt,
+    /// Charge failure-rate circuit breaker settings.
+    CircuitBreaker,
+    /// Batch charge outcomes in the current circuit breaker window.
+    ChargeMetrics,
+    /// Minimum interval charge amount in token base units.
+    MinChargeAmount,
+    /// Ed25519 public key that signs legacy subscription imports.
+    MigrationKey,
+    /// Address allowed to call the charge entrypoints, apart from billing agents.
+    BillingEngine,
+    /// Next approval ID for subscriber-approved future charges.
+    NextApprovalId,
+    /// Most one batch charge may debit from a single subscriber.
+    BatchDebitCap,
+    /// Backend keys allowed to call the charge entrypoints besides the admin.
+    BillingAgents,
+    /// Merchant registry contract consulted on charges and withdrawals.
+    MerchantRegistry,
+    /// Risk oracle consulted before charges.
+    RiskOracle,
+}
+
+/// Per-subscription state, stored under [`DataKey::SubState`] together with the
+/// subscription ID.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum SubKey {
+    /// Idempotency keys processed within the retention window.
+    Idempotency = 0,
+    /// Ledger time of the last successful debit, kept while the minimum debit spacing
+    /// is on.
+    LastDebitAt = 1,
+    /// Expiration timestamp, when one was set.
+    Expiration = 2,
+    /// Auto top-up settings.
+    AutoTopup = 3,
+    /// Billing token, when it is not the vault token.
+    BillingToken = 4,
+    /// Amount and period of the last interval charge, for cancellation proration.
+    LastPeriodCharge = 5,
+    /// Open charge disputes and usage flags.
+    OpenDisputes = 6,
+    /// Context of the last failed operation.
+    ErrorContext = 7,
+    /// Billing cycle limit of a fixed-term subscription.
+    MaxCycles = 8,
+    /// Guarantor backing the subscription.
+    Guarantee = 9,
+    /// External ID of an imported legacy subscription.
+    LegacyOrigin = 10,
+    /// Low balance warning threshold.
+    LowBalanceThreshold = 11,
+    /// Amount carried over from charges below the minimum charge.
+    MinChargeCarry = 12,
+    /// Contract notified after each charge.
+    ChargeCallback = 13,
+    /// One-off charge allowance.
+    OneOffAllowance = 14,
+    /// Merchant credit of all settled charges, for refunds.
+    ChargedTotal = 15,
+    /// Total refunded to the subscriber.
+    RefundedTotal = 16,
+    /// Round-up donation settings.
+    RoundUp = 17,
+    /// Marks a sandbox subscription.
+    TestMode = 18,
+    /// Skip record.
+    Skips = 19,
+    /// Spending cap per billing period.
+    SpendingCap = 20,
+    /// Amount charged in the current billing period.
+    PeriodSpend = 21,
+    /// Terms waiting for the next billing boundary.
+    PendingTerms = 22,
+    /// Subscriber consent to merchant-proposed terms.
+    TermsConsent = 23,
+    /// Usage charge history.
+    UsageHistory = 24,
+    /// Unspent usage credit.
+    UsageCredit = 25,
+    /// Usage credit rollover settings.
+    CreditRollover = 26,
+    /// Overage tier.
+    UsageTier = 27,
+    /// Usage consumed in the current tier period.
+    TierConsumed = 28,
+}
+
+/// Per-merchant state, stored under [`DataKey::Merchant`] together with the merchant
+/// address.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum MerchantKey {
+    /// Accrued balance in the vault token.
+    Balance = 0,
+    /// Subscriptions paused by a merchant-wide pause.
+    Paused = 1,
+    /// Cancellation proration policy.
+    CancelProration = 2,
+    /// What happens to subscriptions when their grace period lapses.
+    LapseAction = 3,
+    /// How interval charges running past an expiration are handled.
+    ExpiryProration = 4,
+    /// Whether residual balances are refunded on expiry.
+    ExpiryAutoRefund = 5,
+    /// Liveness requirement.
+    LivenessWindow = 6,
+    /// Last time the merchant was seen live.
+    LastSeen = 7,
+    /// Minimum charge policy.
+    MinChargePolicy = 8,
+    /// Secondary tokens the merchant has been credited in.
+    SecondaryTokens = 9,
+    /// Contract notified after each charge of the merchant's subscriptions.
+    ChargeHook = 10,
+    /// Payout address for withdrawals.
+    PayoutAddress = 11,
+    /// Payout schedule.
+    PayoutSchedule = 12,
+    /// Automatic payout settings.
+    AutoPayout = 13,
+    /// Skips allowed per subscription.
+    MaxSkips = 14,
+    /// Open statement period.
+    StatementPeriod = 15,
+}
+
+/// Records keyed by something other than a subscription or a merchant, stored under
+/// [`DataKey::Record`].
+#[contracttype]
+#[derive(Clone)]
+pub enum RecordKey {
+    /// Plan template keyed by plan ID.
+    PlanTemplate(u32),
+    /// Catalog terms of a plan.
+    PlanTerms(u32),
+    /// Subscriber-approved future charge keyed by approval ID.
+    ApprovedCharge(u32),
+    /// Admin-allowed billing token.
+    BillingToken(Address),
+    /// Admin-approved charge callback contract.
+    ApprovedCallback(Address),
+    /// Admin-allowed swap venue for token migrations.
+    SwapVenue(Address),
+    /// Timelocked admin action.
+    Timelock(Symbol),
+    /// Subscription ID of an imported legacy record, keyed by its external ID.
+    LegacyExternalId(String),
+    /// Round-up donations made by a subscriber.
+    Donated(Address),
+    /// Amount a guarantor paid in, keyed by (subscription ID, guarantor).
+    GuarantorContribution(u32, Address),
+    /// One-off charge memo already used, keyed by (subscription ID, memo hash).
+    OneOffMemo(u32, BytesN<32>),
+    /// Sandbox agent designation, keyed by (merchant, agent).
+    SandboxAgent(Address, Address),
+    /// Marks a wallet that started a plan trial, keyed by (merchant, wallet).
+    TrialTaken(Address, Address),
 }
 
 /// Detailed error information for insufficient balance scenarios.
@@ -87,14 +371,10 @@ pub enum Error {
     Replay = 1007,
     /// Invalid amount.
     InvalidRecoveryAmount = 1008,
-    /// Charge interval has not elapsed yet.
-    IntervalNotElapsed = 1001,
-    /// Subscription is not in the Active state.
-    NotActive = 1002,
     /// Emergency stop is active - critical operations are blocked.
     EmergencyStopActive = 1009,
     /// Already initialized.
-    AlreadyInitialized = 1009,
+    AlreadyInitialized = 1016,
     /// Recovery operation not allowed for this reason or context.
     RecoveryNotAllowed = 1011,
     /// Invalid input provided to a function.
@@ -119,6 +399,69 @@ pub enum Error {
     NotInitialized = 1013,
     /// The requested export limit exceeds the maximum allowed.
     InvalidExportLimit = 1014,
+    /// The charge has already been disputed.
+    DisputeAlreadyOpen = 1017,
+    /// The dispute has already been resolved.
+    DisputeNotOpen = 1018,
+    /// Only cancelled subscriptions with no prepaid balance or open dispute can be archived.
+    ArchiveNotAllowed = 1019,
+    /// The token has no conversion rate configured and cannot be held or drawn.
+    TokenNotAccepted = 1020,
+    /// The reserved subscription ID can no longer be claimed, or the approved charge's
+    /// execution window has closed.
+    Expired = 1021,
+    /// This charge type is disabled by the subscription's charge mask.
+    ChargeTypeBlocked = 1022,
+    /// Promo subscriptions are free until converted to paid and cannot be charged.
+    PromoNotChargeable = 1023,
+    /// The subscriber or merchant already holds the admin-configured maximum of subscriptions.
+    SubscriptionLimitReached = 1024,
+    /// The usage charge is older than the flag window.
+    DisputeWindowClosed = 1025,
+    /// The merchant was declared inactive; its subscriptions can no longer be charged.
+    MerchantInactive = 1026,
+    /// Escrow can only be reclaimed once the merchant is inactive.
+    MerchantActive = 1027,
+    /// The usage charge would exceed the cap for the current report window.
+    UsageCapExceeded = 1028,
+    /// Allowlist mode is on and the subscriber has not been admitted.
+    SubscriberNotAllowlisted = 1029,
+    /// Another debit on the subscription succeeded less than the minimum spacing ago.
+    DebitTooSoon = 1030,
+    /// The plan was archived and accepts no new subscriptions or changes.
+    PlanArchived = 1031,
+    /// The merchant's yearly cap on skipped periods has been used up.
+    SkipLimitReached = 1032,
+    /// A skip of the next charge is already waiting.
+    SkipAlreadyPending = 1033,
+    /// The refund would exceed what the subscription has been charged, less refunds.
+    RefundExceedsCharged = 1034,
+    /// The merchant has not signed any merchant entrypoint within its liveness window.
+    MerchantStale = 1035,
+    /// The vault token no longer matches the fingerprint cached at init.
+    TokenMismatch = 1036,
+    /// The timelocked action cannot be carried out before its delay has passed.
+    TimelockPending = 1037,
+    /// Charges are suspended by the failure-rate circuit breaker until the admin resets it.
+    CircuitBreakerOpen = 1038,
+    /// The charge would take the billing period past the subscriber's spending cap, or a
+    /// one-off charge past the subscriber's monthly one-off allowance.
+    SpendingCapExceeded = 1039,
+    /// The charge would take the subscriber past the batch's per-subscriber debit cap.
+    BatchDebitCapExceeded = 1041,
+    /// The interval charge's period runs past the subscription's expiration and the
+    /// merchant's expiry proration policy does not allow it.
+    BlockedByExpiry = 1042,
+    /// The operation only supports subscriptions billed in the vault token.
+    BillingTokenUnsupported = 1043,
+    /// The admin's risk oracle declined the charge, or could not be consulted.
+    RiskDeclined = 1044,
+    /// The merchant registry has no active entry for the merchant, or could not be
+    /// consulted.
+    MerchantNotApproved = 1045,
+    /// The merchant's accrued balance not held by a reserve or a dispute lock cannot
+    /// cover the withdrawal, payout or debit.
+    InsufficientMerchantBalance = 1046,
 }
 
 impl Error {
@@ -141,11 +484,40 @@ impl Error {
             Error::Replay => 1007,
             Error::InvalidRecoveryAmount => 1008,
             Error::EmergencyStopActive => 1009,
-            Error::AlreadyInitialized => 1009,
+            Error::AlreadyInitialized => 1016,
             Error::RecoveryNotAllowed => 1011,
             Error::InvalidInput => 1015,
             Error::NotInitialized => 1013,
             Error::InvalidExportLimit => 1014,
+            Error::DisputeAlreadyOpen => 1017,
+            Error::DisputeNotOpen => 1018,
+            Error::ArchiveNotAllowed => 1019,
+            Error::TokenNotAccepted => 1020,
+            Error::Expired => 1021,
+            Error::ChargeTypeBlocked => 1022,
+            Error::PromoNotChargeable => 1023,
+            Error::SubscriptionLimitReached => 1024,
+            Error::DisputeWindowClosed => 1025,
+            Error::MerchantInactive => 1026,
+            Error::MerchantActive => 1027,
+            Error::UsageCapExceeded => 1028,
+            Error::SubscriberNotAllowlisted => 1029,
+            Error::DebitTooSoon => 1030,
+            Error::PlanArchived => 1031,
+            Error::SkipLimitReached => 1032,
+            Error::SkipAlreadyPending => 1033,
+            Error::RefundExceedsCharged => 1034,
+            Error::MerchantStale => 1035,
+            Error::TokenMismatch => 1036,
+            Error::TimelockPending => 1037,
+            Error::CircuitBreakerOpen => 1038,
+            Error::SpendingCapExceeded => 1039,
+            Error::BatchDebitCapExceeded => 1041,
+            Error::BlockedByExpiry => 1042,
+            Error::BillingTokenUnsupported => 1043,
+            Error::RiskDeclined => 1044,
+            Error::MerchantNotApproved => 1045,
+            Error::InsufficientMerchantBalance => 1046,
         }
     }
 }
@@ -160,6 +532,43 @@ pub struct BatchChargeResult {
     pub error_code: u32,
 }
 
+/// One subscription to create in `batch_create_subscriptions`; the arguments of
+/// `create_subscription`.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct CreateSubscriptionRequest {
+    pub subscriber: Address,
+    pub merchant: Address,
+    pub amount: i128,
+    pub interval_seconds: u64,
+    pub usage_enabled: bool,
+    pub expiration: Option<u64>,
+}
+
+/// Result of creating one subscription in a batch. Used by
+/// [`crate::SubscriptionVault::batch_create_subscriptions`].
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct BatchCreateResult {
+    /// True if the subscription was created.
+    pub success: bool,
+    /// ID of the new subscription if success is true; otherwise 0.
+    pub subscription_id: u32,
+    /// If success is false, the error code (from [`Error::to_code`]); otherwise 0.
+    pub error_code: u32,
+}
+
+/// Result of one subscription in `merchant_pause_all` or `merchant_resume_all`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantPauseResult {
+    pub subscription_id: u32,
+    /// True if the subscription was paused (or resumed).
+    pub success: bool,
+    /// If success is false, the error code (e.g. from [`Error::to_code`]); otherwise 0.
+    pub error_code: u32,
+}
+
 /// Represents the lifecycle state of a subscription.
 ///
 /// See `docs/subscription_lifecycle.md` for how each status is entered and exited and for invariants.
@@ -242,6 +651,9 @@ pub enum SubscriptionStatus {
 /// transition helpers to modify status, never set it directly.
 /// See `docs/subscription_lifecycle.md` for lifecycle and on-chain representation.
 ///
+/// The optional expiration is not a field: it is stored under
+/// [`DataKey::Expiration`] and read with `get_expiration` (see `expiration.rs`).
+///
 /// Serialization: This named-field struct is encoded on-ledger as a ScMap keyed
 /// by the field names. Renaming fields, reordering is inconsequential to map
 /// semantics but still alters the encoded bytes and will break golden vectors.
@@ -295,6 +707,67 @@ pub struct SubscriptionSummary {
     pub usage_enabled: bool,
 }
 
+/// Position and size of a page in a subscription listing.
+///
+/// Pass `Cursor { start_id: 0, limit }` for the first page, then move `start_id` to
+/// the returned `next_cursor` until it is `None`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cursor {
+    /// Inclusive lower bound on the subscription ID.
+    pub start_id: u32,
+    /// Maximum number of items to return.
+    pub limit: u32,
+}
+
+/// One page of a cursor-based subscription listing.
+///
+/// Every listing (by merchant, subscriber, status, or due) returns this shape, so
+/// client pagination code is written once.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct SubscriptionPage {
+    pub items: Vec<SubscriptionSummary>,
+    /// `start_id` of the next page, or `None` once the listing is exhausted. Kept as a
+    /// bare ID because the SDK cannot encode an optional struct field.
+    pub next_cursor: Option<u32>,
+    /// Total matches across all pages, when it is known without a full scan.
+    pub total_hint: Option<u32>,
+}
+
+/// Merchant of a subscription as shown to subscribers: the address, or a salted hash of
+/// it when the deployment hides merchant identities.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MerchantRef {
+    Address(Address),
+    Hashed(BytesN<32>),
+}
+
+/// Subscriber-facing view of a subscription, with the merchant as a [`MerchantRef`].
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct SubscriberView {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    pub merchant: MerchantRef,
+    pub amount: i128,
+    pub interval_seconds: u64,
+    pub last_payment_timestamp: u64,
+    pub status: SubscriptionStatus,
+    pub prepaid_balance: i128,
+    pub usage_enabled: bool,
+}
+
+/// One page of [`SubscriberView`]s, paged like [`SubscriptionPage`].
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct SubscriberViewPage {
+    pub items: Vec<SubscriberView>,
+    pub next_cursor: Option<u32>,
+    pub total_hint: Option<u32>,
+}
+
 /// Event emitted when subscriptions are exported for migration.
 #[contracttype]
 #[derive(Clone, Debug)]
@@ -330,6 +803,33 @@ pub struct PlanTemplate {
     pub usage_enabled: bool,
 }
 
+/// Catalog terms kept next to a [`PlanTemplate`]: trial length, metadata and archive flag.
+/// Templates created without them read as no trial, empty metadata and not archived.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct PlanTerms {
+    /// Seconds the first charge of a new subscription is deferred by.
+    pub trial_seconds: u64,
+    /// Free-form merchant metadata (plan name, feature list reference, ...).
+    pub metadata: String,
+    /// Archived plans accept no new subscriptions; existing ones are unaffected.
+    pub archived: bool,
+}
+
+/// Catalog view of a plan: the template's pricing joined with its [`PlanTerms`].
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct Plan {
+    pub merchant: Address,
+    /// Charge per interval for subscriptions created from now on.
+    pub price: i128,
+    pub interval_seconds: u64,
+    pub usage_enabled: bool,
+    pub trial_seconds: u64,
+    pub metadata: String,
+    pub archived: bool,
+}
+
 /// Result of computing next charge information for a subscription.
 ///
 /// Contains the estimated next charge timestamp and a flag indicating
@@ -349,24 +849,11 @@ pub struct NextChargeInfo {
     /// - `false` for Paused subscriptions (no charges until resumed)
     /// - `false` for Cancelled subscriptions (terminal state, no future charges)
     pub is_charge_expected: bool,
-}
 
-/// Computes the estimated next charge timestamp for a subscription.
-///
-/// This is a readonly helper that does not mutate contract state. It provides
-/// information for off-chain scheduling systems and UX displays.
-pub fn compute_next_charge_info(subscription: &Subscription) -> NextChargeInfo {
-    let next_charge_timestamp = subscription
-        .last_payment_timestamp
-        .saturating_add(subscription.interval_seconds);
-
-    let is_charge_expected = match subscription.status {
-        SubscriptionStatus::Active => true,
-        SubscriptionStatus::InsufficientBalance => true, // Will be retried after funding
-        SubscriptionStatus::GracePeriod => true,         // Will be retried after grace period
-        SubscriptionStatus::Paused => false,
-        SubscriptionStatus::Cancelled => false,
-    };
+    /// Billing cycles left before a fixed-term subscription completes (see
+    /// `set_max_cycles`), or `None` if the subscription is open-ended.
+    pub cycles_remaining: Option<u32>,
+}
 
 /// Event emitted when emergency stop is enabled.
 #[contracttype]
@@ -388,19 +875,6 @@ pub struct EmergencyStopDisabledEvent {
     pub timestamp: u64,
 }
 
-/// Emitted when a merchant-initiated one-off charge is applied to a subscription.
-#[contracttype]
-#[derive(Clone, Debug)]
-pub struct OneOffChargedEvent {
-    pub subscription_id: u32,
-    pub merchant: Address,
-    pub amount: i128,
-    NextChargeInfo {
-        next_charge_timestamp,
-        is_charge_expected,
-    }
-}
-
 /// Represents the reason for stranded funds that can be recovered by admin.
 ///
 /// This enum documents the specific, well-defined cases where funds may become
@@ -464,6 +938,8 @@ pub struct FundsDepositedEvent {
     pub subscription_id: u32,
     pub subscriber: Address,
     pub amount: i128,
+    /// Prepaid balance after the deposit.
+    pub new_balance: i128,
 }
 
 #[contracttype]
@@ -472,6 +948,8 @@ pub struct SubscriptionChargedEvent {
     pub subscription_id: u32,
     pub merchant: Address,
     pub amount: i128,
+    /// Prepaid balance left after the charge.
+    pub remaining_balance: i128,
 }
 
 #[contracttype]
@@ -510,4 +988,931 @@ pub struct OneOffChargedEvent {
     pub subscription_id: u32,
     pub merchant: Address,
     pub amount: i128,
+    /// Merchant's memo hash for `charge_one_off`; `None` for executed approved charges.
+    pub memo_hash: Option<BytesN<32>>,
+}
+
+/// Emitted when a charge leaves the prepaid balance below the subscription's low-balance
+/// threshold (see `low_balance.rs`).
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct LowBalanceEvent {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    pub merchant: Address,
+    pub prepaid_balance: i128,
+    /// Configured threshold, in upcoming intervals.
+    pub intervals: u32,
+    /// Balance needed to cover those intervals at the subscription's amount.
+    pub required: i128,
+}
+
+/// Emitted when a merchant refunds part of a subscription's earlier charges.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct RefundedEvent {
+    pub subscription_id: u32,
+    pub merchant: Address,
+    pub amount: i128,
+    /// Paid to the subscriber's wallet rather than the prepaid balance.
+    pub to_wallet: bool,
+    /// Charged total less all refunds, after this one.
+    pub remaining_refundable: i128,
+}
+
+/// Lifecycle of a charge dispute.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DisputeStatus {
+    /// Disputed amount is ring-fenced in the merchant's balance.
+    Open = 0,
+    /// Resolved in the merchant's favour; the amount is withdrawable again.
+    ResolvedForMerchant = 1,
+    /// Resolved in the subscriber's favour; the amount was returned to the prepaid balance.
+    Refunded = 2,
+}
+
+/// A dispute against a single interval charge.
+///
+/// Only the disputed `amount` is locked in the merchant's accrued balance; the
+/// subscription itself keeps billing normally while the dispute is open.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct Dispute {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    pub merchant: Address,
+    /// Billing period index of the disputed charge (see `charge_core`).
+    pub period: u64,
+    pub amount: i128,
+    pub opened_at: u64,
+    pub status: DisputeStatus,
+}
+
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct DisputeOpenedEvent {
+    pub dispute_id: u32,
+    pub subscription_id: u32,
+    pub merchant: Address,
+    pub amount: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct DisputeResolvedEvent {
+    pub dispute_id: u32,
+    pub subscription_id: u32,
+    pub refunded: bool,
+    pub amount: i128,
+}
+
+/// Share of each protocol fee withdrawal routed to a public-goods donation address.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DonationConfig {
+    /// Address receiving the donated share.
+    pub recipient: Address,
+    /// Donated share in basis points (0-10_000).
+    pub bps: u32,
+}
+
+/// Emitted by `withdraw_protocol_fees`, recording how the withdrawal was split.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct ProtocolFeesWithdrawnEvent {
+    pub fee_recipient: Address,
+    pub fee_amount: i128,
+    pub donation_recipient: Option<Address>,
+    pub donation_amount: i128,
+}
+
+/// Emitted when the platform fee is taken from a settled charge.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct FeeCollectedEvent {
+    pub subscription_id: u32,
+    pub merchant: Address,
+    pub amount: i128,
+}
+
+/// Platform fee settings, returned by `get_fee_config`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeConfig {
+    /// Share of each settled charge kept as protocol fees.
+    pub platform_fee_bps: u32,
+    /// Treasury address `withdraw_protocol_fees` pays out to.
+    pub fee_recipient: Address,
+}
+
+/// Emitted when a terminal subscription is archived.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct SubscriptionArchivedEvent {
+    pub subscription_id: u32,
+    pub archived_by: Address,
+}
+
+/// Lateness of billing-path charges for one merchant in one SLA period.
+///
+/// Lateness is `charged_at - due_at` in seconds, where `due_at` is
+/// `last_payment_timestamp + interval_seconds` at the time of the charge.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BillingSla {
+    /// Number of interval charges recorded in the period.
+    pub charge_count: u32,
+    /// Sum of lateness across those charges.
+    pub total_lateness: u64,
+    /// Largest single lateness observed.
+    pub max_lateness: u64,
+}
+
+/// Merchant loyalty programme: `bps_per_tier` off for every `cycles_per_tier`
+/// completed cycles, up to `max_tiers` tiers.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LoyaltyConfig {
+    pub cycles_per_tier: u32,
+    pub bps_per_tier: u32,
+    pub max_tiers: u32,
+}
+
+/// Preview of the next interval charge, returned by `preview_charge`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChargePreview {
+    /// Undiscounted plan amount.
+    pub amount: i128,
+    /// Loyalty discount currently earned, in basis points.
+    pub discount_bps: u32,
+    /// Amount the next interval charge will take, including `setup_fee_due`.
+    pub amount_due: i128,
+    /// Setup fee installment added to the next interval charge.
+    pub setup_fee_due: i128,
+    /// Successful interval charges so far.
+    pub cycles_completed: u32,
+    /// Earliest time the next interval charge can be taken.
+    pub next_charge_timestamp: u64,
+    /// The merchant's expiry proration policy blocks the next interval charge.
+    pub blocked_by_expiry: bool,
+}
+
+/// One expected interval charge, returned by `get_upcoming_charges`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UpcomingCharge {
+    pub subscription_id: u32,
+    /// Earliest time the charge can be taken; in the past if the charge is overdue.
+    pub due_at: u64,
+    /// Amount due with the loyalty discount earned so far, plus any setup fee installment.
+    pub estimated_amount: i128,
+}
+
+/// Revenue a merchant can expect over a horizon, returned by `forecast_merchant_revenue`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RevenueForecast {
+    /// Sum of the interval charges expected within the horizon.
+    pub expected: i128,
+    /// Part of `expected` the subscriptions' prepaid balances already cover.
+    pub funded: i128,
+    /// Number of interval charges expected within the horizon.
+    pub charges: u32,
+    /// Number of subscriptions with at least one expected charge.
+    pub subscriptions: u32,
+}
+
+/// Breakdown of `estimate_topup_for_intervals`: what the next intervals are expected to
+/// cost, and the top-up still needed on top of the prepaid balance.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TopupEstimate {
+    /// Fixed interval amount times the number of intervals.
+    pub base: i128,
+    /// Usage spend projected from the trailing average of closed usage windows.
+    pub projected_usage: i128,
+    /// Setup fee installments the interval charges will add.
+    pub fees: i128,
+    /// Recommended top-up: `base + projected_usage + fees` minus the prepaid balance,
+    /// or 0 if the balance already covers it.
+    pub total: i128,
+}
+
+/// Which charge types a subscription currently accepts. `Paused` still blocks both.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChargeMask {
+    pub allow_interval: bool,
+    pub allow_usage: bool,
+}
+
+/// Entitlement index entry for a (subscriber, merchant) pair.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EntitlementRecord {
+    pub subscription_id: u32,
+    /// Plan template the subscription was created from, if any.
+    pub plan_id: Option<u32>,
+}
+
+/// Result of `check_entitlement`, intended for cross-contract access gating.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EntitlementStatus {
+    /// True if the subscriber currently has access.
+    pub active: bool,
+    /// End of the last paid interval (`last_payment_timestamp + interval_seconds`); 0 if none.
+    pub paid_through: u64,
+    /// Plan template ID, if the subscription was created from a plan.
+    pub plan_id: Option<u32>,
+}
+
+/// Global contract configuration, returned by `get_config`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VaultConfig {
+    pub admin: Address,
+    /// Address allowed to call the charge entrypoints (see `set_billing_engine`).
+    pub billing_engine: Address,
+    pub token: Address,
+    pub token_decimals: u32,
+    pub min_topup: i128,
+    pub grace_period: u64,
+    /// Seconds before the interval boundary within which charges are still accepted.
+    pub charge_tolerance_seconds: u64,
+    /// Minimum seconds between two successful debits on one subscription; 0 if unset.
+    pub min_debit_spacing_seconds: u64,
+    /// True while the emergency stop blocks charges and new subscriptions.
+    pub emergency_stop: bool,
+    /// Address receiving protocol fee withdrawals (the admin unless overridden).
+    pub fee_recipient: Address,
+    /// Per-subscriber and per-merchant subscription caps (0 = unlimited).
+    pub subscription_limits: SubscriptionLimits,
+    /// True while only allowlisted subscribers may open subscriptions.
+    pub allowlist_mode: bool,
+}
+
+/// Caps on live subscriptions enforced at creation. 0 means unlimited.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionLimits {
+    pub max_per_subscriber: u32,
+    pub max_per_merchant: u32,
+}
+
+/// One product in a subscription bundle and its share of each charge.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BundleLineItem {
+    /// Merchant credited for this line item.
+    pub merchant: Address,
+    /// Relative share of each charge; shares are `weight / sum(weights)`.
+    pub weight: u32,
+}
+
+/// Emitted once per line item when a bundled subscription is charged.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct BundleItemChargedEvent {
+    pub subscription_id: u32,
+    pub item_index: u32,
+    pub merchant: Address,
+    pub amount: i128,
+}
+
+/// Merchant preferences for retrying and announcing failed interval charges.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DunningPolicy {
+    /// Number of retries after the first failure before the retry queue gives up.
+    pub max_retries: u32,
+    /// Minimum spacing between retries, in seconds.
+    pub retry_spacing_seconds: u64,
+    /// Notification channels (e.g. `email`, `webhook`) reminder services should use.
+    pub notify_topics: Vec<Symbol>,
+}
+
+/// Where a subscription goes when its grace period lapses without a successful charge.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GraceLapseAction {
+    /// Suspend as `InsufficientBalance`; a deposit and resume reactivate it (default).
+    Suspend = 0,
+    /// Cancel; the remaining balance becomes withdrawable by the subscriber.
+    Cancel = 1,
+}
+
+/// How an interval charge below the minimum charge amount is handled.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MinChargePolicy {
+    /// Book the period without a debit and add the amount to the next charge (default).
+    RollOver = 0,
+    /// Charge the minimum amount instead.
+    RoundUp = 1,
+}
+
+/// How an interval charge whose period runs past the subscription's expiration is
+/// handled (see `expiration.rs`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ExpiryProration {
+    /// Charge the full amount, as for any other period (default).
+    FullCharge = 0,
+    /// Charge only the part of the period before the expiration.
+    Prorate = 1,
+    /// Take no charge for the period.
+    Block = 2,
+}
+
+/// Whether cancelling mid-period refunds the unused part of the last interval charge
+/// (see `cancel_proration.rs`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CancellationProration {
+    /// No refund on cancellation (default).
+    None = 0,
+    /// Refund the unused part of the period, in proportion to the time left.
+    Linear = 1,
+}
+
+/// Emitted when an interval charge fails for lack of funds.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct PaymentFailedEvent {
+    pub subscription_id: u32,
+    pub merchant: Address,
+    /// Consecutive failed attempts, including this one.
+    pub attempt: u32,
+    /// When the retry queue should try again; `None` once retries are exhausted.
+    pub next_retry_at: Option<u64>,
+    pub notify_topics: Vec<Symbol>,
+}
+
+/// Rolling reserve applied to a merchant's charge proceeds.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReserveConfig {
+    /// Share of each credited charge held back, in basis points.
+    pub bps: u32,
+    /// How long each hold lasts before it becomes withdrawable.
+    pub reserve_period_seconds: u64,
+}
+
+/// Reserved funds that become withdrawable at `release_at`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReserveBucket {
+    pub amount: i128,
+    pub release_at: u64,
+}
+
+/// Merchant earnings accrued in one secondary token.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TokenBalance {
+    pub token: Address,
+    pub amount: i128,
+}
+
+/// A merchant's full position across tokens, returned by `get_merchant_exposure`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantExposure {
+    /// Vault token the primary balance is held in.
+    pub token: Address,
+    /// Accrued balance in the vault token, including held and locked funds.
+    pub balance: i128,
+    /// Part of `balance` held by unreleased rolling reserve buckets.
+    pub reserved: i128,
+    /// Part of `balance` ring-fenced by open disputes.
+    pub locked: i128,
+    /// What a standard withdrawal could take now.
+    pub available: i128,
+    /// Secondary tokens the merchant has been credited in, including drained ones.
+    pub token_balances: Vec<TokenBalance>,
+}
+
+/// Promotional (zero-amount) subscription state, kept until converted to paid.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PromoSubscription {
+    /// Entitlement ends at this timestamp unless the subscription is converted.
+    pub expires_at: u64,
+    /// Price offered by the merchant for continuing after the promo, awaiting consent.
+    pub offered_amount: Option<i128>,
+}
+
+/// A usage charge line item the subscriber may flag within the flag window.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsageChargeRecord {
+    /// Per-subscription sequence number, as emitted in the `usage_charged` event.
+    pub seq: u32,
+    /// What the merchant was credited for the charge: the debit less the platform fee.
+    pub amount: i128,
+    pub charged_at: u64,
+    /// True while the amount is held from the merchant awaiting confirmation.
+    pub flagged: bool,
+}
+
+/// Usage billing terms of a subscription, returned by `get_usage_config`.
+///
+/// Subscriptions without a stored config use `enabled: usage_enabled` and zero for
+/// every other field, which is the legacy behavior.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsageConfig {
+    /// Mirrors `Subscription::usage_enabled`.
+    pub enabled: bool,
+    /// Price per unit for `charge_usage_units`; 0 disables unit-based charging.
+    pub unit_price: i128,
+    /// Maximum usage charged per report window; 0 means uncapped.
+    pub cap: i128,
+    /// Units per report window that `charge_usage_units` does not bill.
+    pub included_units: u64,
+    /// Report window length in seconds; 0 means the subscription's billing interval.
+    pub report_window: u64,
+}
+
+/// Usage accumulated in the current report window.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsageWindow {
+    pub started_at: u64,
+    /// Units reported through `charge_usage_units`.
+    pub units: u64,
+    /// Amount charged by all usage charges.
+    pub charged: i128,
+}
+
+/// Overage pricing for `charge_usage`, set by the merchant (see `usage_tiers.rs`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsageTier {
+    /// Usage amount per billing interval that is not debited.
+    pub included_quota: i128,
+    /// Share of the usage beyond the quota that is debited, in basis points; 10_000
+    /// charges it at face value.
+    pub overage_rate_bps: u32,
+}
+
+/// One-time setup fee split across the first `installments` interval charges.
+///
+/// Each installment is `total / installments`, with the last one taking the rounding
+/// remainder, so exactly `total` is collected.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SetupFee {
+    pub total: i128,
+    pub installments: u32,
+    /// Installments collected so far.
+    pub paid_installments: u32,
+    /// Amount collected so far.
+    pub collected: i128,
+}
+
+/// Lifetime dispute and pause counters of a subscription.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HealthCounters {
+    pub disputes_opened: u32,
+    pub pauses: u32,
+    /// When the subscription was last paused; 0 if never.
+    pub last_paused_at: u64,
+}
+
+/// Risk indicators of a subscription, returned by `get_subscription_health`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionHealth {
+    pub status: SubscriptionStatus,
+    /// Whole interval charges the prepaid balance covers at the next charge's amount.
+    pub runway_periods: u32,
+    /// Consecutive failed interval charges since the last successful one.
+    pub failed_attempts: u32,
+    pub disputes_opened: u32,
+    pub pauses: u32,
+    /// When the subscription was last paused; 0 if never.
+    pub last_paused_at: u64,
+    /// Set when the next charge is not covered, a charge has failed, or the
+    /// subscription is already behind on payment.
+    pub at_risk: bool,
+}
+
+/// Why the last charge of a subscription failed, as recorded by the charge guards.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ErrorContext {
+    /// Numeric [`Error`] code, as in batch results.
+    pub error_code: u32,
+    /// Offending value, e.g. the prepaid balance or the attempted charge time.
+    pub value: i128,
+    /// Limit the value failed against, e.g. the amount due or the next allowed time.
+    pub limit: i128,
+    /// Ledger time of the failure.
+    pub timestamp: u64,
+}
+
+/// Usage charge sequence counter and the records still open to flags.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct UsageChargeLedger {
+    pub next_seq: u32,
+    pub recent: Vec<UsageChargeRecord>,
+}
+
+/// Subscriber's round-up donation setting for one subscription.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoundUp {
+    /// Each interval charge is rounded up to a multiple of this amount.
+    pub unit: i128,
+    /// Receives the difference.
+    pub recipient: Address,
+}
+
+/// Skip history of a subscription, returned by `get_skip_record`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SkipRecord {
+    /// A skip is waiting for the next interval charge.
+    pub pending: bool,
+    /// Start of the current 365-day window the merchant's cap counts against.
+    pub window_start: u64,
+    /// Skips requested in the current window.
+    pub window_skips: u32,
+    /// Periods skipped over the subscription's lifetime.
+    pub total_skipped: u32,
+    /// Billing period index of the most recently skipped charge; 0 if none.
+    pub last_skipped_period: u64,
+}
+
+/// A merchant's liveness requirement, returned by `get_merchant_liveness`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantLiveness {
+    /// Longest the merchant may go without signing before its charges stop.
+    pub max_idle_seconds: u64,
+    /// When the merchant last signed a merchant entrypoint (or the requirement was set).
+    pub last_seen: u64,
+}
+
+/// An idempotency key processed by a charge, kept for the retention window.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IdempotencyRecord {
+    pub key: BytesN<32>,
+    pub processed_at: u64,
+}
+
+/// Subscriber's auto-topup setting for one subscription, returned by `get_auto_topup`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AutoTopup {
+    /// Most that may be pulled from the wallet within one billing period.
+    pub max_pull_per_period: i128,
+    /// Billing period index `pulled_in_period` refers to.
+    pub period_index: u64,
+    /// Amount pulled so far in that period.
+    pub pulled_in_period: i128,
+}
+
+/// Subscriber's monthly allowance for merchant-initiated one-off charges, returned by
+/// `get_one_off_allowance`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OneOffAllowance {
+    /// Most the merchant may charge in one-off charges within one month.
+    pub monthly_limit: i128,
+    /// Month index (`timestamp / ONE_OFF_MONTH_SECONDS`) `spent_in_month` refers to.
+    pub month_index: u64,
+    /// Amount charged in one-off charges so far in that month.
+    pub spent_in_month: i128,
+}
+
+/// Emitted when a charge shortfall is pulled from the subscriber's wallet.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct AutoTopupEvent {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    pub amount: i128,
+    /// Total pulled in the billing period, including this pull.
+    pub pulled_in_period: i128,
+}
+
+/// A subscription exported from an off-chain billing system, as signed by the
+/// migration key for `import_legacy_subscription`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LegacySubscriptionRecord {
+    /// Identifier in the system migrated from; each can be imported once.
+    pub external_id: String,
+    pub subscriber: Address,
+    pub merchant: Address,
+    pub amount: i128,
+    pub interval_seconds: u64,
+    /// When the customer originally subscribed.
+    pub started_at: u64,
+    /// Last billing boundary paid in the old system; the next charge is due one
+    /// interval later.
+    pub last_payment_timestamp: u64,
+    /// Billing cycles completed so far, carried into loyalty tiers.
+    pub cycles_completed: u32,
+}
+
+/// A legacy record with the migration key's ed25519 signature over its XDR encoding.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SignedLegacySubscription {
+    pub record: LegacySubscriptionRecord,
+    pub signature: BytesN<64>,
+}
+
+/// Origin of an imported subscription, returned by `get_legacy_origin`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LegacyOrigin {
+    pub external_id: String,
+    pub started_at: u64,
+}
+
+/// Terms scheduled by `update_subscription_terms`, returned by `get_pending_terms`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingTerms {
+    pub amount: i128,
+    pub interval_seconds: u64,
+    /// Billing boundary at which the terms apply: they replace the current ones once the
+    /// charge due there is booked.
+    pub effective_at: u64,
+}
+
+/// Emitted when new subscription terms are scheduled.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct TermsUpdatedEvent {
+    pub subscription_id: u32,
+    pub authorizer: Address,
+    pub old_amount: i128,
+    pub new_amount: i128,
+    pub old_interval: u64,
+    pub new_interval: u64,
+    pub effective_at: u64,
+}
+
+/// Guarantor backing a subscription, returned by `get_guarantor`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Guarantee {
+    pub guarantor: Address,
+    /// Most the guarantor may have outstanding for the subscription at once.
+    pub cap: i128,
+}
+
+/// What one guarantor has contributed to a subscription, returned by
+/// `get_guarantor_contributions`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GuarantorContributions {
+    /// Pulled amounts the guarantor has not yet recorded as repaid.
+    pub outstanding: i128,
+    /// Everything ever pulled from the guarantor.
+    pub total_contributed: i128,
+}
+
+/// Emitted when a charge shortfall is pulled from the guarantor's wallet.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct GuarantorPullEvent {
+    pub subscription_id: u32,
+    pub guarantor: Address,
+    pub amount: i128,
+    /// Guarantor's outstanding contributions, including this pull.
+    pub outstanding: i128,
+}
+
+/// A merchant's scheduled payout setting, returned by `get_payout_schedule`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayoutSchedule {
+    /// Minimum time between two scheduled payouts.
+    pub interval_seconds: u64,
+    /// Payouts wait until at least this much is available.
+    pub min_amount: i128,
+    /// Time of the last scheduled payout; 0 if none yet.
+    pub last_payout: u64,
+}
+
+/// Result of paying out one merchant in `batch_payout`.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct BatchPayoutResult {
+    pub merchant: Address,
+    /// Amount paid out; 0 on failure.
+    pub amount: i128,
+    /// Error code if the payout failed (e.g. from [`Error::to_code`]); otherwise 0.
+    pub error_code: u32,
+}
+
+/// Identity of the vault token cached at init, returned by `get_token_fingerprint`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TokenFingerprint {
+    pub token: Address,
+    pub decimals: u32,
+    /// SHA-256 of the XDR-encoded token symbol.
+    pub symbol_hash: BytesN<32>,
+}
+
+/// Failure rate at which the circuit breaker suspends charges (see `circuit_breaker.rs`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CircuitBreakerConfig {
+    /// Trip when failures exceed this share of attempts, in basis points.
+    pub max_failure_bps: u32,
+    /// Attempts a window needs before it can trip, so a few early failures do not.
+    pub min_attempts: u32,
+    /// Length of a counting window.
+    pub window_seconds: u64,
+}
+
+/// Batch charge outcomes counted by the circuit breaker in the current window.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChargeMetrics {
+    pub window_start: u64,
+    pub attempts: u32,
+    pub failures: u32,
+    /// When the breaker tripped, or 0 while charges are allowed.
+    pub tripped_at: u64,
+}
+
+/// An admin action waiting out the timelock (see `timelock.rs`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimelockEntry {
+    /// Hash of the action's payload; the action must be carried out with the same one.
+    pub payload_hash: BytesN<32>,
+    /// Earliest time the action can be carried out.
+    pub ready_at: u64,
+}
+
+/// Audit record of a status forced past the state machine (see `force_status.rs`).
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct StatusForcedEvent {
+    pub subscription_id: u32,
+    pub admin: Address,
+    pub from: SubscriptionStatus,
+    pub to: SubscriptionStatus,
+    /// Support reason given when the change was scheduled.
+    pub reason: String,
+    pub prepaid_balance: i128,
+    /// When the change was scheduled and when it was carried out.
+    pub proposed_at: u64,
+    pub forced_at: u64,
+}
+
+/// One-off charge a subscriber approved in advance, returned by `get_approved_charge`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApprovedCharge {
+    pub subscription_id: u32,
+    pub amount: i128,
+    /// Earliest time the charge may be executed.
+    pub execute_after: u64,
+    /// The charge can no longer be executed from this time on.
+    pub expires_at: u64,
+}
+
+/// Debits and adjustments on a merchant's accrued balance since its last statement
+/// (see `statements.rs`). Charges are not tracked; they are whatever else moved the
+/// balance.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StatementPeriod {
+    pub start: u64,
+    /// Merchant balance when the period opened.
+    pub opening: i128,
+    pub refunds: i128,
+    pub fees: i128,
+    pub payouts: i128,
+    pub adjustments: i128,
+}
+
+/// Emitted when a merchant's statement period closes, on every payout or on
+/// `close_merchant_statement`. `closing_accrual` equals `opening_accrual + charges -
+/// refunds - fees - payouts + adjustments`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantStatementEvent {
+    pub merchant: Address,
+    pub period_start: u64,
+    pub period_end: u64,
+    pub opening_accrual: i128,
+    /// Charge proceeds credited, net of the platform fee; derived from the other fields.
+    pub charges: i128,
+    pub refunds: i128,
+    /// Fees debited from the balance, such as expedited withdrawal fees.
+    pub fees: i128,
+    pub payouts: i128,
+    /// Other balance changes, such as usage refunded after a dispute.
+    pub adjustments: i128,
+    /// Part of the closing accrual held by the rolling reserve.
+    pub reserved: i128,
+    pub closing_accrual: i128,
+}
+
+/// Where `migrate_subscription_token` gets the new-token value of the balances it
+/// converts (see `token_migration.rs`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConversionRateSource {
+    /// Fixed rate: new-token units per `RATE_SCALE` old-token units. The admin supplies
+    /// the new tokens and takes the old ones.
+    Fixed(i128),
+    /// Allowlisted swap venue, called with
+    /// `swap(token_in, token_out, amount_in, recipient) -> amount_out`.
+    Swap(Address),
+}
+
+/// Emitted for each subscription moved to another billing token.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct TokenMigratedEvent {
+    pub subscription_id: u32,
+    pub from_token: Address,
+    pub to_token: Address,
+    /// Prepaid balance before and after conversion.
+    pub old_balance: i128,
+    pub new_balance: i128,
+    /// Recurring amount before and after conversion.
+    pub old_amount: i128,
+    pub new_amount: i128,
+}
+
+/// External risk oracle consulted before charges above `threshold` (see `risk.rs`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RiskOracle {
+    pub oracle: Address,
+    pub threshold: i128,
+}
+
+/// A setup step missing before a subscription can be charged and refunded (see
+/// `readiness.rs`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReadinessIssue {
+    /// The subscription is paused or cancelled, or its expiry policy blocks the next
+    /// charge.
+    NotChargeable = 0,
+    /// The prepaid balance does not cover the next charge and auto-topup is off.
+    InsufficientFunds = 1,
+    /// Auto-topup is on but its per-period cap is below the shortfall.
+    AutoTopupCapTooLow = 2,
+    /// Auto-topup is on but the subscriber's allowance to the vault is below the
+    /// shortfall.
+    AllowanceTooLow = 3,
+    /// Auto-topup is on but the subscriber's wallet holds less than the shortfall.
+    WalletBalanceTooLow = 4,
+    /// The subscriber cannot hold the token (missing or unauthorized trustline), so
+    /// withdrawals and refunds to the wallet would fail.
+    SubscriberCannotReceive = 5,
+    /// The merchant cannot hold the token, so merchant withdrawals would fail.
+    MerchantCannotReceive = 6,
+}
+
+/// Payment readiness report of a subscription.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct PaymentReadiness {
+    /// Token the subscription's funds move in.
+    pub token: Address,
+    /// Amount the next interval charge will take.
+    pub amount_due: i128,
+    pub prepaid_balance: i128,
+    /// Subscriber's wallet balance in `token`; 0 if it cannot be read.
+    pub wallet_balance: i128,
+    /// Allowance the subscriber granted the vault in `token`.
+    pub allowance: i128,
+    /// Steps still missing, in the order above; empty when `ready`.
+    pub issues: Vec<ReadinessIssue>,
+    pub ready: bool,
+}
+
+/// A subscription ID reserved for a given subscriber and merchant (see `ids.rs`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionIdReservation {
+    /// Only this subscriber can create the subscription under the ID.
+    pub subscriber: Address,
+    /// Merchant the subscription has to be created for.
+    pub merchant: Address,
+    /// The reservation can be claimed until this timestamp (inclusive).
+    pub expires_at: u64,
 }
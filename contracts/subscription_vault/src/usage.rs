@@ -0,0 +1,232 @@
+//! Usage billing configuration: unit price, per-window cap, included units and report
+//! window, layered over the legacy `Subscription::usage_enabled` flag.
+//!
+//! `usage_enabled` stays on the subscription record, since removing it would change the
+//! encoded form, and always mirrors `UsageConfig::enabled`. Subscriptions without a
+//! stored config behave as before: usage charges are allowed when the flag is set, with
+//! no cap. Usage windows are rolling: a new one starts with the first usage charge after
+//! the previous one ends. The charged totals of the last [`USAGE_HISTORY_WINDOWS`]
+//! closed windows are kept for top-up estimates.
+//!
+//! **PRs that only change usage configuration should edit this file only.**
+
+use crate::queries::get_subscription;
+use crate::types::{DataKey, Error, SubKey, Subscription, UsageConfig, UsageWindow};
+use soroban_sdk::{Address, Env, Symbol, Vec};
+
+/// Closed usage windows whose charged totals are kept for the trailing average.
+pub const USAGE_HISTORY_WINDOWS: u32 = 3;
+
+fn history_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::UsageHistory, subscription_id)
+}
+
+/// Usage terms of the subscription, defaulting to the legacy flag-only behavior.
+pub fn get_usage_config(env: &Env, subscription_id: u32, sub: &Subscription) -> UsageConfig {
+    env.storage()
+        .instance()
+        .get(&DataKey::UsageConfig(subscription_id))
+        .unwrap_or(UsageConfig {
+            enabled: sub.usage_enabled,
+            unit_price: 0,
+            cap: 0,
+            included_units: 0,
+            report_window: 0,
+        })
+}
+
+fn validate_config(config: &UsageConfig) -> Result<(), Error> {
+    if config.unit_price < 0 || config.cap < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    Ok(())
+}
+
+/// Subscriber creates a subscription with full usage terms.
+pub fn do_create_subscription_with_usage(
+    env: &Env,
+    subscriber: Address,
+    merchant: Address,
+    amount: i128,
+    interval_seconds: u64,
+    config: UsageConfig,
+) -> Result<u32, Error> {
+    validate_config(&config)?;
+    let id = crate::subscription::do_create_subscription(
+        env,
+        subscriber,
+        merchant,
+        amount,
+        interval_seconds,
+        config.enabled,
+    )?;
+    env.storage()
+        .instance()
+        .set(&DataKey::UsageConfig(id), &config);
+    Ok(id)
+}
+
+/// Replaces the usage terms. Subscriber and merchant must both authorize, since the
+/// terms bound what the merchant may charge. The current window's totals are kept.
+pub fn do_set_usage_config(
+    env: &Env,
+    subscription_id: u32,
+    config: UsageConfig,
+) -> Result<(), Error> {
+    let mut sub = get_subscription(env, subscription_id)?;
+    sub.subscriber.require_auth();
+    crate::liveness::require_merchant_auth(env, &sub.merchant);
+    validate_config(&config)?;
+
+    sub.usage_enabled = config.enabled;
+    env.storage()
+        .instance()
+        .set(&DataKey::Sub(subscription_id), &sub);
+    env.storage()
+        .instance()
+        .set(&DataKey::UsageConfig(subscription_id), &config);
+
+    env.events().publish(
+        (Symbol::new(env, "usage_config_updated"), subscription_id),
+        config,
+    );
+    Ok(())
+}
+
+/// Usage recorded in the current report window, or an empty window starting now if the
+/// previous one has ended.
+pub fn get_usage_window(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    config: &UsageConfig,
+) -> UsageWindow {
+    let now = env.ledger().timestamp();
+    let length = if config.report_window == 0 {
+        sub.interval_seconds
+    } else {
+        config.report_window
+    };
+    match env
+        .storage()
+        .instance()
+        .get::<_, UsageWindow>(&DataKey::UsageWindow(subscription_id))
+    {
+        Some(window) if now < window.started_at.saturating_add(length) => window,
+        _ => UsageWindow {
+            started_at: now,
+            units: 0,
+            charged: 0,
+        },
+    }
+}
+
+/// Amount billed for `units`, after the included units left in `window`.
+///
+/// Fails with `InvalidInput` if no unit price is configured.
+pub fn price_units(config: &UsageConfig, window: &UsageWindow, units: u64) -> Result<i128, Error> {
+    if config.unit_price == 0 {
+        return Err(Error::InvalidInput);
+    }
+    let billable = units.saturating_sub(config.included_units.saturating_sub(window.units));
+    i128::from(billable)
+        .checked_mul(config.unit_price)
+        .ok_or(Error::Overflow)
+}
+
+/// Window total after charging `amount`. Fails with `UsageCapExceeded` if it would pass
+/// the cap.
+pub fn ensure_within_cap(
+    window: &UsageWindow,
+    config: &UsageConfig,
+    amount: i128,
+) -> Result<i128, Error> {
+    let charged = window.charged.checked_add(amount).ok_or(Error::Overflow)?;
+    if config.cap > 0 && charged > config.cap {
+        return Err(Error::UsageCapExceeded);
+    }
+    Ok(charged)
+}
+
+/// Adds a usage charge of `amount` for `units` to the window.
+///
+/// Fails with `UsageCapExceeded` if the window's charged total would pass the cap.
+pub fn record_in_window(
+    env: &Env,
+    subscription_id: u32,
+    mut window: UsageWindow,
+    config: &UsageConfig,
+    units: u64,
+    amount: i128,
+) -> Result<(), Error> {
+    window.charged = ensure_within_cap(&window, config, amount)?;
+    window.units = window.units.saturating_add(units);
+    close_previous_window(env, subscription_id, &window);
+    env.storage()
+        .instance()
+        .set(&DataKey::UsageWindow(subscription_id), &window);
+    Ok(())
+}
+
+/// The last stored window, which may already have ended.
+pub fn get_stored_window(env: &Env, subscription_id: u32) -> Option<UsageWindow> {
+    env.storage()
+        .instance()
+        .get(&DataKey::UsageWindow(subscription_id))
+}
+
+/// Moves the stored window into the history if `window` replaces it.
+fn close_previous_window(env: &Env, subscription_id: u32, window: &UsageWindow) {
+    let Some(previous) = get_stored_window(env, subscription_id) else {
+        return;
+    };
+    if previous.started_at == window.started_at {
+        return;
+    }
+    let mut history = get_usage_history(env, subscription_id);
+    if history.len() >= USAGE_HISTORY_WINDOWS {
+        history.pop_front();
+    }
+    history.push_back(previous.charged);
+    env.storage()
+        .instance()
+        .set(&history_key(subscription_id), &history);
+}
+
+/// Charged totals of the last closed usage windows, oldest first.
+pub fn get_usage_history(env: &Env, subscription_id: u32) -> Vec<i128> {
+    env.storage()
+        .instance()
+        .get(&history_key(subscription_id))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Usage spend expected over `num_intervals` billing intervals: the average charged
+/// per closed window, scaled from the report window to the billing interval. 0 without
+/// history.
+pub fn project_usage(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    num_intervals: u32,
+) -> Result<i128, Error> {
+    let history = get_usage_history(env, subscription_id);
+    if history.is_empty() {
+        return Ok(0);
+    }
+    let mut sum: i128 = 0;
+    for charged in history.iter() {
+        sum = sum.checked_add(charged).ok_or(Error::Overflow)?;
+    }
+    let config = get_usage_config(env, subscription_id, sub);
+    let window_length = if config.report_window == 0 {
+        sub.interval_seconds
+    } else {
+        config.report_window
+    };
+    let covered = i128::from(sub.interval_seconds)
+        .checked_mul(num_intervals.into())
+        .ok_or(Error::Overflow)?;
+    let divisor = i128::from(history.len()) * i128::from(window_length.max(1));
+    Ok(sum.checked_mul(covered).ok_or(Error::Overflow)? / divisor)
+}
@@ -0,0 +1,138 @@
+//! Usage credits granted by the merchant, spent by usage charges before the prepaid
+//! balance.
+//!
+//! A merchant can grant a subscription credit (goodwill, a promotion, a plan's bundled
+//! allowance) with [`do_grant_usage_credit`]. Each usage charge draws on the credit
+//! first and debits only the rest from the prepaid balance; the merchant is not paid for
+//! the covered part.
+//!
+//! With rollover on ([`do_set_usage_rollover`]), the included units a report window
+//! left unused become credit at the unit price when the next window opens. Only the
+//! stored window rolls over, so a window with no usage charge at all rolls nothing over.
+//!
+//! **PRs that only change usage credits should edit this file only.**
+
+use crate::types::{DataKey, Error, SubKey, SubscriptionStatus, UsageConfig, UsageWindow};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn credit_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::UsageCredit, subscription_id)
+}
+
+fn rollover_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::CreditRollover, subscription_id)
+}
+
+/// Unspent usage credit of the subscription.
+pub fn get_usage_credit(env: &Env, subscription_id: u32) -> i128 {
+    env.storage()
+        .instance()
+        .get(&credit_key(subscription_id))
+        .unwrap_or(0)
+}
+
+/// Whether unused included units roll over into credit.
+pub fn get_usage_rollover(env: &Env, subscription_id: u32) -> bool {
+    env.storage()
+        .instance()
+        .get(&rollover_key(subscription_id))
+        .unwrap_or(false)
+}
+
+fn set_usage_credit(env: &Env, subscription_id: u32, credit: i128) {
+    let key = credit_key(subscription_id);
+    if credit == 0 {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &credit);
+    }
+}
+
+/// The subscription's merchant grants `amount` of usage credit. Returns the new credit.
+pub fn do_grant_usage_credit(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    amount: i128,
+) -> Result<i128, Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.merchant != merchant {
+        return Err(Error::Forbidden);
+    }
+    if sub.status == SubscriptionStatus::Cancelled {
+        return Err(Error::NotActive);
+    }
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let credit = get_usage_credit(env, subscription_id)
+        .checked_add(amount)
+        .ok_or(Error::Overflow)?;
+    set_usage_credit(env, subscription_id, credit);
+    env.events().publish(
+        (Symbol::new(env, "usage_credit_granted"), subscription_id),
+        (amount, credit),
+    );
+    Ok(credit)
+}
+
+/// The subscription's merchant turns rollover of unused included units on or off.
+pub fn do_set_usage_rollover(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    enabled: bool,
+) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, &merchant);
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.merchant != merchant {
+        return Err(Error::Forbidden);
+    }
+    let key = rollover_key(subscription_id);
+    if enabled {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events().publish(
+        (Symbol::new(env, "usage_rollover_set"), subscription_id),
+        enabled,
+    );
+    Ok(())
+}
+
+/// Credit available to a usage charge in `window`: the stored credit plus, if `window`
+/// replaces the stored one and rollover is on, the stored window's unused included units
+/// at the unit price. Reads only; [`consume`] stores the result.
+pub fn available_credit(
+    env: &Env,
+    subscription_id: u32,
+    config: &UsageConfig,
+    window: &UsageWindow,
+) -> i128 {
+    let credit = get_usage_credit(env, subscription_id);
+    if !get_usage_rollover(env, subscription_id) {
+        return credit;
+    }
+    let Some(previous) = crate::usage::get_stored_window(env, subscription_id) else {
+        return credit;
+    };
+    if previous.started_at == window.started_at {
+        return credit;
+    }
+    let unused = config.included_units.saturating_sub(previous.units);
+    credit.saturating_add(i128::from(unused).saturating_mul(config.unit_price))
+}
+
+/// Stores what is left of `available` credit after a charge covered `used` of it.
+pub fn consume(env: &Env, subscription_id: u32, available: i128, used: i128) {
+    let remaining = available.saturating_sub(used);
+    set_usage_credit(env, subscription_id, remaining);
+    if used > 0 {
+        env.events().publish(
+            (Symbol::new(env, "usage_credit_used"), subscription_id),
+            (used, remaining),
+        );
+    }
+}
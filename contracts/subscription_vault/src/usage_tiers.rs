@@ -0,0 +1,124 @@
+//! Merchant-configured overage pricing for amount-based usage charges.
+//!
+//! With a [`UsageTier`] set, `charge_usage` amounts are treated as consumption: the
+//! first `included_quota` consumed since the last interval charge is free, and only the
+//! part beyond it is debited, at `overage_rate_bps` of its amount. The consumption
+//! counter resets on every successful interval charge (see `charge_core.rs`).
+//!
+//! Unit-priced charges (`charge_usage_units`) keep using `UsageConfig::included_units`
+//! and are not affected by the tier.
+//!
+//! **PRs that only change usage tiers should edit this file only.**
+
+use crate::fees::MAX_BPS;
+use crate::types::{DataKey, Error, SubKey, SubscriptionStatus, UsageTier};
+use soroban_sdk::{Address, Env, Symbol};
+
+fn tier_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::UsageTier, subscription_id)
+}
+
+fn consumed_key(subscription_id: u32) -> DataKey {
+    DataKey::SubState(SubKey::TierConsumed, subscription_id)
+}
+
+/// Overage tier of the subscription, or `None` if usage is charged at face value.
+pub fn get_usage_tier(env: &Env, subscription_id: u32) -> Option<UsageTier> {
+    env.storage().instance().get(&tier_key(subscription_id))
+}
+
+/// Consumption charged through `charge_usage` since the last interval charge.
+pub fn get_tier_consumption(env: &Env, subscription_id: u32) -> i128 {
+    env.storage()
+        .instance()
+        .get(&consumed_key(subscription_id))
+        .unwrap_or(0)
+}
+
+fn load_merchant_owned(env: &Env, subscription_id: u32, merchant: &Address) -> Result<(), Error> {
+    crate::liveness::require_merchant_auth(env, merchant);
+    let sub = crate::queries::get_subscription(env, subscription_id)?;
+    if sub.merchant != *merchant {
+        return Err(Error::Forbidden);
+    }
+    if sub.status == SubscriptionStatus::Cancelled {
+        return Err(Error::NotActive);
+    }
+    Ok(())
+}
+
+/// The subscription's merchant sets its overage tier, replacing any earlier one. The
+/// consumption counted so far in the current interval is kept.
+pub fn do_set_usage_tier(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    tier: UsageTier,
+) -> Result<(), Error> {
+    load_merchant_owned(env, subscription_id, &merchant)?;
+    if tier.included_quota < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if tier.overage_rate_bps == 0 {
+        return Err(Error::InvalidInput);
+    }
+    env.storage()
+        .instance()
+        .set(&tier_key(subscription_id), &tier);
+    env.events()
+        .publish((Symbol::new(env, "usage_tier_set"), subscription_id), tier);
+    Ok(())
+}
+
+/// The subscription's merchant goes back to charging usage at face value.
+pub fn do_remove_usage_tier(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+) -> Result<(), Error> {
+    load_merchant_owned(env, subscription_id, &merchant)?;
+    let storage = env.storage().instance();
+    storage.remove(&tier_key(subscription_id));
+    storage.remove(&consumed_key(subscription_id));
+    env.events().publish(
+        (Symbol::new(env, "usage_tier_removed"), subscription_id),
+        merchant,
+    );
+    Ok(())
+}
+
+/// Amount billed for `consumed` of usage: `consumed` itself without a tier, otherwise
+/// the overage beyond what is left of the quota, at the overage rate (rounded down).
+/// Reads only; [`record_consumption`] counts the usage once the charge goes through.
+pub fn billable_amount(env: &Env, subscription_id: u32, consumed: i128) -> Result<i128, Error> {
+    let Some(tier) = get_usage_tier(env, subscription_id) else {
+        return Ok(consumed);
+    };
+    let left = tier
+        .included_quota
+        .saturating_sub(get_tier_consumption(env, subscription_id))
+        .max(0);
+    let overage = consumed.saturating_sub(left).max(0);
+    let billed = overage
+        .checked_mul(i128::from(tier.overage_rate_bps))
+        .ok_or(Error::Overflow)?;
+    Ok(billed / i128::from(MAX_BPS))
+}
+
+/// Adds `consumed` to the interval's counter if the subscription has a tier.
+pub fn record_consumption(env: &Env, subscription_id: u32, consumed: i128) {
+    if consumed <= 0 || get_usage_tier(env, subscription_id).is_none() {
+        return;
+    }
+    let total = get_tier_consumption(env, subscription_id).saturating_add(consumed);
+    env.storage()
+        .instance()
+        .set(&consumed_key(subscription_id), &total);
+}
+
+/// Starts a new quota after a successful interval charge.
+pub fn reset_consumption(env: &Env, subscription_id: u32) {
+    env.storage()
+        .instance()
+        .remove(&consumed_key(subscription_id));
+}